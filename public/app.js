@@ -116,7 +116,12 @@
     });
   }
 
-  const ws = new WebSocket("ws://localhost:54321/ws");
+  // The page is served by the server itself, whose host and port can be configured.
+  // Browsers cannot set headers on websockets, so the token of the page (if any) goes in the query.
+  const wsProtocol = location.protocol === "https:" ? "wss:" : "ws:";
+  const token = new URLSearchParams(location.search).get("token");
+  const wsQuery = token ? `?token=${encodeURIComponent(token)}` : "";
+  const ws = new WebSocket(`${wsProtocol}//${location.host}/ws${wsQuery}`);
   ws.addEventListener("message", message => {
     const command = JSON.parse(message.data);
     console.log(`Received command`, command);
@@ -128,6 +133,10 @@
       }
     } else if (command.SpotifyToken) {
       initSpotifyPlayer(command.SpotifyToken.access_token);
+    } else if (command.SpotifyPlaylists) {
+      console.table(command.SpotifyPlaylists.playlists);
+    } else if (command.SpotifyDevicesList) {
+      console.table(command.SpotifyDevicesList.devices);
     } else if (command.YoutubePlay) {
       playYoutubeVideo(command.YoutubePlay.video_id);
     } else if (command === 'YoutubePause') {
@@ -139,6 +148,31 @@
     }
   });
 
+  // Playlists can be listed and switched from the browser’s console, e.g.:
+  // midiHub.selectSpotifyPlaylist('37i9dQZF1DXcBWIGoYBM5M')
+  // Same goes for Spotify Connect devices, e.g. midiHub.selectSpotifyDevice(null) to use the active one
+  // The YouTube playlist can be pulled again with midiHub.refreshYoutubePlaylist()
+  // Writing to devices can also be stopped (and resumed) with midiHub.freeze(true|false),
+  // or restricted to the output of a single app with midiHub.soloApp('paint'|null)
+  // Newly plugged devices can be picked up right away with midiHub.rescanDevices()
+  // In selection mode, apps can be cycled through with midiHub.selectNextApp() and midiHub.selectPreviousApp()
+  // The grid of a device can be dimmed with midiHub.setBrightness('launchpad', 0.5)
+  // and can show any JPEG, until its app renders again, with midiHub.renderImage('launchpad', base64Jpeg)
+  global.midiHub = {
+    listSpotifyPlaylists: () => ws.send(JSON.stringify('SpotifyListPlaylists')),
+    selectSpotifyPlaylist: (playlistId) => ws.send(JSON.stringify({ SpotifySelectPlaylist: { playlist_id: playlistId } })),
+    listSpotifyDevices: () => ws.send(JSON.stringify('SpotifyListDevices')),
+    selectSpotifyDevice: (deviceId) => ws.send(JSON.stringify({ SpotifySelectDevice: { device_id: deviceId } })),
+    freeze: (enabled) => ws.send(JSON.stringify({ Freeze: { enabled } })),
+    soloApp: (name) => ws.send(JSON.stringify({ SoloApp: { name } })),
+    refreshYoutubePlaylist: () => ws.send(JSON.stringify('YoutubeRefresh')),
+    rescanDevices: () => ws.send(JSON.stringify('RescanDevices')),
+    selectNextApp: () => ws.send(JSON.stringify('SelectNextApp')),
+    selectPreviousApp: () => ws.send(JSON.stringify('SelectPreviousApp')),
+    setBrightness: (device, brightness) => ws.send(JSON.stringify({ SetBrightness: { device, brightness } })),
+    renderImage: (device, imageBase64) => ws.send(JSON.stringify({ RenderImage: { device, image_base64: imageBase64 } })),
+  };
+
   document.body.addEventListener("click", () => {
     document.body.requestFullscreen();
   });
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc::Sender;
+
+use crate::apps::Out;
+use crate::midi::features::Features;
+
+/// Time given to press the same pad again, to confirm the action triggered by the first press
+pub const CONFIRM_WINDOW: Duration = Duration::from_millis(3_000);
+
+/// Color of the pad waiting for a second press
+pub const CONFIRM_COLOR: [u8; 3] = [255, 128, 0];
+
+/// Index pressed once, and when, waiting for a second press
+pub type PendingConfirmation = Option<(usize, Instant)>;
+
+/// Return whether pressing the given index confirms the previous press of the same index, within
+/// the confirmation window. Otherwise, this press becomes the one waiting for confirmation.
+pub fn confirm(pending: &mut PendingConfirmation, index: usize, now: Instant) -> bool {
+    let confirmed = match pending {
+        Some((pending_index, pressed_at)) => *pending_index == index && now.duration_since(*pressed_at) <= CONFIRM_WINDOW,
+        None => false,
+    };
+
+    *pending = if confirmed { None } else { Some((index, now)) };
+    return confirmed;
+}
+
+/// Light the pad waiting for a second press, if the device supports it
+pub async fn render_pending_confirmation(
+    features: &(dyn Features + Sync + Send),
+    sender: &Sender<Out>,
+    index: usize,
+) {
+    match features.from_index_to_highlight_with_color(index, CONFIRM_COLOR) {
+        Ok(event) => sender.send(event.into()).await.unwrap_or_else(|err| {
+            eprintln!("[confirm] could not send the pending confirmation: {}", err);
+        }),
+        Err(err) => eprintln!("[confirm] could not render the pending confirmation: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn confirm_when_nothing_is_pending_then_wait_for_a_second_press() {
+        let now = Instant::now();
+        let mut pending = None;
+
+        assert!(!confirm(&mut pending, 3, now));
+        assert_eq!(pending, Some((3, now)));
+    }
+
+    #[test]
+    fn confirm_when_the_same_index_is_pressed_within_the_window_then_confirm() {
+        let now = Instant::now();
+        let mut pending = Some((3, now));
+
+        assert!(confirm(&mut pending, 3, now + Duration::from_millis(500)));
+        assert_eq!(pending, None);
+    }
+
+    #[test]
+    fn confirm_when_another_index_is_pressed_then_wait_for_it_instead() {
+        let now = Instant::now();
+        let mut pending = Some((3, now));
+
+        assert!(!confirm(&mut pending, 4, now + Duration::from_millis(500)));
+        assert_eq!(pending, Some((4, now + Duration::from_millis(500))));
+    }
+
+    #[test]
+    fn confirm_when_the_window_has_elapsed_then_wait_for_a_second_press_again() {
+        let now = Instant::now();
+        let mut pending = Some((3, now));
+
+        assert!(!confirm(&mut pending, 3, now + CONFIRM_WINDOW + Duration::from_millis(1)));
+        assert_eq!(pending, Some((3, now + CONFIRM_WINDOW + Duration::from_millis(1))));
+    }
+}
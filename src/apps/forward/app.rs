@@ -1,16 +1,24 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::sync::Arc;
 
 use tokio::sync::mpsc;
 
 use crate::apps::{App, In, Out};
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
 use crate::image::Image;
+use crate::midi::{Event, MidiMessage};
 use crate::midi::features::Features;
 
-use super::config::Config;
+use super::config::{Boundary, Config};
 
 pub struct Forward {
+    config: Config,
+    /// Channels whose sustain pedal is currently held
+    sustained_channels: HashSet<u8>,
     sender: mpsc::Sender<In>,
     receiver: mpsc::Receiver<In>,
+    send_policy: SendPolicy,
 }
 
 pub const NAME: &'static str = "forward";
@@ -18,15 +26,19 @@ pub const COLOR: [u8; 3] = [0, 0, 255];
 
 impl Forward {
     pub fn new(
-        _config: Config,
+        config: Config,
         _input_features: Arc<dyn Features + Sync + Send>,
         _output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
     ) -> Self {
         let (sender, receiver) = mpsc::channel::<In>(32);
 
         Forward {
+            config,
+            sustained_channels: HashSet::new(),
             sender,
             receiver,
+            send_policy,
         }
     }
 }
@@ -46,7 +58,16 @@ impl App for Forward {
 
     fn send(&mut self, event: In) -> Result<(), mpsc::error::SendError<In>> {
         match event {
-            In::Midi(event) => self.sender.blocking_send(In::Midi(event)),
+            In::Midi(event) => {
+                // Sustain gets tracked on the forced channel, which is the one it gets released on
+                let event = force_channel(event, self.config.force_channel);
+                self.track_sustain(&event);
+
+                match transpose(event, self.config.transpose, self.config.boundary) {
+                    Some(event) => send_with_policy(NAME, &self.sender, In::Midi(event), self.send_policy),
+                    None => Ok(()),
+                }
+            },
             _ => Ok(()),
         }
     }
@@ -59,6 +80,87 @@ impl App for Forward {
     }
 
     fn on_select(&mut self) {}
+
+    fn on_deselect(&mut self) {
+        self.release_sustain();
+    }
+}
+
+impl Forward {
+    /// Controller 64: sustain pedal, which is held from value 64 and upwards
+    fn track_sustain(&mut self, event: &Event) {
+        if let Ok(MidiMessage::ControlChange { channel, control: 64, value }) = MidiMessage::try_from(event.clone()) {
+            if value >= 64 {
+                self.sustained_channels.insert(channel);
+            } else {
+                self.sustained_channels.remove(&channel);
+            }
+        }
+    }
+
+    /// Release the sustain pedal of every channel where it is held,
+    /// so that the synthesizer does not keep sustaining notes forever.
+    fn release_sustain(&mut self) {
+        let mut channels = self.sustained_channels.drain().collect::<Vec<u8>>();
+        channels.sort();
+        for channel in channels {
+            let release = MidiMessage::ControlChange { channel, control: 64, value: 0 };
+            self.sender.try_send(In::Midi(release.into())).unwrap_or_else(|err| {
+                eprintln!("[forward] could not release the sustain pedal of channel {}: {}", channel, err)
+            });
+        }
+    }
+}
+
+/// Rewrite the channel of channel-voice messages. System messages are returned as they are.
+fn force_channel(event: Event, channel: Option<u8>) -> Event {
+    return match channel {
+        Some(channel) => match MidiMessage::try_from(event.clone()) {
+            Ok(message) => message.with_channel(channel).into_event_like(&event),
+            Err(_) => event,
+        },
+        None => event,
+    };
+}
+
+/// Transpose note events, and return None if the resulting note must be dropped.
+/// Events that don’t carry a note are returned as they are.
+fn transpose(event: Event, semitones: i8, boundary: Boundary) -> Option<Event> {
+    if semitones == 0 {
+        return Some(event);
+    }
+
+    let transpose_note = |note: u8| apply_boundary(note as i16 + semitones as i16, boundary);
+    return match MidiMessage::try_from(event.clone()) {
+        Ok(MidiMessage::NoteOff { channel, note, velocity }) => {
+            Some(MidiMessage::NoteOff { channel, note: transpose_note(note)?, velocity }.into_event_like(&event))
+        },
+        Ok(MidiMessage::NoteOn { channel, note, velocity }) => {
+            Some(MidiMessage::NoteOn { channel, note: transpose_note(note)?, velocity }.into_event_like(&event))
+        },
+        Ok(MidiMessage::PolyPressure { channel, note, pressure }) => {
+            Some(MidiMessage::PolyPressure { channel, note: transpose_note(note)?, pressure }.into_event_like(&event))
+        },
+        _ => Some(event),
+    };
+}
+
+fn apply_boundary(note: i16, boundary: Boundary) -> Option<u8> {
+    return match boundary {
+        _ if (0..=127).contains(&note) => Some(note as u8),
+        Boundary::Clamp => Some(note.clamp(0, 127) as u8),
+        Boundary::Drop => None,
+        Boundary::WrapOctave => {
+            let mut note = note;
+            while note > 127 {
+                note -= 12;
+            }
+            while note < 0 {
+                note += 12;
+            }
+            Some(note as u8)
+        },
+    };
 }
 
 pub fn get_logo() -> Image {
@@ -68,3 +170,189 @@ pub fn get_logo() -> Image {
         bytes: vec![],
     };
 }
+
+#[cfg(test)]
+mod test {
+    use crate::midi::devices::default::DefaultFeatures;
+    use super::*;
+
+    fn get_forward(transpose: i8, boundary: Boundary) -> Forward {
+        return get_forward_with_channel(transpose, boundary, None);
+    }
+
+    fn get_forward_with_channel(transpose: i8, boundary: Boundary, force_channel: Option<u8>) -> Forward {
+        return Forward::new(
+            Config { transpose, boundary, force_channel, merge_inputs: vec![] },
+            Arc::new(DefaultFeatures::new()),
+            Arc::new(DefaultFeatures::new()),
+            SendPolicy::default(),
+        );
+    }
+
+    #[test]
+    fn send_when_channel_is_full_then_drop_the_event_instead_of_blocking() {
+        let mut forward = Forward::new(
+            Config { transpose: 0, boundary: Boundary::Drop, force_channel: None, merge_inputs: vec![] },
+            Arc::new(DefaultFeatures::new()),
+            Arc::new(DefaultFeatures::new()),
+            SendPolicy::Drop,
+        );
+
+        // nothing gets received in between, as when the router stops polling the app
+        for note in 0..40 {
+            forward.send(In::Midi(Event::Midi([144, note, 100, 0]))).unwrap();
+        }
+
+        let mut received = vec![];
+        while let Ok(Out::Midi(Event::Midi([_, note, _, _]))) = forward.receive() {
+            received.push(note);
+        }
+        assert_eq!(received, (0..32).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn send_when_no_transposition_then_forward_event_as_is() {
+        let mut forward = get_forward(0, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([144, 60, 100, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([144, 60, 100, 0]))));
+    }
+
+    #[test]
+    fn send_when_transposing_note_within_range_then_forward_transposed_note() {
+        let mut forward = get_forward(-12, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([128, 60, 0, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([128, 48, 0, 0]))));
+    }
+
+    #[test]
+    fn send_when_transposing_and_forcing_channel_then_keep_the_fourth_byte() {
+        let mut forward = get_forward_with_channel(12, Boundary::Drop, Some(2));
+        forward.send(In::Midi(Event::Midi([144, 60, 100, 7]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([146, 72, 100, 7]))));
+    }
+
+    #[test]
+    fn send_when_transposing_control_change_then_forward_event_as_is() {
+        let mut forward = get_forward(12, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([176, 120, 64, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([176, 120, 64, 0]))));
+    }
+
+    #[test]
+    fn send_when_transposing_note_past_127_with_drop_then_forward_nothing() {
+        let mut forward = get_forward(12, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([144, 120, 100, 0]))).unwrap();
+        assert!(forward.receive().is_err());
+    }
+
+    #[test]
+    fn send_when_transposing_note_past_127_with_clamp_then_forward_127() {
+        let mut forward = get_forward(12, Boundary::Clamp);
+        forward.send(In::Midi(Event::Midi([144, 120, 100, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([144, 127, 100, 0]))));
+    }
+
+    #[test]
+    fn send_when_transposing_note_past_127_with_wrap_octave_then_forward_note_an_octave_below() {
+        let mut forward = get_forward(5, Boundary::WrapOctave);
+        forward.send(In::Midi(Event::Midi([144, 125, 100, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([144, 118, 100, 0]))));
+    }
+
+    #[test]
+    fn send_when_transposing_note_below_0_with_wrap_octave_then_forward_note_an_octave_above() {
+        let mut forward = get_forward(-5, Boundary::WrapOctave);
+        forward.send(In::Midi(Event::Midi([144, 2, 100, 0]))).unwrap();
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([144, 9, 100, 0]))));
+    }
+
+    #[test]
+    fn on_deselect_when_sustain_is_held_then_release_it() {
+        let mut forward = get_forward(0, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([176, 64, 127, 0]))).unwrap();
+        forward.send(In::Midi(Event::Midi([178, 64, 100, 0]))).unwrap();
+        forward.send(In::Midi(Event::Midi([144, 60, 100, 0]))).unwrap();
+        while forward.receive().is_ok() {}
+
+        forward.on_deselect();
+
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([176, 64, 0, 0]))));
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([178, 64, 0, 0]))));
+        assert!(forward.receive().is_err());
+    }
+
+    #[test]
+    fn on_deselect_when_sustain_was_released_then_emit_nothing() {
+        let mut forward = get_forward(0, Boundary::Drop);
+        forward.send(In::Midi(Event::Midi([176, 64, 127, 0]))).unwrap();
+        forward.send(In::Midi(Event::Midi([176, 64, 0, 0]))).unwrap();
+        while forward.receive().is_ok() {}
+
+        forward.on_deselect();
+
+        assert!(forward.receive().is_err());
+    }
+
+    #[test]
+    fn send_when_channel_is_forced_then_forward_notes_and_control_changes_on_that_channel() {
+        let mut forward = get_forward_with_channel(0, Boundary::Drop, Some(2));
+        for event in [
+            Event::Midi([144, 60, 100, 0]),
+            Event::Midi([151, 62, 100, 0]),
+            Event::Midi([143, 60, 0, 0]),
+            Event::Midi([176, 1, 64, 0]),
+            Event::Midi([191, 7, 100, 0]),
+            Event::Midi([224, 0, 64, 0]),
+        ] {
+            forward.send(In::Midi(event)).unwrap();
+        }
+
+        let mut events = vec![];
+        while let Ok(out) = forward.receive() {
+            events.push(out);
+        }
+
+        assert_eq!(events, vec![
+            Out::Midi(Event::Midi([146, 60, 100, 0])),
+            Out::Midi(Event::Midi([146, 62, 100, 0])),
+            Out::Midi(Event::Midi([130, 60, 0, 0])),
+            Out::Midi(Event::Midi([178, 1, 64, 0])),
+            Out::Midi(Event::Midi([178, 7, 100, 0])),
+            Out::Midi(Event::Midi([226, 0, 64, 0])),
+        ]);
+    }
+
+    #[test]
+    fn send_when_channel_is_forced_then_forward_system_messages_as_they_are() {
+        let mut forward = get_forward_with_channel(0, Boundary::Drop, Some(2));
+        forward.send(In::Midi(Event::Midi([248, 0, 0, 0]))).unwrap();
+        forward.send(In::Midi(Event::SysEx(vec![240, 1, 247]))).unwrap();
+
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([248, 0, 0, 0]))));
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::SysEx(vec![240, 1, 247]))));
+    }
+
+    #[test]
+    fn on_deselect_when_channel_is_forced_then_release_sustain_on_that_channel() {
+        let mut forward = get_forward_with_channel(0, Boundary::Drop, Some(5));
+        forward.send(In::Midi(Event::Midi([176, 64, 127, 0]))).unwrap();
+        forward.send(In::Midi(Event::Midi([178, 64, 127, 0]))).unwrap();
+        while forward.receive().is_ok() {}
+
+        forward.on_deselect();
+
+        assert_eq!(forward.receive(), Ok(Out::Midi(Event::Midi([181, 64, 0, 0]))));
+        assert!(forward.receive().is_err());
+    }
+
+    #[test]
+    fn config_when_boundary_is_missing_then_default_to_drop() {
+        let config: Config = toml::from_str("transpose = 3").unwrap();
+        assert_eq!(config.transpose, 3);
+        assert_eq!(config.boundary, Boundary::Drop);
+
+        let config: Config = toml::from_str("boundary = \"wrapoctave\"").unwrap();
+        assert_eq!(config.transpose, 0);
+        assert_eq!(config.boundary, Boundary::WrapOctave);
+    }
+}
@@ -1,9 +1,46 @@
 use serde::{Serialize, Deserialize};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Config {}
+pub struct Config {
+    /// Number of semitones added to the notes being forwarded
+    #[serde(default)]
+    pub transpose: i8,
+    /// What to do with notes that get transposed outside of the 0-127 range
+    #[serde(default)]
+    pub boundary: Boundary,
+    /// Channel (from 0 to 15) every channel-voice message gets rewritten to, if set, so that
+    /// the events of several controllers reach the downstream gear on a single channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_channel: Option<u8>,
+    /// Ids of extra input devices, whose events get forwarded along with the ones of the linked
+    /// input device, e.g. to play a synth from both a keyboard and a pedalboard
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merge_inputs: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    /// Replace the note by the closest valid one
+    Clamp,
+    /// Do not forward the note at all
+    Drop,
+    /// Shift the note by as many octaves as needed to make it valid
+    WrapOctave,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        return Boundary::Drop;
+    }
+}
 
-/// The application doesn’t need configuration at the moment
+/// Notes get forwarded as they are, unless the configuration file says otherwise
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
-    return Ok(Config {});
+    return Ok(Config {
+        transpose: 0,
+        boundary: Boundary::default(),
+        force_channel: None,
+        merge_inputs: vec![],
+    });
 }
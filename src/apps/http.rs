@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+
+/// Timeouts applied to the requests sent by the apps relying on a web API
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HttpConfig {
+    pub request_timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        return HttpConfig {
+            request_timeout_ms: 10_000,
+            connect_timeout_ms: 5_000,
+        };
+    }
+}
+
+/// Build a client meant to be reused across requests, so that connections get pooled
+pub fn build_client(config: &HttpConfig) -> Client {
+    return Client::builder()
+        .timeout(Duration::from_millis(config.request_timeout_ms))
+        .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+        .build()
+        .expect("[apps] the HTTP client should be built from valid timeouts");
+}
+
+/// A server that accepts connections but never responds, to test how clients deal with timeouts
+#[cfg(test)]
+pub async fn start_unresponsive_server() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut sockets = vec![];
+        while let Ok((socket, _)) = listener.accept().await {
+            // keep the socket open without writing anything back
+            sockets.push(socket);
+        }
+    });
+
+    return format!("http://{}", address);
+}
+
+/// A server that responds to every request with "429 Too Many Requests",
+/// and the given Retry-After header if any, to test how clients deal with rate limits
+#[cfg(test)]
+pub async fn start_rate_limiting_server(retry_after: Option<&'static str>) -> String {
+    use warp::Filter;
+
+    let routes = warp::any().map(move || {
+        let mut response = warp::http::Response::builder().status(429);
+        if let Some(retry_after) = retry_after {
+            response = response.header("Retry-After", retry_after);
+        }
+        return response.body("").unwrap();
+    });
+
+    let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    return format!("http://{}", address);
+}
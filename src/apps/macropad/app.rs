@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::mpsc::{channel, Sender, Receiver};
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+
+use crate::apps::{App, Image, In, Out};
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
+use crate::midi::features::Features;
+use super::config::Config;
+use super::keys::{KeyCombo, Keyboard, SystemKeyboard};
+
+pub const NAME: &'static str = "macropad";
+pub const COLOR: [u8; 3] = [255, 0, 255];
+
+pub struct Macropad {
+    input_features: Arc<dyn Features + Sync + Send>,
+    output_features: Arc<dyn Features + Sync + Send>,
+    sender: Sender<Out>,
+    receiver: Receiver<Out>,
+    shortcuts: HashMap<usize, KeyCombo>,
+    keyboard: Box<dyn Keyboard + Send>,
+    send_policy: SendPolicy,
+}
+
+impl Macropad {
+    pub fn new(
+        config: Config,
+        input_features: Arc<dyn Features + Sync + Send>,
+        output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
+    ) -> Self {
+        return Macropad::new_with_keyboard(config, input_features, output_features, Box::new(SystemKeyboard::new()), send_policy);
+    }
+
+    pub fn new_with_keyboard(
+        config: Config,
+        input_features: Arc<dyn Features + Sync + Send>,
+        output_features: Arc<dyn Features + Sync + Send>,
+        keyboard: Box<dyn Keyboard + Send>,
+        send_policy: SendPolicy,
+    ) -> Self {
+        let (sender, receiver) = channel::<Out>(32);
+
+        return Macropad {
+            input_features,
+            output_features,
+            sender,
+            receiver,
+            shortcuts: parse_shortcuts(&config),
+            keyboard,
+            send_policy,
+        };
+    }
+
+    /// Light the pads that trigger a key combo
+    fn render_shortcuts(&self) {
+        let (width, height) = match self.output_features.get_grid_size() {
+            Ok(size) => size,
+            Err(err) => {
+                eprintln!("[macropad] cannot render the shortcuts, as the grid size cannot be retrieved: {}", err);
+                return;
+            },
+        };
+
+        let mut image = Image { width, height, bytes: vec![0; width * height * 3] };
+        for index in self.shortcuts.keys() {
+            if let Ok(Some((x, y))) = self.output_features.from_index_to_coordinates(*index) {
+                let byte_pos = (y * width + x) * 3;
+                image.bytes[byte_pos..(byte_pos + 3)].copy_from_slice(&COLOR);
+            }
+        }
+
+        match self.output_features.from_image(image) {
+            Ok(event) => send_with_policy(NAME, &self.sender, event.into(), self.send_policy).unwrap_or_else(|err| {
+                eprintln!("[macropad] could not send event back to the router: {}", err)
+            }),
+            Err(err) => eprintln!("[macropad] could not transform the shortcuts into a MIDI event: {}", err),
+        }
+    }
+}
+
+/// Invalid indices or key combos are logged and ignored, so that they don’t prevent the others
+/// from working.
+pub fn parse_shortcuts(config: &Config) -> HashMap<usize, KeyCombo> {
+    let mut shortcuts = HashMap::new();
+
+    for (index, combo) in &config.shortcuts {
+        match (index.trim().parse::<usize>(), combo.parse::<KeyCombo>()) {
+            (Ok(index), Ok(combo)) => {
+                shortcuts.insert(index, combo);
+            },
+            (Err(err), _) => eprintln!("[macropad] ignoring invalid index \"{}\": {}", index, err),
+            (_, Err(err)) => eprintln!("[macropad] ignoring shortcut for index {}: {}", index, err),
+        }
+    }
+
+    return shortcuts;
+}
+
+impl App for Macropad {
+    fn get_name(&self) -> &'static str {
+        return NAME;
+    }
+
+    fn get_color(&self) -> [u8; 3] {
+        return COLOR;
+    }
+
+    fn get_logo(&self) -> Image {
+        return Image { width: 1, height: 1, bytes: COLOR.to_vec() };
+    }
+
+    fn send(&mut self, event: In) -> Result<(), SendError<In>> {
+        match event {
+            In::Midi(event) => match self.input_features.into_index(event) {
+                Ok(Some(index)) => match self.shortcuts.get(&index) {
+                    Some(combo) => self.keyboard.send(combo),
+                    None => println!("[macropad] no shortcut configured for index {}", index),
+                },
+                Ok(None) => {}, // we ignore events that don’t map to an index
+                Err(err) => eprintln!("[macropad] error when transforming incoming event into an index: {}", err),
+            },
+            _ => {}, // we ignore events that are not MIDI events
+        }
+        return Ok(());
+    }
+
+    fn receive(&mut self) -> Result<Out, TryRecvError> {
+        return self.receiver.try_recv();
+    }
+
+    fn on_select(&mut self) {
+        self.render_shortcuts();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use crate::midi::Event;
+    use crate::midi::features::{R, GridController, ImageRenderer, IndexSelector};
+    use super::super::keys::{Key, Modifier};
+    use super::*;
+
+    fn get_config() -> Config {
+        return Config {
+            shortcuts: vec![
+                ("0", "ctrl+c"),
+                ("1", "ctrl+v"),
+                ("3", "cmd+shift+f5"),
+                ("one", "ctrl+z"),
+                ("2", "ctrl+nope"),
+            ].into_iter().map(|(index, combo)| (index.to_string(), combo.to_string())).collect(),
+        };
+    }
+
+    #[test]
+    fn parse_shortcuts_should_ignore_invalid_entries() {
+        let shortcuts = parse_shortcuts(&get_config());
+
+        assert_eq!(shortcuts, vec![
+            (0, KeyCombo { modifiers: vec![Modifier::Control], key: Key::Char('c') }),
+            (1, KeyCombo { modifiers: vec![Modifier::Control], key: Key::Char('v') }),
+            (3, KeyCombo { modifiers: vec![Modifier::Meta, Modifier::Shift], key: Key::Function(5) }),
+        ].into_iter().collect());
+    }
+
+    #[test]
+    fn send_when_pressing_pad_with_shortcut_then_send_key_combo() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let mut macropad = get_macropad(Arc::clone(&sent));
+
+        // index 1, as per our fake implementation of features
+        macropad.send(In::Midi(Event::Midi([144, 1, 100, 0]))).unwrap();
+
+        assert_eq!(*sent.lock().unwrap(), vec![
+            KeyCombo { modifiers: vec![Modifier::Control], key: Key::Char('v') },
+        ]);
+    }
+
+    #[test]
+    fn send_when_pressing_pad_without_shortcut_then_do_nothing() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let mut macropad = get_macropad(Arc::clone(&sent));
+
+        macropad.send(In::Midi(Event::Midi([144, 2, 100, 0]))).unwrap();
+        macropad.send(In::Midi(Event::Midi([128, 1, 0, 0]))).unwrap();
+
+        assert_eq!(*sent.lock().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn on_select_should_render_the_pads_with_shortcuts() {
+        let mut macropad = get_macropad(Arc::new(Mutex::new(vec![])));
+        macropad.on_select();
+
+        // indices 0 and 1 are at the bottom of the 2x2 grid, and index 3 is at the top-right
+        let event = macropad.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e',
+            000, 000, 000, 255, 000, 255,
+            255, 000, 255, 255, 000, 255,
+        ])));
+
+        let event = macropad.receive();
+        assert!(event.is_err());
+    }
+
+    fn get_macropad(sent: Arc<Mutex<Vec<KeyCombo>>>) -> Macropad {
+        return Macropad::new_with_keyboard(
+            get_config(),
+            Arc::new(FakeFeatures {}),
+            Arc::new(FakeFeatures {}),
+            Box::new(FakeKeyboard { sent }),
+            SendPolicy::default(),
+        );
+    }
+
+    struct FakeKeyboard {
+        sent: Arc<Mutex<Vec<KeyCombo>>>,
+    }
+    impl Keyboard for FakeKeyboard {
+        fn send(&mut self, combo: &KeyCombo) {
+            self.sent.lock().unwrap().push(combo.clone());
+        }
+    }
+
+    struct FakeFeatures {}
+    impl GridController for FakeFeatures {
+        fn get_grid_size(&self) -> R<(usize, usize)> {
+            Ok((2, 2))
+        }
+    }
+    impl IndexSelector for FakeFeatures {
+        fn into_index(&self, event: Event) -> R<Option<usize>> {
+            Ok(match event {
+                Event::Midi([144, index, _, _]) => Some(index.into()),
+                _ => None,
+            })
+        }
+    }
+    impl ImageRenderer for FakeFeatures {
+        fn from_image(&self, mut image: Image) -> R<Event> {
+            let mut bytes = Vec::from("image".as_bytes());
+            bytes.append(&mut image.bytes);
+            return Ok(Event::SysEx(bytes));
+        }
+    }
+    impl Features for FakeFeatures {}
+}
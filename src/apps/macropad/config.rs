@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use dialoguer::{theme::ColorfulTheme, Input};
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Key combos (e.g. "ctrl+shift+a") indexed by the index of the pad triggering them.
+    /// Indices are stored as strings, as TOML tables only accept string keys.
+    pub shortcuts: HashMap<String, String>,
+}
+
+pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
+    let mut shortcuts = HashMap::new();
+
+    loop {
+        let index: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .with_prompt("[macropad] please enter the index of the pad to configure (leave empty to stop):")
+            .allow_empty(true)
+            .interact()?;
+
+        let index = index.trim().to_string();
+        if index.is_empty() {
+            break;
+        }
+
+        let combo: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("[macropad] please enter the key combo for the pad {} (e.g. ctrl+shift+a):", index))
+            .interact()?;
+
+        shortcuts.insert(index, combo.trim().to_string());
+    }
+
+    return Ok(Config { shortcuts });
+}
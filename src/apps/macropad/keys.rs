@@ -0,0 +1,241 @@
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Modifier {
+    Alt,
+    Control,
+    Meta,
+    Shift,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Function(u8),
+    Backspace,
+    Delete,
+    Down,
+    End,
+    Enter,
+    Escape,
+    Home,
+    Left,
+    PageDown,
+    PageUp,
+    Right,
+    Space,
+    Tab,
+    Up,
+}
+
+/// A key combo is a key, pressed while holding zero or more modifiers.
+/// It is written as a list of keys separated by `+`, the last one being the key: "ctrl+shift+a".
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyCombo {
+    pub modifiers: Vec<Modifier>,
+    pub key: Key,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyComboParseError {
+    combo: String,
+}
+
+impl StdError for KeyComboParseError {}
+impl Display for KeyComboParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "invalid key combo: \"{}\"", self.combo)
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = KeyComboParseError;
+
+    fn from_str(combo: &str) -> Result<Self, Self::Err> {
+        let error = || KeyComboParseError { combo: combo.to_string() };
+        let mut parts = combo.split('+').map(|part| part.trim().to_lowercase()).collect::<Vec<String>>();
+
+        let key = parts.pop().filter(|key| !key.is_empty()).ok_or_else(error)?;
+        let key = parse_key(&key).ok_or_else(error)?;
+
+        let mut modifiers = vec![];
+        for part in parts {
+            let modifier = parse_modifier(&part).ok_or_else(error)?;
+            if !modifiers.contains(&modifier) {
+                modifiers.push(modifier);
+            }
+        }
+
+        return Ok(KeyCombo { modifiers, key });
+    }
+}
+
+fn parse_modifier(modifier: &str) -> Option<Modifier> {
+    return match modifier {
+        "alt" | "option" => Some(Modifier::Alt),
+        "ctrl" | "control" => Some(Modifier::Control),
+        "cmd" | "meta" | "super" | "win" => Some(Modifier::Meta),
+        "shift" => Some(Modifier::Shift),
+        _ => None,
+    };
+}
+
+fn parse_key(key: &str) -> Option<Key> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Char(c));
+    }
+
+    return match key {
+        "backspace" => Some(Key::Backspace),
+        "delete" | "del" => Some(Key::Delete),
+        "down" => Some(Key::Down),
+        "end" => Some(Key::End),
+        "enter" | "return" => Some(Key::Enter),
+        "escape" | "esc" => Some(Key::Escape),
+        "home" => Some(Key::Home),
+        "left" => Some(Key::Left),
+        "pagedown" => Some(Key::PageDown),
+        "pageup" => Some(Key::PageUp),
+        "right" => Some(Key::Right),
+        "space" => Some(Key::Space),
+        "tab" => Some(Key::Tab),
+        "up" => Some(Key::Up),
+        _ => key.strip_prefix('f')
+            .and_then(|number| number.parse::<u8>().ok())
+            .filter(|number| *number >= 1 && *number <= 12)
+            .map(Key::Function),
+    };
+}
+
+/// A keyboard is what the macropad app will send key combos to
+pub trait Keyboard {
+    fn send(&mut self, combo: &KeyCombo);
+}
+
+/// Without the "hid" feature, key combos are only logged
+#[cfg(not(feature = "hid"))]
+pub struct SystemKeyboard {}
+
+#[cfg(not(feature = "hid"))]
+impl SystemKeyboard {
+    pub fn new() -> Self {
+        return SystemKeyboard {};
+    }
+}
+
+#[cfg(not(feature = "hid"))]
+impl Keyboard for SystemKeyboard {
+    fn send(&mut self, combo: &KeyCombo) {
+        println!("[macropad] the \"hid\" feature is disabled, not sending {:?}", combo);
+    }
+}
+
+#[cfg(feature = "hid")]
+pub struct SystemKeyboard {
+    enigo: enigo::Enigo,
+}
+
+#[cfg(feature = "hid")]
+impl SystemKeyboard {
+    pub fn new() -> Self {
+        return SystemKeyboard { enigo: enigo::Enigo::new() };
+    }
+}
+
+#[cfg(feature = "hid")]
+impl Keyboard for SystemKeyboard {
+    fn send(&mut self, combo: &KeyCombo) {
+        use enigo::KeyboardControllable;
+
+        let modifiers = combo.modifiers.iter().map(|modifier| match modifier {
+            Modifier::Alt => enigo::Key::Alt,
+            Modifier::Control => enigo::Key::Control,
+            Modifier::Meta => enigo::Key::Meta,
+            Modifier::Shift => enigo::Key::Shift,
+        }).collect::<Vec<enigo::Key>>();
+
+        let key = match combo.key {
+            Key::Char(c) => enigo::Key::Layout(c),
+            Key::Function(1) => enigo::Key::F1,
+            Key::Function(2) => enigo::Key::F2,
+            Key::Function(3) => enigo::Key::F3,
+            Key::Function(4) => enigo::Key::F4,
+            Key::Function(5) => enigo::Key::F5,
+            Key::Function(6) => enigo::Key::F6,
+            Key::Function(7) => enigo::Key::F7,
+            Key::Function(8) => enigo::Key::F8,
+            Key::Function(9) => enigo::Key::F9,
+            Key::Function(10) => enigo::Key::F10,
+            Key::Function(11) => enigo::Key::F11,
+            Key::Function(_) => enigo::Key::F12,
+            Key::Backspace => enigo::Key::Backspace,
+            Key::Delete => enigo::Key::Delete,
+            Key::Down => enigo::Key::DownArrow,
+            Key::End => enigo::Key::End,
+            Key::Enter => enigo::Key::Return,
+            Key::Escape => enigo::Key::Escape,
+            Key::Home => enigo::Key::Home,
+            Key::Left => enigo::Key::LeftArrow,
+            Key::PageDown => enigo::Key::PageDown,
+            Key::PageUp => enigo::Key::PageUp,
+            Key::Right => enigo::Key::RightArrow,
+            Key::Space => enigo::Key::Space,
+            Key::Tab => enigo::Key::Tab,
+            Key::Up => enigo::Key::UpArrow,
+        };
+
+        for modifier in &modifiers {
+            self.enigo.key_down(*modifier);
+        }
+        self.enigo.key_click(key);
+        for modifier in modifiers.iter().rev() {
+            self.enigo.key_up(*modifier);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_str_when_single_character_then_return_combo_without_modifiers() {
+        assert_eq!("a".parse::<KeyCombo>(), Ok(KeyCombo { modifiers: vec![], key: Key::Char('a') }));
+    }
+
+    #[test]
+    fn from_str_when_modifiers_then_return_them_in_order() {
+        assert_eq!("ctrl+shift+a".parse::<KeyCombo>(), Ok(KeyCombo {
+            modifiers: vec![Modifier::Control, Modifier::Shift],
+            key: Key::Char('a'),
+        }));
+        assert_eq!(" Cmd + Space ".parse::<KeyCombo>(), Ok(KeyCombo {
+            modifiers: vec![Modifier::Meta],
+            key: Key::Space,
+        }));
+    }
+
+    #[test]
+    fn from_str_when_named_keys_then_return_corresponding_keys() {
+        assert_eq!("f5".parse::<KeyCombo>().map(|combo| combo.key), Ok(Key::Function(5)));
+        assert_eq!("alt+f12".parse::<KeyCombo>().map(|combo| combo.key), Ok(Key::Function(12)));
+        assert_eq!("enter".parse::<KeyCombo>().map(|combo| combo.key), Ok(Key::Enter));
+        assert_eq!("esc".parse::<KeyCombo>().map(|combo| combo.key), Ok(Key::Escape));
+        assert_eq!("shift+pageup".parse::<KeyCombo>().map(|combo| combo.key), Ok(Key::PageUp));
+    }
+
+    #[test]
+    fn from_str_when_plus_is_doubled_then_fail() {
+        assert!("ctrl++".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn from_str_when_invalid_combo_then_fail() {
+        for combo in ["", "ctrl+", "hyper+a", "f13", "f0", "ctrl+shift", "enterr"] {
+            assert_eq!(combo.parse::<KeyCombo>(), Err(KeyComboParseError { combo: combo.to_string() }));
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod app;
+pub mod config;
+pub mod keys;
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use std::convert::From;
 use std::sync::Arc;
 
 use serde::{Serialize, Deserialize};
+use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::error::{SendError, TryRecvError};
 
 use dialoguer::{theme::ColorfulTheme, Select};
@@ -9,12 +11,20 @@ use dialoguer::{theme::ColorfulTheme, Select};
 use crate::image::Image;
 pub use crate::midi::Event as MidiEvent;
 pub use crate::midi::features::Features;
+use crate::midi::devices::config::DeviceType;
+use crate::midi::devices::get_default_features;
 pub use crate::server::Command as ServerCommand;
 
+pub mod confirm;
 pub mod forward;
+pub mod http;
+pub mod macropad;
 pub mod paint;
+pub mod render;
 pub mod selection;
+pub mod send_policy;
 pub mod spotify;
+pub mod webhook;
 pub mod youtube;
 
 pub trait App {
@@ -35,15 +45,152 @@ pub trait App {
 
     /// Lifecycle callback that gets called every time the app gets the focus
     fn on_select(&mut self);
+
+    /// Lifecycle callback that gets called when the app loses the focus, before the next app gets
+    /// selected, or when its input device gets disconnected: apps can use it to stop what they
+    /// were doing, or to release what they were holding (e.g. sustained notes). Does nothing by default.
+    fn on_deselect(&mut self) {}
+
+    /// Render the current view of the app again, e.g. when its output device gets reconnected
+    /// and has lost what was displayed on it.
+    fn render(&mut self) {
+        self.on_select();
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     pub forward: Option<forward::config::Config>,
+    pub macropad: Option<macropad::config::Config>,
     pub paint: Option<paint::config::Config>,
     pub spotify: Option<spotify::config::Config>,
     pub youtube: Option<youtube::config::Config>,
+    pub webhook: Option<webhook::config::Config>,
     pub selection: Option<selection::config::Config>,
+    /// Per-app device types, used to interpret events regardless of the devices linked to the app
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub overrides: HashMap<String, FeaturesOverride>,
+    /// What the router does with events sent to apps that are not keeping up with them
+    #[serde(default)]
+    pub send_policy: send_policy::SendPolicy,
+}
+
+const OVERRIDES_KEY: &'static str = "overrides";
+const SEND_POLICY_KEY: &'static str = "send_policy";
+
+type Starter = fn(
+    &Config,
+    Arc<dyn Features + Sync + Send>,
+    Arc<dyn Features + Sync + Send>,
+) -> Option<Box<dyn App>>;
+
+type Configurer = fn(&mut Config) -> Result<(), Box<dyn std::error::Error>>;
+
+/// How to start an app from its configuration (if any), and how to configure it interactively
+struct AppRegistration {
+    name: &'static str,
+    start: Starter,
+    configure: Configurer,
+}
+
+/// Every app needs to be registered here, on top of having its own field in `Config`
+const APPS: [AppRegistration; 7] = [
+    AppRegistration {
+        name: forward::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.forward.as_ref()?;
+            Some(Box::new(forward::app::Forward::new(config.clone(), input_features, output_features, send_policy)))
+        },
+        configure: |config| {
+            config.forward = configure_app(forward::app::NAME, forward::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: macropad::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.macropad.as_ref()?;
+            Some(Box::new(macropad::app::Macropad::new(config.clone(), input_features, output_features, send_policy)))
+        },
+        configure: |config| {
+            config.macropad = configure_app(macropad::app::NAME, macropad::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: paint::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.paint.as_ref()?;
+            Some(Box::new(paint::app::Paint::new(config.clone(), input_features, output_features, send_policy)))
+        },
+        configure: |config| {
+            config.paint = configure_app(paint::app::NAME, paint::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: spotify::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.spotify.as_ref()?;
+            Some(Box::new(spotify::app::Spotify::new(
+                config.clone(),
+                Box::new(spotify::client::SpotifyApiClientImpl::from(&config.http)),
+                input_features,
+                output_features,
+                send_policy)))
+        },
+        configure: |config| {
+            config.spotify = configure_app(spotify::app::NAME, spotify::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: youtube::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.youtube.as_ref()?;
+            Some(Box::new(youtube::app::Youtube::new(config.clone(), input_features, output_features, send_policy)))
+        },
+        configure: |config| {
+            config.youtube = configure_app(youtube::app::NAME, youtube::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: webhook::app::NAME,
+        start: |config, input_features, output_features| {
+            let send_policy = config.send_policy;
+            let config = config.webhook.as_ref()?;
+            Some(Box::new(webhook::app::Webhook::new(config.clone(), input_features, output_features, send_policy)))
+        },
+        configure: |config| {
+            config.webhook = configure_app(webhook::app::NAME, webhook::config::configure)?;
+            Ok(())
+        },
+    },
+    AppRegistration {
+        name: selection::app::NAME,
+        start: |config, input_features, output_features| {
+            let config = config.selection.as_ref()?;
+            Some(Box::new(selection::app::Selection::new(config.clone(), input_features, output_features)))
+        },
+        configure: |config| {
+            config.selection = configure_app(selection::app::NAME, selection::config::configure)?;
+            Ok(())
+        },
+    },
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeaturesOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_device_type: Option<DeviceType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_device_type: Option<DeviceType>,
 }
 
 impl Config {
@@ -53,36 +200,21 @@ impl Config {
         input_features: Arc<dyn Features + Sync + Send>,
         output_features: Arc<dyn Features + Sync + Send>,
     ) -> Option<Box<dyn App>> {
-        return match app_name {
-            forward::app::NAME => {
-                let config = self.forward.as_ref()?;
-                Some(Box::new(forward::app::Forward::new(config.clone(), input_features, output_features)))
-            }
-            paint::app::NAME => {
-                let config = self.paint.as_ref()?;
-                Some(Box::new(paint::app::Paint::new(config.clone(), input_features, output_features)))
-            },
-            spotify::app::NAME => {
-                let config = self.spotify.as_ref()?;
-                Some(Box::new(spotify::app::Spotify::new(
-                    config.clone(),
-                    Box::new(spotify::client::SpotifyApiClientImpl::new()),
-                    input_features,
-                    output_features)))
-            }
-            youtube::app::NAME => {
-                let config = self.youtube.as_ref()?;
-                Some(Box::new(youtube::app::Youtube::new(config.clone(), input_features, output_features)))
-            }
-            selection::app::NAME => {
-                let config = self.selection.as_ref()?;
-                Some(Box::new(selection::app::Selection::new(config.clone(), input_features, output_features)))
-            }
-            _ => {
+        let (input_features, output_features) = match self.overrides.get(app_name) {
+            None => (input_features, output_features),
+            Some(features_override) => (
+                features_override.input_device_type.map(get_default_features).unwrap_or(input_features),
+                features_override.output_device_type.map(get_default_features).unwrap_or(output_features),
+            ),
+        };
+
+        return match APPS.iter().find(|app| app.name == app_name) {
+            Some(app) => (app.start)(self, input_features, output_features),
+            None => {
                 eprintln!("[apps] unknown application: {}", app_name);
                 None
             },
-        }
+        };
     }
 
     pub fn start_all(
@@ -102,18 +234,31 @@ impl Config {
             _ => toml::map::Map::new(),
         };
 
-        return app_config.keys().map(|key| key.to_string()).collect::<Vec<String>>();
+        return app_config.keys()
+            .filter(|key| key.as_str() != OVERRIDES_KEY && key.as_str() != SEND_POLICY_KEY)
+            .map(|key| key.to_string())
+            .collect::<Vec<String>>();
     }
 }
 
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
-    return Ok(Config {
-        forward: configure_app(forward::app::NAME, forward::config::configure)?,
-        paint: configure_app(paint::app::NAME, paint::config::configure)?,
-        spotify: configure_app(spotify::app::NAME, spotify::config::configure)?,
-        youtube: configure_app(youtube::app::NAME, youtube::config::configure)?,
-        selection: configure_app(selection::app::NAME, selection::config::configure)?,
-    });
+    let mut config = Config {
+        forward: None,
+        macropad: None,
+        paint: None,
+        spotify: None,
+        youtube: None,
+        webhook: None,
+        selection: None,
+        overrides: HashMap::new(),
+        send_policy: send_policy::SendPolicy::default(),
+    };
+
+    for app in &APPS {
+        (app.configure)(&mut config)?;
+    }
+
+    return Ok(config);
 }
 
 fn configure_app<F, C>(name: &'static str, conf: F) -> Result<Option<C>, Box<dyn std::error::Error>> where
@@ -143,7 +288,27 @@ pub enum In {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Out {
     Midi(MidiEvent),
+    /// Events written contiguously, without events from other apps in between (e.g. clear+draw)
+    MidiBatch(Vec<MidiEvent>),
     Server(ServerCommand),
+    /// Events that are not meant for any device, but get logged and counted by the router
+    Telemetry(TelemetryEvent),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum TelemetryEvent {
+    /// The app started playing the track at the given index of its playlist
+    TrackChanged { index: usize, name: String },
+    /// The app failed to do what it was asked for (e.g. pulling its playlist, or playing a track)
+    Error { message: String },
+}
+
+/// Log the error, and send it to the router as well, so that it shows up in the stats
+pub async fn report_error(app_name: &str, sender: &Sender<Out>, message: String) {
+    eprintln!("[{}] {}", app_name, message);
+    sender.send(TelemetryEvent::Error { message }.into()).await.unwrap_or_else(|err| {
+        eprintln!("[{}] could not send the error back to the router: {}", app_name, err)
+    });
 }
 
 impl From<MidiEvent> for In {
@@ -158,6 +323,12 @@ impl From<MidiEvent> for Out {
     }
 }
 
+impl From<TelemetryEvent> for Out {
+    fn from(event: TelemetryEvent) -> Self {
+        return Out::Telemetry(event);
+    }
+}
+
 impl From<ServerCommand> for In {
     fn from(command: ServerCommand) -> Self {
         return In::Server(command);
@@ -206,6 +377,88 @@ mod test {
         assert_eq!(app.unwrap().get_name(), "forward");
     }
 
+    #[test]
+    pub fn test_start_configured_macropad() {
+        let config: Config = toml::from_str(r#"
+            [macropad.shortcuts]
+            0 = "ctrl+c"
+            1 = "ctrl+v"
+        "#).unwrap();
+
+        let app = config.start(
+            "macropad",
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+        );
+
+        assert!(app.is_some());
+        assert_eq!(app.unwrap().get_name(), "macropad");
+    }
+
+    #[test]
+    pub fn test_start_with_input_override() {
+        let config: Config = toml::from_str(r#"
+            [paint]
+            [overrides.paint]
+            input_device_type = "launchkey"
+        "#).unwrap();
+
+        let app = config.start(
+            "paint",
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+        ).unwrap();
+
+        // the logo of the paint app has the size of the input device’s grid
+        let logo = app.get_logo();
+        assert_eq!((logo.width, logo.height), (8, 2));
+    }
+
+    #[test]
+    pub fn test_start_with_output_override() {
+        let config: Config = toml::from_str(r#"
+            [paint]
+            [overrides.paint]
+            output_device_type = "launchkey"
+        "#).unwrap();
+
+        let mut app = config.start(
+            "paint",
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+        ).unwrap();
+
+        // the color palette is rendered with the Launchkey’s SysEx messages
+        app.on_select();
+        match app.receive() {
+            Ok(Out::Midi(MidiEvent::SysEx(bytes))) => assert_eq!(bytes[0..7], [240, 0, 32, 41, 2, 15, 3]),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    pub fn test_get_configured_app_names_with_overrides() {
+        let config: Config = toml::from_str(r#"
+            [forward]
+            [overrides.forward]
+            input_device_type = "launchpadpro"
+        "#).unwrap();
+
+        assert_eq!(config.get_configured_app_names(), vec!["forward"]);
+    }
+
+    #[test]
+    pub fn test_get_configured_app_names_with_send_policy() {
+        let config: Config = toml::from_str(r#"
+            [forward]
+            [send_policy]
+            type = "drop"
+        "#).unwrap();
+
+        assert_eq!(config.send_policy, send_policy::SendPolicy::Drop);
+        assert_eq!(config.get_configured_app_names(), vec!["forward"]);
+    }
+
     #[test]
     pub fn test_start_all_with_no_apps() {
         let config: Config = toml::from_str(r#"
@@ -228,4 +481,58 @@ mod test {
 
         assert_eq!(apps.iter().map(|app| app.get_name()).collect::<Vec<&str>>(), vec!["forward", "youtube"]);
     }
+
+    #[test]
+    pub fn test_start_every_registered_app() {
+        let config: Config = toml::from_str(r#"
+            [forward]
+            [macropad.shortcuts]
+            0 = "ctrl+c"
+            [paint]
+            [spotify]
+            playlist_id = "playlist_id"
+            client_id = "client_id"
+            client_secret = "client_secret"
+            refresh_token = "refresh_token"
+            [youtube]
+            api_key = "api_key"
+            playlist_id = "playlist_id"
+            [webhook.hooks]
+            0 = { url = "http://localhost" }
+            [selection.apps.forward]
+        "#).unwrap();
+
+        for registration in &APPS {
+            let app = config.start(
+                registration.name,
+                Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+                Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            );
+
+            assert_eq!(app.map(|app| app.get_name()), Some(registration.name));
+        }
+    }
+
+    #[test]
+    pub fn test_every_configured_app_is_registered() {
+        let config: Config = toml::from_str(r#"
+            [forward]
+            [macropad.shortcuts]
+            [paint]
+            [spotify]
+            playlist_id = "playlist_id"
+            client_id = "client_id"
+            client_secret = "client_secret"
+            refresh_token = "refresh_token"
+            [youtube]
+            api_key = "api_key"
+            playlist_id = "playlist_id"
+            [webhook.hooks]
+            [selection.apps]
+        "#).unwrap();
+
+        let mut registered_names = APPS.iter().map(|app| app.name.to_string()).collect::<Vec<String>>();
+        registered_names.sort();
+        assert_eq!(config.get_configured_app_names(), registered_names);
+    }
 }
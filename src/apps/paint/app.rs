@@ -1,15 +1,22 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::sync::Arc;
 
 use tokio::sync::mpsc::{channel, Sender, Receiver};
 use tokio::sync::mpsc::error::{SendError, TryRecvError};
 
 use crate::apps::{App, Image, In, Out};
-use crate::midi::features::Features;
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
+use crate::midi::MidiMessage;
+use crate::midi::features::{Features, UnsupportedFeatureError};
 use super::config::Config;
 
 pub const NAME: &'static str = "paint";
 pub const COLOR: [u8; 3] = [255, 255, 0];
 
+/// Size of the canvas when neither the input device nor the configuration tell otherwise
+pub const DEFAULT_GRID_SIZE: (usize, usize) = (8, 8);
+
 pub const COLOR_PALETTE: [[u8; 3]; 8] = [
     [000, 000, 000],
     [000, 000, 255],
@@ -28,18 +35,29 @@ pub struct Paint {
     receiver: Receiver<Out>,
     image: Image,
     color: [u8; 3],
+    /// Index of the selected color in the palette
+    color_index: usize,
+    min_velocity: u8,
+    pressure_brightness: bool,
+    /// Highest pressure applied on each pad since it got pressed, for the pads still held
+    peak_pressures: HashMap<(usize, usize), u8>,
+    /// Pixel drawn by the last pressed pad, which channel pressure applies to while it is held
+    last_pixel: Option<(usize, usize)>,
+    send_policy: SendPolicy,
 }
 
 impl Paint {
     pub fn new(
-        _config: Config,
+        config: Config,
         input_features: Arc<dyn Features + Sync + Send>,
         output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
     ) -> Self {
         let (sender, receiver) = channel::<Out>(32);
         let (width, height) = input_features.get_grid_size().unwrap_or_else(|err| {
-            eprintln!("[paint] falling back to a zero-pixel image, as the input device’s grid size cannot be retrieved: {}", err);
-            (0, 0)
+            let (width, height) = config.default_grid_size.unwrap_or(DEFAULT_GRID_SIZE);
+            eprintln!("[paint] WARNING: falling back to a {}x{} canvas, as the input device’s grid size cannot be retrieved: {}", width, height, err);
+            (width, height)
         });
 
         let image = Image { width, height, bytes: vec![0; width * height * 3] };
@@ -51,47 +69,106 @@ impl Paint {
             receiver,
             image,
             color: COLOR_PALETTE[0],
+            color_index: 0,
+            min_velocity: config.min_velocity,
+            pressure_brightness: config.pressure_brightness,
+            peak_pressures: HashMap::new(),
+            last_pixel: None,
+            send_policy,
         };
     }
 
     fn render_color_palette(&self) {
-        match self.output_features.from_color_palette(Vec::from(COLOR_PALETTE)) {
-            Ok(event) => self.sender.blocking_send(event.into()).unwrap_or_else(|err| {
-                eprintln!("[paint] could not send event back to router: {}", err)
-            }),
+        match self.output_features.from_color_palette_to_events(Vec::from(COLOR_PALETTE)) {
+            Ok(events) => for event in events {
+                send_with_policy(NAME, &self.sender, event.into(), self.send_policy).unwrap_or_else(|err| {
+                    eprintln!("[paint] could not send event back to router: {}", err)
+                });
+            },
             Err(err) => eprintln!("[paint] could not transform the COLOR_PALETTE into a midi event: {}", err)
         }
     }
 
-    fn render_pixel(&mut self, x: usize, y: usize) {
+    fn render_pixel(&mut self, x: usize, y: usize, color: [u8; 3]) {
         if x < self.image.width && y < self.image.height {
-            let byte_pos = y * 3 * 8 + x * 3;
+            let byte_pos = (y * self.image.width + x) * 3;
             let pixel = &mut self.image.bytes[byte_pos..(byte_pos + 3)];
 
             // Set the pixel yellow!
-            pixel[0] = self.color[0];
-            pixel[1] = self.color[1];
-            pixel[2] = self.color[2];
+            pixel[0] = color[0];
+            pixel[1] = color[1];
+            pixel[2] = color[2];
 
-            match self.output_features.from_image(self.image.clone()) {
-                Ok(event) => self.sender.blocking_send(event.into()).unwrap_or_else(|err| {
+            // Only light the pad that changed, unless the device can only render full images
+            match self.output_features.from_pixel(x, y, color) {
+                Ok(event) => send_with_policy(NAME, &self.sender, event.into(), self.send_policy).unwrap_or_else(|err| {
                     eprintln!("[paint] could not send event back to the router: {}", err)
                 }),
-                Err(err) => eprintln!("[paint] could not transform the image into a MIDI event: {}", err),
+                Err(err) if err.is::<UnsupportedFeatureError>() => self.render_image(),
+                Err(err) => eprintln!("[paint] could not transform the pixel into a MIDI event: {}", err),
             }
         } else {
             eprintln!("[paint] ({}, {}) is out of bound", x, y);
         }
     }
 
+    /// Scale the selected color by the peak pressure applied on the pad while it is held, from
+    /// black (0) to the full color (127), so that easing off at the end of a stroke keeps it lit.
+    fn apply_pressure(&mut self, coordinates: Option<(usize, usize)>, value: u8) {
+        // pads that are not held anymore keep the color they were left with
+        let peak_pressure = match coordinates.and_then(|coordinates| self.peak_pressures.get_mut(&coordinates)) {
+            Some(peak_pressure) if value > *peak_pressure => {
+                *peak_pressure = value;
+                value
+            },
+            _ => return,
+        };
+
+        if let Some((x, y)) = coordinates {
+            let color = self.color.map(|channel| (channel as u32 * peak_pressure.min(127) as u32 / 127) as u8);
+            self.render_pixel(x, y, color);
+        }
+    }
+
+    fn release_pad(&mut self, channel: u8, note: u8) {
+        if let Some(coordinates) = self.into_pad_coordinates(channel, note) {
+            self.peak_pressures.remove(&coordinates);
+            if self.last_pixel == Some(coordinates) {
+                self.last_pixel = None;
+            }
+        }
+    }
+
+    /// Pads are identified by the note-on event they emit when pressed
+    fn into_pad_coordinates(&self, channel: u8, note: u8) -> Option<(usize, usize)> {
+        return self.input_features.into_coordinates(MidiMessage::NoteOn { channel, note, velocity: 127 }.into()).ok().flatten();
+    }
+
+    fn render_image(&self) {
+        match self.output_features.from_image(self.image.clone()) {
+            Ok(event) => send_with_policy(NAME, &self.sender, event.into(), self.send_policy).unwrap_or_else(|err| {
+                eprintln!("[paint] could not send event back to the router: {}", err)
+            }),
+            Err(err) => eprintln!("[paint] could not transform the image into a MIDI event: {}", err),
+        }
+    }
+
     fn select_color(&mut self, index: usize) {
         if index < COLOR_PALETTE.len() {
             self.color = COLOR_PALETTE[index];
+            self.color_index = index;
             println!("[paint] selected color: {:?}", self.color);
         } else {
             eprintln!("[paint] color {} is out of bound", index);
         }
     }
+
+    /// Move the selection through the palette, going around it when reaching one of its ends
+    fn move_color(&mut self, delta: i8) {
+        let palette_size = COLOR_PALETTE.len() as isize;
+        let index = (self.color_index as isize + isize::from(delta)).rem_euclid(palette_size);
+        self.select_color(index as usize);
+    }
 }
 
 impl App for Paint {
@@ -119,8 +196,48 @@ impl App for Paint {
                     Err(e) => eprintln!("[paint] error when transforming incoming event into color index: {}", e),
                 }
 
+                match self.input_features.into_palette_delta(event.clone()) {
+                    Ok(Some(delta)) => {
+                        self.move_color(delta);
+                        return Ok(());
+                    },
+                    Ok(_) => {},
+                    Err(e) if e.is::<UnsupportedFeatureError>() => {},
+                    Err(e) => eprintln!("[paint] error when transforming incoming event into color delta: {}", e),
+                }
+
+                let message = MidiMessage::try_from(event.clone());
+                if self.pressure_brightness {
+                    match message {
+                        Ok(MidiMessage::PolyPressure { channel, note, pressure }) => {
+                            self.apply_pressure(self.into_pad_coordinates(channel, note), pressure);
+                            return Ok(());
+                        },
+                        // channel pressure doesn’t tell which pad is pressed: we assume it’s the last one
+                        Ok(MidiMessage::ChannelPressure { pressure, .. }) => {
+                            self.apply_pressure(self.last_pixel, pressure);
+                            return Ok(());
+                        },
+                        Ok(MidiMessage::NoteOff { channel, note, .. }) | Ok(MidiMessage::NoteOn { channel, note, velocity: 0 }) => {
+                            self.release_pad(channel, note);
+                            return Ok(());
+                        },
+                        _ => {},
+                    }
+                }
+
+                if let Ok(MidiMessage::NoteOn { velocity, .. }) = message {
+                    if velocity < self.min_velocity {
+                        return Ok(());
+                    }
+                }
+
                 match self.input_features.into_coordinates(event) {
-                    Ok(Some((x, y))) => self.render_pixel(x, y),
+                    Ok(Some((x, y))) => {
+                        self.peak_pressures.insert((x, y), 0);
+                        self.last_pixel = Some((x, y));
+                        self.render_pixel(x, y, self.color);
+                    },
                     Ok(_) => {}, // we ignore events that don’t map to a set of coordinates
                     Err(e) => eprintln!("[paint] error when transforming incoming event: {}", e),
                 }
@@ -137,6 +254,11 @@ impl App for Paint {
     fn on_select(&mut self) {
         self.render_color_palette();
     }
+
+    fn render(&mut self) {
+        self.render_color_palette();
+        self.render_image();
+    }
 }
 
 #[cfg(test)]
@@ -172,6 +294,42 @@ mod test {
         assert!(event.is_err());
     }
 
+    #[test]
+    fn render_when_pixels_were_drawn_then_render_color_palette_and_canvas() {
+        let mut paint = get_paint();
+
+        // select cyan and press (1, 0), then discard the events it triggered
+        paint.send(In::Midi(Event::Midi([176, 3, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 1, 0, 0]))).unwrap();
+        while paint.receive().is_ok() {}
+
+        paint.render();
+
+        let event = paint.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![
+            b'p', b'a', b'l', b'e', b't', b't', b'e',
+            000, 000, 000,
+            000, 000, 255,
+            000, 255, 000,
+            000, 255, 255,
+            255, 000, 000,
+            255, 000, 255,
+            255, 255, 000,
+            255, 255, 255,
+        ])));
+
+        let event = paint.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e',
+            000, 000, 000, 000, 255, 255,
+            000, 000, 000, 000, 000, 000,
+        ])));
+
+        // We don’t expect any additional event
+        let event = paint.receive();
+        assert!(event.is_err());
+    }
+
     #[test]
     fn get_logo_when_app_starts_then_return_a_black_image_of_the_size_of_the_grid() {
         let paint = get_paint();
@@ -212,15 +370,188 @@ mod test {
         assert!(event.is_err());
     }
 
+    #[test]
+    fn when_velocity_is_below_min_velocity_then_do_not_draw() {
+        let mut paint = get_paint_with_min_velocity(64);
+
+        // press (1, 0) too lightly
+        paint.send(In::Midi(Event::Midi([144, 1, 40, 0]))).unwrap();
+        assert!(paint.receive().is_err());
+        assert_eq!(paint.get_logo().bytes, vec![0; 12]);
+
+        // press (1, 0) hard enough, with the default color (black)
+        paint.send(In::Midi(Event::Midi([144, 1, 64, 0]))).unwrap();
+        let event = paint.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e',
+            000, 000, 000, 000, 000, 000,
+            000, 000, 000, 000, 000, 000,
+        ])));
+    }
+
     fn get_paint() -> Paint {
-        return Paint::new(
-            Config {},
-            Arc::new(FakeFeatures {}),
-            Arc::new(FakeFeatures {}),
-        );
+        return get_paint_with_min_velocity(0);
+    }
+
+    fn get_paint_with_min_velocity(min_velocity: u8) -> Paint {
+        let features = Arc::new(FakeFeatures { supports_from_pixel: false });
+        return Paint::new(Config { min_velocity, pressure_brightness: false, default_grid_size: None }, Arc::clone(&features) as Arc<dyn Features + Sync + Send>, features, SendPolicy::default());
+    }
+
+    #[test]
+    fn when_device_can_light_single_pads_then_only_send_the_drawn_pixel() {
+        let features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        let mut paint = Paint::new(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }, Arc::clone(&features) as Arc<dyn Features + Sync + Send>, features, SendPolicy::default());
+
+        // select cyan, then press (1, 0)
+        paint.send(In::Midi(Event::Midi([176, 3, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 1, 100, 0]))).unwrap();
+
+        let event = paint.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![b'p', b'i', b'x', b'e', b'l', 1, 0, 000, 255, 255])));
+
+        // We don’t expect a full frame
+        let event = paint.receive();
+        assert!(event.is_err());
+
+        // The canvas still gets updated
+        assert_eq!(paint.get_logo().bytes, vec![
+            000, 000, 000, 000, 255, 255,
+            000, 000, 000, 000, 000, 000,
+        ]);
     }
 
-    struct FakeFeatures {}
+    fn get_paint_with_pressure_brightness() -> Paint {
+        let features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        return Paint::new(Config { min_velocity: 0, pressure_brightness: true, default_grid_size: None }, Arc::clone(&features) as Arc<dyn Features + Sync + Send>, features, SendPolicy::default());
+    }
+
+    #[test]
+    fn when_aftertouch_follows_a_note_then_scale_the_pixel_color_by_the_pressure() {
+        let mut paint = get_paint_with_pressure_brightness();
+
+        // select cyan, press (1, 0), then apply half of the maximum pressure on it
+        paint.send(In::Midi(Event::Midi([176, 3, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 1, 100, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 1, 64, 0]))).unwrap();
+
+        assert_eq!(paint.receive().unwrap(), Out::Midi(Event::SysEx(vec![b'p', b'i', b'x', b'e', b'l', 1, 0, 000, 255, 255])));
+        assert_eq!(paint.receive().unwrap(), Out::Midi(Event::SysEx(vec![b'p', b'i', b'x', b'e', b'l', 1, 0, 000, 128, 128])));
+        assert_eq!(paint.get_logo().bytes, vec![
+            000, 000, 000, 000, 128, 128,
+            000, 000, 000, 000, 000, 000,
+        ]);
+    }
+
+    #[test]
+    fn when_channel_pressure_follows_a_note_then_scale_the_last_pixel_color_by_the_pressure() {
+        let mut paint = get_paint_with_pressure_brightness();
+
+        // select red, press (0, 0), then apply a light pressure on the whole channel
+        paint.send(In::Midi(Event::Midi([176, 4, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([208, 32, 0, 0]))).unwrap();
+
+        assert_eq!(paint.get_logo().bytes, vec![
+            064, 000, 000, 000, 000, 000,
+            000, 000, 000, 000, 000, 000,
+        ]);
+    }
+
+    #[test]
+    fn when_aftertouch_decreases_then_keep_the_color_of_the_peak_pressure() {
+        let mut paint = get_paint_with_pressure_brightness();
+
+        // select cyan, press (1, 0), then press harder before easing off completely
+        paint.send(In::Midi(Event::Midi([176, 3, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 1, 100, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 1, 64, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 1, 32, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 1, 0, 0]))).unwrap();
+
+        assert_eq!(paint.receive().unwrap(), Out::Midi(Event::SysEx(vec![b'p', b'i', b'x', b'e', b'l', 1, 0, 000, 255, 255])));
+        assert_eq!(paint.receive().unwrap(), Out::Midi(Event::SysEx(vec![b'p', b'i', b'x', b'e', b'l', 1, 0, 000, 128, 128])));
+        assert!(paint.receive().is_err());
+        assert_eq!(paint.get_logo().bytes, vec![
+            000, 000, 000, 000, 128, 128,
+            000, 000, 000, 000, 000, 000,
+        ]);
+    }
+
+    #[test]
+    fn when_pressure_follows_the_release_of_the_pad_then_ignore_it() {
+        let mut paint = get_paint_with_pressure_brightness();
+
+        // select red, press and release (0, 0), then apply pressure on the key and the channel
+        paint.send(In::Midi(Event::Midi([176, 4, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([128, 0, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 0, 32, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([208, 32, 0, 0]))).unwrap();
+
+        assert_eq!(paint.get_logo().bytes, vec![
+            255, 000, 000, 000, 000, 000,
+            000, 000, 000, 000, 000, 000,
+        ]);
+    }
+
+    #[test]
+    fn when_pressure_brightness_is_disabled_then_ignore_aftertouch() {
+        let features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        let mut paint = Paint::new(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }, Arc::clone(&features) as Arc<dyn Features + Sync + Send>, features, SendPolicy::default());
+
+        paint.send(In::Midi(Event::Midi([176, 3, 0, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([144, 1, 100, 0]))).unwrap();
+        paint.send(In::Midi(Event::Midi([160, 1, 64, 0]))).unwrap();
+
+        assert_eq!(paint.get_logo().bytes, vec![
+            000, 000, 000, 000, 255, 255,
+            000, 000, 000, 000, 000, 000,
+        ]);
+    }
+
+    #[test]
+    fn when_palette_encoder_turns_then_move_the_selected_color_around_the_palette() {
+        let input_features = Arc::new(crate::midi::devices::default::DefaultFeatures {
+            palette_encoder: Some(crate::midi::devices::config::RelativeEncoder {
+                control: 70,
+                encoding: crate::midi::devices::config::RelativeEncoding::TwosComplement,
+            }),
+            ..crate::midi::devices::default::DefaultFeatures::new()
+        });
+        let output_features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        let mut paint = Paint::new(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }, input_features, output_features, SendPolicy::default());
+
+        // one step backwards from the first color leads to the last one
+        paint.send(In::Midi(Event::Midi([176, 70, 127, 0]))).unwrap();
+        assert_eq!(paint.color, COLOR_PALETTE[7]);
+
+        paint.send(In::Midi(Event::Midi([176, 70, 3, 0]))).unwrap();
+        assert_eq!(paint.color, COLOR_PALETTE[2]);
+    }
+
+    #[test]
+    fn new_when_input_device_has_no_grid_then_fall_back_to_the_default_grid_size() {
+        let input_features = Arc::new(crate::midi::devices::default::DefaultFeatures::new());
+        let output_features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        let paint = Paint::new(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }, input_features, output_features, SendPolicy::default());
+
+        assert_eq!(paint.get_logo(), Image { width: 8, height: 8, bytes: vec![0; 8 * 8 * 3] });
+    }
+
+    #[test]
+    fn new_when_input_device_has_no_grid_and_a_default_grid_size_is_configured_then_use_it() {
+        let input_features = Arc::new(crate::midi::devices::default::DefaultFeatures::new());
+        let output_features = Arc::new(FakeFeatures { supports_from_pixel: true });
+        let paint = Paint::new(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: Some((4, 2)) }, input_features, output_features, SendPolicy::default());
+
+        assert_eq!(paint.get_logo(), Image { width: 4, height: 2, bytes: vec![0; 4 * 2 * 3] });
+    }
+
+    /// Devices that cannot light single pads get full frames
+    struct FakeFeatures {
+        supports_from_pixel: bool,
+    }
     impl GridController for FakeFeatures {
         fn get_grid_size(&self) -> R<(usize, usize)> {
             Ok((2, 2))
@@ -228,7 +559,8 @@ mod test {
 
         fn into_coordinates(&self, event: Event) -> R<Option<(usize, usize)>> {
             Ok(match event {
-                Event::Midi([144, x, y, _]) => Some((x as usize, y as usize)),
+                // pads are numbered from left to right, then from top to bottom
+                Event::Midi([144, index, _, _]) => Some((index as usize % 2, index as usize / 2)),
                 _ => None,
             })
         }
@@ -255,6 +587,16 @@ mod test {
             bytes.append(&mut image.bytes);
             return Ok(Event::SysEx(bytes));
         }
+
+        fn from_pixel(&self, x: usize, y: usize, color: [u8; 3]) -> R<Event> {
+            if !self.supports_from_pixel {
+                return Err(Box::new(crate::midi::features::UnsupportedFeatureError::from("from_pixel")));
+            }
+
+            let mut bytes = Vec::from("pixel".as_bytes());
+            bytes.append(&mut vec![x as u8, y as u8, color[0], color[1], color[2]]);
+            return Ok(Event::SysEx(bytes));
+        }
     }
     impl Features for FakeFeatures {}
 }
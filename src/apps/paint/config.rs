@@ -3,10 +3,21 @@ use serde::{Serialize, Deserialize};
 /// Add (de)serializable attributes to this structure
 /// to make the Paint application configurable.
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Config {}
+pub struct Config {
+    /// Note-on events with a lower velocity are ignored, so that light touches don’t draw pixels
+    #[serde(default)]
+    pub min_velocity: u8,
+    /// Scale the color of the last drawn pixel by the pressure applied on its pad (aftertouch)
+    #[serde(default)]
+    pub pressure_brightness: bool,
+    /// Size of the canvas (width, height) when the input device cannot report the size of its
+    /// grid (8x8 by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_grid_size: Option<(usize, usize)>,
+}
 
 /// This function is supposed to onboard the user with configuration,
 /// prompting them questions to create an instance of Config at the end.
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
-    return Ok(Config {});
+    return Ok(Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None });
 }
@@ -0,0 +1,403 @@
+use tokio::sync::mpsc::Sender;
+
+use crate::apps::Out;
+use crate::image::{scale, Image};
+use crate::midi::Event;
+use crate::midi::features::{Features, R, UnsupportedFeatureError};
+
+/// Render an image on the output device.
+///
+/// Devices that cannot render a full image, but can light each pad with its own color, get a
+/// mosaic of the image scaled down to their grid size instead.
+pub fn from_image_or_mosaic(features: &(dyn Features + Sync + Send), image: Image) -> R<Event> {
+    return match features.from_image(image.clone()) {
+        Err(err) if err.is::<UnsupportedFeatureError>() => {
+            let (width, height) = features.get_grid_size()?;
+            let mosaic = scale(&image, width, height).map_err(|err| {
+                let err: Box<dyn std::error::Error + Send> = Box::new(err);
+                return err;
+            })?;
+
+            let images = mosaic.bytes
+                .chunks(3)
+                .map(|pixel| Image { width: 1, height: 1, bytes: pixel.to_vec() })
+                .collect();
+
+            features.from_images(images)
+        },
+        result => result,
+    };
+}
+
+/// Render an image on the output device, then highlight the given index (if any) on top of it.
+///
+/// Failing to render the image does not prevent the index from being highlighted, as some devices
+/// only support the latter.
+pub async fn render_image_with_highlight(
+    app_name: &str,
+    features: &(dyn Features + Sync + Send),
+    sender: &Sender<Out>,
+    image: Image,
+    index: Option<usize>,
+) {
+    match from_image_or_mosaic(features, image) {
+        Err(err) => eprintln!("[{}] could not render the image: {}", app_name, err),
+        Ok(event) => {
+            sender.send(event.into()).await.unwrap_or_else(|err| {
+                eprintln!("[{}] could not send the image back to the router: {}", app_name, err)
+            });
+        },
+    }
+
+    if let Some(index) = index {
+        match features.from_index_to_highlight(index) {
+            Err(err) => eprintln!("[{}] could not highlight the index {}: {}", app_name, index, err),
+            Ok(event) => {
+                sender.send(event.into()).await.unwrap_or_else(|err| {
+                    eprintln!("[{}] could not send the highlighting-index event back to the router: {}", app_name, err)
+                });
+            },
+        }
+    }
+}
+
+/// Color of the cross rendered when a playlist is empty, unless configured otherwise
+pub const DEFAULT_EMPTY_PLAYLIST_COLOR: [u8; 3] = [255, 0, 0];
+
+/// Render a cross of the given color, so that users know why pressing pads does nothing
+pub async fn render_empty_playlist(
+    app_name: &str,
+    features: &(dyn Features + Sync + Send),
+    sender: &Sender<Out>,
+    color: Option<[u8; 3]>,
+) {
+    let glyph = get_empty_playlist_glyph(color.unwrap_or(DEFAULT_EMPTY_PLAYLIST_COLOR));
+    render_image_with_highlight(app_name, features, sender, glyph, None).await;
+}
+
+/// An 8x8 cross on a black background
+pub fn get_empty_playlist_glyph(color: [u8; 3]) -> Image {
+    let bytes = (0..64)
+        .flat_map(|pixel| {
+            let (x, y) = (pixel % 8, pixel / 8);
+            if x == y || x + y == 7 { color } else { [0, 0, 0] }
+        })
+        .collect();
+    return Image { width: 8, height: 8, bytes };
+}
+
+/// Render the number of the selected index (counting from 1) instead of the image, if the device
+/// supports it. Otherwise, or if no index is selected, render the image with the highlighted index.
+pub async fn render_number_or_image_with_highlight(
+    app_name: &str,
+    features: &(dyn Features + Sync + Send),
+    sender: &Sender<Out>,
+    image: Image,
+    index: Option<usize>,
+    color: [u8; 3],
+) {
+    if let Some(index) = index {
+        match features.from_number((index + 1) as u16, color) {
+            Ok(event) => {
+                sender.send(event.into()).await.unwrap_or_else(|err| {
+                    eprintln!("[{}] could not send the number back to the router: {}", app_name, err)
+                });
+                return;
+            },
+            Err(err) => eprintln!("[{}] could not render the number {}: {}", app_name, index + 1, err),
+        }
+    }
+
+    render_image_with_highlight(app_name, features, sender, image, index).await;
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+
+    use tokio::runtime::Builder;
+
+    use crate::midi::features::{GridController, ImageRenderer, IndexSelector};
+    use super::*;
+
+    struct FakeFeatures {}
+    impl ImageRenderer for FakeFeatures {
+        fn from_image(&self, mut image: Image) -> R<Event> {
+            let mut bytes = Vec::from("IMG".as_bytes());
+            bytes.append(&mut image.bytes);
+            return Ok(Event::SysEx(bytes));
+        }
+    }
+    impl IndexSelector for FakeFeatures {
+        fn from_index_to_highlight(&self, index: usize) -> R<Event> {
+            return Ok(Event::Midi([index as u8, index as u8, index as u8, index as u8]));
+        }
+    }
+    impl Features for FakeFeatures {}
+
+    fn get_image() -> Image {
+        return Image { width: 1, height: 1, bytes: vec![1, 2, 3] };
+    }
+
+    #[test]
+    fn render_image_with_highlight_when_no_index_then_only_render_image() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_image_with_highlight("fake", &FakeFeatures {}, &sender, get_image(), None).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 1, 2, 3]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_image_with_highlight_when_index_then_render_image_and_highlight_index() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_image_with_highlight("fake", &FakeFeatures {}, &sender, get_image(), Some(42)).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 1, 2, 3]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::Midi([42, 42, 42, 42]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_image_with_highlight_when_image_unsupported_then_still_highlight_index() {
+        struct HighlightOnlyFeatures {}
+        impl IndexSelector for HighlightOnlyFeatures {
+            fn from_index_to_highlight(&self, index: usize) -> R<Event> {
+                return Ok(Event::Midi([index as u8, index as u8, index as u8, index as u8]));
+            }
+        }
+        impl Features for HighlightOnlyFeatures {}
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_image_with_highlight("fake", &HighlightOnlyFeatures {}, &sender, get_image(), Some(42)).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::Midi([42, 42, 42, 42]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_empty_playlist_when_no_color_then_render_a_red_cross() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_empty_playlist("fake", &FakeFeatures {}, &sender, None).await;
+            drop(sender);
+
+            let mut bytes = Vec::from("IMG".as_bytes());
+            bytes.append(&mut get_empty_playlist_glyph([255, 0, 0]).bytes);
+            assert_eq!(receiver.recv().await, Some(Out::Midi(Event::SysEx(bytes))));
+            assert_eq!(receiver.recv().await, None);
+        });
+    }
+
+    #[test]
+    fn get_empty_playlist_glyph_should_draw_both_diagonals() {
+        const X: [u8; 3] = [0, 0, 255];
+        const K: [u8; 3] = [0, 0, 0];
+        assert_eq!(get_empty_playlist_glyph(X).bytes, vec![
+            X, K, K, K, K, K, K, X,
+            K, X, K, K, K, K, X, K,
+            K, K, X, K, K, X, K, K,
+            K, K, K, X, X, K, K, K,
+            K, K, K, X, X, K, K, K,
+            K, K, X, K, K, X, K, K,
+            K, X, K, K, K, K, X, K,
+            X, K, K, K, K, K, K, X,
+        ].concat());
+    }
+
+    struct MosaicFeatures {}
+    impl GridController for MosaicFeatures {
+        fn get_grid_size(&self) -> R<(usize, usize)> {
+            return Ok((2, 1));
+        }
+    }
+    impl ImageRenderer for MosaicFeatures {
+        fn from_images(&self, images: Vec<Image>) -> R<Event> {
+            let mut bytes = Vec::from("PADS".as_bytes());
+            for mut image in images {
+                bytes.append(&mut image.bytes);
+            }
+            return Ok(Event::SysEx(bytes));
+        }
+    }
+    impl Features for MosaicFeatures {}
+
+    #[test]
+    fn from_image_or_mosaic_when_image_supported_then_render_image() {
+        let event = from_image_or_mosaic(&FakeFeatures {}, get_image()).unwrap();
+        assert_eq!(event, Event::SysEx(vec![b'I', b'M', b'G', 1, 2, 3]));
+    }
+
+    #[test]
+    fn from_image_or_mosaic_when_only_per_pad_colors_supported_then_render_scaled_mosaic() {
+        let image = Image {
+            width: 4,
+            height: 2,
+            bytes: vec![
+                255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255,
+                255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255,
+            ],
+        };
+
+        let event = from_image_or_mosaic(&MosaicFeatures {}, image).unwrap();
+        assert_eq!(event, Event::SysEx(vec![b'P', b'A', b'D', b'S', 255, 0, 0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn from_image_or_mosaic_when_nothing_supported_then_return_err() {
+        struct NoFeatures {}
+        impl Features for NoFeatures {}
+
+        assert!(from_image_or_mosaic(&NoFeatures {}, get_image()).is_err());
+    }
+
+    #[test]
+    fn render_image_with_highlight_when_only_per_pad_colors_supported_then_render_mosaic() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            let image = Image { width: 2, height: 1, bytes: vec![1, 2, 3, 4, 5, 6] };
+            render_image_with_highlight("fake", &MosaicFeatures {}, &sender, image, None).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'P', b'A', b'D', b'S', 1, 2, 3, 4, 5, 6]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    struct NumberFeatures {}
+    impl ImageRenderer for NumberFeatures {
+        fn from_image(&self, mut image: Image) -> R<Event> {
+            let mut bytes = Vec::from("IMG".as_bytes());
+            bytes.append(&mut image.bytes);
+            return Ok(Event::SysEx(bytes));
+        }
+
+        fn from_number(&self, n: u16, color: [u8; 3]) -> R<Event> {
+            return Ok(Event::SysEx(vec![n as u8, color[0], color[1], color[2]]));
+        }
+    }
+    impl Features for NumberFeatures {}
+
+    #[test]
+    fn render_number_or_image_with_highlight_when_index_then_render_number_from_one() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_number_or_image_with_highlight("fake", &NumberFeatures {}, &sender, get_image(), Some(11), [0, 255, 0]).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![12, 0, 255, 0]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_number_or_image_with_highlight_when_no_index_then_render_image() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_number_or_image_with_highlight("fake", &NumberFeatures {}, &sender, get_image(), None, [0, 255, 0]).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 1, 2, 3]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_number_or_image_with_highlight_when_numbers_unsupported_then_render_image_and_highlight_index() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        with_runtime(async move {
+            render_number_or_image_with_highlight("fake", &FakeFeatures {}, &sender, get_image(), Some(42), [0, 255, 0]).await;
+            drop(sender);
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 1, 2, 3]))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, Some(Out::Midi(Event::Midi([42, 42, 42, 42]))));
+        });
+    }
+
+    fn with_runtime<F>(f: F) -> F::Output where F: Future {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod logo_test {
+    use crate::image::Image;
+    use crate::midi::Event;
+    use crate::midi::devices::config::Origin;
+    use crate::midi::devices::launchpadpro::LaunchpadProFeatures;
+    use crate::midi::features::ImageRenderer;
+
+    use super::from_image_or_mosaic;
+
+    /// Render the logo on Launchpad Pros numbering their LEDs from either corner, and check that
+    /// each pixel lights the LED that the device lights when asked for the same pixel alone.
+    pub(crate) fn assert_logo_renders_right_side_up(logo: Image) {
+        for origin in [Origin::BottomLeft, Origin::TopLeft] {
+            let features = LaunchpadProFeatures { origin, ..LaunchpadProFeatures::new() };
+            let leds = match from_image_or_mosaic(&features, logo.clone()) {
+                // skip the header, up to the suffix
+                Ok(Event::SysEx(bytes)) => bytes[8..bytes.len() - 1].to_vec(),
+                result => panic!("unexpected result: {:?}", result),
+            };
+
+            for y in 0..logo.height {
+                for x in 0..logo.width {
+                    let led = match features.from_pixel(x, y, [0, 0, 0]) {
+                        Ok(Event::SysEx(bytes)) => usize::from(bytes[7]),
+                        result => panic!("unexpected result: {:?}", result),
+                    };
+                    // LEDs of the full-grid message go from 11 to 88, row by row
+                    let offset = 3 * ((led / 10 - 1) * logo.width + led % 10 - 1);
+                    let pixel = 3 * (y * logo.width + x);
+
+                    let expected_color = logo.bytes[pixel..pixel + 3].iter().map(|byte| byte / 4).collect::<Vec<u8>>();
+                    assert_eq!(leds[offset..offset + 3].to_vec(), expected_color, "{:?} ({}, {})", origin, x, y);
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use tokio::sync::mpsc::{Sender, Receiver, channel};
 use tokio::sync::mpsc::error::{SendError, TryRecvError};
 
-use crate::apps::{App, In, Out};
+use crate::apps::{App, In, Out, ServerCommand};
+use crate::apps::render::from_image_or_mosaic;
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
 
-use crate::midi::Image;
-use crate::midi::features::Features;
+use crate::midi::{Event, Image};
+use crate::midi::features::{Features, R, UnsupportedFeatureError};
 
-use super::config::Config;
+use super::config::{AttractModeConfig, Config};
 
 pub const NAME: &str = "selection";
 pub const COLOR: [u8; 3] = [255, 255, 255];
@@ -20,6 +24,9 @@ pub struct Selection {
     output_features: Arc<dyn Features + Sync + Send>,
     out_sender: Sender<Out>,
     out_receiver: Receiver<Out>,
+    /// Whether the attract mode animation is running, until the user selects an app
+    attracting: Arc<AtomicBool>,
+    send_policy: SendPolicy,
 }
 
 impl Selection {
@@ -30,26 +37,216 @@ impl Selection {
     ) -> Self {
         let (out_sender, out_receiver) = channel::<Out>(32);
         let selection = Selection {
-            apps: config.apps.start_all(Arc::clone(&input_features), Arc::clone(&output_features)),
+            apps: order_apps(config.apps.start_all(Arc::clone(&input_features), Arc::clone(&output_features)), &config.app_order),
             selected_app: 0,
             input_features,
             output_features,
             out_sender,
             out_receiver,
+            attracting: Arc::new(AtomicBool::new(false)),
+            send_policy: config.apps.send_policy,
         };
 
         selection.render_app_colors();
+        if let Some(attract_mode) = &config.attract_mode {
+            selection.start_attract_mode(attract_mode);
+        }
 
         return selection;
     }
 
+    fn get_app_colors(&self) -> Vec<[u8; 3]> {
+        return self.apps.iter().map(|app| app.get_color()).collect();
+    }
+
     fn render_app_colors(&self) {
-        self.output_features.from_app_colors(self.apps.iter().map(|app| app.get_color()).collect())
+        let event = match self.output_features.from_app_colors(self.get_app_colors(), Some(self.selected_app)) {
+            Err(err) if err.is::<UnsupportedFeatureError>() => from_apps_to_pads(self.output_features.as_ref(), &self.apps),
+            result => result,
+        };
+
+        event
             .map_err(|err| format!("[selection] could not render app colors: {}", err))
-            .and_then(|event| self.out_sender.blocking_send(event.into())
+            .and_then(|event| send_with_policy(NAME, &self.out_sender, event.into(), self.send_policy)
                 .map_err(|err| format!("[selection] could not send app colors: {}", err)))
             .unwrap_or_else(|err| eprintln!("{}", err));
     }
+
+    /// Give the focus to the app of the given index, and render its logo.
+    /// Return false if there is no such app.
+    fn select_app(&mut self, app_index: usize) -> bool {
+        if app_index >= self.apps.len() {
+            return false;
+        }
+
+        let app_changed = app_index != self.selected_app;
+        if app_changed {
+            self.deselect_app();
+        }
+
+        self.selected_app = app_index;
+        // Devices may render the selected app differently from the others (e.g. dimming the others)
+        let app_colors = if app_changed {
+            match self.output_features.from_app_colors(self.get_app_colors(), Some(app_index)) {
+                Ok(event) => Some(event),
+                Err(err) if err.is::<UnsupportedFeatureError>() => None,
+                Err(err) => {
+                    eprintln!("[selection] could not render app colors: {}", err);
+                    None
+                },
+            }
+        } else {
+            None
+        };
+
+        let selected_app = &mut self.apps[app_index];
+
+        println!("[selection] selecting {}", selected_app.get_name());
+        self.attracting.store(false, Ordering::Relaxed);
+        // Sent as a single batch, so that the router writes the logo right after
+        // having cleared the palette, and never the other way around
+        let mut events = self.output_features.from_color_palette_to_events(vec![[0, 0, 0]; 8])
+            .unwrap_or_else(|err| {
+                eprintln!("[selection] could not transform color palette: {}", err);
+                vec![]
+            });
+        events.extend(app_colors);
+
+        match from_image_or_mosaic(self.output_features.as_ref(), selected_app.get_logo()) {
+            Ok(event) => events.push(event),
+            Err(err) => eprintln!("[selection] could not transform the image: {}", err),
+        }
+
+        if !events.is_empty() {
+            send_with_policy(NAME, &self.out_sender, Out::MidiBatch(events), self.send_policy).unwrap_or_else(|err| {
+                eprintln!("[selection] could not clean the color palette and send the image: {}", err)
+            });
+        }
+
+        selected_app.on_select();
+        return true;
+    }
+
+    /// Let the selected app clean up before it loses the focus, and flush the events it emitted
+    /// while doing so, as only the events of the selected app get polled.
+    fn deselect_app(&mut self) {
+        if let Some(app) = self.apps.get_mut(self.selected_app) {
+            app.on_deselect();
+            while let Ok(out) = app.receive() {
+                self.out_sender.try_send(out).unwrap_or_else(|err| {
+                    eprintln!("[selection][{}] could not flush event: {}", app.get_name(), err)
+                });
+            }
+        }
+    }
+
+    /// Render frames of a rainbow sweeping across the grid, until the user selects an app
+    fn start_attract_mode(&self, config: &AttractModeConfig) {
+        let (width, height) = match self.output_features.get_grid_size() {
+            Ok(size) => size,
+            Err(err) => {
+                eprintln!("[selection] cannot start the attract mode without knowing the grid size: {}", err);
+                return;
+            },
+        };
+
+        self.attracting.store(true, Ordering::Relaxed);
+
+        let attracting = Arc::clone(&self.attracting);
+        let output_features = Arc::clone(&self.output_features);
+        let sender = self.out_sender.clone();
+        let send_policy = self.send_policy;
+        let frame_interval = Duration::from_millis(config.frame_interval_ms);
+
+        std::thread::spawn(move || {
+            let mut step = 0;
+            while attracting.load(Ordering::Relaxed) {
+                let frame = get_attract_frame(width, height, step);
+                let sent = from_image_or_mosaic(output_features.as_ref(), frame)
+                    .map_err(|err| format!("[selection] could not transform the attract mode frame: {}", err))
+                    // the user may have selected an app while the frame was being transformed
+                    .and_then(|event| if attracting.load(Ordering::Relaxed) {
+                        send_with_policy(NAME, &sender, event.into(), send_policy)
+                            .map_err(|err| format!("[selection] could not send the attract mode frame: {}", err))
+                    } else {
+                        Ok(())
+                    });
+
+                // The app may have been dropped, or the device may not be able to render images
+                if let Err(err) = sent {
+                    eprintln!("{}", err);
+                    attracting.store(false, Ordering::Relaxed);
+                }
+
+                step += 1;
+                std::thread::sleep(frame_interval);
+            }
+        });
+    }
+}
+
+/// Sort the apps by their position in the given order, so that both the rendering of the apps and
+/// the mapping of indexes to apps follow it. Unlisted apps keep their relative order, after the others.
+fn order_apps(mut apps: Vec<Box<dyn App>>, app_order: &Vec<String>) -> Vec<Box<dyn App>> {
+    for name in app_order {
+        if !apps.iter().any(|app| app.get_name() == name) {
+            eprintln!("[selection] {} is part of the app order, but is not configured", name);
+        }
+    }
+
+    apps.sort_by_key(|app| app_order.iter()
+        .position(|name| name == app.get_name())
+        .unwrap_or(app_order.len()));
+    return apps;
+}
+
+/// Devices without an app-selection UI get the apps rendered on their first pads instead (starting
+/// from the top-left corner): one logo per pad if they can render several images, or one color per
+/// pad otherwise. Apps without a logo are represented by their color in both cases.
+fn from_apps_to_pads(features: &(dyn Features + Sync + Send), apps: &Vec<Box<dyn App>>) -> R<Event> {
+    let (width, height) = features.get_grid_size()?;
+    let pads = width * height;
+    let black = Image { width: 1, height: 1, bytes: vec![0, 0, 0] };
+
+    let images = (0..pads)
+        .map(|pad| match apps.get(pad) {
+            Some(app) => {
+                let logo = app.get_logo();
+                if logo.width > 0 && logo.height > 0 {
+                    logo
+                } else {
+                    Image { width: 1, height: 1, bytes: app.get_color().to_vec() }
+                }
+            },
+            None => black.clone(),
+        })
+        .collect();
+
+    return match features.from_images(images) {
+        Err(err) if err.is::<UnsupportedFeatureError>() => {
+            let bytes = (0..pads)
+                .flat_map(|pad| apps.get(pad).map(|app| app.get_color()).unwrap_or([0, 0, 0]))
+                .collect();
+            features.from_image(Image { width, height, bytes })
+        },
+        result => result,
+    };
+}
+
+/// Every diagonal of the grid gets a color going from red to blue, shifted by one at each step
+fn get_attract_frame(width: usize, height: usize, step: usize) -> Image {
+    let colors = width + height - 1;
+    let mut bytes = Vec::with_capacity(width * height * 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = (x + y + step) % colors;
+            let ratio = if colors > 1 { 255 * index / (colors - 1) } else { 0 };
+            bytes.append(&mut vec![(255 - ratio) as u8, 0, ratio as u8]);
+        }
+    }
+
+    return Image { width, height, bytes };
 }
 
 impl App for Selection {
@@ -69,39 +266,22 @@ impl App for Selection {
     fn send(&mut self, event: In) -> Result<(), SendError<In>> {
         match event {
             In::Midi(event) => {
-                let selected_app = self.input_features.into_app_index(event.clone()).ok().flatten()
-                    .and_then(|app_index| {
-                        let selected_app = self.apps.get_mut(app_index as usize);
-                        if selected_app.is_some() {
-                            self.selected_app = app_index as usize;
-                        }
-                        return selected_app;
-                    });
-
-                selected_app
-                    .map(|selected_app| {
-                        println!("[selection] selecting {}", selected_app.get_name());
-                        self.output_features.from_color_palette(vec![[0, 0, 0]; 8])
-                            .map_err(|err| format!("[selection] could not transform color palette: {}", err))
-                            .and_then(|event| self.out_sender.blocking_send(event.into())
-                                .map_err(|err| format!("[selection] could not clean the color palette: {}", err)))
-                            .unwrap_or_else(|err| eprintln!("{}", err));
-
-                        self.output_features.from_image(selected_app.get_logo())
-                            .map_err(|err| format!("[selection] could not transform the image: {}", err))
-                            .and_then(|event| self.out_sender.blocking_send(event.into())
-                                .map_err(|err| format!("[selection] could not send the image: {}", err)))
-                            .unwrap_or_else(|err| eprintln!("{}", err));
-
-                        selected_app.on_select();
-                    })
-                    .unwrap_or_else(|| {
-                        match self.apps.get_mut(self.selected_app) {
-                            Some(app) => app.send(event.into())
-                                .unwrap_or_else(|err| eprintln!("[selection][{}] could not send event: {}", app.get_name(), err)),
-                            None => eprintln!("No app found for index: {}", self.selected_app),
-                        }
-                    });
+                let app_index = self.input_features.into_app_index(event.clone()).ok().flatten();
+                if !app_index.map(|app_index| self.select_app(app_index)).unwrap_or(false) {
+                    match self.apps.get_mut(self.selected_app) {
+                        Some(app) => app.send(event.into())
+                            .unwrap_or_else(|err| eprintln!("[selection][{}] could not send event: {}", app.get_name(), err)),
+                        None => eprintln!("No app found for index: {}", self.selected_app),
+                    }
+                }
+                Ok(())
+            },
+            In::Server(ServerCommand::SelectNextApp) if !self.apps.is_empty() => {
+                self.select_app((self.selected_app + 1) % self.apps.len());
+                Ok(())
+            },
+            In::Server(ServerCommand::SelectPreviousApp) if !self.apps.is_empty() => {
+                self.select_app((self.selected_app + self.apps.len() - 1) % self.apps.len());
                 Ok(())
             },
             In::Server(command)  => {
@@ -129,18 +309,45 @@ impl App for Selection {
     }
 
     fn on_select(&mut self) {}
+
+    fn render(&mut self) {
+        self.render_app_colors();
+        if let Some(app) = self.apps.get_mut(self.selected_app) {
+            app.render();
+        }
+    }
+
+    /// The selection itself loses the focus when its input device gets disconnected
+    fn on_deselect(&mut self) {
+        self.deselect_app();
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
     use crate::midi::Event;
-    use crate::midi::features::{R, AppSelector, Features};
+    use crate::midi::features::{R, AppSelector, ColorPalette, Features, GridController, ImageRenderer};
     use crate::apps;
     use super::*;
 
     struct TestFeatures {}
+    impl GridController for TestFeatures {
+        fn get_grid_size(&self) -> R<(usize, usize)> {
+            return Ok((2, 2));
+        }
+    }
+    impl ImageRenderer for TestFeatures {
+        fn from_image(&self, image: Image) -> R<Event> {
+            let mut bytes = Vec::from("image".as_bytes());
+            bytes.append(&mut image.bytes.clone());
+            return Ok(Event::SysEx(bytes));
+        }
+    }
     impl AppSelector for TestFeatures {
-        fn from_app_colors(&self, app_colors: Vec<[u8; 3]>) -> R<Event> {
+        fn from_app_colors(&self, app_colors: Vec<[u8; 3]>, _selected_app: Option<usize>) -> R<Event> {
             let mut bytes = vec![];
             for app_color in &app_colors {
                 bytes.push(app_color[0]);
@@ -150,6 +357,13 @@ mod test {
             return Ok(Event::SysEx(bytes));
         }
     }
+    impl ColorPalette for TestFeatures {
+        fn from_color_palette(&self, colors: Vec<[u8; 3]>) -> R<Event> {
+            let mut bytes = Vec::from("palette".as_bytes());
+            bytes.append(&mut colors.concat());
+            return Ok(Event::SysEx(bytes));
+        }
+    }
     impl Features for TestFeatures {}
 
     #[test]
@@ -158,19 +372,25 @@ mod test {
             Config {
                 apps: Box::new(apps::Config {
                     forward: None,
+                    macropad: None,
                     paint: None,
-                    spotify: Some(apps::spotify::config::Config {
-                        playlist_id: "playlist_id".to_string(),
-                        client_id: "client_id".to_string(),
-                        client_secret: "client_secret".to_string(),
-                        refresh_token: "refresh_token".to_string(),
-                    }),
+                    spotify: Some(apps::spotify::config::test_config()),
                     youtube: Some(apps::youtube::config::Config {
                         api_key: "api_key".to_string(),
                         playlist_id: "playlist_id".to_string(),
+                        http: apps::http::HttpConfig::default(),
+                        show_track_number: false,
+                        playlist_polling_interval_s: None,
+                        empty_playlist_color: None,
+                        confirm_mode: false,
                     }),
+                    webhook: None,
                     selection: None,
+                    overrides: std::collections::HashMap::new(),
+                    send_policy: apps::send_policy::SendPolicy::default(),
                 }),
+                attract_mode: None,
+                app_order: vec![],
             },
             Arc::new(TestFeatures {}),
             Arc::new(TestFeatures {}),
@@ -180,4 +400,370 @@ mod test {
 
         assert_eq!(event, Event::SysEx(vec![0, 255, 0, 255, 0, 0]).into());
     }
+
+    /// Devices that cannot render app colors, but can render images
+    struct ImageFeatures {
+        supports_from_images: bool,
+    }
+    impl GridController for ImageFeatures {
+        fn get_grid_size(&self) -> R<(usize, usize)> {
+            return Ok((2, 2));
+        }
+    }
+    impl ImageRenderer for ImageFeatures {
+        fn from_image(&self, image: Image) -> R<Event> {
+            let mut bytes = Vec::from("image".as_bytes());
+            bytes.append(&mut image.bytes.clone());
+            return Ok(Event::SysEx(bytes));
+        }
+
+        fn from_images(&self, images: Vec<Image>) -> R<Event> {
+            if !self.supports_from_images {
+                return Err(Box::new(crate::midi::features::UnsupportedFeatureError::from("from_images")));
+            }
+
+            let mut bytes = Vec::from("images".as_bytes());
+            for image in images {
+                bytes.push(image.width as u8);
+                bytes.append(&mut image.bytes.clone());
+            }
+            return Ok(Event::SysEx(bytes));
+        }
+    }
+    impl Features for ImageFeatures {}
+
+    #[test]
+    fn render_app_colors_when_from_app_colors_is_unsupported_then_render_one_image_per_pad() {
+        let mut selection_app = get_forward_selection_with_features(None, Arc::new(ImageFeatures { supports_from_images: true }));
+
+        // the forward app has no logo, so it is represented by its color
+        let event = selection_app.receive().expect("an event should be received");
+        assert_eq!(event, Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e', b's',
+            1, 0, 0, 255,
+            1, 0, 0, 0,
+            1, 0, 0, 0,
+            1, 0, 0, 0,
+        ]).into());
+    }
+
+    #[test]
+    fn render_app_colors_when_from_images_is_unsupported_then_render_one_color_per_pad() {
+        let mut selection_app = get_forward_selection_with_features(None, Arc::new(ImageFeatures { supports_from_images: false }));
+
+        let event = selection_app.receive().expect("an event should be received");
+        assert_eq!(event, Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e',
+            0, 0, 255, 0, 0, 0,
+            0, 0, 0, 0, 0, 0,
+        ]).into());
+    }
+
+    fn get_forward_selection(attract_mode: Option<AttractModeConfig>) -> Selection {
+        return get_forward_selection_with_features(attract_mode, Arc::new(TestFeatures {}));
+    }
+
+    fn get_forward_selection_with_features(
+        attract_mode: Option<AttractModeConfig>,
+        features: Arc<dyn Features + Sync + Send>,
+    ) -> Selection {
+        return Selection::new(
+            Config {
+                apps: Box::new(apps::Config {
+                    forward: Some(apps::forward::config::Config {
+                        transpose: 0,
+                        boundary: apps::forward::config::Boundary::Drop,
+                        force_channel: None,
+                        merge_inputs: vec![],
+                    }),
+                    macropad: None,
+                    paint: None,
+                    spotify: None,
+                    youtube: None,
+                    webhook: None,
+                    selection: None,
+                    overrides: std::collections::HashMap::new(),
+                    send_policy: apps::send_policy::SendPolicy::default(),
+                }),
+                attract_mode,
+                app_order: vec![],
+            },
+            Arc::clone(&features),
+            features,
+        );
+    }
+
+    fn receive_all(selection_app: &mut Selection) -> Vec<Out> {
+        let mut events = vec![];
+        while let Ok(event) = selection_app.receive() {
+            events.push(event);
+        }
+        return events;
+    }
+
+    #[test]
+    fn attract_mode_when_enabled_then_render_frames_periodically_until_an_app_is_selected() {
+        let mut selection_app = get_forward_selection(Some(AttractModeConfig { frame_interval_ms: 10 }));
+        std::thread::sleep(Duration::from_millis(100));
+
+        let events = receive_all(&mut selection_app);
+        let frames = events.iter()
+            .filter(|event| matches!(event, Out::Midi(Event::SysEx(bytes)) if bytes.starts_with(b"image")))
+            .collect::<Vec<&Out>>();
+
+        assert_eq!(events[0], Event::SysEx(vec![0, 0, 255]).into());
+        assert!(frames.len() >= 3, "expected periodic frames, got {}", frames.len());
+        assert_ne!(frames[0], frames[1]);
+
+        // select the forward app
+        selection_app.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        receive_all(&mut selection_app);
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(receive_all(&mut selection_app), vec![]);
+    }
+
+    #[test]
+    fn send_when_selecting_an_app_then_clear_the_palette_and_draw_the_logo_in_a_single_batch() {
+        let mut selection_app = get_forward_selection(None);
+        receive_all(&mut selection_app);
+
+        selection_app.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+
+        let mut palette = Vec::from("palette".as_bytes());
+        palette.append(&mut vec![0; 24]);
+        let logo = Event::SysEx(Vec::from("image".as_bytes()));
+        assert_eq!(receive_all(&mut selection_app), vec![Out::MidiBatch(vec![Event::SysEx(palette), logo])]);
+    }
+
+    #[test]
+    fn send_when_selecting_another_app_then_render_app_colors_again_within_the_batch() {
+        let features = crate::midi::devices::launchpadpro::LaunchpadProFeatures {
+            inactive_app_brightness: Some(0.5),
+            ..crate::midi::devices::launchpadpro::LaunchpadProFeatures::new()
+        };
+        let mut selection_app = Selection::new(
+            Config {
+                apps: Box::new(apps::Config {
+                    forward: Some(apps::forward::config::Config {
+                        transpose: 0,
+                        boundary: apps::forward::config::Boundary::Drop,
+                        force_channel: None,
+                        merge_inputs: vec![],
+                    }),
+                    macropad: None,
+                    paint: Some(apps::paint::config::Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }),
+                    spotify: None,
+                    youtube: None,
+                    webhook: None,
+                    selection: None,
+                    overrides: std::collections::HashMap::new(),
+                    send_policy: apps::send_policy::SendPolicy::default(),
+                }),
+                attract_mode: None,
+                app_order: vec![],
+            },
+            Arc::new(TestFeatures {}),
+            Arc::new(crate::midi::devices::launchpadpro::LaunchpadProFeatures {
+                inactive_app_brightness: Some(0.5),
+                ..crate::midi::devices::launchpadpro::LaunchpadProFeatures::new()
+            }),
+        );
+        receive_all(&mut selection_app);
+
+        selection_app.send(In::Server(ServerCommand::SelectNextApp)).unwrap();
+
+        let app_colors = features.from_app_colors(selection_app.get_app_colors(), Some(1)).unwrap();
+        match receive_all(&mut selection_app).first() {
+            Some(Out::MidiBatch(events)) => assert!(events.contains(&app_colors)),
+            events => panic!("unexpected events: {:?}", events),
+        }
+    }
+
+    #[test]
+    fn send_when_selecting_next_or_previous_app_then_cycle_through_apps() {
+        let mut selection_app = Selection::new(
+            Config {
+                apps: Box::new(apps::Config {
+                    forward: Some(apps::forward::config::Config {
+                        transpose: 0,
+                        boundary: apps::forward::config::Boundary::Drop,
+                        force_channel: None,
+                        merge_inputs: vec![],
+                    }),
+                    macropad: None,
+                    paint: Some(apps::paint::config::Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }),
+                    spotify: None,
+                    youtube: None,
+                    webhook: None,
+                    selection: None,
+                    overrides: std::collections::HashMap::new(),
+                    send_policy: apps::send_policy::SendPolicy::default(),
+                }),
+                attract_mode: None,
+                app_order: vec![],
+            },
+            Arc::new(TestFeatures {}),
+            Arc::new(TestFeatures {}),
+        );
+        assert_eq!(selection_app.selected_app, 0);
+
+        selection_app.send(In::Server(ServerCommand::SelectNextApp)).unwrap();
+        assert_eq!(selection_app.selected_app, 1);
+
+        // the last app is followed by the first one
+        selection_app.send(In::Server(ServerCommand::SelectNextApp)).unwrap();
+        assert_eq!(selection_app.selected_app, 0);
+
+        // and the first app is preceded by the last one
+        selection_app.send(In::Server(ServerCommand::SelectPreviousApp)).unwrap();
+        assert_eq!(selection_app.selected_app, 1);
+    }
+
+    fn get_forward_and_paint_selection(app_order: Vec<String>) -> Selection {
+        return Selection::new(
+            Config {
+                apps: Box::new(apps::Config {
+                    forward: Some(apps::forward::config::Config {
+                        transpose: 0,
+                        boundary: apps::forward::config::Boundary::Drop,
+                        force_channel: None,
+                        merge_inputs: vec![],
+                    }),
+                    macropad: None,
+                    paint: Some(apps::paint::config::Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None }),
+                    spotify: None,
+                    youtube: None,
+                    webhook: None,
+                    selection: None,
+                    overrides: std::collections::HashMap::new(),
+                    send_policy: apps::send_policy::SendPolicy::default(),
+                }),
+                attract_mode: None,
+                app_order,
+            },
+            Arc::new(TestFeatures {}),
+            Arc::new(TestFeatures {}),
+        );
+    }
+
+    fn get_app_names(selection_app: &Selection) -> Vec<&'static str> {
+        return selection_app.apps.iter().map(|app| app.get_name()).collect();
+    }
+
+    #[test]
+    fn new_when_app_order_is_empty_then_keep_the_configured_order() {
+        let selection_app = get_forward_and_paint_selection(vec![]);
+        assert_eq!(get_app_names(&selection_app), vec!["forward", "paint"]);
+    }
+
+    #[test]
+    fn new_when_app_order_is_configured_then_render_app_colors_in_that_order() {
+        let mut selection_app = get_forward_and_paint_selection(vec!["paint".to_string()]);
+
+        assert_eq!(get_app_names(&selection_app), vec!["paint", "forward"]);
+        assert_eq!(selection_app.receive(), Ok(Event::SysEx(vec![255, 255, 0, 0, 0, 255]).into()));
+    }
+
+    #[test]
+    fn send_when_app_order_is_configured_then_select_apps_in_that_order() {
+        let mut selection_app = get_forward_and_paint_selection(vec!["paint".to_string(), "forward".to_string()]);
+        assert_eq!(selection_app.apps[selection_app.selected_app].get_name(), "paint");
+
+        selection_app.send(In::Server(ServerCommand::SelectNextApp)).unwrap();
+        assert_eq!(selection_app.apps[selection_app.selected_app].get_name(), "forward");
+    }
+
+    #[test]
+    fn new_when_app_order_lists_unknown_apps_then_ignore_them() {
+        let selection_app = get_forward_and_paint_selection(vec!["youtube".to_string(), "paint".to_string()]);
+        assert_eq!(get_app_names(&selection_app), vec!["paint", "forward"]);
+    }
+
+    /// Records the lifecycle callbacks it gets called with, prefixed with its name
+    struct LifecycleApp {
+        name: &'static str,
+        calls: Rc<RefCell<Vec<String>>>,
+    }
+    impl App for LifecycleApp {
+        fn get_name(&self) -> &'static str {
+            return self.name;
+        }
+
+        fn get_color(&self) -> [u8; 3] {
+            return [0, 0, 0];
+        }
+
+        fn get_logo(&self) -> Image {
+            return Image { width: 0, height: 0, bytes: vec![] };
+        }
+
+        fn send(&mut self, _event: In) -> Result<(), SendError<In>> {
+            return Ok(());
+        }
+
+        fn receive(&mut self) -> Result<Out, TryRecvError> {
+            return Err(TryRecvError::Empty);
+        }
+
+        fn on_select(&mut self) {
+            self.calls.borrow_mut().push(format!("{}:on_select", self.name));
+        }
+
+        fn on_deselect(&mut self) {
+            self.calls.borrow_mut().push(format!("{}:on_deselect", self.name));
+        }
+    }
+
+    fn get_lifecycle_selection(calls: &Rc<RefCell<Vec<String>>>) -> Selection {
+        let mut selection_app = get_forward_and_paint_selection(vec![]);
+        selection_app.apps = vec!["first", "second"].into_iter()
+            .map(|name| Box::new(LifecycleApp { name, calls: Rc::clone(calls) }) as Box<dyn App>)
+            .collect();
+        return selection_app;
+    }
+
+    #[test]
+    fn send_when_selecting_another_app_then_deselect_the_outgoing_app_before_selecting_the_new_one() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut selection_app = get_lifecycle_selection(&calls);
+
+        selection_app.send(In::Server(ServerCommand::SelectNextApp)).unwrap();
+
+        assert_eq!(*calls.borrow(), vec!["first:on_deselect".to_string(), "second:on_select".to_string()]);
+    }
+
+    #[test]
+    fn send_when_selecting_the_selected_app_again_then_do_not_deselect_it() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut selection_app = get_lifecycle_selection(&calls);
+
+        selection_app.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+
+        assert_eq!(*calls.borrow(), vec!["first:on_select".to_string()]);
+    }
+
+    #[test]
+    fn attract_mode_when_disabled_then_only_render_app_colors() {
+        let mut selection_app = get_forward_selection(None);
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(receive_all(&mut selection_app), vec![Event::SysEx(vec![0, 0, 255]).into()]);
+    }
+
+    #[test]
+    fn get_attract_frame_should_shift_colors_along_the_diagonals() {
+        let frame = get_attract_frame(2, 2, 0);
+        assert_eq!(frame.bytes, vec![
+            255, 0, 0, 128, 0, 127,
+            128, 0, 127, 0, 0, 255,
+        ]);
+
+        let frame = get_attract_frame(2, 2, 1);
+        assert_eq!(frame.bytes, vec![
+            128, 0, 127, 0, 0, 255,
+            0, 0, 255, 255, 0, 0,
+        ]);
+    }
 }
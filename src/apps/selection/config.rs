@@ -3,6 +3,18 @@ use serde::{Serialize, Deserialize};
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub apps: Box<crate::apps::Config>,
+    /// Animate the grid until an app gets selected, if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attract_mode: Option<AttractModeConfig>,
+    /// Names of the apps in the order they get selected with, unlisted apps coming after them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub app_order: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AttractModeConfig {
+    /// Time between two frames of the animation
+    pub frame_interval_ms: u64,
 }
 
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
@@ -16,5 +28,7 @@ pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
 
     return Ok(Config {
         apps: Box::new(apps),
+        attract_mode: None,
+        app_order: vec![],
     });
 }
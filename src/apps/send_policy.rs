@@ -0,0 +1,114 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Deserialize};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// What to do with an event sent from the router thread when the channel of its receiver is full
+/// (e.g. because an app is stalled), so that a single receiver cannot freeze every link.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SendPolicy {
+    /// Wait for the receiver to make room for the event, then drop it after the given delay
+    Block { timeout_ms: u64 },
+    /// Drop the event right away
+    Drop,
+}
+
+impl Default for SendPolicy {
+    fn default() -> Self {
+        return SendPolicy::Block { timeout_ms: 500 };
+    }
+}
+
+/// Send the value from a synchronous context, dropping it (with a warning) according to the
+/// policy if the channel is full. Only an error if the receiver has been dropped.
+pub fn send_with_policy<T>(name: &str, sender: &Sender<T>, value: T, policy: SendPolicy) -> Result<(), SendError<T>> {
+    let deadline = match policy {
+        SendPolicy::Block { timeout_ms } => Instant::now() + Duration::from_millis(timeout_ms),
+        SendPolicy::Drop => Instant::now(),
+    };
+
+    let mut value = value;
+    loop {
+        match sender.try_send(value) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Closed(value)) => return Err(SendError(value)),
+            Err(TrySendError::Full(_)) if Instant::now() >= deadline => {
+                eprintln!("[{}] dropping an event, as its receiver is not keeping up", name);
+                return Ok(());
+            },
+            Err(TrySendError::Full(full_value)) => {
+                value = full_value;
+                thread::sleep(RETRY_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn send_with_policy_when_channel_has_room_then_send_the_value() {
+        let (sender, mut receiver) = mpsc::channel::<u8>(1);
+        assert_eq!(send_with_policy("test", &sender, 1, SendPolicy::Drop), Ok(()));
+        assert_eq!(receiver.try_recv(), Ok(1));
+    }
+
+    #[test]
+    fn send_with_policy_when_channel_is_full_and_policy_is_drop_then_drop_the_value_right_away() {
+        let (sender, mut receiver) = mpsc::channel::<u8>(1);
+        sender.try_send(1).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(send_with_policy("test", &sender, 2, SendPolicy::Drop), Ok(()));
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(receiver.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+    }
+
+    #[test]
+    fn send_with_policy_when_channel_stays_full_then_drop_the_value_after_the_timeout() {
+        let (sender, mut receiver) = mpsc::channel::<u8>(1);
+        sender.try_send(1).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(send_with_policy("test", &sender, 2, SendPolicy::Block { timeout_ms: 50 }), Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_millis(1_000));
+
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(receiver.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+    }
+
+    #[test]
+    fn send_with_policy_when_receiver_makes_room_before_the_timeout_then_send_the_value() {
+        let (sender, mut receiver) = mpsc::channel::<u8>(1);
+        sender.try_send(1).unwrap();
+
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let first = receiver.try_recv();
+            thread::sleep(Duration::from_millis(50));
+            (first, receiver.try_recv())
+        });
+
+        assert_eq!(send_with_policy("test", &sender, 2, SendPolicy::Block { timeout_ms: 1_000 }), Ok(()));
+        assert_eq!(handle.join().unwrap(), (Ok(1), Ok(2)));
+    }
+
+    #[test]
+    fn send_with_policy_when_receiver_is_dropped_then_give_the_value_back() {
+        let (sender, receiver) = mpsc::channel::<u8>(1);
+        drop(receiver);
+        assert_eq!(send_with_policy("test", &sender, 1, SendPolicy::Drop), Err(SendError(1)));
+    }
+}
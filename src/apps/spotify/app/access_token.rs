@@ -1,34 +1,68 @@
 use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::apps::spotify::client::{SpotifyApiError, SpotifyApiResult}; 
+use crate::apps::spotify::config::Config;
 
 use super::app::*;
 
+/// How long before its expiry an access token gets refreshed, so that requests in flight don’t fail
+pub const ACCESS_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
 pub async fn with_access_token<A, F, Fut>(state: Arc<State>, f: F) -> SpotifyApiResult<A> where
     F: Fn(String) -> Fut,
     Fut: Future<Output = SpotifyApiResult<A>>,
 {
     let token = state.access_token.lock().unwrap().clone();
-    return match token {
-        Some(token) => {
-            println!("[Spotify] Found token in memory");
-            match f(token.to_string()).await {
-                Err(SpotifyApiError::Unauthorized) => {
-                    println!("[Spotify] Retrying because of expired token");
-                    let token = fetch_and_store_access_token(state).await?;
-                    return f(token).await;
+    let expires_at = *state.access_token_expires_at.lock().unwrap();
+    let token = match token {
+        Some(token) if should_refresh(expires_at, Instant::now()) => {
+            println!("[Spotify] Refreshing token about to expire");
+            match fetch_and_store_access_token(Arc::clone(&state)).await {
+                Ok(token) => token,
+                Err(err) => {
+                    // the token might still be valid for a bit, so it’s worth a try
+                    eprintln!("[Spotify] Could not refresh token, using the one in memory: {}", err);
+                    token
                 },
-                Err(err) => Err(err),
-                Ok(a) => Ok(a),
             }
         },
+        Some(token) => {
+            println!("[Spotify] Found token in memory");
+            token
+        },
         None => {
             println!("[Spotify] No token in memory");
             let token = fetch_and_store_access_token(state).await?;
             return f(token).await;
         },
     };
+
+    return match f(token).await {
+        Err(SpotifyApiError::Unauthorized) => {
+            println!("[Spotify] Retrying because of expired token");
+            let token = fetch_and_store_access_token(state).await?;
+            f(token).await
+        },
+        result => result,
+    };
+}
+
+/// Tokens whose expiry is unknown are only refreshed once they get rejected
+pub fn should_refresh(expires_at: Option<Instant>, now: Instant) -> bool {
+    return expires_at.map(|expires_at| now + ACCESS_TOKEN_EXPIRY_MARGIN >= expires_at).unwrap_or(false);
+}
+
+/// Convert the configured Unix timestamp into an instant, considering timestamps in the past as now
+pub fn get_initial_access_token_expiry(config: &Config, now: SystemTime, now_instant: Instant) -> Option<Instant> {
+    if config.initial_access_token.is_none() {
+        return None;
+    }
+
+    let expires_at = UNIX_EPOCH + Duration::from_secs(config.initial_access_token_expires_at?);
+    let remaining = expires_at.duration_since(now).unwrap_or(Duration::ZERO);
+    return Some(now_instant + remaining);
 }
 
 async fn fetch_and_store_access_token(state: Arc<State>) ->  SpotifyApiResult<String> {
@@ -40,6 +74,10 @@ async fn fetch_and_store_access_token(state: Arc<State>) ->  SpotifyApiResult<St
 
     let mut new_token = state.access_token.lock().unwrap();
     *new_token = Some(token_response.access_token.clone());
+
+    let expires_in = Duration::from_secs(token_response.expires_in.max(0) as u64);
+    *state.access_token_expires_at.lock().unwrap() = Some(Instant::now() + expires_in);
+
     return Ok(token_response.access_token);
 }
 
@@ -51,8 +89,9 @@ mod test {
     use mockall::predicate::*;
     use tokio::runtime::Builder;
 
-    use crate::apps::spotify::config::Config;
+    use crate::apps::spotify::app::app::test_state;
     use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyTokenResponse};
+    use crate::apps::spotify::config::test_config;
 
     use super::*;
 
@@ -162,29 +201,163 @@ mod test {
         });
     }
 
+    #[test]
+    fn with_access_token_when_token_about_to_expire_then_refresh_it_beforehand() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token()
+            .times(1)
+            .returning(|_, _, _| Ok(SpotifyTokenResponse {
+                access_token: "fresh_access_token".to_string(),
+                token_type: "bearer".to_string(),
+                expires_in: 3600,
+                scope: Some("scope".to_string()),
+                refresh_token: Some("refresh_token".to_string()),
+            }));
+
+        let state = get_state_with_token_and_client(Some("expiring_access_token"), client);
+        *state.access_token_expires_at.lock().unwrap() = Some(Instant::now() + Duration::from_secs(30));
+
+        let state_copy = Arc::clone(&state);
+        with_runtime(async move {
+            let result = with_access_token(state_copy, |token| async {
+                let token = token;
+                assert_eq!(token, "fresh_access_token".to_string());
+                Ok(())
+            }).await;
+
+            assert!(result.is_ok());
+        });
+
+        let expires_at = state.access_token_expires_at.lock().unwrap().unwrap();
+        assert!(expires_at > Instant::now() + Duration::from_secs(3500));
+    }
+
+    #[test]
+    fn with_access_token_when_token_about_to_expire_and_refresh_fails_then_use_it_anyway() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token()
+            .times(1)
+            .returning(|_, _, _| Err(SpotifyApiError::Other(Box::new(std::io::Error::from(std::io::ErrorKind::NotConnected)))));
+
+        let state = get_state_with_token_and_client(Some("expiring_access_token"), client);
+        *state.access_token_expires_at.lock().unwrap() = Some(Instant::now() + Duration::from_secs(30));
+
+        with_runtime(async move {
+            let result = with_access_token(state, |token| async {
+                let token = token;
+                assert_eq!(token, "expiring_access_token".to_string());
+                Ok(())
+            }).await;
+
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn with_access_token_when_token_about_to_expire_could_not_be_refreshed_and_is_rejected_then_retry_with_a_fresh_one() {
+        let mut client = MockSpotifyApiClient::new();
+        let mut refreshes = 0;
+        client.expect_refresh_token()
+            .times(2)
+            .returning(move |_, _, _| {
+                refreshes += 1;
+                if refreshes == 1 {
+                    Err(SpotifyApiError::Other(Box::new(std::io::Error::from(std::io::ErrorKind::NotConnected))))
+                } else {
+                    Ok(SpotifyTokenResponse {
+                        access_token: "fresh_access_token".to_string(),
+                        token_type: "bearer".to_string(),
+                        expires_in: 3600,
+                        scope: Some("scope".to_string()),
+                        refresh_token: Some("refresh_token".to_string()),
+                    })
+                }
+            });
+
+        let state = get_state_with_token_and_client(Some("expiring_access_token"), client);
+        *state.access_token_expires_at.lock().unwrap() = Some(Instant::now() + Duration::from_secs(30));
+
+        let tokens = Arc::new(Mutex::new(vec![]));
+        let thread_tokens = Arc::clone(&tokens);
+        with_runtime(async move {
+            let thread_tokens = Arc::clone(&thread_tokens);
+            let result = with_access_token(state, |token| async {
+                let token = token;
+                let mut tokens = thread_tokens.lock().unwrap();
+                tokens.push(token.clone());
+
+                if token == "expiring_access_token".to_string() {
+                    Err(SpotifyApiError::Unauthorized)
+                } else {
+                    Ok(())
+                }
+            }).await;
+
+            assert!(result.is_ok());
+        });
+
+        let tokens = tokens.lock().unwrap();
+        assert_eq!(*tokens, ["expiring_access_token", "fresh_access_token"]);
+    }
+
+    #[test]
+    fn with_access_token_when_token_far_from_expiry_then_do_not_refresh_token() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+
+        let state = get_state_with_token_and_client(Some("access_token"), client);
+        *state.access_token_expires_at.lock().unwrap() = Some(Instant::now() + Duration::from_secs(600));
+
+        with_runtime(async move {
+            let result = with_access_token(state, |token| async {
+                let token = token;
+                assert_eq!(token, "access_token".to_string());
+                Ok(())
+            }).await;
+
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn should_refresh_when_expiry_is_within_the_margin_then_return_true() {
+        let now = Instant::now();
+        assert!(!should_refresh(None, now));
+        assert!(!should_refresh(Some(now + ACCESS_TOKEN_EXPIRY_MARGIN + Duration::from_secs(1)), now));
+        assert!(should_refresh(Some(now + ACCESS_TOKEN_EXPIRY_MARGIN), now));
+        assert!(should_refresh(Some(now + Duration::from_secs(1)), now));
+        assert!(should_refresh(Some(now), now));
+    }
+
+    #[test]
+    fn get_initial_access_token_expiry_should_convert_the_timestamp_into_an_instant() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let now_instant = Instant::now();
+
+        let mut config = get_state_with_token_and_client(None, MockSpotifyApiClient::new()).config.clone();
+        config.initial_access_token_expires_at = Some(1_000_600);
+        // without a token, the expiry is meaningless
+        assert_eq!(get_initial_access_token_expiry(&config, now, now_instant), None);
+
+        config.initial_access_token = Some("access_token".to_string());
+        assert_eq!(get_initial_access_token_expiry(&config, now, now_instant), Some(now_instant + Duration::from_secs(600)));
+
+        config.initial_access_token_expires_at = Some(999_000);
+        assert_eq!(get_initial_access_token_expiry(&config, now, now_instant), Some(now_instant));
+
+        config.initial_access_token_expires_at = None;
+        assert_eq!(get_initial_access_token_expiry(&config, now, now_instant), None);
+    }
+
     fn get_state_with_token_and_client(
         initial_access_token: Option<&'static str>,
         mocked_client: MockSpotifyApiClient,
     ) -> Arc<State> {
         let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
 
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
-
         Arc::new(State {
-            client: Box::new(mocked_client),
-            input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
             access_token: Mutex::new(initial_access_token.map(|s| s.into())),
-            last_action: Mutex::new(Instant::now()),
-            tracks: Mutex::new(None),
-            playback: Mutex::new(PlaybackState::PAUSED),
-            config,
-            sender,
+            ..test_state(test_config(), mocked_client, sender)
         })
     }
 
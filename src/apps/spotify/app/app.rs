@@ -1,28 +1,32 @@
-use tokio::runtime::Builder;
-use tokio::sync::mpsc;
+use tokio::runtime::{Builder, Handle};
+use tokio::sync::{mpsc, Semaphore};
 
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::AtomicBool;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::apps::App;
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
 use crate::image::Image;
 use crate::midi::features::Features;
 
 use super::super::config::Config;
 use super::super::client::*;
 
+use super::access_token::get_initial_access_token_expiry;
 use super::playback::*;
 use super::poll_events::*;
 use super::poll_state::*;
 use super::poll_playlist::*;
 use super::render_state::*;
+use super::resume::*;
 
 pub const NAME: &'static str = "spotify";
 pub const COLOR: [u8; 3] = [0, 255, 0];
 
 pub const DELAY: Duration = Duration::from_millis(5_000);
 pub const PLAYLIST_POLLING_INTERVAL: Duration = Duration::from_secs(600);
+pub const DEFAULT_MAX_CONCURRENT_COVER_FETCHES: usize = 2;
 
 pub type In = crate::apps::In;
 pub type Out = crate::apps::Out;
@@ -34,13 +38,45 @@ pub struct State {
     pub input_features: Arc<dyn Features + Sync + Send>,
     pub output_features: Arc<dyn Features + Sync + Send>,
     pub access_token: Mutex<Option<String>>,
+    /// When the access token expires, if known, so that it gets refreshed shortly before
+    pub access_token_expires_at: Mutex<Option<Instant>>,
     pub last_action: Mutex<Instant>,
     pub tracks: Mutex<Option<Vec<SpotifyTrack>>>,
+    /// Initialized from the configuration, but can be changed at runtime
+    pub playlist_id: Mutex<String>,
+    /// Spotify Connect device to play tracks on, selected from the web UI
+    pub device_id: Mutex<Option<String>>,
     pub playback: Mutex<PlaybackState>,
+    /// Permits to download covers, shared by all the tasks rendering them
+    pub cover_fetches: Semaphore,
+    /// Loaded once from the configured path, rendered when a cover cannot be retrieved
+    pub fallback_cover: Option<Image>,
     pub config: Config,
     pub sender: Sender<Out>,
 }
 
+/// A state with an access token, no tracks, nothing being played, and devices without any
+/// feature, for tests to override
+#[cfg(test)]
+pub fn test_state(config: Config, client: MockSpotifyApiClient, sender: Sender<Out>) -> State {
+    return State {
+        client: Box::new(client),
+        input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+        output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+        access_token: Mutex::new(Some("access_token".to_string())),
+        access_token_expires_at: Mutex::new(None),
+        last_action: Mutex::new(Instant::now()),
+        tracks: Mutex::new(None),
+        playback: Mutex::new(PlaybackState::PAUSED),
+        playlist_id: Mutex::new(config.playlist_id.clone()),
+        device_id: Mutex::new(None),
+        cover_fetches: Semaphore::new(1),
+        fallback_cover: None,
+        config,
+        sender,
+    };
+}
+
 #[derive(Clone, Debug)]
 pub enum PlaybackState {
     PAUSED,
@@ -50,8 +86,11 @@ pub enum PlaybackState {
 }
 
 pub struct Spotify {
+    state: Arc<State>,
+    runtime: Handle,
     in_sender: Sender<In>,
     out_receiver: Receiver<Out>,
+    send_policy: SendPolicy,
 }
 
 impl Spotify {
@@ -60,18 +99,25 @@ impl Spotify {
         client: Box<dyn SpotifyApiClient + Send + Sync>,
         input_features: Arc<dyn Features + Sync + Send>,
         output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
     ) -> Self {
         let (in_sender, in_receiver) = mpsc::channel::<In>(32);
         let (out_sender, out_receiver) = mpsc::channel::<Out>(32);
+        let fallback_cover = load_fallback_cover(&config);
 
         let state = Arc::new(State {
             client,
             input_features,
             output_features,
-            access_token: Mutex::new(None),
+            access_token: Mutex::new(config.initial_access_token.clone()),
+            access_token_expires_at: Mutex::new(get_initial_access_token_expiry(&config, SystemTime::now(), Instant::now())),
             last_action: Mutex::new(Instant::now() - DELAY),
             tracks: Mutex::new(None),
             playback: Mutex::new(PlaybackState::PAUSED),
+            playlist_id: Mutex::new(config.playlist_id.clone()),
+            device_id: Mutex::new(None),
+            cover_fetches: get_cover_fetches(&config),
+            fallback_cover,
             config,
             sender: out_sender,
         });
@@ -81,10 +127,19 @@ impl Spotify {
             .build()
             .unwrap();
 
+        let runtime_handle = runtime.handle().clone();
+        let state_copy = Arc::clone(&state);
         std::thread::spawn(move || {
             runtime.block_on(async move {
+                let state = state_copy;
                 let poll_playlist_state = Arc::clone(&state);
                 tokio::spawn(async move {
+                    // Resuming pulls the tracks of the playlist already
+                    if poll_playlist_state.config.resume_on_start {
+                        resume_on_start(Arc::clone(&poll_playlist_state)).await;
+                        tokio::time::sleep(PLAYLIST_POLLING_INTERVAL).await;
+                    }
+
                     poll_playlist(
                         poll_playlist_state,
                         PLAYLIST_POLLING_INTERVAL,
@@ -114,8 +169,11 @@ impl Spotify {
         });
 
         let spotify = Spotify {
+            state,
+            runtime: runtime_handle,
             in_sender,
             out_receiver,
+            send_policy,
         };
 
         return spotify;
@@ -136,7 +194,7 @@ impl App for Spotify {
     }
 
     fn send(&mut self, event: In) -> Result<(), mpsc::error::SendError<In>> {
-        return self.in_sender.blocking_send(event);
+        return send_with_policy(NAME, &self.in_sender, event, self.send_policy);
     }
 
     fn receive(&mut self) -> Result<Out, mpsc::error::TryRecvError> {
@@ -144,4 +202,8 @@ impl App for Spotify {
     }
 
     fn on_select(&mut self) {}
+
+    fn render(&mut self) {
+        self.runtime.spawn(render_state(Arc::clone(&self.state)));
+    }
 }
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use crate::apps::ServerCommand;
+use crate::server::SpotifyDeviceSummary;
+use super::app::State;
+
+use super::access_token::with_access_token;
+
+/// Send the user’s Spotify Connect devices back to the server
+pub async fn list_devices(state: Arc<State>) {
+    let devices = with_access_token(Arc::clone(&state), |token| async {
+        return state.client.get_available_devices(token).await;
+    }).await;
+
+    match devices {
+        Ok(devices) => {
+            let command = ServerCommand::SpotifyDevicesList {
+                devices: devices.devices.into_iter().map(|device| SpotifyDeviceSummary {
+                    id: device.id,
+                    name: device.name,
+                    is_active: device.is_active,
+                }).collect(),
+            };
+
+            state.sender.send(command.into()).await.unwrap_or_else(|err| {
+                eprintln!("[spotify] could not send the devices back to the router: {}", err)
+            });
+        },
+        Err(err) => eprintln!("[spotify] could not retrieve available devices: {}", err),
+    }
+}
+
+/// Play the next tracks on the given device, or on the active one if `None`
+pub fn select_device(state: Arc<State>, device_id: Option<String>) {
+    println!("[spotify] selecting device {:?}", device_id);
+    let mut state_device_id = state.device_id.lock().unwrap();
+    *state_device_id = device_id;
+}
+
+/// Query the available devices again, after the selected one could not be found: keep it if it
+/// came back, or fall back to the active device (or any device) otherwise.
+pub async fn resolve_device(state: Arc<State>, access_token: String) -> Option<String> {
+    let devices = match state.client.get_available_devices(access_token).await {
+        Ok(devices) => devices.devices,
+        Err(err) => {
+            eprintln!("[spotify] could not retrieve available devices: {}", err);
+            return None;
+        },
+    };
+
+    let preferred_device_id = state.device_id.lock().unwrap().clone();
+    let device = devices.iter().find(|device| Some(&device.id) == preferred_device_id.as_ref())
+        .or_else(|| devices.iter().find(|device| device.is_active))
+        .or_else(|| devices.first());
+
+    let device_id = device.map(|device| device.id.clone());
+    println!("[spotify] resolved device {:?}", device_id);
+    *state.device_id.lock().unwrap() = device_id.clone();
+    return device_id;
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+
+    use mockall::predicate::*;
+    use tokio::runtime::Builder;
+
+    use crate::apps::Out;
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::client::{
+        MockSpotifyApiClient,
+        SpotifyApiError,
+        SpotifyDevice,
+        SpotifyDevices,
+    };
+    use crate::apps::spotify::config::test_config;
+
+    use super::*;
+
+    #[test]
+    fn test_list_devices_then_send_them_to_the_server() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_available_devices()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(SpotifyDevices {
+                devices: vec![
+                    SpotifyDevice {
+                        id: "laptop_id".to_string(),
+                        is_active: true,
+                        name: "Laptop".to_string(),
+                    },
+                    SpotifyDevice {
+                        id: "speaker_id".to_string(),
+                        is_active: false,
+                        name: "Kitchen Speaker".to_string(),
+                    },
+                ],
+            }));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(client, sender);
+
+        with_runtime(async move {
+            list_devices(state).await;
+        });
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Server(ServerCommand::SpotifyDevicesList {
+            devices: vec![
+                SpotifyDeviceSummary {
+                    id: "laptop_id".to_string(),
+                    name: "Laptop".to_string(),
+                    is_active: true,
+                },
+                SpotifyDeviceSummary {
+                    id: "speaker_id".to_string(),
+                    name: "Kitchen Speaker".to_string(),
+                    is_active: false,
+                },
+            ],
+        })));
+    }
+
+    #[test]
+    fn test_list_devices_when_request_fails_then_send_nothing() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_available_devices()
+            .times(1)
+            .returning(|_| Err(SpotifyApiError::Timeout));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(client, sender);
+
+        with_runtime(async move {
+            list_devices(state).await;
+        });
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_select_device_then_store_it_in_state() {
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(MockSpotifyApiClient::new(), sender);
+
+        select_device(Arc::clone(&state), Some("speaker_id".to_string()));
+        assert_eq!(*state.device_id.lock().unwrap(), Some("speaker_id".to_string()));
+
+        select_device(Arc::clone(&state), None);
+        assert_eq!(*state.device_id.lock().unwrap(), None);
+    }
+
+    fn get_devices() -> SpotifyDevices {
+        return SpotifyDevices {
+            devices: vec![
+                SpotifyDevice {
+                    id: "laptop_id".to_string(),
+                    is_active: false,
+                    name: "Laptop".to_string(),
+                },
+                SpotifyDevice {
+                    id: "speaker_id".to_string(),
+                    is_active: true,
+                    name: "Kitchen Speaker".to_string(),
+                },
+            ],
+        };
+    }
+
+    #[test]
+    fn test_resolve_device_when_preferred_device_is_available_then_keep_it() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_available_devices()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(get_devices()));
+
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(client, sender);
+        *state.device_id.lock().unwrap() = Some("laptop_id".to_string());
+
+        let device_id = with_runtime(resolve_device(Arc::clone(&state), "access_token".to_string()));
+        assert_eq!(device_id, Some("laptop_id".to_string()));
+        assert_eq!(*state.device_id.lock().unwrap(), Some("laptop_id".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_device_when_preferred_device_is_gone_then_fall_back_to_the_active_one() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_available_devices()
+            .times(1)
+            .returning(|_| Ok(get_devices()));
+
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(client, sender);
+        *state.device_id.lock().unwrap() = Some("phone_id".to_string());
+
+        let device_id = with_runtime(resolve_device(Arc::clone(&state), "access_token".to_string()));
+        assert_eq!(device_id, Some("speaker_id".to_string()));
+        assert_eq!(*state.device_id.lock().unwrap(), Some("speaker_id".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_device_when_no_device_is_available_then_return_none() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_available_devices()
+            .times(1)
+            .returning(|_| Ok(SpotifyDevices { devices: vec![] }));
+
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_sender(client, sender);
+        *state.device_id.lock().unwrap() = Some("phone_id".to_string());
+
+        let device_id = with_runtime(resolve_device(Arc::clone(&state), "access_token".to_string()));
+        assert_eq!(device_id, None);
+        assert_eq!(*state.device_id.lock().unwrap(), None);
+    }
+
+    fn get_state_with_client_and_sender(
+        mocked_client: MockSpotifyApiClient,
+        sender: tokio::sync::mpsc::Sender<Out>,
+    ) -> Arc<State> {
+        Arc::new(test_state(test_config(), mocked_client, sender))
+    }
+
+    fn with_runtime<F>(f: F) -> F::Output where F: Future {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+}
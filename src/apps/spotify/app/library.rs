@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::app::*;
+use super::access_token::with_access_token;
+
+/// Color flashed on the pad of the track being played, once saved to the user’s library
+pub const SAVED_COLOR: [u8; 3] = [0, 255, 128];
+/// How long the pad stays lit with SAVED_COLOR, before being highlighted as usual again
+pub const SAVED_FLASH_DURATION: Duration = Duration::from_millis(500);
+
+/// Return whether the event is the press of the button reserved to saving tracks,
+/// on devices that have one.
+pub fn is_save_gesture(state: &State, event: &In) -> bool {
+    return match event {
+        In::Midi(event) => state.input_features.into_save_gesture(event.clone()).unwrap_or(false),
+        _ => false,
+    };
+}
+
+/// Add the track being played to the user’s library, and flash its pad once it is saved
+pub async fn save_current_track(state: Arc<State>) {
+    let index = get_playback_index(&state);
+    let track = index.and_then(|index| state.tracks.lock().unwrap().as_ref()
+        .and_then(|tracks| tracks.get(index))
+        .map(|track| (index, track.clone())));
+
+    let (index, track) = match track {
+        Some(track) => track,
+        None => {
+            println!("[spotify] no track is being played, so there is nothing to save");
+            return;
+        },
+    };
+
+    let result = with_access_token(Arc::clone(&state), |token| async {
+        return state.client.save_track(token, track.id.clone()).await;
+    }).await;
+
+    match result {
+        Ok(()) => flash_saved_track(state, index).await,
+        Err(err) => eprintln!("[spotify] could not save track {}: {}", track.name, err),
+    }
+}
+
+async fn flash_saved_track(state: Arc<State>, index: usize) {
+    match state.output_features.from_index_to_highlight_with_color(index, SAVED_COLOR) {
+        Ok(event) => state.sender.send(event.into()).await.unwrap_or_else(|err| {
+            eprintln!("[spotify] could not send the saved track confirmation: {}", err)
+        }),
+        Err(err) => {
+            eprintln!("[spotify] could not render the saved track confirmation: {}", err);
+            return;
+        },
+    }
+
+    // Restore the usual highlight later on, without holding up the events coming next
+    tokio::spawn(async move {
+        tokio::time::sleep(SAVED_FLASH_DURATION).await;
+        if get_playback_index(&state) != Some(index) {
+            return;
+        }
+
+        if let Ok(event) = state.output_features.from_index_to_highlight(index) {
+            state.sender.send(event.into()).await.unwrap_or_else(|err| {
+                eprintln!("[spotify] could not send the highlight of the saved track: {}", err)
+            });
+        }
+    });
+}
+
+fn get_playback_index(state: &State) -> Option<usize> {
+    return match *state.playback.lock().unwrap() {
+        PlaybackState::REQUESTED(index) | PlaybackState::PLAYING(index) => Some(index),
+        PlaybackState::PAUSED | PlaybackState::PAUSING => None,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::sync::Mutex;
+
+    use mockall::predicate::*;
+    use tokio::runtime::Builder;
+
+    use crate::apps::Out;
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::test_config;
+    use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyAlbum, SpotifyApiError, SpotifyTrack};
+    use crate::midi::Event;
+    use crate::midi::devices::launchpadpro::LaunchpadProFeatures;
+    use crate::midi::features::IndexSelector;
+
+    use super::*;
+
+    #[test]
+    fn is_save_gesture_when_the_save_button_is_pressed_then_return_true() {
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state(MockSpotifyApiClient::new(), PlaybackState::PAUSED, sender);
+
+        assert!(is_save_gesture(&state, &In::Midi(Event::Midi([176, 10, 127, 0]))));
+        assert!(!is_save_gesture(&state, &In::Midi(Event::Midi([176, 10, 0, 0]))));
+        assert!(!is_save_gesture(&state, &In::Midi(Event::Midi([144, 11, 127, 0]))));
+    }
+
+    #[test]
+    fn save_current_track_when_a_track_is_playing_then_save_it_and_flash_its_pad() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_save_track()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("track_id_1".to_string()))
+            .returning(|_, _| Ok(()));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state(client, PlaybackState::PLAYING(1), sender);
+
+        with_runtime(save_current_track(state));
+
+        let features = LaunchpadProFeatures::new();
+        let event = features.from_index_to_highlight_with_color(1, SAVED_COLOR).unwrap();
+        assert_eq!(receiver.try_recv(), Ok(event.into()));
+    }
+
+    #[test]
+    fn save_current_track_when_nothing_is_playing_then_save_nothing() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_save_track().times(0);
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state(client, PlaybackState::PAUSED, sender);
+
+        with_runtime(save_current_track(state));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn save_current_track_when_the_request_fails_then_do_not_flash_the_pad() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_save_track()
+            .times(1)
+            .returning(|_, _| Err(SpotifyApiError::Timeout));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state(client, PlaybackState::REQUESTED(0), sender);
+
+        with_runtime(save_current_track(state));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    fn get_track(id: &str) -> SpotifyTrack {
+        return SpotifyTrack {
+            id: id.to_string(),
+            name: format!("name_{}", id),
+            uri: format!("spotify:track:{}", id),
+            album: SpotifyAlbum { images: vec![] },
+        };
+    }
+
+    fn get_state(
+        mocked_client: MockSpotifyApiClient,
+        playback: PlaybackState,
+        sender: tokio::sync::mpsc::Sender<Out>,
+    ) -> Arc<State> {
+        Arc::new(State {
+            input_features: Arc::new(LaunchpadProFeatures::new()),
+            output_features: Arc::new(LaunchpadProFeatures::new()),
+            tracks: Mutex::new(Some(vec![get_track("track_id_0"), get_track("track_id_1")])),
+            playback: Mutex::new(playback),
+            ..test_state(test_config(), mocked_client, sender)
+        })
+    }
+
+    fn with_runtime<F>(f: F) -> F::Output where F: Future {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+}
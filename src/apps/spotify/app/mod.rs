@@ -1,10 +1,13 @@
 mod app;
 mod access_token;
+mod devices;
+mod library;
 mod playback;
 mod poll_events;
 mod poll_playlist;
 mod poll_state;
 mod render_state;
+mod resume;
 
 pub use app::NAME;
 pub use app::Spotify;
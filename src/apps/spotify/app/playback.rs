@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
-use crate::apps::ServerCommand;
+use crate::apps::{report_error, ServerCommand};
+use crate::apps::spotify::client::SpotifyApiError;
 use super::app::*;
+use super::devices::resolve_device;
 
 pub async fn play_or_pause(
     state: Arc<State>,
@@ -43,8 +45,24 @@ async fn play(
             state.sender.send(command.into()).await
                 .unwrap_or_else(|err| eprintln!("[spotify] could not send token command: {}", err));
 
-            state.client.start_or_resume_playback(access_token, vec![track.uri], None).await
-                .unwrap_or_else(|err| eprintln!("[spotify] could not send play command: {}", err));
+            let device_id = state.device_id.lock().unwrap().clone();
+            let result = state.client.start_or_resume_playback(access_token.clone(), vec![track.uri.clone()], device_id).await;
+
+            // The device may have disconnected since it got selected, so let’s find another one and retry once
+            let result = match result {
+                Err(SpotifyApiError::DeviceNotFound) => {
+                    eprintln!("[spotify] device not found, resolving it again");
+                    match resolve_device(Arc::clone(&state), access_token.clone()).await {
+                        Some(device_id) => state.client.start_or_resume_playback(access_token, vec![track.uri], Some(device_id)).await,
+                        None => Err(SpotifyApiError::DeviceNotFound),
+                    }
+                },
+                result => result,
+            };
+
+            if let Err(err) = result {
+                report_error("spotify", &state.sender, format!("could not send play command: {}", err)).await;
+            }
 
             let mut playback = state.playback.lock().unwrap();
             *playback = PlaybackState::REQUESTED(index);
@@ -68,7 +86,6 @@ async fn pause(state: Arc<State>) {
 #[cfg(test)]
 mod test {
     use std::future::Future;
-    use std::time::Instant;
     use std::sync::Mutex;
 
     use mockall::predicate::*;
@@ -76,8 +93,16 @@ mod test {
     use tokio::runtime::Builder;
     use tokio::sync::mpsc::channel;
 
-    use crate::apps::spotify::config::Config;
-    use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyAlbum, SpotifyAlbumImage, SpotifyTrack};
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::test_config;
+    use crate::apps::spotify::client::{
+        MockSpotifyApiClient,
+        SpotifyAlbum,
+        SpotifyAlbumImage,
+        SpotifyDevice,
+        SpotifyDevices,
+        SpotifyTrack,
+    };
 
     use super::*;
     use super::PlaybackState::{PAUSED, PAUSING, REQUESTED, PLAYING};
@@ -152,6 +177,73 @@ mod test {
         });
     }
 
+    #[test]
+    fn play_or_pause_when_device_selected_then_call_start_or_resume_on_that_device() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:5vmFVIJV9XN1l01YsFuKL3".to_string()]), eq(Some("device_id".to_string())))
+            .returning(|_, _, _| Ok(()));
+        client.expect_pause_playback().never();
+
+        let state = get_state_with_playing_and_client(PAUSED, client);
+        *state.device_id.lock().unwrap() = Some("device_id".to_string());
+
+        with_runtime(async move {
+            play_or_pause(Arc::clone(&state), 1).await;
+        });
+    }
+
+    #[test]
+    fn play_or_pause_when_device_not_found_then_resolve_it_again_and_retry_once() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:5vmFVIJV9XN1l01YsFuKL3".to_string()]), eq(Some("device_id".to_string())))
+            .returning(|_, _, _| Err(SpotifyApiError::DeviceNotFound));
+        client.expect_get_available_devices()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(SpotifyDevices {
+                devices: vec![SpotifyDevice {
+                    id: "other_device_id".to_string(),
+                    is_active: true,
+                    name: "Other device".to_string(),
+                }],
+            }));
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:5vmFVIJV9XN1l01YsFuKL3".to_string()]), eq(Some("other_device_id".to_string())))
+            .returning(|_, _, _| Ok(()));
+
+        let state = get_state_with_playing_and_client(PAUSED, client);
+        *state.device_id.lock().unwrap() = Some("device_id".to_string());
+
+        with_runtime(async {
+            play_or_pause(Arc::clone(&state), 1).await;
+        });
+
+        assert_eq!(*state.device_id.lock().unwrap(), Some("other_device_id".to_string()));
+        assert!(matches!(*state.playback.lock().unwrap(), REQUESTED(1)));
+    }
+
+    #[test]
+    fn play_or_pause_when_device_not_found_and_none_available_then_do_not_retry() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .returning(|_, _, _| Err(SpotifyApiError::DeviceNotFound));
+        client.expect_get_available_devices()
+            .times(1)
+            .returning(|_| Ok(SpotifyDevices { devices: vec![] }));
+
+        let state = get_state_with_playing_and_client(PAUSED, client);
+
+        with_runtime(async move {
+            play_or_pause(Arc::clone(&state), 1).await;
+        });
+    }
+
     #[test]
     fn play_or_pause_when_no_song_playing_and_index_out_of_bound_then_ignore() {
         let mut client = MockSpotifyApiClient::new();
@@ -212,23 +304,11 @@ mod test {
 
     fn get_state_with_playing_and_client(playback: PlaybackState, client: MockSpotifyApiClient) -> Arc<State> {
         let (sender, _) = channel::<Out>(32);
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
 
         Arc::new(State {
-            client: Box::new(client),
-            input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            access_token: Mutex::new(Some("access_token".to_string())),
-            last_action: Mutex::new(Instant::now()),
             tracks: Mutex::new(Some(vec![lingus(), conscious_club()])),
             playback: Mutex::new(playback),
-            config,
-            sender,
+            ..test_state(test_config(), client, sender)
         })
     }
 
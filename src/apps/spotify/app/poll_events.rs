@@ -2,7 +2,13 @@ use std::future::Future;
 use std::sync::Arc;
 use std::time::Instant;
 
+use crate::apps::ServerCommand;
+use crate::apps::confirm::{confirm, render_pending_confirmation, PendingConfirmation};
 use super::app::*;
+use super::devices::{list_devices, select_device};
+use super::library::{is_save_gesture, save_current_track};
+use super::poll_playlist::{list_playlists, select_playlist};
+use super::render_state::render_preview;
 
 pub async fn poll_events<F, Fut>(
     state: Arc<State>,
@@ -12,7 +18,35 @@ pub async fn poll_events<F, Fut>(
     F: Fn(Arc<State>, usize) -> Fut + Copy,
     Fut: Future<Output = ()>,
 {
+    let mut previewed_index = None;
+    let mut pending_confirmation = None;
     while let Some(event) = in_receiver.recv().await {
+        // Commands coming from the server are not subject to throttling
+        if let In::Server(command) = event {
+            handle_command(Arc::clone(&state), command).await;
+            continue;
+        }
+
+        if let Some(playlist_id) = get_selected_playlist(&state, &event) {
+            select_playlist(Arc::clone(&state), playlist_id).await;
+            continue;
+        }
+
+        // Saving a track does not change what is being played, so it is not subject to throttling
+        if is_save_gesture(&state, &event) {
+            save_current_track(Arc::clone(&state)).await;
+            continue;
+        }
+
+        if state.config.preview_mode {
+            previewed_index = handle_preview_event(Arc::clone(&state), play_or_pause, event, previewed_index).await;
+            continue;
+        }
+
+        if state.config.confirm_mode && !is_confirmed(Arc::clone(&state), &event, &mut pending_confirmation).await {
+            continue;
+        }
+
         let time_elapsed = Arc::clone(&state).last_action.lock().unwrap().elapsed();
         if time_elapsed > DELAY {
             handle_event(Arc::clone(&state), play_or_pause, event).await;
@@ -40,6 +74,83 @@ async fn handle_event<F, Fut>(state: Arc<State>, play_or_pause: F, event: In) wh
     }
 }
 
+/// In confirm mode, a first press of a pad lights it, and only a second press plays the track.
+///
+/// Return whether the event should be handled, i.e. whether it is not the press of a pad waiting
+/// for confirmation.
+async fn is_confirmed(state: Arc<State>, event: &In, pending_confirmation: &mut PendingConfirmation) -> bool {
+    let index = match event {
+        In::Midi(event) => state.input_features.into_index(event.clone()).ok().flatten(),
+        _ => None,
+    };
+
+    return match index {
+        Some(index) if !confirm(pending_confirmation, index, Instant::now()) => {
+            println!("[spotify] waiting for a second press of index {} to confirm", index);
+            render_pending_confirmation(state.output_features.as_ref(), &state.sender, index).await;
+            false
+        },
+        _ => true,
+    };
+}
+
+/// Return the configured playlist whose button is being pressed, if any
+fn get_selected_playlist(state: &State, event: &In) -> Option<String> {
+    let index = match event {
+        In::Midi(event) => state.input_features.into_playlist_index(event.clone()).ok().flatten(),
+        _ => None,
+    };
+    return index.and_then(|index| state.config.playlists.get(index).cloned());
+}
+
+async fn handle_command(state: Arc<State>, command: ServerCommand) {
+    match command {
+        ServerCommand::SpotifySelectPlaylist { playlist_id } => select_playlist(state, playlist_id).await,
+        ServerCommand::SpotifyListPlaylists => list_playlists(state).await,
+        ServerCommand::SpotifyListDevices => list_devices(state).await,
+        ServerCommand::SpotifySelectDevice { device_id } => select_device(state, device_id),
+        _ => {},
+    }
+}
+
+/// In preview mode, holding a pad renders the cover of the corresponding track,
+/// and releasing it plays the track (if throttling allows it).
+///
+/// Return the index of the track being previewed after having handled the event.
+async fn handle_preview_event<F, Fut>(
+    state: Arc<State>,
+    play_or_pause: F,
+    event: In,
+    previewed_index: Option<usize>,
+) -> Option<usize> where
+    F: Fn(Arc<State>, usize) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let event = match event {
+        In::Midi(event) => event,
+        _ => return previewed_index,
+    };
+
+    if let Ok(Some(index)) = state.input_features.into_index(event.clone()) {
+        render_preview(Arc::clone(&state), index).await;
+        return Some(index);
+    }
+
+    match state.input_features.into_released_index(event) {
+        Ok(Some(index)) if previewed_index == Some(index) => {
+            let time_elapsed = Arc::clone(&state).last_action.lock().unwrap().elapsed();
+            if time_elapsed > DELAY {
+                track_last_action(Arc::clone(&state));
+                play_or_pause(Arc::clone(&state), index).await;
+            } else {
+                println!("[spotify] ignoring release of index {}: {:?}ms", index, time_elapsed);
+            }
+            return None;
+        },
+        _ => return previewed_index,
+    }
+}
+
 fn track_last_action(state: Arc<State>) {
     let mut last_action = state.last_action.lock().unwrap();
     *last_action = Instant::now();
@@ -54,8 +165,11 @@ mod test {
     use tokio::sync::mpsc::error::TryRecvError;
 
     use crate::apps::{MidiEvent, ServerCommand};
-    use crate::apps::spotify::config::Config;
-    use crate::apps::spotify::client::MockSpotifyApiClient;
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::{test_config, Config};
+    use mockall::predicate::*;
+
+    use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyAlbum, SpotifyTrack};
     use super::*;
 
     #[test]
@@ -208,25 +322,347 @@ mod test {
         assert_eq!(event, Err(TryRecvError::Disconnected));
     }
 
+    #[test]
+    fn poll_events_when_preview_mode_and_pad_held_then_released_then_play_on_release() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, mut out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:1".to_string()]), eq(None))
+            .returning(|_, _, _| Ok(()));
+        let state = get_preview_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                // Hold the second pad…
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+                // … then release it
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([128, 37, 0, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::REQUESTED(1)));
+        });
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Ok(Out::Server(ServerCommand::SpotifyToken {
+            access_token: "access_token".to_string(),
+        })));
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn poll_events_when_preview_mode_and_pad_held_then_do_not_play() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, mut out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback().never();
+        let state = get_preview_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::PAUSED));
+        });
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn poll_events_when_preview_mode_and_other_pad_held_then_only_play_on_release_of_the_last_one() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, mut out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:1".to_string()]), eq(None))
+            .returning(|_, _, _| Ok(()));
+        let state = get_preview_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                // Hold the first pad, then the second one
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 36, 100, 0]))).unwrap();
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+                // Releasing the first pad is ignored, as it is not previewed anymore
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 36, 0, 0]))).unwrap();
+                // Releasing the second pad commits to playing it
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 0, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::REQUESTED(1)));
+        });
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Ok(Out::Server(ServerCommand::SpotifyToken {
+            access_token: "access_token".to_string(),
+        })));
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn poll_events_when_select_playlist_command_then_pull_tracks_from_the_new_playlist() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, _out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("other_playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![SpotifyTrack {
+                name: "Track".to_string(),
+                id: "track".to_string(),
+                uri: "spotify:track:track".to_string(),
+                album: SpotifyAlbum { images: vec![] },
+            }]));
+        // server commands are not throttled, even if the last action is recent
+        let state = get_state_with_client_and_last_action_and_sender(client, Instant::now(), out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                in_sender.blocking_send(In::Server(ServerCommand::SpotifySelectPlaylist {
+                    playlist_id: "other_playlist_id".to_string(),
+                })).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert_eq!(*state.playlist_id.lock().unwrap(), "other_playlist_id".to_string());
+            assert_eq!(state.tracks.lock().unwrap().as_ref().map(|tracks| tracks.len()), Some(1));
+        });
+    }
+
+    #[test]
+    fn poll_events_when_playlist_button_pressed_then_pull_tracks_from_the_corresponding_playlist() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, _out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("other_playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![SpotifyTrack {
+                name: "Track".to_string(),
+                id: "track".to_string(),
+                uri: "spotify:track:track".to_string(),
+                album: SpotifyAlbum { images: vec![] },
+            }]));
+        client.expect_start_or_resume_playback().never();
+        let state = get_playlists_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                // the second button of the top row of the Launchpad Pro
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([176, 92, 127, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert_eq!(*state.playlist_id.lock().unwrap(), "other_playlist_id".to_string());
+            assert_eq!(state.tracks.lock().unwrap().as_ref().map(|tracks| tracks.len()), Some(1));
+        });
+    }
+
+    #[test]
+    fn poll_events_when_unassigned_playlist_button_pressed_then_keep_the_current_playlist() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, _out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks().never();
+        let state = get_playlists_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                // only two playlists are configured, the third button has nothing to select
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([176, 93, 127, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert_eq!(*state.playlist_id.lock().unwrap(), "playlist_id".to_string());
+        });
+    }
+
+    #[test]
+    fn poll_events_when_confirm_mode_and_pad_pressed_once_then_do_not_play() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, mut out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback().never();
+        let state = get_confirm_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::PAUSED));
+        });
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn poll_events_when_confirm_mode_and_pad_pressed_twice_then_play() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, mut out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec!["spotify:track:1".to_string()]), eq(None))
+            .returning(|_, _, _| Ok(()));
+        let state = get_confirm_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::REQUESTED(1)));
+        });
+
+        let event = out_receiver.try_recv();
+        assert_eq!(event, Ok(Out::Server(ServerCommand::SpotifyToken {
+            access_token: "access_token".to_string(),
+        })));
+    }
+
+    #[test]
+    fn poll_events_when_confirm_mode_and_other_pad_pressed_in_between_then_do_not_play() {
+        let (in_sender, in_receiver) = tokio::sync::mpsc::channel::<In>(32);
+        let (out_sender, _out_receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_start_or_resume_playback().never();
+        let state = get_confirm_state_with_client_and_sender(client, out_sender);
+
+        with_runtime(async move {
+            std::thread::spawn(move || {
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 36, 100, 0]))).unwrap();
+                in_sender.blocking_send(In::Midi(MidiEvent::Midi([144, 37, 100, 0]))).unwrap();
+            });
+
+            poll_events(
+                Arc::clone(&state),
+                in_receiver,
+                super::super::playback::play_or_pause,
+            ).await;
+
+            assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::PAUSED));
+        });
+    }
+
+    fn get_confirm_state_with_client_and_sender(client: MockSpotifyApiClient, sender: Sender<Out>) -> Arc<State> {
+        let state = get_preview_state_with_client_and_sender(client, sender);
+        let state = Arc::try_unwrap(state).ok().expect("the state should not be shared yet");
+        return Arc::new(State {
+            config: Config { preview_mode: false, confirm_mode: true, ..state.config },
+            ..state
+        });
+    }
+
+    fn get_playlists_state_with_client_and_sender(client: MockSpotifyApiClient, sender: Sender<Out>) -> Arc<State> {
+        let state = get_state_with_client_and_last_action_and_sender(client, Instant::now() - Duration::from_millis(5_000), sender);
+        let state = Arc::try_unwrap(state).ok().expect("the state should not be shared yet");
+        return Arc::new(State {
+            input_features: Arc::new(crate::midi::devices::launchpadpro::LaunchpadProFeatures::new()),
+            config: Config {
+                playlists: vec!["playlist_id".to_string(), "other_playlist_id".to_string()],
+                ..state.config
+            },
+            ..state
+        });
+    }
+
+    fn get_preview_state_with_client_and_sender(client: MockSpotifyApiClient, sender: Sender<Out>) -> Arc<State> {
+        let config = Config { preview_mode: true, ..test_config() };
+
+        // Tracks without any cover, so that previewing them does not require any network access
+        let tracks = (0..2).map(|index| SpotifyTrack {
+            name: format!("Track {}", index),
+            id: format!("{}", index),
+            uri: format!("spotify:track:{}", index),
+            album: SpotifyAlbum { images: vec![] },
+        }).collect();
+
+        Arc::new(State {
+            last_action: Mutex::new(Instant::now() - Duration::from_millis(5_000)),
+            tracks: Mutex::new(Some(tracks)),
+            ..test_state(config, client, sender)
+        })
+    }
+
     fn get_state_with_last_action_and_sender(last_action: Instant, sender: Sender<Out>) -> Arc<State> {
-        let client = Box::new(MockSpotifyApiClient::new());
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
+        return get_state_with_client_and_last_action_and_sender(MockSpotifyApiClient::new(), last_action, sender);
+    }
 
+    fn get_state_with_client_and_last_action_and_sender(
+        client: MockSpotifyApiClient,
+        last_action: Instant,
+        sender: Sender<Out>,
+    ) -> Arc<State> {
         Arc::new(State {
-            client,
-            input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            access_token: Mutex::new(Some("access_token".to_string())),
             last_action: Mutex::new(last_action),
             tracks: Mutex::new(Some(vec![])),
-            playback: Mutex::new(PlaybackState::PAUSED),
-            config,
-            sender,
+            ..test_state(test_config(), client, sender)
         })
     }
 
@@ -2,9 +2,13 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use super::app::State;
+use crate::apps::{report_error, ServerCommand};
+use crate::apps::render::render_empty_playlist;
+use crate::server::SpotifyPlaylistSummary;
+use super::app::{PlaybackState, State};
 
 use super::access_token::with_access_token;
+use super::render_state::render_state;
 
 pub async fn poll_playlist(
     state: Arc<State>,
@@ -17,36 +21,107 @@ pub async fn poll_playlist(
     }
 }
 
-async fn pull_playlist_tracks(state: Arc<State>) {
-    with_access_token(Arc::clone(&state), |token| async {
-        let tracks = state.client.get_playlist_tracks(token, Arc::clone(&state).config.playlist_id.clone()).await?;
-        let mut state_tracks = state.tracks.lock().unwrap();
-        *state_tracks = Some(tracks);
-        Ok(())
-    }).await.unwrap_or_else(|err| {
-        eprintln!("[spotify] could not pull tracks from playlist {}: {}", state.config.playlist_id, err);
-    });
+pub async fn pull_playlist_tracks(state: Arc<State>) {
+    let playlist_id = state.playlist_id.lock().unwrap().clone();
+    let pulled = with_access_token(Arc::clone(&state), |token| async {
+        let tracks = state.client.get_playlist_tracks(token, playlist_id.clone()).await?;
+        let is_empty = tracks.is_empty();
+
+        // The playlist may have been switched while we were pulling its tracks
+        if *state.playlist_id.lock().unwrap() == playlist_id {
+            let mut state_tracks = state.tracks.lock().unwrap();
+            *state_tracks = Some(tracks);
+        }
+        Ok(is_empty)
+    }).await;
+
+    let is_empty = match pulled {
+        Ok(is_empty) => is_empty,
+        Err(err) => {
+            report_error("spotify", &state.sender, format!("could not pull tracks from playlist {}: {}", playlist_id, err)).await;
+            false
+        },
+    };
+
+    if is_empty {
+        eprintln!("[spotify] playlist {} has no tracks: pressing pads will have no effect", playlist_id);
+        render_empty_playlist("spotify", state.output_features.as_ref(), &state.sender, state.config.empty_playlist_color).await;
+    }
+}
+
+/// Switch to another playlist, and pull its tracks right away.
+///
+/// The playback index refers to the previous playlist, so it gets reset and the app is rendered
+/// again, unless the new playlist turned out to be empty and its glyph is already showing.
+pub async fn select_playlist(state: Arc<State>, playlist_id: String) {
+    println!("[spotify] selecting playlist {}", playlist_id);
+    {
+        let mut state_playlist_id = state.playlist_id.lock().unwrap();
+        *state_playlist_id = playlist_id;
+    }
+    {
+        let mut playback = state.playback.lock().unwrap();
+        *playback = PlaybackState::PAUSED;
+    }
+    pull_playlist_tracks(Arc::clone(&state)).await;
+
+    let is_empty = state.tracks.lock().unwrap().as_ref().map_or(false, |tracks| tracks.is_empty());
+    if !is_empty {
+        render_state(state).await;
+    }
+}
+
+/// Send the user’s playlists back to the server
+pub async fn list_playlists(state: Arc<State>) {
+    let playlists = with_access_token(Arc::clone(&state), |token| async {
+        return state.client.get_playlists(token).await;
+    }).await;
+
+    match playlists {
+        Ok(playlists) => {
+            let command = ServerCommand::SpotifyPlaylists {
+                playlists: playlists.items.into_iter().map(|playlist| SpotifyPlaylistSummary {
+                    id: playlist.id,
+                    name: playlist.name,
+                    tracks: playlist.tracks.total,
+                }).collect(),
+            };
+
+            state.sender.send(command.into()).await.unwrap_or_else(|err| {
+                eprintln!("[spotify] could not send the playlists back to the router: {}", err)
+            });
+        },
+        Err(err) => eprintln!("[spotify] could not retrieve user playlists: {}", err),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::future::Future;
-    use std::time::Instant;
     use std::sync::Mutex;
 
     use mockall::predicate::*;
     use tokio::runtime::Builder;
 
     use crate::apps::Out;
-    use crate::apps::spotify::app::app::PlaybackState;
-    use crate::apps::spotify::config::Config;
+    use crate::apps::render::get_empty_playlist_glyph;
+    use crate::apps::spotify::app::render_state::get_logo;
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::test_config;
     use crate::apps::spotify::client::{
         MockSpotifyApiClient,
         SpotifyAlbum,
         SpotifyAlbumImage,
         SpotifyApiError,
+        SpotifyPlaylist,
+        SpotifyPlaylists,
+        SpotifyPlaylistTracks,
         SpotifyTrack
     };
+    use crate::image::Image;
+    use crate::midi::Event;
+    use crate::midi::devices::default::DefaultFeatures;
+    use crate::midi::features::{R, Features, ImageRenderer};
 
     use super::*;
 
@@ -218,29 +293,215 @@ mod test {
         assert_eq!(*state.tracks.lock().unwrap(), Some(vec![lingus(), conscious_club()]));
     }
 
+    #[test]
+    fn test_pull_playlist_tracks_when_request_fails_then_report_the_error() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .returning(|_, _| Err(SpotifyApiError::Timeout));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender(client, vec![], sender);
+
+        with_runtime(async move {
+            pull_playlist_tracks(state).await;
+        });
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Telemetry(crate::apps::TelemetryEvent::Error {
+            message: "could not pull tracks from playlist playlist_id: Spotify Web API did not respond in time".to_string(),
+        })));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    struct ImageFeatures {}
+    impl ImageRenderer for ImageFeatures {
+        fn from_image(&self, image: Image) -> R<Event> {
+            return Ok(Event::SysEx(image.bytes));
+        }
+    }
+    impl Features for ImageFeatures {}
+
+    #[test]
+    fn test_pull_playlist_tracks_when_playlist_is_empty_then_render_the_empty_playlist_glyph() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender_and_features(client, vec![], sender, Arc::new(ImageFeatures {}));
+
+        with_runtime(async move {
+            pull_playlist_tracks(state).await;
+        });
+
+        let glyph = get_empty_playlist_glyph([255, 0, 0]);
+        assert_eq!(receiver.try_recv(), Ok(Out::Midi(Event::SysEx(glyph.bytes))));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_pull_playlist_tracks_when_playlist_has_tracks_then_render_nothing() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .returning(|_, _| Ok(vec![lingus()]));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender_and_features(client, vec![], sender, Arc::new(ImageFeatures {}));
+
+        with_runtime(async move {
+            pull_playlist_tracks(state).await;
+        });
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_select_playlist_then_pull_tracks_from_the_new_playlist() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("other_playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![conscious_club()]));
+
+        let state = get_state_with_client_and_tracks(client, vec![lingus()]);
+
+        let thread_state = Arc::clone(&state);
+        with_runtime(async move {
+            select_playlist(thread_state, "other_playlist_id".to_string()).await;
+        });
+
+        assert_eq!(*state.playlist_id.lock().unwrap(), "other_playlist_id".to_string());
+        assert_eq!(*state.tracks.lock().unwrap(), Some(vec![conscious_club()]));
+    }
+
+    #[test]
+    fn test_select_playlist_then_reset_the_playback_and_render_the_logo() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("other_playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![conscious_club()]));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender_and_features(client, vec![lingus()], sender, Arc::new(ImageFeatures {}));
+        *state.playback.lock().unwrap() = PlaybackState::PLAYING(0);
+
+        let thread_state = Arc::clone(&state);
+        with_runtime(async move {
+            select_playlist(thread_state, "other_playlist_id".to_string()).await;
+        });
+
+        assert!(matches!(*state.playback.lock().unwrap(), PlaybackState::PAUSED));
+        assert_eq!(receiver.try_recv(), Ok(Out::Midi(Event::SysEx(get_logo().bytes))));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_select_playlist_when_playlist_is_empty_then_keep_the_empty_playlist_glyph() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender_and_features(client, vec![lingus()], sender, Arc::new(ImageFeatures {}));
+
+        with_runtime(async move {
+            select_playlist(state, "other_playlist_id".to_string()).await;
+        });
+
+        let glyph = get_empty_playlist_glyph([255, 0, 0]);
+        assert_eq!(receiver.try_recv(), Ok(Out::Midi(Event::SysEx(glyph.bytes))));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_poll_playlist_after_select_playlist_then_keep_pulling_the_new_playlist() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlist_tracks()
+            .times(2)
+            .with(eq("access_token".to_string()), eq("other_playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![conscious_club()]));
+
+        let state = get_state_with_client_and_tracks(client, vec![]);
+
+        with_runtime(async move {
+            select_playlist(Arc::clone(&state), "other_playlist_id".to_string()).await;
+
+            let terminate = Arc::new(AtomicBool::new(false));
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_playlist(
+                Arc::clone(&state),
+                Duration::from_millis(100),
+                terminate,
+            ).await;
+        });
+    }
+
+    #[test]
+    fn test_list_playlists_then_send_them_to_the_server() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_get_playlists()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(SpotifyPlaylists {
+                items: vec![SpotifyPlaylist {
+                    id: "playlist_id".to_string(),
+                    name: "Sunday Morning".to_string(),
+                    tracks: SpotifyPlaylistTracks { total: 42 },
+                }],
+            }));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_client_and_tracks_and_sender(client, vec![], sender);
+
+        with_runtime(async move {
+            list_playlists(state).await;
+        });
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Server(ServerCommand::SpotifyPlaylists {
+            playlists: vec![SpotifyPlaylistSummary {
+                id: "playlist_id".to_string(),
+                name: "Sunday Morning".to_string(),
+                tracks: 42,
+            }],
+        })));
+    }
+
     fn get_state_with_client_and_tracks(
         mocked_client: MockSpotifyApiClient,
         tracks: Vec<SpotifyTrack>,
     ) -> Arc<State> {
         let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        return get_state_with_client_and_tracks_and_sender(mocked_client, tracks, sender);
+    }
 
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
+    fn get_state_with_client_and_tracks_and_sender(
+        mocked_client: MockSpotifyApiClient,
+        tracks: Vec<SpotifyTrack>,
+        sender: tokio::sync::mpsc::Sender<Out>,
+    ) -> Arc<State> {
+        let features = Arc::new(DefaultFeatures::new());
+        return get_state_with_client_and_tracks_and_sender_and_features(mocked_client, tracks, sender, features);
+    }
 
+    fn get_state_with_client_and_tracks_and_sender_and_features(
+        mocked_client: MockSpotifyApiClient,
+        tracks: Vec<SpotifyTrack>,
+        sender: tokio::sync::mpsc::Sender<Out>,
+        features: Arc<dyn Features + Sync + Send>,
+    ) -> Arc<State> {
         Arc::new(State {
-            client: Box::new(mocked_client),
-            input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            access_token: Mutex::new(Some("access_token".to_string())),
-            last_action: Mutex::new(Instant::now()),
+            input_features: Arc::clone(&features),
+            output_features: features,
             tracks: Mutex::new(Some(tracks)),
-            playback: Mutex::new(PlaybackState::PAUSED),
-            config,
-            sender,
+            ..test_state(test_config(), mocked_client, sender)
         })
     }
 
@@ -2,78 +2,126 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use crate::apps::spotify::client::SpotifyApiResult;
-use super::app::State;
+use crate::apps::TelemetryEvent;
+use crate::apps::spotify::client::{SpotifyApiError, SpotifyApiResult};
+use super::app::{PlaybackState, State};
 use super::app::PlaybackState::*;
 
 use super::access_token::with_access_token;
 
+const POLLING_INTERVAL: Duration = Duration::from_millis(1_000);
+
 pub async fn poll_state(
     state: Arc<State>,
     terminate: Arc<AtomicBool>,
 ) {
     while terminate.load(Ordering::Relaxed) != true {
-        match get_currently_playing_index(Arc::clone(&state)).await {
+        let delay = match get_currently_playing_index(Arc::clone(&state)).await {
             Ok(spotify_playback) => {
-                let mut playback = state.playback.lock().unwrap();
-                let throttling_elapsed = state.last_action.lock().unwrap().elapsed() > super::app::DELAY;
-
-                match (playback.clone(), spotify_playback) {
-                    (PAUSING, None) => {
-                        // Spotify has caught up with our local state
-                        *playback = PAUSED;
-                    },
-                    (PAUSING, Some(spotify_index)) => {
-                        // We only accept that our local state is corrupted after the throttling
-                        // delay has elapsed.
-                        if throttling_elapsed {
-                            *playback = PLAYING(spotify_index);
-                        }
-                    },
-                    (REQUESTED(local_index), Some(spotify_index)) if local_index == spotify_index => {
-                        *playback = PLAYING(spotify_index);
-                    },
-                    (REQUESTED(_), Some(spotify_index)) => {
-                        // We only accept that our local state is corrupted after the throttling
-                        // delay has elapsed.
-                        if throttling_elapsed {
-                            *playback = PLAYING(spotify_index);
-                        }
-                    },
-                    (REQUESTED(_), None) => {
-                        // We only accept that our local state is corrupted after the throttling
-                        // delay has elapsed.
-                        if throttling_elapsed {
-                            *playback = PAUSED;
-                        }
-                    },
-                    // For all other cases, we accept the state we get back from Spotify
-                    (_, None) => {
-                        *playback = PAUSED;
-                    }
-                    (_, Some(index)) => {
-                        *playback = PLAYING(index);
-                    },
+                if let Some(index) = update_playback(&state, spotify_playback) {
+                    send_track_changed(Arc::clone(&state), index).await;
                 }
+                POLLING_INTERVAL
             },
-            Err(err) => eprintln!("[spotify] could not poll playback state: {}", err),
-        }
+            Err(err) => {
+                eprintln!("[spotify] could not poll playback state: {}", err);
+                get_retry_delay(&err)
+            },
+        };
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// When being rate-limited, wait for as long as Spotify asks to before polling again
+fn get_retry_delay(err: &SpotifyApiError) -> Duration {
+    return match err {
+        SpotifyApiError::RateLimited(retry_after) => POLLING_INTERVAL.max(*retry_after),
+        _ => POLLING_INTERVAL,
+    };
+}
 
-        tokio::time::sleep(Duration::from_millis(1_000)).await;
+/// Reconcile the local playback state with the index Spotify is playing, and return the index of
+/// the track that started playing, if the playback has moved to another track.
+fn update_playback(state: &State, spotify_playback: Option<usize>) -> Option<usize> {
+    let mut playback = state.playback.lock().unwrap();
+    let previous_playback = playback.clone();
+    let throttling_elapsed = state.last_action.lock().unwrap().elapsed() > super::app::DELAY;
+    let trust_remote = state.config.trust_remote_immediately;
+
+    match (playback.clone(), spotify_playback) {
+        (PAUSING, None) => {
+            // Spotify has caught up with our local state
+            *playback = PAUSED;
+        },
+        (PAUSING, Some(spotify_index)) => {
+            // We only accept that our local state is corrupted after the throttling
+            // delay has elapsed, unless we have been told to trust Spotify.
+            if throttling_elapsed || trust_remote {
+                *playback = PLAYING(spotify_index);
+            }
+        },
+        (REQUESTED(local_index), Some(spotify_index)) if local_index == spotify_index => {
+            *playback = PLAYING(spotify_index);
+        },
+        (REQUESTED(_), Some(spotify_index)) => {
+            // We only accept that our local state is corrupted after the throttling
+            // delay has elapsed, unless we have been told to trust Spotify.
+            if throttling_elapsed || trust_remote {
+                *playback = PLAYING(spotify_index);
+            }
+        },
+        (REQUESTED(_), None) => {
+            // We only accept that our local state is corrupted after the throttling
+            // delay has elapsed.
+            if throttling_elapsed {
+                *playback = PAUSED;
+            }
+        },
+        // For all other cases, we accept the state we get back from Spotify
+        (_, None) => {
+            *playback = PAUSED;
+        }
+        (_, Some(index)) => {
+            *playback = PLAYING(index);
+        },
     }
+
+    return get_changed_index(&previous_playback, &playback);
 }
 
-async fn get_currently_playing_index(state: Arc<State>) -> SpotifyApiResult<Option<usize>> {
+/// The index of the track that started playing, if the playback has moved to another track
+fn get_changed_index(previous: &PlaybackState, current: &PlaybackState) -> Option<usize> {
+    return match (previous, current) {
+        (PLAYING(previous_index), PLAYING(index)) if previous_index == index => None,
+        (_, PLAYING(index)) => Some(*index),
+        _ => None,
+    };
+}
+
+async fn send_track_changed(state: Arc<State>, index: usize) {
+    let name = state.tracks.lock().unwrap().as_ref()
+        .and_then(|tracks| tracks.get(index))
+        .map(|track| track.name.clone())
+        .unwrap_or_default();
+
+    state.sender.send(TelemetryEvent::TrackChanged { index, name }.into()).await.unwrap_or_else(|err| {
+        eprintln!("[spotify] could not send the track change back to the router: {}", err)
+    });
+}
+
+pub async fn get_currently_playing_index(state: Arc<State>) -> SpotifyApiResult<Option<usize>> {
     with_access_token(Arc::clone(&state), |token| async {
         let playback_state = state.client.get_playback_state(token).await?;
 
         return Ok(playback_state
             .filter(|playback_state| playback_state.is_playing)
-            .and_then(|playback_state| {
+            .and_then(|playback_state| playback_state.item)
+            .and_then(|item| {
                 let tracks = state.tracks.lock().unwrap();
                 if let Some(tracks) = tracks.as_ref() {
                     for i in 0..tracks.len() {
-                        if tracks[i].id == playback_state.item.id {
+                        if tracks[i].id == item.id {
                             return Some(i);
                         }
                     }
@@ -87,15 +135,14 @@ async fn get_currently_playing_index(state: Arc<State>) -> SpotifyApiResult<Opti
 #[cfg(test)]
 mod test {
     use std::future::Future;
-    use std::time::Instant;
     use std::sync::Mutex;
 
     use mockall::predicate::*;
     use tokio::runtime::Builder;
 
     use crate::apps::Out;
-    use crate::apps::spotify::app::app::PlaybackState;
-    use crate::apps::spotify::config::Config;
+    use crate::apps::spotify::app::app::{test_state, PlaybackState};
+    use crate::apps::spotify::config::{test_config, Config};
     use crate::apps::spotify::client::{
         MockSpotifyApiClient,
         SpotifyAlbum,
@@ -230,7 +277,7 @@ mod test {
             .with(eq("access_token".to_string()))
             .returning(|_| Ok(Some(SpotifyPlaybackState {
                 is_playing: true,
-                item: conscious_club(),
+                item: Some(conscious_club()),
             })));
 
         let state = get_state_with_playing_and_tracks_and_client(PAUSED, vec![lingus(), conscious_club()], client);
@@ -262,7 +309,7 @@ mod test {
             .with(eq("access_token".to_string()))
             .returning(|_| Ok(Some(SpotifyPlaybackState {
                 is_playing: true,
-                item: lingus(),
+                item: Some(lingus()),
             })));
 
         // Returns a nothing the third time
@@ -300,7 +347,7 @@ mod test {
             .with(eq("access_token".to_string()))
             .returning(|_| Ok(Some(SpotifyPlaybackState {
                 is_playing: true,
-                item: lingus(),
+                item: Some(lingus()),
             })));
 
         // Returns a paused Lingus the third time
@@ -309,7 +356,7 @@ mod test {
             .with(eq("access_token".to_string()))
             .returning(|_| Ok(Some(SpotifyPlaybackState {
                 is_playing: false,
-                item: lingus(),
+                item: Some(lingus()),
             })));
 
         let state = get_state_with_playing_and_tracks_and_client(PLAYING(0), vec![lingus(), conscious_club()], client);
@@ -341,7 +388,7 @@ mod test {
             .with(eq("access_token".to_string()))
             .returning(|_| Ok(Some(SpotifyPlaybackState {
                 is_playing: true,
-                item: conscious_club(),
+                item: Some(conscious_club()),
             })));
 
         let state = get_state_with_playing_and_tracks_and_client(PAUSED, vec![lingus()], client);
@@ -361,30 +408,230 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_get_currently_playing_index_when_item_is_missing_then_return_none() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: None,
+            })));
+
+        let state = get_state_with_playing_and_tracks_and_client(PLAYING(0), vec![lingus()], client);
+
+        let index = with_runtime(async move {
+            get_currently_playing_index(Arc::clone(&state)).await
+        });
+
+        assert!(matches!(index, Ok(None)), "unexpected index: {:?}", index);
+    }
+
+    #[test]
+    fn test_poll_state_when_another_track_is_requested_and_throttling_applies_then_keep_local_state() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+
+        // Conscious Club has been selected from another device
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: Some(conscious_club()),
+            })));
+
+        let state = get_state_with_playing_and_tracks_and_client(REQUESTED(0), vec![lingus(), conscious_club()], client);
+
+        let thread_state = Arc::clone(&state);
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_state(
+                thread_state,
+                terminate,
+            ).await;
+        });
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, REQUESTED(0)), "expected REQUESTED(0), got {:?}", playback);
+    }
+
+    #[test]
+    fn test_poll_state_when_another_track_is_requested_and_remote_is_trusted_then_adopt_remote_index() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+
+        // Conscious Club has been selected from another device
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: Some(conscious_club()),
+            })));
+
+        let state = get_state_with_trust_and_playing_and_tracks_and_client(true, REQUESTED(0), vec![lingus(), conscious_club()], client);
+
+        let thread_state = Arc::clone(&state);
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_state(
+                thread_state,
+                terminate,
+            ).await;
+        });
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, PLAYING(1)), "expected PLAYING(1), got {:?}", playback);
+    }
+
+    #[test]
+    fn test_poll_state_when_pausing_and_remote_is_trusted_then_adopt_remote_index() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+
+        // Lingus has been resumed from another device
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: Some(lingus()),
+            })));
+
+        let state = get_state_with_trust_and_playing_and_tracks_and_client(true, PAUSING, vec![lingus(), conscious_club()], client);
+
+        let thread_state = Arc::clone(&state);
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_state(
+                thread_state,
+                terminate,
+            ).await;
+        });
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, PLAYING(0)), "expected PLAYING(0), got {:?}", playback);
+    }
+
+    #[test]
+    fn test_poll_state_when_another_track_starts_playing_then_send_a_track_change() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: Some(conscious_club()),
+            })));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_sender_and_trust_and_playing_and_tracks_and_client(sender, false, PLAYING(0), vec![lingus(), conscious_club()], client);
+
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_state(
+                state,
+                terminate,
+            ).await;
+        });
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Telemetry(TelemetryEvent::TrackChanged {
+            index: 1,
+            name: "Conscious Club".to_string(),
+        })));
+    }
+
+    #[test]
+    fn test_poll_state_when_the_same_track_keeps_playing_then_send_nothing() {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(|_| Ok(Some(SpotifyPlaybackState {
+                is_playing: true,
+                item: Some(lingus()),
+            })));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_with_sender_and_trust_and_playing_and_tracks_and_client(sender, false, PLAYING(0), vec![lingus(), conscious_club()], client);
+
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            poll_state(
+                state,
+                terminate,
+            ).await;
+        });
+
+        assert!(receiver.try_recv().is_err());
+    }
+
     fn get_state_with_playing_and_tracks_and_client(
         playback: PlaybackState,
         tracks: Vec<SpotifyTrack>,
         mocked_client: MockSpotifyApiClient,
+    ) -> Arc<State> {
+        return get_state_with_trust_and_playing_and_tracks_and_client(false, playback, tracks, mocked_client);
+    }
+
+    fn get_state_with_trust_and_playing_and_tracks_and_client(
+        trust_remote_immediately: bool,
+        playback: PlaybackState,
+        tracks: Vec<SpotifyTrack>,
+        mocked_client: MockSpotifyApiClient,
     ) -> Arc<State> {
         let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+        return get_state_with_sender_and_trust_and_playing_and_tracks_and_client(sender, trust_remote_immediately, playback, tracks, mocked_client);
+    }
 
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
+    fn get_state_with_sender_and_trust_and_playing_and_tracks_and_client(
+        sender: tokio::sync::mpsc::Sender<Out>,
+        trust_remote_immediately: bool,
+        playback: PlaybackState,
+        tracks: Vec<SpotifyTrack>,
+        mocked_client: MockSpotifyApiClient,
+    ) -> Arc<State> {
+        let config = Config { trust_remote_immediately, ..test_config() };
 
         Arc::new(State {
-            client: Box::new(mocked_client),
-            input_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            output_features: Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
-            access_token: Mutex::new(Some("access_token".to_string())),
-            last_action: Mutex::new(Instant::now()),
             tracks: Mutex::new(Some(tracks)),
             playback: Mutex::new(playback),
-            config,
-            sender,
+            ..test_state(config, mocked_client, sender)
         })
     }
 
@@ -395,4 +642,16 @@ mod test {
             .unwrap()
             .block_on(f)
     }
+
+    #[test]
+    fn get_retry_delay_when_rate_limited_then_wait_for_the_retry_after_delay() {
+        assert_eq!(get_retry_delay(&SpotifyApiError::RateLimited(Duration::from_secs(30))), Duration::from_secs(30));
+        assert_eq!(get_retry_delay(&SpotifyApiError::RateLimited(Duration::from_secs(0))), POLLING_INTERVAL);
+    }
+
+    #[test]
+    fn get_retry_delay_when_other_error_then_wait_for_the_polling_interval() {
+        assert_eq!(get_retry_delay(&SpotifyApiError::Timeout), POLLING_INTERVAL);
+        assert_eq!(get_retry_delay(&SpotifyApiError::Unauthorized), POLLING_INTERVAL);
+    }
 }
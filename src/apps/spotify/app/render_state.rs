@@ -1,8 +1,14 @@
+use std::future::Future;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use crate::image::Image;
+use tokio::sync::Semaphore;
+
+use crate::apps::render::{from_image_or_mosaic, render_image_with_highlight, render_number_or_image_with_highlight};
+use crate::image::{Image, ImageRgba};
+use super::super::client::{SpotifyAlbumImage, SpotifyTrack};
+use super::super::config::{Config, CoverImageSize};
 use super::app::*;
 use super::app::PlaybackState::*;
 
@@ -24,7 +30,7 @@ pub async fn render_state_reactively(
         match playback {
             PAUSED | PAUSING => {
                 if r_index != None {
-                    render_state(Arc::clone(&state)).await;
+                    render_paused(Arc::clone(&state), r_index).await;
                     let mut rendered_index = rendered_index.lock().unwrap();
                     *rendered_index = None;
                 }
@@ -50,82 +56,178 @@ pub async fn render_state_reactively(
 }
 
 pub async fn render_state(state: Arc<State>) {
-    render_logo(Arc::clone(&state)).await;
-    render_highlighted_index(Arc::clone(&state)).await;
-}
+    let index = get_playback_index(&state);
 
-async fn render_logo(state: Arc<State>) {
-    match state.output_features.from_image(get_logo()) {
-        Err(err) => eprintln!("[spotify] could not render the spotify logo: {}", err),
-        Ok(event) => {
-            state.sender.send(event.into()).await.unwrap_or_else(|err| {
-                eprintln!("[spotify] could send the logo event back to the router: {}", err)
-            });
-        },
+    if state.config.show_track_number {
+        render_number_or_image_with_highlight("spotify", state.output_features.as_ref(), &state.sender, get_logo(), index, G).await;
+    } else {
+        render_image_with_highlight("spotify", state.output_features.as_ref(), &state.sender, get_logo(), index).await;
     }
 }
 
-async fn render_highlighted_index(state: Arc<State>) {
-    let playback = state.playback.lock().unwrap().clone();
+/// Render the logo with a pause glyph on top of it, so that users know playback got paused.
+///
+/// If a paused highlight color is configured, the pad of the last track is lit with it.
+async fn render_paused(state: Arc<State>, last_index: Option<usize>) {
+    let image = ImageRgba::transparent(8, 8)
+        .composite(&get_pause_glyph(), 2, 1)
+        .and_then(|layer| layer.flatten(&get_logo()))
+        .unwrap_or_else(|err| {
+            eprintln!("[spotify] could not draw the pause glyph over the logo: {:?}", err);
+            get_logo()
+        });
+    render_image_with_highlight("spotify", state.output_features.as_ref(), &state.sender, image, None).await;
 
-    match playback {
-        REQUESTED(index) | PLAYING(index) => match state.output_features.from_index_to_highlight(index) {
-            Err(err) => eprintln!("[spotify] could not highlight the index {}: {}", index, err),
+    if let (Some(index), Some(color)) = (last_index, state.config.paused_highlight_color) {
+        match state.output_features.from_index_to_highlight_with_color(index, color) {
+            Err(err) => eprintln!("[spotify] could not highlight the paused index {}: {}", index, err),
             Ok(event) => {
                 state.sender.send(event.into()).await.unwrap_or_else(|err| {
-                    eprintln!("[spotify] could not send the highlighting-index event back to the router: {}", err)
+                    eprintln!("[spotify] could not send the paused highlight back to the router: {}", err)
                 });
             },
-        },
-        _ => {},
+        }
     }
 }
 
+async fn render_logo(state: Arc<State>) {
+    render_image_with_highlight("spotify", state.output_features.as_ref(), &state.sender, get_logo(), None).await;
+}
+
 async fn render_cover(state: Arc<State>) {
-    let track = {
-        let playback = state.playback.lock().unwrap().clone();
-        match playback {
-            PAUSED | PAUSING => None,
-            PLAYING(index) | REQUESTED(index) => {
-                let tracks = state.tracks.lock().unwrap();
-                tracks.as_ref().map(|tracks| tracks[index as usize].clone())
+    let track = get_playback_index(&state).and_then(|index| {
+        let tracks = state.tracks.lock().unwrap();
+        let tracks = tracks.as_ref()?;
+        // The playlist may have shrunk since the track got selected
+        match tracks.get(index) {
+            Some(track) => Some((index, track.clone())),
+            None => {
+                eprintln!("[spotify] WARNING: track {} is out of the playlist ({} tracks), rendering the logo instead", index, tracks.len());
+                None
             },
         }
-    };
+    });
 
     match track {
         None => render_logo(state).await,
-        Some(track) => {
-            match track.album.images.last().map(|image| image.url.clone()) {
-                None => {
-                    eprintln!("[spotify] no cover found for track {}", track.uri);
-                    render_logo(state).await
-                },
-                Some(cover_url) => {
-                    let image = Image::from_url(&cover_url).await.map_err(|err| {
-                        eprintln!("[spotify] could not retrieve image: {:?}", err)
-                    });
-
-                    let event_out = image.and_then(|image| {
-                        return state.output_features.from_image(image).map_err(|err| {
-                            eprintln!("[spotify] could not transform image into a MIDI event: {}", err)
-                        });
-                    });
-
-                    if let Ok(event) = event_out {
-                        state.sender.send(event.into()).await.unwrap_or_else(|err| {
-                            eprintln!("[spotify] could send the image back to the router: {}", err)
-                        });
-
-                        // Render the cover image for as long as throttling takes effect
-                        tokio::time::sleep(super::app::DELAY).await;
-                    }
-                },
+        Some((index, track)) => {
+            let image = get_cover_or_fallback(&state, &track).await;
+            render_fetched_cover(state, index, image).await;
+        },
+    }
+}
+
+/// Covers can take a while to be retrieved: only render them if their track is still the one
+/// being played, so that a slow fetch cannot override the cover of a track requested afterwards.
+async fn render_fetched_cover(state: Arc<State>, index: usize, image: Option<Image>) {
+    if get_playback_index(&state) != Some(index) {
+        println!("[spotify] discarding the cover of track {}, as the track changed in the meantime", index);
+        return;
+    }
+
+    match image {
+        None => render_logo(state).await,
+        Some(image) => {
+            let event_out = from_image_or_mosaic(state.output_features.as_ref(), image).map_err(|err| {
+                eprintln!("[spotify] could not transform image into a MIDI event: {}", err)
+            });
+
+            if let Ok(event) = event_out {
+                state.sender.send(event.into()).await.unwrap_or_else(|err| {
+                    eprintln!("[spotify] could send the image back to the router: {}", err)
+                });
+
+                // Render the cover image for as long as throttling takes effect
+                tokio::time::sleep(super::app::DELAY).await;
             }
         },
     }
 }
 
+fn get_playback_index(state: &State) -> Option<usize> {
+    return match *state.playback.lock().unwrap() {
+        REQUESTED(index) | PLAYING(index) => Some(index),
+        PAUSED | PAUSING => None,
+    };
+}
+
+/// Render the cover of the track being previewed (or the logo if it has none),
+/// and highlight its index.
+pub async fn render_preview(state: Arc<State>, index: usize) {
+    let track = state.tracks.lock().unwrap().as_ref()
+        .and_then(|tracks| tracks.get(index))
+        .map(|track| track.clone());
+
+    let image = match track {
+        None => None,
+        Some(track) => get_cover_or_fallback(&state, &track).await,
+    };
+
+    render_image_with_highlight(
+        "spotify",
+        state.output_features.as_ref(),
+        &state.sender,
+        image.unwrap_or_else(get_logo),
+        Some(index),
+    ).await;
+}
+
+/// Load the image to render in place of the covers that cannot be retrieved, if configured
+pub fn load_fallback_cover(config: &Config) -> Option<Image> {
+    let path = config.fallback_cover.as_ref()?;
+    return Image::from_path(path).map_err(|err| {
+        eprintln!("[spotify] could not load the fallback cover {}: {:?}", path.display(), err)
+    }).ok();
+}
+
+/// Limit the concurrent downloads of covers, always letting at least one of them through
+pub fn get_cover_fetches(config: &Config) -> Semaphore {
+    let permits = config.max_concurrent_cover_fetches.unwrap_or(DEFAULT_MAX_CONCURRENT_COVER_FETCHES);
+    return Semaphore::new(permits.max(1));
+}
+
+async fn get_cover_or_fallback(state: &State, track: &SpotifyTrack) -> Option<Image> {
+    return match get_cover(&state.cover_fetches, track, &state.config.cover_image_size).await {
+        None => state.fallback_cover.clone(),
+        cover => cover,
+    };
+}
+
+async fn get_cover(fetches: &Semaphore, track: &SpotifyTrack, size: &CoverImageSize) -> Option<Image> {
+    return match select_cover_image(&track.album.images, size).map(|image| image.url.clone()) {
+        None => {
+            eprintln!("[spotify] no cover found for track {}", track.uri);
+            None
+        },
+        Some(cover_url) => {
+            with_fetch_permit(fetches, Image::from_url(&cover_url)).await.map_err(|err| {
+                eprintln!("[spotify] could not retrieve image: {:?}", err)
+            }).ok()
+        },
+    };
+}
+
+/// Wait for one of the permits to be available before fetching, and hold it until the fetch is done
+async fn with_fetch_permit<F: Future>(fetches: &Semaphore, fetch: F) -> F::Output {
+    // The semaphore is never closed, but fetching without a permit beats not fetching at all
+    let _permit = fetches.acquire().await.map_err(|err| {
+        eprintln!("[spotify] could not wait for a cover fetch permit: {}", err)
+    });
+    return fetch.await;
+}
+
+/// Pick the album image matching the configured size, comparing images by their area
+fn select_cover_image<'a>(images: &'a [SpotifyAlbumImage], size: &CoverImageSize) -> Option<&'a SpotifyAlbumImage> {
+    let area = |image: &&SpotifyAlbumImage| image.width as u32 * image.height as u32;
+    return match size {
+        CoverImageSize::Smallest => images.iter().min_by_key(area),
+        CoverImageSize::Largest => images.iter().max_by_key(area),
+        CoverImageSize::Nearest(target) => images.iter().min_by_key(|image| {
+            (image.width as i32 - *target as i32).abs() + (image.height as i32 - *target as i32).abs()
+        }),
+    };
+}
+
 pub fn get_logo() -> Image {
     return Image {
         width: 8,
@@ -143,20 +245,38 @@ pub fn get_logo() -> Image {
     };
 }
 
+/// Two black bars, letting the logo show through the pixels between them
+fn get_pause_glyph() -> ImageRgba {
+    let (k, t) = ([0, 0, 0, 255], [0, 0, 0, 0]);
+    return ImageRgba {
+        width: 4,
+        height: 6,
+        bytes: vec![
+            k, t, t, k,
+            k, t, t, k,
+            k, t, t, k,
+            k, t, t, k,
+            k, t, t, k,
+            k, t, t, k,
+        ].concat(),
+    };
+}
+
 #[cfg(test)]
 mod test {
     use std::future::Future;
     use std::sync::Mutex;
-    use std::time::Instant;
 
     use tokio::runtime::Builder;
 
-    use crate::apps::spotify::config::Config;
-    use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyTrack};
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::{test_config, Config};
+    use crate::apps::spotify::client::{MockSpotifyApiClient, SpotifyAlbum, SpotifyTrack};
     use crate::midi::Event;
     use crate::midi::features::{R, ImageRenderer, IndexSelector, Features};
     use super::*;
 
+    const K: [u8; 3] = [0, 0, 0];
 
     #[test]
     fn render_state_when_working_features_and_no_playing_index_then_render_state() {
@@ -311,34 +431,375 @@ mod test {
         });
     }
 
+    #[test]
+    fn render_paused_when_working_features_then_render_logo_with_pause_glyph() {
+        struct FakeFeatures {}
+        impl ImageRenderer for FakeFeatures {
+            fn from_image(&self, mut image: Image) -> R<Event> {
+                let mut bytes = Vec::from("IMG".as_bytes());
+                bytes.append(&mut image.bytes);
+                return Ok(Event::SysEx(bytes));
+            }
+        }
+        impl Features for FakeFeatures {}
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let state = get_state_with(
+            Arc::new(FakeFeatures {}),
+            vec![],
+            PAUSED,
+            sender,
+        );
+
+        with_runtime(async move {
+            render_paused(state, Some(42)).await;
+
+            let event = receiver.recv().await.unwrap();
+            assert_eq!(event, Out::Midi(Event::SysEx([
+                Vec::from("IMG".as_bytes()),
+                vec![
+                    G, G, G, G, G, G, G, G,
+                    G, G, K, W, W, K, G, G,
+                    G, W, K, G, G, K, W, G,
+                    G, G, K, W, W, K, G, G,
+                    G, W, K, G, G, K, W, G,
+                    G, G, K, W, W, K, G, G,
+                    G, W, K, G, G, K, W, G,
+                    G, G, G, G, G, G, G, G,
+                ].concat(),
+            ].concat())));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_paused_when_paused_highlight_color_then_light_the_last_index_with_it() {
+        struct FakeFeatures {}
+        impl ImageRenderer for FakeFeatures {
+            fn from_image(&self, _image: Image) -> R<Event> {
+                return Ok(Event::SysEx(Vec::from("IMG".as_bytes())));
+            }
+        }
+        impl IndexSelector for FakeFeatures {
+            fn from_index_to_highlight_with_color(&self, index: usize, color: [u8; 3]) -> R<Event> {
+                return Ok(Event::Midi([index as u8, color[0], color[1], color[2]]));
+            }
+        }
+        impl Features for FakeFeatures {}
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let state = get_state_with_paused_highlight_color(
+            Arc::new(FakeFeatures {}),
+            vec![],
+            PAUSED,
+            sender,
+            Some([0, 64, 0]),
+        );
+
+        with_runtime(async move {
+            render_paused(state, Some(42)).await;
+
+            let event = receiver.recv().await.unwrap();
+            assert_eq!(event, Out::Midi(Event::SysEx(Vec::from("IMG".as_bytes()))));
+
+            let event = receiver.recv().await.unwrap();
+            assert_eq!(event, Out::Midi(Event::Midi([42, 0, 64, 0])));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_paused_when_paused_highlight_color_but_no_last_index_then_only_render_logo() {
+        struct FakeFeatures {}
+        impl ImageRenderer for FakeFeatures {
+            fn from_image(&self, _image: Image) -> R<Event> {
+                return Ok(Event::SysEx(Vec::from("IMG".as_bytes())));
+            }
+        }
+        impl IndexSelector for FakeFeatures {
+            fn from_index_to_highlight_with_color(&self, index: usize, color: [u8; 3]) -> R<Event> {
+                return Ok(Event::Midi([index as u8, color[0], color[1], color[2]]));
+            }
+        }
+        impl Features for FakeFeatures {}
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let state = get_state_with_paused_highlight_color(
+            Arc::new(FakeFeatures {}),
+            vec![],
+            PAUSING,
+            sender,
+            Some([0, 64, 0]),
+        );
+
+        with_runtime(async move {
+            render_paused(state, None).await;
+
+            let event = receiver.recv().await.unwrap();
+            assert_eq!(event, Out::Midi(Event::SysEx(Vec::from("IMG".as_bytes()))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    #[test]
+    fn render_paused_when_paused_highlight_color_is_unsupported_then_only_render_logo() {
+        struct FakeFeatures {}
+        impl ImageRenderer for FakeFeatures {
+            fn from_image(&self, _image: Image) -> R<Event> {
+                return Ok(Event::SysEx(Vec::from("IMG".as_bytes())));
+            }
+        }
+        impl Features for FakeFeatures {}
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let state = get_state_with_paused_highlight_color(
+            Arc::new(FakeFeatures {}),
+            vec![],
+            PAUSED,
+            sender,
+            Some([0, 64, 0]),
+        );
+
+        with_runtime(async move {
+            render_paused(state, Some(42)).await;
+
+            let event = receiver.recv().await.unwrap();
+            assert_eq!(event, Out::Midi(Event::SysEx(Vec::from("IMG".as_bytes()))));
+
+            let event = receiver.recv().await;
+            assert_eq!(event, None);
+        });
+    }
+
+    fn get_album_images() -> Vec<SpotifyAlbumImage> {
+        return vec![
+            SpotifyAlbumImage { width: 640, height: 640, url: "large".to_string() },
+            SpotifyAlbumImage { width: 64, height: 64, url: "small".to_string() },
+            SpotifyAlbumImage { width: 300, height: 300, url: "medium".to_string() },
+        ];
+    }
+
+    #[test]
+    fn select_cover_image_when_smallest_then_return_the_smallest_image() {
+        let images = get_album_images();
+        let image = select_cover_image(&images, &CoverImageSize::Smallest);
+        assert_eq!(image.map(|image| image.url.as_str()), Some("small"));
+    }
+
+    #[test]
+    fn select_cover_image_when_largest_then_return_the_largest_image() {
+        let images = get_album_images();
+        let image = select_cover_image(&images, &CoverImageSize::Largest);
+        assert_eq!(image.map(|image| image.url.as_str()), Some("large"));
+    }
+
+    #[test]
+    fn select_cover_image_when_nearest_then_return_the_image_closest_to_the_given_size() {
+        let images = get_album_images();
+        assert_eq!(select_cover_image(&images, &CoverImageSize::Nearest(8)).map(|image| image.url.as_str()), Some("small"));
+        assert_eq!(select_cover_image(&images, &CoverImageSize::Nearest(256)).map(|image| image.url.as_str()), Some("medium"));
+        assert_eq!(select_cover_image(&images, &CoverImageSize::Nearest(1000)).map(|image| image.url.as_str()), Some("large"));
+    }
+
+    #[test]
+    fn select_cover_image_when_no_images_then_return_none() {
+        assert_eq!(select_cover_image(&[], &CoverImageSize::Largest), None);
+    }
+
+    fn get_track_without_cover() -> SpotifyTrack {
+        return SpotifyTrack {
+            name: "Conscious Club".to_string(),
+            id: "5vmFVIJV9XN1l01YsFuKL3".to_string(),
+            uri: "spotify:track:5vmFVIJV9XN1l01YsFuKL3".to_string(),
+            album: SpotifyAlbum { images: vec![] },
+        };
+    }
+
+    /// State whose images get rendered as their bytes, prefixed with "IMG"
+    fn get_state_rendering_images(tracks: Vec<SpotifyTrack>, playback: PlaybackState, sender: Sender<Out>) -> Arc<State> {
+        struct FakeFeatures {}
+        impl ImageRenderer for FakeFeatures {
+            fn from_image(&self, mut image: Image) -> R<Event> {
+                let mut bytes = Vec::from("IMG".as_bytes());
+                bytes.append(&mut image.bytes);
+                return Ok(Event::SysEx(bytes));
+            }
+        }
+        impl Features for FakeFeatures {}
+
+        return get_state_with(Arc::new(FakeFeatures {}), tracks, playback, sender);
+    }
+
+    /// Render the cover of the track being played, and return the first event sent, without
+    /// waiting for the cover to stay rendered
+    fn get_first_rendered_cover_event(fallback_cover: Option<Image>) -> Option<Out> {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_rendering_images(vec![get_track_without_cover()], PLAYING(0), sender);
+        let state = Arc::try_unwrap(state).ok().expect("the state should not be shared yet");
+        let state = Arc::new(State { fallback_cover, ..state });
+
+        return with_runtime(async move {
+            let rendering = tokio::spawn(render_cover(state));
+            let event = receiver.recv().await;
+            rendering.abort();
+            event
+        });
+    }
+
+    #[test]
+    fn render_cover_when_cover_cannot_be_retrieved_and_fallback_configured_then_render_the_fallback() {
+        let fallback_cover = Image { width: 1, height: 1, bytes: vec![255, 0, 0] };
+        assert_eq!(get_first_rendered_cover_event(Some(fallback_cover)), Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 255, 0, 0]))));
+    }
+
+    #[test]
+    fn render_cover_when_cover_cannot_be_retrieved_and_no_fallback_then_render_the_logo() {
+        let mut logo = Vec::from("IMG".as_bytes());
+        logo.append(&mut get_logo().bytes);
+        assert_eq!(get_first_rendered_cover_event(None), Some(Out::Midi(Event::SysEx(logo))));
+    }
+
+    #[test]
+    fn render_fetched_cover_when_track_changed_during_the_fetch_then_discard_the_cover() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let tracks = vec![get_track_without_cover(), get_track_without_cover()];
+        let state = get_state_rendering_images(tracks, REQUESTED(0), sender);
+
+        with_runtime(async move {
+            // the fetch of the cover of track 0 completes after track 1 got requested
+            *state.playback.lock().unwrap() = REQUESTED(1);
+            render_fetched_cover(Arc::clone(&state), 0, Some(Image { width: 1, height: 1, bytes: vec![255, 0, 0] })).await;
+            assert!(receiver.try_recv().is_err());
+
+            let rendering = tokio::spawn(render_fetched_cover(state, 1, Some(Image { width: 1, height: 1, bytes: vec![0, 0, 255] })));
+            let event = receiver.recv().await;
+            rendering.abort();
+            assert_eq!(event, Some(Out::Midi(Event::SysEx(vec![b'I', b'M', b'G', 0, 0, 255]))));
+        });
+    }
+
+    #[test]
+    fn render_fetched_cover_when_playback_paused_during_the_fetch_then_discard_the_cover() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_rendering_images(vec![get_track_without_cover()], PAUSED, sender);
+
+        with_runtime(async move {
+            render_fetched_cover(state, 0, None).await;
+            assert!(receiver.try_recv().is_err());
+        });
+    }
+
+    #[test]
+    fn render_cover_when_playback_index_is_beyond_the_tracks_then_render_the_logo() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Out>(32);
+        let state = get_state_rendering_images(vec![get_track_without_cover()], PLAYING(3), sender);
+
+        with_runtime(render_cover(state));
+
+        let mut logo = Vec::from("IMG".as_bytes());
+        logo.append(&mut get_logo().bytes);
+        assert_eq!(receiver.try_recv(), Ok(Out::Midi(Event::SysEx(logo))));
+    }
+
+    #[test]
+    fn load_fallback_cover_when_path_is_valid_then_load_the_image() {
+        let config = Config {
+            fallback_cover: Some(std::path::Path::new(file!()).with_file_name("../../../image/test/cover.jpg")),
+            ..get_config(None)
+        };
+
+        let image = load_fallback_cover(&config).expect("the fallback cover should be loaded");
+        assert_eq!((image.width, image.height), (64, 64));
+    }
+
+    #[test]
+    fn load_fallback_cover_when_path_is_invalid_or_missing_then_return_none() {
+        let config = Config {
+            fallback_cover: Some(std::path::PathBuf::from("/nonexistent/cover.jpg")),
+            ..get_config(None)
+        };
+        assert_eq!(load_fallback_cover(&config), None);
+
+        let config = Config { fallback_cover: None, ..config };
+        assert_eq!(load_fallback_cover(&config), None);
+    }
+
+    #[test]
+    fn get_cover_fetches_when_no_fetch_is_allowed_then_allow_one_anyway() {
+        let config = Config { max_concurrent_cover_fetches: Some(0), ..get_config(None) };
+        assert_eq!(get_cover_fetches(&config).available_permits(), 1);
+
+        let config = Config { max_concurrent_cover_fetches: None, ..config };
+        assert_eq!(get_cover_fetches(&config).available_permits(), DEFAULT_MAX_CONCURRENT_COVER_FETCHES);
+    }
+
+    #[test]
+    fn with_fetch_permit_when_many_fetches_are_pending_then_only_run_as_many_as_permits() {
+        use std::sync::atomic::AtomicUsize;
+
+        let fetches = Semaphore::new(2);
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let fetch = || async {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        };
+
+        with_runtime(futures_util::future::join_all(
+            (0..10).map(|_| with_fetch_permit(&fetches, fetch()))
+        ));
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn get_logo_when_devices_have_different_origins_then_render_it_right_side_up() {
+        crate::apps::render::logo_test::assert_logo_renders_right_side_up(get_logo());
+    }
+
     fn get_state_with(
         features: Arc<dyn Features + Sync + Send>,
         tracks: Vec<SpotifyTrack>,
         playback: PlaybackState,
         sender: Sender<Out>,
     ) -> Arc<State> {
-        let client = Box::new(MockSpotifyApiClient::new());
-
-        let config = Config {
-            playlist_id: "playlist_id".to_string(),
-            client_id: "client_id".to_string(),
-            client_secret: "client_secret".to_string(),
-            refresh_token: "refresh_token".to_string(),
-        };
+        return get_state_with_paused_highlight_color(features, tracks, playback, sender, None);
+    }
 
+    fn get_state_with_paused_highlight_color(
+        features: Arc<dyn Features + Sync + Send>,
+        tracks: Vec<SpotifyTrack>,
+        playback: PlaybackState,
+        sender: Sender<Out>,
+        paused_highlight_color: Option<[u8; 3]>,
+    ) -> Arc<State> {
         Arc::new(State {
-            client,
             input_features: Arc::clone(&features),
             output_features: Arc::clone(&features),
-            access_token: Mutex::new(Some("access_token".to_string())),
-            last_action: Mutex::new(Instant::now()),
             tracks: Mutex::new(Some(tracks)),
             playback: Mutex::new(playback),
-            config,
-            sender,
+            ..test_state(get_config(paused_highlight_color), MockSpotifyApiClient::new(), sender)
         })
     }
 
+    fn get_config(paused_highlight_color: Option<[u8; 3]>) -> Config {
+        return Config { paused_highlight_color, ..test_config() };
+    }
+
     fn with_runtime<F>(f: F) -> F::Output where F: Future {
         Builder::new_current_thread()
             .enable_all()
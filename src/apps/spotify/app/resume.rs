@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use super::app::*;
+use super::playback::play_or_pause;
+use super::poll_playlist::pull_playlist_tracks;
+use super::poll_state::get_currently_playing_index;
+
+/// Pull the tracks of the playlist, then adopt the track Spotify is playing, so that it gets
+/// highlighted right away. If nothing from the playlist is playing, play the default track, if any.
+pub async fn resume_on_start(state: Arc<State>) {
+    pull_playlist_tracks(Arc::clone(&state)).await;
+
+    match get_currently_playing_index(Arc::clone(&state)).await {
+        Ok(Some(index)) => {
+            println!("[spotify] resuming track {}", index);
+            let mut playback = state.playback.lock().unwrap();
+            *playback = PlaybackState::PLAYING(index);
+        },
+        Ok(None) => if let Some(index) = state.config.default_track_index {
+            println!("[spotify] nothing is playing, playing the default track {}", index);
+            play_or_pause(state, index).await;
+        },
+        Err(err) => eprintln!("[spotify] could not read the playback state to resume it: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+
+    use mockall::predicate::*;
+    use tokio::runtime::Builder;
+
+    use crate::apps::spotify::app::app::test_state;
+    use crate::apps::spotify::config::{test_config, Config};
+    use crate::apps::spotify::client::{
+        MockSpotifyApiClient,
+        SpotifyAlbum,
+        SpotifyPlaybackState,
+        SpotifyTrack,
+    };
+
+    use super::*;
+    use super::PlaybackState::{PAUSED, PLAYING, REQUESTED};
+
+    fn lingus() -> SpotifyTrack {
+        SpotifyTrack {
+            name: "We Like It Here".to_string(),
+            id: "68d6ZfyMUYURol2y15Ta2Y".to_string(),
+            uri: "spotify:track:68d6ZfyMUYURol2y15Ta2Y".to_string(),
+            album: SpotifyAlbum { images: vec![] },
+        }
+    }
+
+    fn conscious_club() -> SpotifyTrack {
+        SpotifyTrack {
+            name: "Conscious Club".to_string(),
+            id: "5vmFVIJV9XN1l01YsFuKL3".to_string(),
+            uri: "spotify:track:5vmFVIJV9XN1l01YsFuKL3".to_string(),
+            album: SpotifyAlbum { images: vec![] },
+        }
+    }
+
+    fn get_client_with_playback_state(playback_state: Option<SpotifyPlaybackState>) -> MockSpotifyApiClient {
+        let mut client = MockSpotifyApiClient::new();
+        client.expect_refresh_token().times(0);
+        client.expect_get_playlist_tracks()
+            .times(1)
+            .with(eq("access_token".to_string()), eq("playlist_id".to_string()))
+            .returning(|_, _| Ok(vec![lingus(), conscious_club()]));
+        client.expect_get_playback_state()
+            .times(1)
+            .with(eq("access_token".to_string()))
+            .returning(move |_| Ok(playback_state.clone()));
+        return client;
+    }
+
+    #[test]
+    fn resume_on_start_when_a_track_of_the_playlist_is_playing_then_adopt_it() {
+        let mut client = get_client_with_playback_state(Some(SpotifyPlaybackState {
+            is_playing: true,
+            item: Some(conscious_club()),
+        }));
+        client.expect_start_or_resume_playback().times(0);
+
+        let state = get_state_with_client_and_default_track_index(client, Some(0));
+        with_runtime(resume_on_start(Arc::clone(&state)));
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, PLAYING(1)), "expected PLAYING(1), got {:?}", playback);
+    }
+
+    #[test]
+    fn resume_on_start_when_nothing_is_playing_then_play_the_default_track() {
+        let mut client = get_client_with_playback_state(None);
+        client.expect_start_or_resume_playback()
+            .times(1)
+            .with(eq("access_token".to_string()), eq(vec![lingus().uri]), eq(None))
+            .returning(|_, _, _| Ok(()));
+
+        let state = get_state_with_client_and_default_track_index(client, Some(0));
+        with_runtime(resume_on_start(Arc::clone(&state)));
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, REQUESTED(0)), "expected REQUESTED(0), got {:?}", playback);
+    }
+
+    #[test]
+    fn resume_on_start_when_nothing_is_playing_and_no_default_track_then_do_nothing() {
+        let mut client = get_client_with_playback_state(Some(SpotifyPlaybackState {
+            is_playing: false,
+            item: Some(conscious_club()),
+        }));
+        client.expect_start_or_resume_playback().times(0);
+
+        let state = get_state_with_client_and_default_track_index(client, None);
+        with_runtime(resume_on_start(Arc::clone(&state)));
+
+        let playback = state.playback.lock().unwrap().clone();
+        assert!(matches!(playback, PAUSED), "expected PAUSED, got {:?}", playback);
+    }
+
+    fn get_state_with_client_and_default_track_index(
+        mocked_client: MockSpotifyApiClient,
+        default_track_index: Option<usize>,
+    ) -> Arc<State> {
+        let (sender, _) = tokio::sync::mpsc::channel::<Out>(32);
+
+        let config = Config {
+            resume_on_start: true,
+            default_track_index,
+            ..test_config()
+        };
+
+        Arc::new(test_state(config, mocked_client, sender))
+    }
+
+    fn with_runtime<F>(f: F) -> F::Output where F: Future {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+}
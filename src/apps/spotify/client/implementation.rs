@@ -1,26 +1,50 @@
 use std::collections::HashMap;
 use std::future::Future;
 use std::marker::Sized;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use base64::encode;
 use reqwest::{Client, Response, StatusCode};
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
 use serde::Serialize;
 
 use super::*;
 
+use crate::apps::http::{HttpConfig, build_client};
+
+const ACCOUNTS_URL: &'static str = "https://accounts.spotify.com";
+const API_URL: &'static str = "https://api.spotify.com";
+/// How long to wait when being rate-limited without being told for how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
 impl From<reqwest::Error> for SpotifyApiError {
     fn from(err: reqwest::Error) -> SpotifyApiError {
+        if err.is_timeout() {
+            return SpotifyApiError::Timeout;
+        }
         return SpotifyApiError::Other(Box::new(err));
     }
 }
 
-pub struct SpotifyApiClientImpl {}
+pub struct SpotifyApiClientImpl {
+    client: Client,
+    accounts_url: String,
+    api_url: String,
+}
 
 impl SpotifyApiClientImpl {
     pub fn new() -> Self {
-        return SpotifyApiClientImpl {};
+        return SpotifyApiClientImpl::from(&HttpConfig::default());
+    }
+}
+
+impl From<&HttpConfig> for SpotifyApiClientImpl {
+    fn from(config: &HttpConfig) -> Self {
+        return SpotifyApiClientImpl {
+            client: build_client(config),
+            accounts_url: ACCOUNTS_URL.to_string(),
+            api_url: API_URL.to_string(),
+        };
     }
 }
 
@@ -32,8 +56,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         client_secret: &String,
         code: &String,
     ) -> SpotifyApiResult<SpotifyTokenResponse> {
-        let client = reqwest::Client::new();
-        let response = client.post("https://accounts.spotify.com/api/token")
+        let response = self.client.post(format!("{}/api/token", self.accounts_url))
             .headers(prepare_headers(client_id, client_secret))
             .body(querystring::stringify(vec![
                 ("grant_type", "authorization_code"),
@@ -56,8 +79,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         client_secret: &String,
         refresh_token: &String,
     ) -> SpotifyApiResult<SpotifyTokenResponse> {
-        let client = reqwest::Client::new();
-        let response = client.post("https://accounts.spotify.com/api/token")
+        let response = self.client.post(format!("{}/api/token", self.accounts_url))
             .headers(prepare_headers(client_id, client_secret))
             .body(querystring::stringify(vec![
                 ("grant_type", "refresh_token"),
@@ -78,7 +100,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         token: String,
     ) -> SpotifyApiResult<SpotifyPlaylists> {
         return log("Get user playlists".to_string(), || async {
-            let response = get("https://api.spotify.com/v1/me/playlists".to_string(), token).await?
+            let response = get(&self.client, format!("{}/v1/me/playlists", self.api_url), token).await?
                 .json::<SpotifyPlaylists>()
                 .await
                 .map_err(SpotifyApiError::from)?;
@@ -92,7 +114,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         playlist_id: String
     ) -> SpotifyApiResult<Vec<SpotifyTrack>> {
         return log(format!("Get tracks from playlist {}", playlist_id), || async {
-            let response = get(format!("https://api.spotify.com/v1/playlists/{}/tracks", playlist_id), token).await?
+            let response = get(&self.client, format!("{}/v1/playlists/{}/tracks", self.api_url, playlist_id), token).await?
                 .json::<SpotifyPlaylistResponse>()
                 .await
                 .map_err(SpotifyApiError::from)?;
@@ -106,7 +128,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         token: String
     ) -> SpotifyApiResult<Option<SpotifyPlaybackState>> {
         return log("Get playback state".to_string(), || async {
-            let response = get("https://api.spotify.com/v1/me/player".to_string(), token).await?;
+            let response = get(&self.client, format!("{}/v1/me/player", self.api_url), token).await?;
             if response.status() == StatusCode::NO_CONTENT {
                 return Ok(None);
             } else {
@@ -127,7 +149,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         return log(format!("Start or resume playback of {:?}", uris), || async {
             let query = device_id.map(|id| format!("?device_id={}", id)).unwrap_or("".to_string());
             let body = HashMap::from([("uris", uris)]);
-            let _ = put(format!("https://api.spotify.com/v1/me/player/play{}", query), token, &body).await?;
+            let _ = put(&self.client, format!("{}/v1/me/player/play{}", self.api_url, query), token, &body).await?;
             return Ok(());
         }).await;
     }
@@ -137,7 +159,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         token: String,
     ) -> SpotifyApiResult<()> {
         return log("Pause playback".to_string(), || async {
-            let _ = put("https://api.spotify.com/v1/me/player/pause".to_string(), token, "").await?;
+            let _ = put(&self.client, format!("{}/v1/me/player/pause", self.api_url), token, "").await?;
             return Ok(());
         }).await;
     }
@@ -147,7 +169,7 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         token: String,
     ) -> SpotifyApiResult<SpotifyDevices> {
         return log("Get available devices".to_string(), || async {
-            let response = get("https://api.spotify.com/v1/me/player/devices".to_string(), token).await?;
+            let response = get(&self.client, format!("{}/v1/me/player/devices", self.api_url), token).await?;
             return response
                 .json::<SpotifyDevices>()
                 .await
@@ -155,6 +177,17 @@ impl SpotifyApiClient for SpotifyApiClientImpl {
         }).await;
     }
 
+    async fn save_track(
+        &self,
+        token: String,
+        track_id: String,
+    ) -> SpotifyApiResult<()> {
+        return log(format!("Save track {}", track_id), || async {
+            let body = HashMap::from([("ids", vec![track_id.clone()])]);
+            let _ = put(&self.client, format!("{}/v1/me/tracks", self.api_url), token, &body).await?;
+            return Ok(());
+        }).await;
+    }
 }
 
 fn prepare_headers(client_id: &String, client_secret: &String) -> HeaderMap {
@@ -176,8 +209,7 @@ async fn log<F, Fut, T>(description: String, action: F) -> T where
     return result;
 }
 
-async fn get(url: String, token: String) -> SpotifyApiResult<Response> {
-    let client = Client::new();
+async fn get(client: &Client, url: String, token: String) -> SpotifyApiResult<Response> {
     let response = client.get(url)
         .headers(headers(token))
         .send()
@@ -186,13 +218,14 @@ async fn get(url: String, token: String) -> SpotifyApiResult<Response> {
 
     if response.status() == StatusCode::UNAUTHORIZED {
         return Err(SpotifyApiError::Unauthorized);
+    } else if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        return Err(SpotifyApiError::RateLimited(get_retry_after(response.headers())));
     } else {
         return Ok(response);
     }
 }
 
-async fn put<P: Serialize + ?Sized>(url: String, token: String, json_body: &P) -> SpotifyApiResult<Response> {
-    let client = Client::new();
+async fn put<P: Serialize + ?Sized>(client: &Client, url: String, token: String, json_body: &P) -> SpotifyApiResult<Response> {
     let response = client.put(url)
         .headers(headers(token))
         .json(json_body)
@@ -202,11 +235,25 @@ async fn put<P: Serialize + ?Sized>(url: String, token: String, json_body: &P) -
 
     if response.status() == StatusCode::UNAUTHORIZED {
         return Err(SpotifyApiError::Unauthorized);
+    } else if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        return Err(SpotifyApiError::RateLimited(get_retry_after(response.headers())));
+    } else if response.status() == StatusCode::NOT_FOUND {
+        // The player endpoints respond with 404 when there is no (or no such) device to act on
+        return Err(SpotifyApiError::DeviceNotFound);
     } else {
         return Ok(response);
     }
 }
 
+/// Spotify gives the number of seconds to wait for in the Retry-After header of 429 responses
+fn get_retry_after(headers: &HeaderMap) -> Duration {
+    return headers.get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_RETRY_AFTER);
+}
+
 fn headers(token: String) -> HeaderMap {
     let mut headers = HeaderMap::new();
     headers.insert("Authorization", format!("Bearer {}", token).parse().unwrap());
@@ -268,4 +315,105 @@ mod test {
                     .expect("Should be able to pause playback");
             });
     }
+
+    #[test]
+    fn get_playback_state_when_server_does_not_respond_then_return_timeout_error() {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let url = crate::apps::http::start_unresponsive_server().await;
+                let client = SpotifyApiClientImpl {
+                    client: build_client(&HttpConfig { request_timeout_ms: 100, connect_timeout_ms: 100 }),
+                    accounts_url: url.clone(),
+                    api_url: url,
+                };
+
+                let result = client.get_playback_state("token".to_string()).await;
+                assert!(matches!(result, Err(SpotifyApiError::Timeout)), "unexpected result: {:?}", result);
+            });
+    }
+
+    #[test]
+    fn refresh_token_when_server_does_not_respond_then_return_timeout_error() {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let url = crate::apps::http::start_unresponsive_server().await;
+                let client = SpotifyApiClientImpl {
+                    client: build_client(&HttpConfig { request_timeout_ms: 100, connect_timeout_ms: 100 }),
+                    accounts_url: url.clone(),
+                    api_url: url,
+                };
+
+                let result = client.refresh_token(&"id".to_string(), &"secret".to_string(), &"token".to_string()).await;
+                assert!(matches!(result, Err(SpotifyApiError::Timeout)), "unexpected result: {:?}", result);
+            });
+    }
+
+    fn get_rate_limited_client(url: String) -> SpotifyApiClientImpl {
+        return SpotifyApiClientImpl {
+            client: build_client(&HttpConfig::default()),
+            accounts_url: url.clone(),
+            api_url: url,
+        };
+    }
+
+    #[test]
+    fn get_playback_state_when_rate_limited_then_return_the_retry_after_delay() {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let url = crate::apps::http::start_rate_limiting_server(Some("7")).await;
+                let client = get_rate_limited_client(url);
+
+                let result = client.get_playback_state("token".to_string()).await;
+                assert!(matches!(result, Err(SpotifyApiError::RateLimited(retry_after)) if retry_after == Duration::from_secs(7)), "unexpected result: {:?}", result);
+            });
+    }
+
+    #[test]
+    fn pause_playback_when_rate_limited_then_return_the_retry_after_delay() {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let url = crate::apps::http::start_rate_limiting_server(Some("12")).await;
+                let client = get_rate_limited_client(url);
+
+                let result = client.pause_playback("token".to_string()).await;
+                assert!(matches!(result, Err(SpotifyApiError::RateLimited(retry_after)) if retry_after == Duration::from_secs(12)), "unexpected result: {:?}", result);
+            });
+    }
+
+    #[test]
+    fn get_playlist_tracks_when_rate_limited_without_retry_after_then_return_the_default_delay() {
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let url = crate::apps::http::start_rate_limiting_server(None).await;
+                let client = get_rate_limited_client(url);
+
+                let result = client.get_playlist_tracks("token".to_string(), "playlist_id".to_string()).await;
+                assert!(matches!(result, Err(SpotifyApiError::RateLimited(retry_after)) if retry_after == DEFAULT_RETRY_AFTER), "unexpected result: {:?}", result);
+            });
+    }
+
+    #[test]
+    fn get_retry_after_when_header_is_not_a_number_of_seconds_then_return_the_default_delay() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert_eq!(get_retry_after(&headers), DEFAULT_RETRY_AFTER);
+
+        headers.insert(RETRY_AFTER, " 3 ".parse().unwrap());
+        assert_eq!(get_retry_after(&headers), Duration::from_secs(3));
+    }
 }
@@ -1,4 +1,6 @@
-use serde::Deserialize;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
 
 #[cfg(test)]
 use mockall::automock;
@@ -54,11 +56,23 @@ pub trait SpotifyApiClient {
         &self,
         token: String
     ) -> SpotifyApiResult<SpotifyDevices>;
+
+    /// Add the track to the user’s library ("Liked Songs")
+    async fn save_track(
+        &self,
+        token: String,
+        track_id: String,
+    ) -> SpotifyApiResult<()>;
 }
 
 #[derive(Debug)]
 pub enum SpotifyApiError {
     Unauthorized,
+    Timeout,
+    /// The player has no device to play on, or the requested one is gone
+    DeviceNotFound,
+    /// Too many requests have been sent: the next ones should wait for the given delay
+    RateLimited(Duration),
     Other(Box<dyn std::error::Error + Send>),
 }
 
@@ -68,6 +82,15 @@ impl std::fmt::Display for SpotifyApiError {
             SpotifyApiError::Unauthorized => {
                 write!(f, "Unauthorized access to Spotify Web API")
             },
+            SpotifyApiError::Timeout => {
+                write!(f, "Spotify Web API did not respond in time")
+            },
+            SpotifyApiError::DeviceNotFound => {
+                write!(f, "Spotify device not found")
+            },
+            SpotifyApiError::RateLimited(retry_after) => {
+                write!(f, "Spotify Web API rate limit exceeded, retry in {}s", retry_after.as_secs())
+            },
             SpotifyApiError::Other(err) => std::fmt::Display::fmt(err, f),
         }
     }
@@ -77,6 +100,9 @@ impl std::error::Error for SpotifyApiError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self {
             SpotifyApiError::Unauthorized => None,
+            SpotifyApiError::Timeout => None,
+            SpotifyApiError::DeviceNotFound => None,
+            SpotifyApiError::RateLimited(_) => None,
             SpotifyApiError::Other(err) => err.source(),
         }
     }
@@ -124,8 +150,20 @@ pub  struct SpotifyPlaylistItem {
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct SpotifyPlaybackState {
+    #[serde(default)]
     pub is_playing: bool,
-    pub item: SpotifyTrack,
+    /// Missing when nothing is being played, or when the item can’t be parsed as a track
+    /// (e.g. a podcast episode): in both cases, we consider that no track is being played.
+    #[serde(default, deserialize_with = "deserialize_track_leniently")]
+    pub item: Option<SpotifyTrack>,
+}
+
+fn deserialize_track_leniently<'de, D>(deserializer: D) -> Result<Option<SpotifyTrack>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    return Ok(serde_json::from_value::<SpotifyTrack>(value).ok());
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -156,3 +194,49 @@ pub struct SpotifyPlaylist {
 pub struct SpotifyPlaylistTracks {
     pub total: u16,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_track_json() -> &'static str {
+        return r#"{
+            "id": "68d6ZfyMUYURol2y15Ta2Y",
+            "name": "We Like It Here",
+            "uri": "spotify:track:68d6ZfyMUYURol2y15Ta2Y",
+            "album": { "images": [] }
+        }"#;
+    }
+
+    #[test]
+    fn playback_state_when_unknown_fields_then_ignore_them() {
+        let body = format!(r#"{{ "is_playing": true, "shuffle_state": false, "device": {{ "id": "id" }}, "item": {} }}"#, get_track_json());
+        let state = serde_json::from_str::<Option<SpotifyPlaybackState>>(&body).unwrap();
+        assert_eq!(state.map(|state| (state.is_playing, state.item.map(|item| item.id))), Some((true, Some("68d6ZfyMUYURol2y15Ta2Y".to_string()))));
+    }
+
+    #[test]
+    fn playback_state_when_item_is_missing_then_no_track_is_played() {
+        let state = serde_json::from_str::<Option<SpotifyPlaybackState>>(r#"{ "is_playing": true }"#).unwrap();
+        assert_eq!(state, Some(SpotifyPlaybackState { is_playing: true, item: None }));
+    }
+
+    #[test]
+    fn playback_state_when_item_is_null_then_no_track_is_played() {
+        let state = serde_json::from_str::<Option<SpotifyPlaybackState>>(r#"{ "is_playing": false, "item": null }"#).unwrap();
+        assert_eq!(state, Some(SpotifyPlaybackState { is_playing: false, item: None }));
+    }
+
+    #[test]
+    fn playback_state_when_item_is_not_a_track_then_no_track_is_played() {
+        let body = r#"{ "is_playing": true, "currently_playing_type": "episode", "item": { "id": "id", "name": "Episode" } }"#;
+        let state = serde_json::from_str::<Option<SpotifyPlaybackState>>(body).unwrap();
+        assert_eq!(state, Some(SpotifyPlaybackState { is_playing: true, item: None }));
+    }
+
+    #[test]
+    fn playback_state_when_every_field_is_missing_then_nothing_is_played() {
+        let state = serde_json::from_str::<Option<SpotifyPlaybackState>>("{}").unwrap();
+        assert_eq!(state, Some(SpotifyPlaybackState { is_playing: false, item: None }));
+    }
+}
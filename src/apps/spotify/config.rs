@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use dialoguer::{theme::ColorfulTheme, Input, Select};
@@ -6,6 +7,7 @@ use serde::{Serialize, Deserialize};
 use tokio::runtime::Builder;
 use warp::Filter;
 
+use crate::apps::http::HttpConfig;
 use super::client::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +16,99 @@ pub struct Config {
     pub client_id: String,
     pub client_secret: String,
     pub refresh_token: String,
+    /// Adopt the track reported by Spotify as soon as it differs from the local state,
+    /// instead of waiting for the throttling delay to elapse.
+    #[serde(default)]
+    pub trust_remote_immediately: bool,
+    /// Render the cover of a track while its pad is being held,
+    /// and only start playing it once the pad gets released.
+    #[serde(default)]
+    pub preview_mode: bool,
+    /// Timeouts applied to the requests sent to the Spotify Web API
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Render the number of the track being played instead of the logo, on devices supporting it
+    #[serde(default)]
+    pub show_track_number: bool,
+    /// When playback is paused, keep the pad of the last track lit with this color
+    /// (on devices supporting it), instead of not highlighting anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_highlight_color: Option<[u8; 3]>,
+    /// Which of the album images to render as the cover of a track
+    #[serde(default)]
+    pub cover_image_size: CoverImageSize,
+    /// Color of the cross rendered when the playlist has no tracks (red by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty_playlist_color: Option<[u8; 3]>,
+    /// Light the pad of a track when first pressed, and only play (or pause) it once the same pad
+    /// gets pressed again within a few seconds, so that accidental presses change nothing
+    #[serde(default)]
+    pub confirm_mode: bool,
+    /// On startup, highlight the track of the playlist Spotify is already playing, if any
+    #[serde(default)]
+    pub resume_on_start: bool,
+    /// Only used with resume_on_start: the index of the track to play when nothing is playing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_track_index: Option<usize>,
+    /// Path to a JPEG image rendered in place of covers that cannot be retrieved, instead of the logo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_cover: Option<PathBuf>,
+    /// How many covers may be downloaded at the same time, so that pressing pads in a row does
+    /// not open dozens of connections (2 by default, and at least 1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_cover_fetches: Option<usize>,
+    /// Playlists to switch between with the top-row buttons of devices supporting it,
+    /// from left to right
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub playlists: Vec<String>,
+    /// Access token to use on startup, instead of requesting one with the refresh token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_access_token: Option<String>,
+    /// Only used with initial_access_token: when it expires, as a Unix timestamp in seconds.
+    /// Without it, the token gets used until the Spotify Web API rejects it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_access_token_expires_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverImageSize {
+    Smallest,
+    Largest,
+    /// The image whose dimensions are the closest to the given number of pixels
+    Nearest(u16),
+}
+
+impl Default for CoverImageSize {
+    fn default() -> Self {
+        return CoverImageSize::Smallest;
+    }
+}
+
+/// A configuration with placeholder credentials and default options, for tests to override
+#[cfg(test)]
+pub fn test_config() -> Config {
+    return Config {
+        playlist_id: "playlist_id".to_string(),
+        client_id: "client_id".to_string(),
+        client_secret: "client_secret".to_string(),
+        refresh_token: "refresh_token".to_string(),
+        trust_remote_immediately: false,
+        preview_mode: false,
+        http: HttpConfig::default(),
+        show_track_number: false,
+        paused_highlight_color: None,
+        cover_image_size: CoverImageSize::default(),
+        empty_playlist_color: None,
+        confirm_mode: false,
+        resume_on_start: false,
+        default_track_index: None,
+        fallback_cover: None,
+        max_concurrent_cover_fetches: None,
+        playlists: vec![],
+        initial_access_token: None,
+        initial_access_token_expires_at: None,
+    };
 }
 
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
@@ -57,6 +152,21 @@ pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
         client_id,
         client_secret,
         refresh_token,
+        trust_remote_immediately: false,
+        preview_mode: false,
+        http: HttpConfig::default(),
+        show_track_number: false,
+        paused_highlight_color: None,
+        cover_image_size: CoverImageSize::default(),
+        empty_playlist_color: None,
+        confirm_mode: false,
+        resume_on_start: false,
+        default_track_index: None,
+        fallback_cover: None,
+        max_concurrent_cover_fetches: None,
+        playlists: vec![],
+        initial_access_token: None,
+        initial_access_token_expires_at: None,
     });
 }
 
@@ -123,7 +233,7 @@ async fn spawn_authorization_browser(client_id: &String) -> Result<(), Box<dyn s
     tokio::time::sleep(Duration::from_millis(3000)).await;
     let client_id = client_id.clone();
     let result = tokio::task::spawn_blocking(move || {
-        return open::that(format!("https://accounts.spotify.com/authorize?client_id={}&response_type=code&scope=streaming+user-read-email+user-modify-playback-state+user-read-private+playlist-read-private&redirect_uri=http://localhost:12345/callback", client_id)).map_err(|err| {
+        return open::that(format!("https://accounts.spotify.com/authorize?client_id={}&response_type=code&scope=streaming+user-read-email+user-modify-playback-state+user-read-private+playlist-read-private+user-library-modify&redirect_uri=http://localhost:12345/callback", client_id)).map_err(|err| {
             eprintln!("[spotify] error when opening the browser tab: {}", err);
             Box::new(std::io::Error::from(err))
         });
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::{Client, StatusCode};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::{channel, Sender, Receiver};
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+
+use crate::apps::{App, Image, In, Out};
+use crate::apps::http::build_client;
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
+use crate::midi::features::Features;
+use super::config::{Config, WebhookRequest};
+
+pub const NAME: &'static str = "webhook";
+pub const COLOR: [u8; 3] = [0, 255, 255];
+
+const DEFAULT_THROTTLING_DELAY: Duration = Duration::from_millis(1_000);
+
+pub struct Webhook {
+    output_features: Arc<dyn Features + Sync + Send>,
+    in_sender: Sender<In>,
+    out_sender: Sender<Out>,
+    out_receiver: Receiver<Out>,
+    hooks: Arc<HashMap<usize, WebhookRequest>>,
+    send_policy: SendPolicy,
+}
+
+impl Webhook {
+    pub fn new(
+        config: Config,
+        input_features: Arc<dyn Features + Sync + Send>,
+        output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
+    ) -> Self {
+        let (in_sender, mut in_receiver) = channel::<In>(32);
+        let (out_sender, out_receiver) = channel::<Out>(32);
+
+        let hooks = Arc::new(parse_hooks(&config));
+        let client = build_client(&config.http);
+        let throttling_delay = config.throttle_ms.map(Duration::from_millis).unwrap_or(DEFAULT_THROTTLING_DELAY);
+
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let thread_hooks = Arc::clone(&hooks);
+        std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let mut last_fired = HashMap::new();
+                while let Some(event) = in_receiver.recv().await {
+                    let index = match event {
+                        In::Midi(event) => match input_features.into_index(event) {
+                            Ok(index) => index,
+                            Err(err) => {
+                                eprintln!("[webhook] error when transforming incoming event into an index: {}", err);
+                                None
+                            },
+                        },
+                        _ => None, // we ignore events that are not MIDI events
+                    };
+
+                    let request = match index.and_then(|index| thread_hooks.get(&index).map(|request| (index, request))) {
+                        Some((index, request)) if !is_throttled(&mut last_fired, index, Instant::now(), throttling_delay) => request,
+                        Some((index, _)) => {
+                            println!("[webhook] ignoring press of pad {}, as its webhook has just been called", index);
+                            continue;
+                        },
+                        None => continue,
+                    };
+
+                    let client = client.clone();
+                    let request = request.clone();
+                    tokio::spawn(async move {
+                        match fire(&client, &request).await {
+                            Ok(status) => println!("[webhook] {:?} {}: {}", request.method, request.url, status),
+                            Err(err) => eprintln!("[webhook] could not call {}: {}", request.url, err),
+                        }
+                    });
+                }
+            });
+        });
+
+        return Webhook {
+            output_features,
+            in_sender,
+            out_sender,
+            out_receiver,
+            hooks,
+            send_policy,
+        };
+    }
+
+    /// Light the pads that trigger a webhook, with the color of their request
+    fn render_hooks(&self) {
+        let (width, height) = match self.output_features.get_grid_size() {
+            Ok(size) => size,
+            Err(err) => {
+                eprintln!("[webhook] cannot render the webhooks, as the grid size cannot be retrieved: {}", err);
+                return;
+            },
+        };
+
+        let mut image = Image { width, height, bytes: vec![0; width * height * 3] };
+        for (index, request) in self.hooks.iter() {
+            if let Ok(Some((x, y))) = self.output_features.from_index_to_coordinates(*index) {
+                let byte_pos = (y * width + x) * 3;
+                image.bytes[byte_pos..(byte_pos + 3)].copy_from_slice(&request.color.unwrap_or(COLOR));
+            }
+        }
+
+        match self.output_features.from_image(image) {
+            Ok(event) => self.out_sender.try_send(event.into()).unwrap_or_else(|err| {
+                eprintln!("[webhook] could not send event back to the router: {}", err)
+            }),
+            Err(err) => eprintln!("[webhook] could not transform the webhooks into a MIDI event: {}", err),
+        }
+    }
+}
+
+/// Invalid indices are logged and ignored, so that they don’t prevent the others from working.
+pub fn parse_hooks(config: &Config) -> HashMap<usize, WebhookRequest> {
+    let mut hooks = HashMap::new();
+
+    for (index, request) in &config.hooks {
+        match index.trim().parse::<usize>() {
+            Ok(index) => {
+                hooks.insert(index, request.clone());
+            },
+            Err(err) => eprintln!("[webhook] ignoring invalid index \"{}\": {}", index, err),
+        }
+    }
+
+    return hooks;
+}
+
+/// Return whether the webhook of the given pad has been called less than `delay` ago,
+/// and track the time it gets called at otherwise.
+fn is_throttled(last_fired: &mut HashMap<usize, Instant>, index: usize, now: Instant, delay: Duration) -> bool {
+    if let Some(fired_at) = last_fired.get(&index) {
+        if now.saturating_duration_since(*fired_at) < delay {
+            return true;
+        }
+    }
+
+    last_fired.insert(index, now);
+    return false;
+}
+
+pub async fn fire(client: &Client, request: &WebhookRequest) -> Result<StatusCode, reqwest::Error> {
+    let mut builder = client.request(request.method.into(), &request.url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = &request.body {
+        builder = builder.body(body.clone());
+    }
+
+    let response = builder.send().await?;
+    return Ok(response.status());
+}
+
+impl App for Webhook {
+    fn get_name(&self) -> &'static str {
+        return NAME;
+    }
+
+    fn get_color(&self) -> [u8; 3] {
+        return COLOR;
+    }
+
+    fn get_logo(&self) -> Image {
+        return Image { width: 1, height: 1, bytes: COLOR.to_vec() };
+    }
+
+    fn send(&mut self, event: In) -> Result<(), SendError<In>> {
+        return send_with_policy(NAME, &self.in_sender, event, self.send_policy);
+    }
+
+    fn receive(&mut self) -> Result<Out, TryRecvError> {
+        return self.out_receiver.try_recv();
+    }
+
+    fn on_select(&mut self) {
+        self.render_hooks();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use warp::Filter;
+
+    use crate::apps::http::HttpConfig;
+    use crate::midi::Event;
+    use crate::midi::features::{R, GridController, ImageRenderer, IndexSelector};
+    use super::super::config::Method;
+    use super::*;
+
+    /// Method, path and body of the requests received by the mock server
+    type Received = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    /// Start a server recording every request it receives, on a runtime that outlives the call
+    fn start_recording_server(runtime: &tokio::runtime::Runtime) -> (String, Received) {
+        let received: Received = Arc::new(Mutex::new(vec![]));
+        let received_copy = Arc::clone(&received);
+
+        let routes = warp::method()
+            .and(warp::path::full())
+            .and(warp::body::bytes())
+            .map(move |method: warp::http::Method, path: warp::path::FullPath, body: warp::hyper::body::Bytes| {
+                received_copy.lock().unwrap().push((
+                    method.to_string(),
+                    path.as_str().to_string(),
+                    String::from_utf8_lossy(&body).to_string(),
+                ));
+                return "";
+            });
+
+        let address = runtime.block_on(async move {
+            let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+            address
+        });
+
+        return (format!("http://{}", address), received);
+    }
+
+    /// Wait for the app, running on its own thread, to call the mock server
+    fn wait_for_requests(received: &Received, count: usize) -> Vec<(String, String, String)> {
+        let start = Instant::now();
+        while received.lock().unwrap().len() < count && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        return received.lock().unwrap().clone();
+    }
+
+    fn get_config(url: &str) -> Config {
+        return Config {
+            hooks: vec![
+                ("0", WebhookRequest {
+                    url: format!("{}/lights/on", url),
+                    method: Method::Post,
+                    headers: HashMap::new(),
+                    body: Some("{\"brightness\":100}".to_string()),
+                    color: Some([255, 255, 0]),
+                }),
+                ("3", WebhookRequest {
+                    url: format!("{}/lights", url),
+                    method: Method::Delete,
+                    headers: HashMap::new(),
+                    body: None,
+                    color: None,
+                }),
+                ("three", WebhookRequest {
+                    url: format!("{}/nope", url),
+                    method: Method::Get,
+                    headers: HashMap::new(),
+                    body: None,
+                    color: None,
+                }),
+            ].into_iter().map(|(index, request)| (index.to_string(), request)).collect(),
+            throttle_ms: None,
+            http: HttpConfig::default(),
+        };
+    }
+
+    fn get_webhook(url: &str) -> Webhook {
+        return Webhook::new(
+            get_config(url),
+            Arc::new(FakeFeatures {}),
+            Arc::new(FakeFeatures {}),
+            SendPolicy::default(),
+        );
+    }
+
+    #[test]
+    fn parse_hooks_should_ignore_invalid_indices() {
+        let hooks = parse_hooks(&get_config("http://localhost"));
+        let mut indices = hooks.keys().map(|index| *index).collect::<Vec<usize>>();
+        indices.sort();
+        assert_eq!(indices, vec![0, 3]);
+    }
+
+    #[test]
+    fn send_when_pressing_pad_with_webhook_then_fire_the_configured_request() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (url, received) = start_recording_server(&runtime);
+        let mut webhook = get_webhook(&url);
+
+        // index 0, as per our fake implementation of features
+        webhook.send(In::Midi(Event::Midi([144, 0, 100, 0]))).unwrap();
+
+        assert_eq!(wait_for_requests(&received, 1), vec![
+            ("POST".to_string(), "/lights/on".to_string(), "{\"brightness\":100}".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn send_when_pressing_pads_then_fire_each_request_with_its_own_method() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (url, received) = start_recording_server(&runtime);
+        let mut webhook = get_webhook(&url);
+
+        // no webhook configured for the index 2, and note-off events are not presses
+        webhook.send(In::Midi(Event::Midi([144, 2, 100, 0]))).unwrap();
+        webhook.send(In::Midi(Event::Midi([128, 0, 0, 0]))).unwrap();
+        webhook.send(In::Midi(Event::Midi([144, 3, 100, 0]))).unwrap();
+
+        assert_eq!(wait_for_requests(&received, 1), vec![
+            ("DELETE".to_string(), "/lights".to_string(), "".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn is_throttled_when_pad_pressed_again_within_the_delay_then_return_true() {
+        let mut last_fired = HashMap::new();
+        let now = Instant::now();
+        let delay = Duration::from_millis(1_000);
+
+        assert!(!is_throttled(&mut last_fired, 0, now, delay));
+        assert!(is_throttled(&mut last_fired, 0, now + Duration::from_millis(999), delay));
+        // other pads have their own delay
+        assert!(!is_throttled(&mut last_fired, 1, now + Duration::from_millis(999), delay));
+        assert!(!is_throttled(&mut last_fired, 0, now + Duration::from_millis(1_000), delay));
+        assert!(is_throttled(&mut last_fired, 0, now + Duration::from_millis(1_500), delay));
+    }
+
+    #[test]
+    fn on_select_should_render_the_pads_with_webhooks() {
+        let mut webhook = get_webhook("http://localhost");
+        webhook.on_select();
+
+        // index 0 is at the bottom-left of the 2x2 grid, and index 3 is at the top-right
+        let event = webhook.receive().unwrap();
+        assert_eq!(event, Out::Midi(Event::SysEx(vec![
+            b'i', b'm', b'a', b'g', b'e',
+            000, 000, 000, 000, 255, 255,
+            255, 255, 000, 000, 000, 000,
+        ])));
+
+        let event = webhook.receive();
+        assert!(event.is_err());
+    }
+
+    struct FakeFeatures {}
+    impl GridController for FakeFeatures {
+        fn get_grid_size(&self) -> R<(usize, usize)> {
+            Ok((2, 2))
+        }
+    }
+    impl IndexSelector for FakeFeatures {
+        fn into_index(&self, event: Event) -> R<Option<usize>> {
+            Ok(match event {
+                Event::Midi([144, index, _, _]) => Some(index.into()),
+                _ => None,
+            })
+        }
+    }
+    impl ImageRenderer for FakeFeatures {
+        fn from_image(&self, mut image: Image) -> R<Event> {
+            let mut bytes = Vec::from("image".as_bytes());
+            bytes.append(&mut image.bytes);
+            return Ok(Event::SysEx(bytes));
+        }
+    }
+    impl Features for FakeFeatures {}
+}
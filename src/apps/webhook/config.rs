@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use serde::{Serialize, Deserialize};
+
+use crate::apps::http::HttpConfig;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    /// Requests indexed by the index of the pad triggering them.
+    /// Indices are stored as strings, as TOML tables only accept string keys.
+    pub hooks: HashMap<String, WebhookRequest>,
+    /// Minimum delay between two requests triggered by the same pad (1000ms by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_ms: Option<u64>,
+    /// Timeouts applied to the requests
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct WebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub method: Method,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Color of the pad triggering the request (the color of the app by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl Default for Method {
+    fn default() -> Self {
+        return Method::Post;
+    }
+}
+
+impl From<Method> for reqwest::Method {
+    fn from(method: Method) -> reqwest::Method {
+        return match method {
+            Method::Get => reqwest::Method::GET,
+            Method::Post => reqwest::Method::POST,
+            Method::Put => reqwest::Method::PUT,
+            Method::Patch => reqwest::Method::PATCH,
+            Method::Delete => reqwest::Method::DELETE,
+        };
+    }
+}
+
+const METHODS: [Method; 5] = [Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete];
+
+pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
+    let mut hooks = HashMap::new();
+
+    loop {
+        let index: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .with_prompt("[webhook] please enter the index of the pad to configure (leave empty to stop):")
+            .allow_empty(true)
+            .interact()?;
+
+        let index = index.trim().to_string();
+        if index.is_empty() {
+            break;
+        }
+
+        let url: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("[webhook] please enter the URL to call when pressing the pad {}:", index))
+            .interact()?;
+
+        let method = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("[webhook] please select the method of the request:")
+            .default(1)
+            .items(&METHODS.iter().map(|method| format!("{:?}", method).to_uppercase()).collect::<Vec<String>>())
+            .interact()?;
+
+        let body: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .with_prompt("[webhook] please enter the body of the request (leave empty for none):")
+            .allow_empty(true)
+            .interact()?;
+
+        hooks.insert(index, WebhookRequest {
+            url: url.trim().to_string(),
+            method: METHODS[method],
+            headers: HashMap::new(),
+            body: Some(body).filter(|body| !body.is_empty()),
+            color: None,
+        });
+    }
+
+    return Ok(Config { hooks, throttle_ms: None, http: HttpConfig::default() });
+}
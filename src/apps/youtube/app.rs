@@ -1,11 +1,15 @@
-use tokio::runtime::Builder;
+use tokio::runtime::{Builder, Handle};
 use tokio::sync::mpsc;
 
 use std::convert::Into;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
-use crate::apps::{App, In, Out, ServerCommand};
+use crate::apps::{report_error, App, In, Out, ServerCommand, TelemetryEvent};
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
+use crate::apps::confirm::{confirm, render_pending_confirmation, PendingConfirmation};
+use crate::apps::render::{render_empty_playlist, render_image_with_highlight, render_number_or_image_with_highlight};
 use crate::image::Image;
 use crate::midi::features::Features;
 
@@ -16,26 +20,35 @@ struct State {
     input_features: Arc<dyn Features + Sync + Send>,
     output_features: Arc<dyn Features + Sync + Send>,
     config: Config,
+    client: Box<dyn client::YoutubeApiClient + Send + Sync>,
     last_action: Mutex<Instant>,
     items: Mutex<Vec<client::playlist::PlaylistItem>>,
     playing: Mutex<Option<usize>>,
+    /// In confirm mode, the index pressed once and waiting for a second press
+    pending_confirmation: Mutex<PendingConfirmation>,
 }
 
 pub struct Youtube {
+    state: Arc<State>,
+    runtime: Handle,
     in_sender: mpsc::Sender<In>,
+    out_sender: Arc<mpsc::Sender<Out>>,
     out_receiver: mpsc::Receiver<Out>,
+    send_policy: SendPolicy,
 }
 
 pub const NAME: &'static str = "youtube";
 pub const COLOR: [u8; 3] = [255, 0, 0];
 
 const DELAY: Duration = Duration::from_millis(5_000);
+const PLAYLIST_POLLING_INTERVAL: Duration = Duration::from_secs(600);
 
 impl Youtube {
     pub fn new(
         config: Config,
         input_features: Arc<dyn Features + Sync + Send>,
         output_features: Arc<dyn Features + Sync + Send>,
+        send_policy: SendPolicy,
     ) -> Self {
         let (in_sender, mut in_receiver) = mpsc::channel::<In>(32);
         let (out_sender, out_receiver) = mpsc::channel::<Out>(32);
@@ -43,10 +56,12 @@ impl Youtube {
         let state = Arc::new(State {
             input_features,
             output_features,
+            client: Box::new(client::YoutubeClient::from(&config.http)),
             config,
             last_action: Mutex::new(Instant::now() - DELAY),
             items: Mutex::new(vec![]),
             playing: Mutex::new(None),
+            pending_confirmation: Mutex::new(None),
         });
 
         let rt = Builder::new_current_thread()
@@ -54,13 +69,27 @@ impl Youtube {
             .build()
             .unwrap();
 
+        let runtime = rt.handle().clone();
         let state_copy = Arc::clone(&state);
         let out_sender = Arc::new(out_sender);
+        let out_sender_copy = Arc::clone(&out_sender);
         std::thread::spawn(move || {
             rt.block_on(async move {
-                let _ = render_youtube_logo(Arc::clone(&state_copy), Arc::clone(&out_sender)).await;
-                let _ = pull_playlist_items(Arc::clone(&state_copy)).await;
+                let out_sender = out_sender_copy;
+                render_youtube_logo(Arc::clone(&state_copy), Arc::clone(&out_sender)).await;
+
+                let polling_interval = state_copy.config.playlist_polling_interval_s
+                    .map(Duration::from_secs)
+                    .unwrap_or(PLAYLIST_POLLING_INTERVAL);
+                tokio::spawn(poll_playlist(Arc::clone(&state_copy), Arc::clone(&out_sender), polling_interval, Arc::new(AtomicBool::new(false))));
+
                 while let Some(event) = in_receiver.recv().await {
+                    // Commands coming from the server are not subject to throttling
+                    if let In::Server(_) = event {
+                        tokio::spawn(handle_youtube_task(Arc::clone(&state_copy), Arc::clone(&out_sender), event));
+                        continue;
+                    }
+
                     let state = Arc::clone(&state_copy);
                     let time_elapsed = {
                         let last_action = state.last_action.lock().unwrap();
@@ -77,8 +106,12 @@ impl Youtube {
         });
 
         Youtube {
+            state,
+            runtime,
             in_sender,
+            out_sender,
             out_receiver,
+            send_policy,
         }
     }
 }
@@ -97,7 +130,7 @@ impl App for Youtube {
     }
 
     fn send(&mut self, event: In) -> Result<(), mpsc::error::SendError<In>> {
-        return self.in_sender.blocking_send(event);
+        return send_with_policy(NAME, &self.in_sender, event, self.send_policy);
     }
 
     fn receive(&mut self) -> Result<Out, mpsc::error::TryRecvError> {
@@ -105,33 +138,23 @@ impl App for Youtube {
     }
 
     fn on_select(&mut self) {}
-}
 
-async fn render_youtube_logo(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>) -> Result<(), ()> {
-    let event = state.output_features.from_image(get_logo()).map_err(|err| {
-        eprintln!("Could not convert the image into a MIDI event: {:?}", err);
-        ()
-    })?;
-
-    sender.send(event.into()).await.unwrap_or_else(|err| {
-        eprintln!("Could not send the event back to the router: {:?}", err);
-    });
+    fn render(&mut self) {
+        self.runtime.spawn(render_youtube_logo(Arc::clone(&self.state), Arc::clone(&self.out_sender)));
+    }
+}
 
+async fn render_youtube_logo(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>) {
     let playing_index = {
         let playing = state.playing.lock().expect("we should be able to lock state.playing");
         playing.clone()
     };
 
-    if let Some(index) = playing_index {
-        let event = state.output_features.from_index_to_highlight(index).map_err(|err| {
-            eprintln!("Could not convert the index to highlight into a  MIDI event: {:?}", err)
-        })?;
-        sender.send(event.into()).await.unwrap_or_else(|err| {
-            eprintln!("Could not send the event back to the router: {:?}", err);
-        });
+    if state.config.show_track_number {
+        render_number_or_image_with_highlight("youtube", state.output_features.as_ref(), &sender, get_logo(), playing_index, COLOR).await;
+    } else {
+        render_image_with_highlight("youtube", state.output_features.as_ref(), &sender, get_logo(), playing_index).await;
     }
-
-    Ok(())
 }
 
 pub fn get_logo() -> Image {
@@ -154,16 +177,38 @@ pub fn get_logo() -> Image {
     };
 }
 
-async fn pull_playlist_items(state: Arc<State>) -> Result<(), client::Error> {
+async fn poll_playlist(
+    state: Arc<State>,
+    sender: Arc<mpsc::Sender<Out>>,
+    polling_interval: Duration,
+    terminate: Arc<AtomicBool>,
+) {
+    while terminate.load(Ordering::Relaxed) != true {
+        if let Err(err) = pull_playlist_items(Arc::clone(&state), Arc::clone(&sender)).await {
+            report_error("youtube", &sender, format!("could not pull playlist items: {}", err)).await;
+        }
+        tokio::time::sleep(polling_interval).await;
+    }
+}
+
+async fn pull_playlist_items(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>) -> Result<(), client::Error> {
     println!("Pulling Youtube playlist items…");
-    let new_items = client::playlist::get_all_items(
+    let new_items = state.client.get_all_items(
         state.config.api_key.clone(),
         state.config.playlist_id.clone(),
     ).await?;
 
-    let mut actual_items = state.items.lock().unwrap();
-    *actual_items = new_items;
+    let is_empty = new_items.is_empty();
+    {
+        let mut actual_items = state.items.lock().unwrap();
+        *actual_items = new_items;
+    }
     println!("Pulling Youtube playlist items, done!");
+
+    if is_empty {
+        eprintln!("[youtube] playlist {} has no items: pressing pads will have no effect", state.config.playlist_id);
+        render_empty_playlist("youtube", state.output_features.as_ref(), &sender, state.config.empty_playlist_color).await;
+    }
     return Ok(());
 }
 
@@ -172,6 +217,15 @@ async fn handle_youtube_task(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>,
         In::Midi(event) => {
             match state.input_features.into_index(event) {
                 Ok(Some(index)) => {
+                    if state.config.confirm_mode {
+                        let confirmed = confirm(&mut state.pending_confirmation.lock().unwrap(), index, Instant::now());
+                        if !confirmed {
+                            println!("[youtube] waiting for a second press of index {} to confirm", index);
+                            render_pending_confirmation(state.output_features.as_ref(), &sender, index).await;
+                            return;
+                        }
+                    }
+
                     let playing_index = {
                         let playing = state.playing.lock().expect("we should be able to lock state.playing");
                         playing.clone()
@@ -204,9 +258,10 @@ async fn handle_youtube_task(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>,
                                         let mut playing = state.playing.lock().expect("we should be able to lock state.playing");
                                         *playing = Some(index);
                                     }
-                                    render_youtube_logo(Arc::clone(&state), sender).await.unwrap_or_else(|err| {
-                                        eprintln!("[youtube] could not render logo: {:?}", err);
+                                    sender.send(TelemetryEvent::TrackChanged { index, name: item.snippet.title }.into()).await.unwrap_or_else(|err| {
+                                        eprintln!("[youtube] could not send the track change back to the router: {}", err);
                                     });
+                                    render_youtube_logo(Arc::clone(&state), sender).await;
                                 },
                                 Err(_) => eprintln!("Could not play track {}", video_id),
                             }
@@ -216,8 +271,6 @@ async fn handle_youtube_task(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>,
                 },
                 _ => {},
             };
-
-            let _ = pull_playlist_items(state).await;
         },
         In::Server(ServerCommand::YoutubePause) => {
             {
@@ -226,10 +279,212 @@ async fn handle_youtube_task(state: Arc<State>, sender: Arc<mpsc::Sender<Out>>,
             }
 
             let state = Arc::clone(&state);
-            render_youtube_logo(state, sender).await.unwrap_or_else(|err| {
-                eprintln!("[youtube] could not render logo: {:?}", err);
-            });
+            render_youtube_logo(state, sender).await;
+        },
+        In::Server(ServerCommand::YoutubeRefresh) => {
+            if let Err(err) = pull_playlist_items(state, Arc::clone(&sender)).await {
+                report_error("youtube", &sender, format!("could not refresh playlist items: {}", err)).await;
+            }
         },
         _ => {},
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tokio::runtime::Builder;
+
+    use crate::apps::render::get_empty_playlist_glyph;
+    use crate::apps::render::logo_test::assert_logo_renders_right_side_up;
+    use crate::apps::http::HttpConfig;
+    use crate::midi::Event;
+    use crate::midi::devices::default::DefaultFeatures;
+    use crate::midi::features::{R, ImageRenderer};
+    use super::client::MockYoutubeApiClient;
+    use super::client::playlist::{PlaylistItem, PlaylistItemSnippet, PlaylistItemSnippetResourceId};
+    use super::*;
+
+    fn item(video_id: &str) -> PlaylistItem {
+        return PlaylistItem {
+            snippet: PlaylistItemSnippet {
+                title: video_id.to_string(),
+                resource_id: PlaylistItemSnippetResourceId { video_id: video_id.to_string() },
+            },
+        };
+    }
+
+    fn get_state_with_client(client: MockYoutubeApiClient) -> Arc<State> {
+        return get_state_with_client_and_features(client, Arc::new(DefaultFeatures::new()));
+    }
+
+    fn get_state_with_client_and_features(
+        client: MockYoutubeApiClient,
+        features: Arc<dyn Features + Sync + Send>,
+    ) -> Arc<State> {
+        return get_state_with_client_and_features_and_confirm_mode(client, features, false);
+    }
+
+    fn get_state_with_client_and_features_and_confirm_mode(
+        client: MockYoutubeApiClient,
+        features: Arc<dyn Features + Sync + Send>,
+        confirm_mode: bool,
+    ) -> Arc<State> {
+        return Arc::new(State {
+            input_features: Arc::clone(&features),
+            output_features: features,
+            config: Config {
+                api_key: "api_key".to_string(),
+                playlist_id: "playlist_id".to_string(),
+                http: HttpConfig::default(),
+                show_track_number: false,
+                playlist_polling_interval_s: None,
+                empty_playlist_color: None,
+                confirm_mode,
+            },
+            client: Box::new(client),
+            last_action: Mutex::new(Instant::now() - DELAY),
+            items: Mutex::new(vec![]),
+            playing: Mutex::new(None),
+            pending_confirmation: Mutex::new(None),
+        });
+    }
+
+    fn with_runtime<F: std::future::Future>(future: F) -> F::Output {
+        return Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future);
+    }
+
+    #[test]
+    fn poll_playlist_when_polling_interval_is_100ms_then_poll_3_times_in_250ms() {
+        let mut client = MockYoutubeApiClient::new();
+        client.expect_get_all_items()
+            .times(3)
+            .returning(|_, _| Ok(vec![item("a"), item("b")]));
+
+        let state = get_state_with_client(client);
+
+        with_runtime(async move {
+            let terminate = Arc::new(AtomicBool::new(false));
+
+            let terminate_copy = Arc::clone(&terminate);
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(250));
+                terminate_copy.store(true, Ordering::Relaxed);
+            });
+
+            let (sender, _receiver) = mpsc::channel::<Out>(32);
+            poll_playlist(Arc::clone(&state), Arc::new(sender), Duration::from_millis(100), terminate).await;
+            assert_eq!(state.items.lock().unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn handle_youtube_task_when_refresh_command_then_pull_playlist_items() {
+        let mut client = MockYoutubeApiClient::new();
+        client.expect_get_all_items()
+            .times(1)
+            .withf(|api_key, playlist_id| api_key == "api_key" && playlist_id == "playlist_id")
+            .returning(|_, _| Ok(vec![item("a")]));
+
+        let state = get_state_with_client(client);
+        let (sender, _receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Server(ServerCommand::YoutubeRefresh)));
+
+        let video_ids = state.items.lock().unwrap().iter()
+            .map(|item| item.snippet.resource_id.video_id.clone())
+            .collect::<Vec<String>>();
+        assert_eq!(video_ids, vec!["a"]);
+    }
+
+    #[test]
+    fn handle_youtube_task_when_refresh_command_and_playlist_is_empty_then_render_the_empty_playlist_glyph() {
+        let mut client = MockYoutubeApiClient::new();
+        client.expect_get_all_items()
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+
+        let state = get_state_with_client_and_features(client, Arc::new(ImageFeatures {}));
+        let (sender, mut receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Server(ServerCommand::YoutubeRefresh)));
+
+        let glyph = get_empty_playlist_glyph([255, 0, 0]);
+        assert_eq!(receiver.try_recv(), Ok(Out::Midi(Event::SysEx(glyph.bytes))));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn handle_youtube_task_when_refresh_command_and_playlist_has_items_then_render_nothing() {
+        let mut client = MockYoutubeApiClient::new();
+        client.expect_get_all_items()
+            .times(1)
+            .returning(|_, _| Ok(vec![item("a")]));
+
+        let state = get_state_with_client_and_features(client, Arc::new(ImageFeatures {}));
+        let (sender, mut receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Server(ServerCommand::YoutubeRefresh)));
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn handle_youtube_task_when_midi_event_then_do_not_pull_playlist_items() {
+        let mut client = MockYoutubeApiClient::new();
+        client.expect_get_all_items().times(0);
+
+        let state = get_state_with_client(client);
+        let (sender, _receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Midi(Event::Midi([144, 36, 100, 0]))));
+    }
+
+    #[test]
+    fn handle_youtube_task_when_confirm_mode_and_pad_pressed_once_then_do_not_play() {
+        let state = get_state_with_client_and_features_and_confirm_mode(MockYoutubeApiClient::new(), Arc::new(DefaultFeatures::new()), true);
+        *state.items.lock().unwrap() = vec![item("a"), item("b")];
+        let (sender, mut receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Midi(Event::Midi([144, 37, 100, 0]))));
+
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(*state.playing.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn handle_youtube_task_when_confirm_mode_and_pad_pressed_twice_then_play() {
+        let state = get_state_with_client_and_features_and_confirm_mode(MockYoutubeApiClient::new(), Arc::new(DefaultFeatures::new()), true);
+        *state.items.lock().unwrap() = vec![item("a"), item("b")];
+        let (sender, mut receiver) = mpsc::channel::<Out>(32);
+        let sender = Arc::new(sender);
+
+        with_runtime(async {
+            handle_youtube_task(Arc::clone(&state), Arc::clone(&sender), In::Midi(Event::Midi([144, 37, 100, 0]))).await;
+            handle_youtube_task(Arc::clone(&state), Arc::clone(&sender), In::Midi(Event::Midi([144, 37, 100, 0]))).await;
+        });
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Server(ServerCommand::YoutubePlay { video_id: "b".to_string() })));
+        assert_eq!(*state.playing.lock().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn handle_youtube_task_when_pad_pressed_then_send_a_track_change() {
+        let state = get_state_with_client(MockYoutubeApiClient::new());
+        *state.items.lock().unwrap() = vec![item("a"), item("b")];
+        let (sender, mut receiver) = mpsc::channel::<Out>(32);
+
+        with_runtime(handle_youtube_task(Arc::clone(&state), Arc::new(sender), In::Midi(Event::Midi([144, 37, 100, 0]))));
+
+        assert_eq!(receiver.try_recv(), Ok(Out::Server(ServerCommand::YoutubePlay { video_id: "b".to_string() })));
+        assert_eq!(receiver.try_recv(), Ok(Out::Telemetry(TelemetryEvent::TrackChanged { index: 1, name: "b".to_string() })));
+    }
+
+    #[test]
+    fn get_logo_when_devices_have_different_origins_then_render_it_right_side_up() {
+        assert_logo_renders_right_side_up(get_logo());
+    }
+}
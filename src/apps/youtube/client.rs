@@ -1,6 +1,82 @@
-pub use reqwest::{Client, Error};
+use reqwest::Client;
 use serde::{Serialize, Deserialize};
 
+#[cfg(test)]
+use mockall::automock;
+
+use crate::apps::http::{HttpConfig, build_client};
+
+const API_URL: &'static str = "https://youtube.googleapis.com";
+
+#[derive(Debug)]
+pub enum Error {
+    Timeout,
+    Other(reqwest::Error),
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        if err.is_timeout() {
+            return Error::Timeout;
+        }
+        return Error::Other(err);
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Error::Timeout => write!(f, "YouTube Data API did not respond in time"),
+            Error::Other(err) => std::fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self {
+            Error::Timeout => None,
+            Error::Other(err) => err.source(),
+        }
+    }
+}
+
+/// Wrap a reqwest client, so that its connection pool gets reused across requests
+pub struct YoutubeClient {
+    client: Client,
+    api_url: String,
+}
+
+impl From<&HttpConfig> for YoutubeClient {
+    fn from(config: &HttpConfig) -> Self {
+        return YoutubeClient {
+            client: build_client(config),
+            api_url: API_URL.to_string(),
+        };
+    }
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait YoutubeApiClient {
+    async fn get_all_items(
+        &self,
+        api_key: String,
+        playlist_id: String,
+    ) -> Result<Vec<playlist::PlaylistItem>, Error>;
+}
+
+#[async_trait]
+impl YoutubeApiClient for YoutubeClient {
+    async fn get_all_items(
+        &self,
+        api_key: String,
+        playlist_id: String,
+    ) -> Result<Vec<playlist::PlaylistItem>, Error> {
+        return playlist::get_all_items(self, api_key, playlist_id).await;
+    }
+}
+
 pub mod playlist {
     use super::*;
 
@@ -30,6 +106,7 @@ pub mod playlist {
     }
 
     pub async fn get_paginated_items(
+        client: &YoutubeClient,
         api_key: &String,
         playlist_id: &String,
         max_results: u8,
@@ -40,9 +117,8 @@ pub mod playlist {
             .map(|token| format!("&pageToken={}", token))
             .unwrap_or("".to_string());
 
-        let client = Client::new();
-        let response = client.get(
-            format!("https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet&maxResults={}&playlistId={}&key={}{}", max_results, playlist_id, api_key, page_token))
+        let response = client.client.get(
+            format!("{}/youtube/v3/playlistItems?part=snippet&maxResults={}&playlistId={}&key={}{}", client.api_url, max_results, playlist_id, api_key, page_token))
             .send()
             .await?;
 
@@ -54,6 +130,7 @@ pub mod playlist {
     }
 
     pub async fn get_all_items(
+        client: &YoutubeClient,
         api_key: String,
         playlist_id: String,
     ) -> Result<Vec<PlaylistItem>, Error> {
@@ -61,7 +138,7 @@ pub mod playlist {
         let mut all_items = vec![];
 
         loop {
-            let playlist = get_paginated_items(&api_key, &playlist_id, 50, &page_token).await;
+            let playlist = get_paginated_items(client, &api_key, &playlist_id, 50, &page_token).await;
             match playlist {
                 Err(err) => {
                     return Err(err);
@@ -81,6 +158,8 @@ pub mod playlist {
 
 #[cfg(test)]
 mod test {
+    use crate::apps::http::{HttpConfig, build_client};
+
     #[test]
     pub fn test_get_paginated_items() {
         use tokio::runtime::Builder;
@@ -93,7 +172,8 @@ mod test {
             .build()
             .unwrap()
             .block_on(async move {
-                let playlist = super::playlist::get_paginated_items(&api_key, &playlist_id, 32, &None).await
+                let client = super::YoutubeClient::from(&HttpConfig::default());
+                let playlist = super::playlist::get_paginated_items(&client, &api_key, &playlist_id, 32, &None).await
                     .expect("retrieving playlist items should not fail");
 
                 assert_eq!(playlist.items.len(), 32);
@@ -118,7 +198,8 @@ mod test {
             .build()
             .unwrap()
             .block_on(async move {
-                let items = super::playlist::get_all_items(api_key, playlist_id).await
+                let client = super::YoutubeClient::from(&HttpConfig::default());
+                let items = super::playlist::get_all_items(&client, api_key, playlist_id).await
                     .expect("retrieving playlist items should not fail");
 
                 assert_eq!(items.len(), 64);
@@ -130,4 +211,23 @@ mod test {
                 assert_eq!(title, Some("Kompisbandet - Krokodilen i bilen".to_string()));
             });
     }
+
+    #[test]
+    pub fn test_get_paginated_items_when_server_does_not_respond_then_return_timeout_error() {
+        use tokio::runtime::Builder;
+
+        Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let client = super::YoutubeClient {
+                    client: build_client(&HttpConfig { request_timeout_ms: 100, connect_timeout_ms: 100 }),
+                    api_url: crate::apps::http::start_unresponsive_server().await,
+                };
+
+                let result = super::playlist::get_paginated_items(&client, &"api_key".to_string(), &"playlist_id".to_string(), 32, &None).await;
+                assert!(matches!(result, Err(super::Error::Timeout)), "unexpected result: {:?}", result);
+            });
+    }
 }
@@ -1,11 +1,29 @@
 use serde::{Serialize, Deserialize};
 
+use crate::apps::http::HttpConfig;
+
 use dialoguer::{theme::ColorfulTheme, Input};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub api_key: String,
     pub playlist_id: String,
+    /// Timeouts applied to the requests sent to the YouTube Data API
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Render the number of the track being played instead of the logo, on devices supporting it
+    #[serde(default)]
+    pub show_track_number: bool,
+    /// Time between two pulls of the playlist items (10 minutes by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_polling_interval_s: Option<u64>,
+    /// Color of the cross rendered when the playlist has no items (red by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty_playlist_color: Option<[u8; 3]>,
+    /// Light the pad of a video when first pressed, and only play (or pause) it once the same pad
+    /// gets pressed again within a few seconds, so that accidental presses change nothing
+    #[serde(default)]
+    pub confirm_mode: bool,
 }
 
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
@@ -24,5 +42,10 @@ pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
     return Ok(Config {
         api_key,
         playlist_id,
+        http: HttpConfig::default(),
+        show_track_number: false,
+        playlist_polling_interval_s: None,
+        empty_playlist_color: None,
+        confirm_mode: false,
     });
 }
@@ -0,0 +1,131 @@
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+
+use super::Image;
+
+const DIGIT_WIDTH: usize = 3;
+const DIGIT_HEIGHT: usize = 5;
+
+/// Width of two digits, separated by a one-pixel gap
+const NUMBER_WIDTH: usize = 2 * DIGIT_WIDTH + 1;
+
+/// Each digit is made of 5 rows of 3 pixels, the 3 least significant bits of each row telling
+/// whether the pixel must be lit (from left to right).
+const DIGITS: [[u8; DIGIT_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    NumberTooLarge(u16),
+    GridTooSmall(usize, usize),
+}
+
+impl StdError for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Error::NumberTooLarge(n) =>
+                write!(f, "number {} cannot be rendered with two digits", n),
+            Error::GridTooSmall(w, h) =>
+                write!(f, "a grid (width: {}, height: {}) is too small to render two digits", w, h),
+        }
+    }
+}
+
+/// Render a number of one or two digits, centered on a black image of the given size.
+pub fn render_number(n: u16, color: [u8; 3], width: usize, height: usize) -> Result<Image, Error> {
+    if n > 99 {
+        return Err(Error::NumberTooLarge(n));
+    }
+
+    if width < NUMBER_WIDTH || height < DIGIT_HEIGHT {
+        return Err(Error::GridTooSmall(width, height));
+    }
+
+    let mut image = Image { width, height, bytes: vec![0; width * height * 3] };
+    let y = (height - DIGIT_HEIGHT) / 2;
+
+    if n < 10 {
+        draw_digit(&mut image, n as usize, (width - DIGIT_WIDTH) / 2, y, color);
+    } else {
+        let x = (width - NUMBER_WIDTH) / 2;
+        draw_digit(&mut image, (n / 10) as usize, x, y, color);
+        draw_digit(&mut image, (n % 10) as usize, x + DIGIT_WIDTH + 1, y, color);
+    }
+
+    return Ok(image);
+}
+
+fn draw_digit(image: &mut Image, digit: usize, x: usize, y: usize, color: [u8; 3]) {
+    for (dy, row) in DIGITS[digit].iter().enumerate() {
+        for dx in 0..DIGIT_WIDTH {
+            if row & (0b100 >> dx) != 0 {
+                let pos = ((y + dy) * image.width + x + dx) * 3;
+                image.bytes[pos..(pos + 3)].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const K: [u8; 3] = [0, 0, 0];
+    const W: [u8; 3] = [255, 255, 255];
+
+    #[test]
+    fn render_number_given_two_digits_should_render_them_side_by_side() {
+        let image = render_number(12, W, 8, 8);
+        assert_eq!(image, Ok(Image {
+            width: 8,
+            height: 8,
+            bytes: [
+                K, K, K, K, K, K, K, K,
+                K, W, K, K, W, W, W, K,
+                W, W, K, K, K, K, W, K,
+                K, W, K, K, W, W, W, K,
+                K, W, K, K, W, K, K, K,
+                W, W, W, K, W, W, W, K,
+                K, K, K, K, K, K, K, K,
+                K, K, K, K, K, K, K, K,
+            ].concat(),
+        }));
+    }
+
+    #[test]
+    fn render_number_given_one_digit_should_center_it() {
+        let image = render_number(7, W, 7, 5);
+        assert_eq!(image, Ok(Image {
+            width: 7,
+            height: 5,
+            bytes: [
+                K, K, W, W, W, K, K,
+                K, K, K, K, W, K, K,
+                K, K, K, W, K, K, K,
+                K, K, K, W, K, K, K,
+                K, K, K, W, K, K, K,
+            ].concat(),
+        }));
+    }
+
+    #[test]
+    fn render_number_given_three_digits_should_return_err() {
+        assert_eq!(render_number(100, W, 8, 8), Err(Error::NumberTooLarge(100)));
+    }
+
+    #[test]
+    fn render_number_given_small_grid_should_return_err() {
+        assert_eq!(render_number(1, W, 8, 2), Err(Error::GridTooSmall(8, 2)));
+    }
+}
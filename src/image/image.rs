@@ -6,6 +6,9 @@ use std::path::Path;
 extern crate jpeg_decoder;
 use jpeg_decoder::{Decoder, PixelFormat};
 
+extern crate jpeg_encoder;
+use jpeg_encoder::{ColorType, Encoder};
+
 use super::Error;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -50,6 +53,73 @@ impl Image {
         let mut decoder = Decoder::new(bytes.as_ref());
         return Image::from_decoder(&mut decoder);
     }
+
+    /// Encode the image as JPEG, with a quality between 1 and 100
+    pub fn encode_jpeg(&self, quality: u8) -> Result<Vec<u8>, Error> {
+        let (width, height) = self.get_jpeg_size()?;
+        // The RGB conversion of jpeg-encoder overflows on saturated reds (255, 0, 0 comes back
+        // green), so we convert pixels to YCbCr ourselves
+        let ycbcr_bytes = self.bytes.chunks(3)
+            .flat_map(|pixel| rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]))
+            .collect::<Vec<u8>>();
+
+        let mut bytes = vec![];
+        Encoder::new(&mut bytes, quality)
+            .encode(&ycbcr_bytes, width, height, ColorType::Ycbcr)
+            .map_err(|_| Error::JpegEncodingError)?;
+        return Ok(bytes);
+    }
+
+    #[allow(dead_code)]
+    pub fn save_jpeg<P: AsRef<Path>>(&self, path: P, quality: u8) -> Result<(), Error> {
+        let bytes = self.encode_jpeg(quality)?;
+        return std::fs::write(path, bytes).map_err(|_| Error::FileWriteError);
+    }
+
+    /// JPEG images can’t be larger than 65535x65535, and must have 3 bytes per pixel
+    fn get_jpeg_size(&self) -> Result<(u16, u16), Error> {
+        if self.bytes.len() != self.width * self.height * 3 {
+            return Err(Error::InvalidImageSize);
+        }
+        let width = u16::try_from(self.width).map_err(|_| Error::InvalidImageSize)?;
+        let height = u16::try_from(self.height).map_err(|_| Error::InvalidImageSize)?;
+        return Ok((width, height));
+    }
+
+    /// Rotate a copy of this image clockwise, by the given number of quarter turns
+    #[allow(dead_code)]
+    pub fn rotate90(&self, times: u8) -> Result<Image, Error> {
+        if self.bytes.len() != self.width * self.height * 3 {
+            return Err(Error::InvalidImageSize);
+        }
+
+        let mut image = self.clone();
+        for _ in 0..(times % 4) {
+            image = image.rotate_clockwise();
+        }
+        return Ok(image);
+    }
+
+    /// The pixel at (x, y) ends up at (height - 1 - y, x)
+    fn rotate_clockwise(&self) -> Image {
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        for x in 0..self.width {
+            for y in (0..self.height).rev() {
+                let pos = (y * self.width + x) * 3;
+                bytes.extend_from_slice(&self.bytes[pos..(pos + 3)]);
+            }
+        }
+        return Image { width: self.height, height: self.width, bytes };
+    }
+}
+
+/// JFIF conversion, see https://www.w3.org/Graphics/JPEG/jfif3.pdf
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = -0.168736 * r - 0.331264 * g + 0.5 * b + 128.0;
+    let cr = 0.5 * r - 0.418688 * g - 0.081312 * b + 128.0;
+    return [y, cb, cr].map(|value| value.round().clamp(0.0, 255.0) as u8);
 }
 
 #[cfg(test)]
@@ -134,4 +204,96 @@ pub mod tests {
             assert_eq!(local_image, remote_image, "Expected the resulting image to match the local copy");
         });
     }
+
+    const R: [u8; 3] = [255, 0, 0];
+    const B: [u8; 3] = [0, 0, 255];
+
+    /// A B
+    /// C D
+    /// E F
+    fn given_asymmetric_2x3_image() -> Image {
+        return Image { width: 2, height: 3, bytes: [[1; 3], [2; 3], [3; 3], [4; 3], [5; 3], [6; 3]].concat() };
+    }
+
+    #[test]
+    fn test_rotate90_given_one_quarter_turn_should_rotate_clockwise() {
+        let image = given_asymmetric_2x3_image().rotate90(1);
+        assert_eq!(image, Ok(Image {
+            width: 3,
+            height: 2,
+            bytes: [
+                [5; 3], [3; 3], [1; 3],
+                [6; 3], [4; 3], [2; 3],
+            ].concat(),
+        }));
+    }
+
+    #[test]
+    fn test_rotate90_given_two_quarter_turns_should_flip_the_image_upside_down() {
+        let image = given_asymmetric_2x3_image().rotate90(2);
+        assert_eq!(image, Ok(Image {
+            width: 2,
+            height: 3,
+            bytes: [
+                [6; 3], [5; 3],
+                [4; 3], [3; 3],
+                [2; 3], [1; 3],
+            ].concat(),
+        }));
+    }
+
+    #[test]
+    fn test_rotate90_given_three_quarter_turns_should_rotate_counterclockwise() {
+        let image = given_asymmetric_2x3_image().rotate90(3);
+        assert_eq!(image, Ok(Image {
+            width: 3,
+            height: 2,
+            bytes: [
+                [2; 3], [4; 3], [6; 3],
+                [1; 3], [3; 3], [5; 3],
+            ].concat(),
+        }));
+    }
+
+    #[test]
+    fn test_rotate90_given_four_quarter_turns_should_return_same_image() {
+        assert_eq!(given_asymmetric_2x3_image().rotate90(4), Ok(given_asymmetric_2x3_image()));
+    }
+
+    #[test]
+    fn test_rotate90_given_inconsistent_image_size_should_return_err() {
+        let image = Image { width: 2, height: 3, bytes: [R, B].concat() };
+        assert_eq!(image.rotate90(1), Err(Error::InvalidImageSize));
+    }
+
+    #[test]
+    fn test_encode_jpeg_then_decode_should_return_a_similar_image() {
+        // Colors are laid out in 16x16 blocks, so that chroma subsampling doesn’t blend them
+        let image = Image { width: 32, height: 16, bytes: [[R; 16], [B; 16]].concat().repeat(16).concat() };
+        let bytes = image.encode_jpeg(100).expect("Expected the image to be encodable");
+        let decoded_image = Image::from_decoder(&mut Decoder::new(bytes.as_slice())).expect("Expected the image to be decodable");
+
+        assert_eq!((decoded_image.width, decoded_image.height), (32, 16));
+        assert_eq!(decoded_image.bytes.len(), image.bytes.len());
+        for (decoded_byte, byte) in decoded_image.bytes.iter().zip(image.bytes.iter()) {
+            assert!((*decoded_byte as i16 - *byte as i16).abs() <= 8, "expected {} to be close to {}", decoded_byte, byte);
+        }
+    }
+
+    #[test]
+    fn test_encode_jpeg_given_inconsistent_image_size_should_return_err() {
+        let image = Image { width: 2, height: 2, bytes: [R, B].concat() };
+        assert_eq!(image.encode_jpeg(100), Err(Error::InvalidImageSize));
+    }
+
+    #[test]
+    fn test_save_jpeg_then_from_path_should_return_an_image_of_the_same_size() {
+        let path = std::env::temp_dir().join(format!("midi-hub-save-jpeg-{}.jpg", std::process::id()));
+        let image = Image { width: 3, height: 3, bytes: [R; 9].concat() };
+        image.save_jpeg(&path, 100).expect("Expected the image to be saved");
+
+        let loaded_image = Image::from_path(&path).expect("Expected the saved image to be parsable");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((loaded_image.width, loaded_image.height, loaded_image.bytes.len()), (3, 3, 27));
+    }
 }
@@ -0,0 +1,174 @@
+use super::{Error, Image};
+
+/// An image with an alpha channel, so that apps can build up a frame in layers.
+///
+/// Devices only render RGB images: layers need to be flattened onto what the grid currently
+/// shows (or onto black) before being rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageRgba {
+    pub width: usize,
+    pub height: usize,
+    /// 4 bytes per pixel: red, green, blue, and alpha (0 being fully transparent)
+    pub bytes: Vec<u8>,
+}
+
+impl ImageRgba {
+    /// A fully transparent image, to draw layers onto
+    pub fn transparent(width: usize, height: usize) -> ImageRgba {
+        return ImageRgba { width, height, bytes: vec![0; width * height * 4] };
+    }
+
+    /// Every pixel of the given image, with the same opacity
+    pub fn from_image(image: &Image, alpha: u8) -> Result<ImageRgba, Error> {
+        if image.bytes.len() != image.width * image.height * 3 {
+            return Err(Error::InvalidImageSize);
+        }
+
+        let bytes = image.bytes.chunks(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], alpha])
+            .collect::<Vec<u8>>();
+        return Ok(ImageRgba { width: image.width, height: image.height, bytes });
+    }
+
+    /// Draw `top` over a copy of this image, with its top-left corner at (x, y).
+    ///
+    /// Pixels of `top` that fall outside of this image are clipped.
+    pub fn composite(&self, top: &ImageRgba, x: usize, y: usize) -> Result<ImageRgba, Error> {
+        if !self.has_valid_size() || !top.has_valid_size() {
+            return Err(Error::InvalidImageSize);
+        }
+
+        let mut image = self.clone();
+        for top_y in 0..top.height {
+            for top_x in 0..top.width {
+                let (image_x, image_y) = (x + top_x, y + top_y);
+                if image_x >= image.width || image_y >= image.height {
+                    continue;
+                }
+
+                let top_pos = (top_y * top.width + top_x) * 4;
+                let image_pos = (image_y * image.width + image_x) * 4;
+                let pixel = blend(&top.bytes[top_pos..(top_pos + 4)], &image.bytes[image_pos..(image_pos + 4)]);
+                image.bytes[image_pos..(image_pos + 4)].copy_from_slice(&pixel);
+            }
+        }
+
+        return Ok(image);
+    }
+
+    /// Draw this image over the given background, which must be of the same size,
+    /// e.g. the image currently rendered on the grid.
+    pub fn flatten(&self, background: &Image) -> Result<Image, Error> {
+        if !self.has_valid_size() || (background.width, background.height) != (self.width, self.height) {
+            return Err(Error::InvalidImageSize);
+        }
+
+        let flattened = ImageRgba::from_image(background, 255)?.composite(self, 0, 0)?;
+        let bytes = flattened.bytes.chunks(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect::<Vec<u8>>();
+        return Ok(Image { width: self.width, height: self.height, bytes });
+    }
+
+    /// Draw this image over a black background
+    #[allow(dead_code)]
+    pub fn to_image(&self) -> Result<Image, Error> {
+        let background = Image { width: self.width, height: self.height, bytes: vec![0; self.width * self.height * 3] };
+        return self.flatten(&background);
+    }
+
+    fn has_valid_size(&self) -> bool {
+        return self.bytes.len() == self.width * self.height * 4;
+    }
+}
+
+/// The "over" operator of Porter-Duff, on straight (non-premultiplied) alpha:
+/// see https://en.wikipedia.org/wiki/Alpha_compositing
+fn blend(top: &[u8], bottom: &[u8]) -> [u8; 4] {
+    let top_alpha = top[3] as f32 / 255.0;
+    let bottom_alpha = bottom[3] as f32 / 255.0 * (1.0 - top_alpha);
+    let alpha = top_alpha + bottom_alpha;
+    if alpha == 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    let channel = |c: usize| {
+        let value = (top[c] as f32 * top_alpha + bottom[c] as f32 * bottom_alpha) / alpha;
+        return value.round().clamp(0.0, 255.0) as u8;
+    };
+    return [channel(0), channel(1), channel(2), (alpha * 255.0).round().clamp(0.0, 255.0) as u8];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    #[test]
+    fn blend_given_an_opaque_top_pixel_should_return_it() {
+        assert_eq!(blend(&[255, 0, 0, 255], &[0, 0, 255, 255]), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn blend_given_a_transparent_top_pixel_should_return_the_bottom_one() {
+        assert_eq!(blend(&[255, 0, 0, 0], &[0, 0, 255, 100]), [0, 0, 255, 100]);
+    }
+
+    #[test]
+    fn blend_given_a_half_transparent_top_pixel_over_an_opaque_one_should_mix_them() {
+        assert_eq!(blend(&[255, 0, 0, 128], &[0, 0, 255, 255]), [128, 0, 127, 255]);
+    }
+
+    #[test]
+    fn blend_given_two_half_transparent_pixels_should_weigh_the_top_one_more() {
+        // alpha: 0.5 + 0.5 * 0.5 = 0.75, red: 255 * 0.5 / 0.75, blue: 255 * 0.25 / 0.75
+        assert_eq!(blend(&[255, 0, 0, 128], &[0, 0, 255, 128]), [170, 0, 85, 192]);
+    }
+
+    #[test]
+    fn blend_given_two_transparent_pixels_should_return_a_transparent_black_pixel() {
+        assert_eq!(blend(&[255, 0, 0, 0], &[0, 0, 255, 0]), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn from_image_then_to_image_given_an_opaque_alpha_should_return_the_same_image() {
+        let image = Image { width: 2, height: 1, bytes: [RED, BLUE].concat() };
+        let rgba = ImageRgba::from_image(&image, 255).expect("Expected the image to be convertible");
+        assert_eq!(rgba.bytes, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(rgba.to_image(), Ok(image));
+    }
+
+    #[test]
+    fn composite_given_top_image_exceeding_bounds_should_blend_and_clip_it() {
+        let bottom = ImageRgba::from_image(&Image { width: 2, height: 1, bytes: [BLUE, BLUE].concat() }, 255).unwrap();
+        let top = ImageRgba { width: 2, height: 1, bytes: vec![255, 0, 0, 128, 255, 0, 0, 255] };
+        assert_eq!(bottom.composite(&top, 1, 0), Ok(ImageRgba {
+            width: 2,
+            height: 1,
+            bytes: vec![0, 0, 255, 255, 128, 0, 127, 255],
+        }));
+    }
+
+    #[test]
+    fn flatten_given_layers_should_let_the_background_show_through_transparent_pixels() {
+        let background = Image { width: 2, height: 1, bytes: [BLUE, BLUE].concat() };
+        let layer = ImageRgba::transparent(2, 1)
+            .composite(&ImageRgba { width: 1, height: 1, bytes: vec![255, 0, 0, 255] }, 1, 0)
+            .unwrap();
+        assert_eq!(layer.flatten(&background), Ok(Image { width: 2, height: 1, bytes: [BLUE, RED].concat() }));
+    }
+
+    #[test]
+    fn flatten_given_a_background_of_another_size_should_return_err() {
+        let background = Image { width: 1, height: 1, bytes: BLUE.to_vec() };
+        assert_eq!(ImageRgba::transparent(2, 1).flatten(&background), Err(Error::InvalidImageSize));
+    }
+
+    #[test]
+    fn composite_given_inconsistent_image_size_should_return_err() {
+        let top = ImageRgba { width: 2, height: 2, bytes: vec![0; 4] };
+        assert_eq!(ImageRgba::transparent(2, 2).composite(&top, 0, 0), Err(Error::InvalidImageSize));
+    }
+}
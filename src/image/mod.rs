@@ -3,9 +3,21 @@ extern crate jpeg_decoder;
 mod image;
 pub use image::Image;
 
+mod image_rgba;
+pub use image_rgba::ImageRgba;
+
+mod font;
+pub use font::render_number;
+
 mod scale;
 pub use scale::scale;
 
+mod quantize;
+pub use quantize::quantize;
+
+mod test_pattern;
+pub use test_pattern::{render_test_pattern, TestPattern};
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Error {
     JpegDecodingError,
@@ -14,19 +26,21 @@ pub enum Error {
     HttpRequestError,
     HttpParseError,
     FileOpenError,
+    FileWriteError,
+    JpegEncodingError,
+    InvalidImageSize,
+    Base64DecodingError,
 }
 
 #[cfg(test)]
 mod tests {
     extern crate insta;
-    extern crate jpeg_encoder;
 
     use std::file;
     use std::path::Path;
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
-    use jpeg_encoder::{Encoder, ColorType};
 
     // This test relies on network calls, on Spotify’s CDN being up, and on the album cover not to
     // change. There’s a risk it becomes flaky, but I’ll keep it until the cost/benefit balance
@@ -39,8 +53,7 @@ mod tests {
             let image = Image::from_url(&url).await.expect("Expected the image to be downloadable");
             let scaled_image = scale(&image, 8, 8).expect("Expected the image to be scalable");
 
-            let encoder = Encoder::new_file(Path::new(file!()).with_file_name("test/cover-generated-small.jpg"), 100).unwrap();
-            let _ = encoder.encode(&scaled_image.bytes, scaled_image.width as u16, scaled_image.height as u16, ColorType::Rgb);
+            let _ = scaled_image.save_jpeg(Path::new(file!()).with_file_name("test/cover-generated-small.jpg"), 100);
 
             insta::assert_debug_snapshot!(scaled_image);
         });
@@ -0,0 +1,75 @@
+/// Replace every pixel of the given RGB bytes by the nearest color of the palette, for a retro
+/// look. Bytes are left untouched if the palette is empty.
+pub fn quantize(bytes: Vec<u8>, palette: &[[u8; 3]]) -> Vec<u8> {
+    if palette.is_empty() {
+        return bytes;
+    }
+
+    return bytes.chunks(3)
+        .flat_map(|pixel| match pixel {
+            [r, g, b] => nearest_color([*r, *g, *b], palette).to_vec(),
+            // incomplete pixels get rejected later on, by whatever checks the size of the image
+            pixel => pixel.to_vec(),
+        })
+        .collect();
+}
+
+/// The color of the palette at the smallest (squared) euclidean distance from the given color.
+/// When several colors are as close, the first one wins.
+pub fn nearest_color(color: [u8; 3], palette: &[[u8; 3]]) -> [u8; 3] {
+    return palette.iter()
+        .min_by_key(|candidate| distance(color, **candidate))
+        .map(|candidate| *candidate)
+        .unwrap_or(color);
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    return (0..3)
+        .map(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32)
+        .sum();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const GREY: [u8; 3] = [128, 128, 128];
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const RED: [u8; 3] = [255, 0, 0];
+
+    #[test]
+    fn nearest_color_given_a_color_of_the_palette_should_return_it() {
+        assert_eq!(nearest_color(RED, &[BLACK, RED, WHITE]), RED);
+    }
+
+    #[test]
+    fn nearest_color_given_equidistant_colors_should_return_the_first_one() {
+        assert_eq!(nearest_color([0, 0, 100], &[[0, 0, 50], [0, 0, 150]]), [0, 0, 50]);
+    }
+
+    #[test]
+    fn nearest_color_given_an_empty_palette_should_return_the_color() {
+        assert_eq!(nearest_color(RED, &[]), RED);
+    }
+
+    #[test]
+    fn quantize_given_a_grey_gradient_should_map_it_onto_the_palette() {
+        let gradient = (0..8).flat_map(|step| [step * 36; 3]).collect::<Vec<u8>>();
+        assert_eq!(quantize(gradient, &[BLACK, GREY, WHITE]), [
+            BLACK, BLACK, GREY, GREY,
+            GREY, GREY, WHITE, WHITE,
+        ].concat());
+    }
+
+    #[test]
+    fn quantize_given_a_red_gradient_should_prefer_the_red_entry_to_the_grey_one() {
+        let gradient = vec![[64, 0, 0], [160, 0, 0], [224, 16, 16]].concat();
+        assert_eq!(quantize(gradient, &[BLACK, GREY, RED]), [BLACK, RED, RED].concat());
+    }
+
+    #[test]
+    fn quantize_given_an_empty_palette_should_return_the_same_bytes() {
+        assert_eq!(quantize(vec![1, 2, 3, 4, 5, 6], &[]), vec![1, 2, 3, 4, 5, 6]);
+    }
+}
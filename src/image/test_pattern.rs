@@ -0,0 +1,82 @@
+use super::Image;
+
+/// Colors of the bars, from left to right, at full intensity
+const COLOR_BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+
+/// Known images to render on a device, to tune how it renders colors
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TestPattern {
+    /// White, yellow, cyan, green, magenta, red, blue and black vertical bars
+    ColorBars,
+    /// Shades of gray, from black on the left to white on the right
+    GrayscaleRamp,
+}
+
+impl TestPattern {
+    pub const ALL: [TestPattern; 2] = [TestPattern::ColorBars, TestPattern::GrayscaleRamp];
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            TestPattern::ColorBars => "color bars",
+            TestPattern::GrayscaleRamp => "grayscale ramp",
+        };
+    }
+}
+
+/// Both patterns are made of columns, so every row of the image is the same
+pub fn render_test_pattern(pattern: TestPattern, width: usize, height: usize) -> Image {
+    let row = (0..width).flat_map(|x| match pattern {
+        TestPattern::ColorBars => COLOR_BARS[x * COLOR_BARS.len() / width],
+        TestPattern::GrayscaleRamp => {
+            let gray = if width > 1 { (255 * x / (width - 1)) as u8 } else { 255 };
+            [gray, gray, gray]
+        },
+    }).collect::<Vec<u8>>();
+
+    return Image { width, height, bytes: row.repeat(height) };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_test_pattern_when_color_bars_then_render_one_bar_per_column() {
+        let image = render_test_pattern(TestPattern::ColorBars, 8, 2);
+        let row = vec![
+            255, 255, 255,  255, 255, 0,  0, 255, 255,  0, 255, 0,
+            255, 0, 255,  255, 0, 0,  0, 0, 255,  0, 0, 0,
+        ];
+        assert_eq!(image, Image { width: 8, height: 2, bytes: row.repeat(2) });
+    }
+
+    #[test]
+    fn render_test_pattern_when_color_bars_on_a_narrow_grid_then_keep_the_bars_in_order() {
+        let image = render_test_pattern(TestPattern::ColorBars, 4, 1);
+        assert_eq!(image.bytes, vec![255, 255, 255,  0, 255, 255,  255, 0, 255,  0, 0, 255]);
+    }
+
+    #[test]
+    fn render_test_pattern_when_grayscale_ramp_then_go_from_black_to_white() {
+        let image = render_test_pattern(TestPattern::GrayscaleRamp, 8, 2);
+        let row = [0, 36, 72, 109, 145, 182, 218, 255].iter()
+            .flat_map(|gray| [*gray, *gray, *gray])
+            .collect::<Vec<u8>>();
+        assert_eq!(image, Image { width: 8, height: 2, bytes: row.repeat(2) });
+    }
+
+    #[test]
+    fn render_test_pattern_when_grayscale_ramp_on_a_single_column_then_render_white() {
+        let image = render_test_pattern(TestPattern::GrayscaleRamp, 1, 3);
+        assert_eq!(image.bytes, vec![255; 9]);
+    }
+}
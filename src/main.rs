@@ -8,7 +8,7 @@ extern crate signal_hook as sh;
 
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use toml::value::Value;
 
 mod apps;
@@ -20,6 +20,10 @@ mod server;
 enum Command {
     INIT,
     RUN,
+    /// Report likely misconfigurations, without running anything
+    CHECK,
+    /// Render test patterns on the device with the given id
+    CALIBRATE(String),
 }
 
 fn main() {
@@ -31,9 +35,15 @@ fn main() {
                 println!("{}", config)
             }),
         Command::RUN => read_config().and_then(|config| {
-            let mut router = router::Router::new(config);
+            let mut router = router::Router::new(config).map_err(|err| format!("{}", err))?;
             router.run().map_err(|err| format!("{}", err))
         }),
+        Command::CHECK => read_config().and_then(|config| {
+            router::check(&config).map_err(|err| format!("{}", err))
+        }),
+        Command::CALIBRATE(device_id) => read_config().and_then(|config| {
+            router::calibrate(&config, &device_id).map_err(|err| format!("{}", err))
+        }),
     });
 
     match result {
@@ -43,27 +53,127 @@ fn main() {
 }
 
 fn get_command() -> Result<Command, String> {
-    let args = env::args().collect::<Vec<String>>();
-    let command = args.get(1).filter(|_| args.len() == 2);
-    return match command.map(|s| s.as_str()) {
-        Some("init") => Ok(Command::INIT),
-        Some("run") => Ok(Command::RUN),
-        _ => Err(String::from("Usage: ./midi-hub [init|run]")),
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    return parse_command(&args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>());
+}
+
+fn parse_command(args: &[&str]) -> Result<Command, String> {
+    return match args {
+        ["init"] => Ok(Command::INIT),
+        ["run"] => Ok(Command::RUN),
+        ["check"] => Ok(Command::CHECK),
+        ["calibrate", device_id] => Ok(Command::CALIBRATE(device_id.to_string())),
+        _ => Err(String::from("Usage: ./midi-hub [init|run|check|calibrate <device id>]")),
     }
 }
 
+/// Supported formats, by order of precedence when several config files exist
+const CONFIG_FILE_NAMES: [&str; 4] = ["config.toml", "config.json", "config.yaml", "config.yml"];
+
 fn read_config() -> Result<router::Config, String> {
-    let mut config_file = std::env::var("XDG_CONFIG_HOME").map(|xdg_config_home| PathBuf::from(xdg_config_home))
+    let mut config_dir = std::env::var("XDG_CONFIG_HOME").map(|xdg_config_home| PathBuf::from(xdg_config_home))
         .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
         .unwrap_or_else(|_| PathBuf::from("."));
 
-    config_file.push("midi-hub");
-    config_file.push("config.toml");
+    config_dir.push("midi-hub");
+
+    let config_file = CONFIG_FILE_NAMES.iter()
+        .map(|name| config_dir.join(name))
+        .find(|config_file| config_file.exists())
+        .ok_or_else(|| format!("Could not find any of {:?} in {:?}", CONFIG_FILE_NAMES, config_dir))?;
 
     let content = fs::read_to_string(config_file.clone())
-        .map_err(|err| format!("Could not find config.toml in {:?}: {:?}", config_file, err))?;
-    let config = content.parse::<Value>()
-        .and_then(|toml_value| toml_value.try_into())
-        .map_err(|err| format!("Could not parse config.toml: {:?}", err))?;
-    return Ok(config);
+        .map_err(|err| format!("Could not read {:?}: {:?}", config_file, err))?;
+    return parse_config(&content, &config_file);
+}
+
+/// Deserialize the config according to the extension of the file it comes from
+fn parse_config(content: &str, config_file: &Path) -> Result<router::Config, String> {
+    return match config_file.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => content.parse::<Value>()
+            .and_then(|toml_value| toml_value.try_into())
+            .map_err(|err| format!("Could not parse {:?}: {:?}", config_file, err)),
+        Some("json") => serde_json::from_str(content)
+            .map_err(|err| format!("Could not parse {:?}: {:?}", config_file, err)),
+        Some("yaml") | Some("yml") => serde_yaml::from_str(content)
+            .map_err(|err| format!("Could not parse {:?}: {:?}", config_file, err)),
+        _ => Err(format!("Unsupported config format: {:?}", config_file)),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TOML_CONFIG: &str = r#"
+[devices.launchpad]
+name = "Launchpad Pro MIDI 2"
+type = "launchpadpro"
+
+[apps.forward]
+transpose = 12
+
+[links]
+launchpad = ["launchpad", "launchpad"]
+"#;
+
+    const JSON_CONFIG: &str = r#"{
+    "devices": {
+        "launchpad": { "name": "Launchpad Pro MIDI 2", "type": "launchpadpro" }
+    },
+    "apps": {
+        "forward": { "transpose": 12 }
+    },
+    "links": {
+        "launchpad": ["launchpad", "launchpad"]
+    }
+}"#;
+
+    const YAML_CONFIG: &str = r#"
+devices:
+  launchpad:
+    name: Launchpad Pro MIDI 2
+    type: launchpadpro
+apps:
+  forward:
+    transpose: 12
+links:
+  launchpad: [launchpad, launchpad]
+"#;
+
+    /// The config does not implement PartialEq, but its JSON representation can be compared
+    fn to_json(config: router::Config) -> serde_json::Value {
+        return serde_json::to_value(&config).expect("the config should be serializable");
+    }
+
+    #[test]
+    fn parse_config_when_formats_differ_then_return_identical_configs() {
+        let toml_config = to_json(parse_config(TOML_CONFIG, &PathBuf::from("config.toml")).unwrap());
+        let json_config = to_json(parse_config(JSON_CONFIG, &PathBuf::from("config.json")).unwrap());
+        let yaml_config = to_json(parse_config(YAML_CONFIG, &PathBuf::from("config.yaml")).unwrap());
+
+        assert_eq!(toml_config, json_config);
+        assert_eq!(toml_config, yaml_config);
+        assert_eq!(toml_config["apps"]["forward"]["transpose"], 12);
+        assert_eq!(toml_config["devices"]["launchpad"]["type"], "launchpadpro");
+    }
+
+    #[test]
+    fn parse_config_when_extension_is_unknown_then_return_an_error() {
+        assert!(parse_config(TOML_CONFIG, &PathBuf::from("config.ini")).is_err());
+    }
+
+    #[test]
+    fn parse_command_when_calibrating_then_keep_the_device_id() {
+        assert!(matches!(parse_command(&["calibrate", "launchpad"]), Ok(Command::CALIBRATE(device_id)) if device_id == "launchpad"));
+    }
+
+    #[test]
+    fn parse_command_when_arguments_are_missing_or_unknown_then_return_the_usage() {
+        assert!(matches!(parse_command(&["run"]), Ok(Command::RUN)));
+        assert!(matches!(parse_command(&["check"]), Ok(Command::CHECK)));
+        assert!(parse_command(&["calibrate"]).is_err());
+        assert!(parse_command(&["run", "launchpad"]).is_err());
+        assert!(parse_command(&[]).is_err());
+    }
 }
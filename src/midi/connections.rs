@@ -1,13 +1,26 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 extern crate portmidi;
 use portmidi::{DeviceInfo, Direction, PortMidi};
 pub use portmidi::{InputPort, OutputPort};
 
+use super::device::{Reader, Writer};
 use super::error::Error;
 
-/// The buffer size is quite arbitrary
-const BUFFER_SIZE: usize = 1024;
+/// The buffer size is quite arbitrary, which is why devices can override it
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+pub const DEFAULT_PORT_RETRIES: u32 = 3;
+pub const DEFAULT_PORT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Thin layer over the creation of ports, so that the parameters given to PortMidi can be tested
+/// without any device being plugged, and so that the router can run with fake devices.
+pub trait PortFactory {
+    fn get_device_names(&self) -> Vec<String>;
+    fn create_input_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Reader + '_>, Error>;
+    fn create_output_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Writer + '_>, Error>;
+}
 
 /// This structure manages all MIDI connections
 ///
@@ -28,15 +41,25 @@ pub struct Connections {
     /// Output devices
     /// These are the MIDI devices you can write MIDI events (or SysEx messages) to
     output_devices: HashMap<String, DeviceInfo>,
+
+    /// Number of times the creation of a port gets retried, and the delay between two attempts
+    port_retries: u32,
+    port_retry_delay: Duration,
 }
 
 impl Connections {
     pub fn new() -> Result<Connections, Error> {
+        return Connections::with_port_retries(DEFAULT_PORT_RETRIES, DEFAULT_PORT_RETRY_DELAY);
+    }
+
+    pub fn with_port_retries(port_retries: u32, port_retry_delay: Duration) -> Result<Connections, Error> {
         let mut connections = PortMidi::new()
             .map(|context| Connections {
                 context,
                 input_devices: HashMap::new(),
                 output_devices: HashMap::new(),
+                port_retries,
+                port_retry_delay,
             })
             .map_err(|_| Error::ConnectionInitializationError)?;
 
@@ -48,13 +71,14 @@ impl Connections {
     fn load_devices(&mut self) -> Result<(), Error> {
         let devices = self.context.devices().map_err(|_| Error::DeviceLoadingError)?;
         for device in devices {
-            let name = device.name().to_string();
             match device.direction() {
                 Direction::Input => {
+                    let name = get_unique_name(&self.input_devices, device.name());
                     println!("[midi] registering {} as an input device", name);
                     self.input_devices.insert(name, device);
                 },
                 Direction::Output =>  {
+                    let name = get_unique_name(&self.output_devices, device.name());
                     println!("[midi] registering {} as an output device", name);
                     self.output_devices.insert(name, device);
                 },
@@ -63,28 +87,10 @@ impl Connections {
         return Ok(());
     }
 
-    pub fn create_input_port(&self, name: &String) -> Result<InputPort, Error> {
-        println!("[midi] initializing input {}", name);
-        let device = self.input_devices.get(name).ok_or(Error::DeviceNotFound)?;
-        return self.context.input_port(device.clone(), BUFFER_SIZE).map_err(|err| {
-            eprintln!("[midi] error when initializing input {}: {}", name, err);
-            Error::PortInitializationError
-        });
-    }
-
-    pub fn create_output_port(&self, name: &String) -> Result<OutputPort, Error> {
-        println!("[midi] initializing output {}", name);
-        let device = self.output_devices.get(name).ok_or(Error::DeviceNotFound)?;
-        return self.context.output_port(device.clone(), BUFFER_SIZE).map_err(|err| {
-            eprintln!("[midi] error when initializing output {}: {}", name, err);
-            Error::PortInitializationError
-        });
-    }
-
     #[allow(dead_code)]
     pub fn create_bidirectional_ports(&self, name: &String) -> Result<(InputPort, OutputPort), Error> {
-        let input_port = self.create_input_port(name)?;
-        let output_port = self.create_output_port(name)?;
+        let input_port = self.open_input_port(name, DEFAULT_BUFFER_SIZE)?;
+        let output_port = self.open_output_port(name, DEFAULT_BUFFER_SIZE)?;
         return Ok((input_port, output_port));
     }
 
@@ -101,10 +107,162 @@ impl Connections {
         device_names.dedup();
         return device_names;
     }
+
+    fn open_input_port(&self, name: &String, buffer_size: usize) -> Result<InputPort<'_>, Error> {
+        println!("[midi] initializing input {}", name);
+        let device = self.input_devices.get(name).ok_or(Error::DeviceNotFound)?;
+        return retry_port_initialization(self.port_retries, self.port_retry_delay, std::thread::sleep, || {
+            return self.context.input_port(device.clone(), buffer_size).map_err(|err| {
+                eprintln!("[midi] error when initializing input {}: {}", name, err);
+                Error::PortInitializationError
+            });
+        });
+    }
+
+    fn open_output_port(&self, name: &String, buffer_size: usize) -> Result<OutputPort<'_>, Error> {
+        println!("[midi] initializing output {}", name);
+        let device = self.output_devices.get(name).ok_or(Error::DeviceNotFound)?;
+        return retry_port_initialization(self.port_retries, self.port_retry_delay, std::thread::sleep, || {
+            return self.context.output_port(device.clone(), buffer_size).map_err(|err| {
+                eprintln!("[midi] error when initializing output {}: {}", name, err);
+                Error::PortInitializationError
+            });
+        });
+    }
+}
+
+impl PortFactory for Connections {
+    fn get_device_names(&self) -> Vec<String> {
+        return Connections::get_device_names(self);
+    }
+
+    fn create_input_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Reader + '_>, Error> {
+        let port = self.open_input_port(name, buffer_size)?;
+        return Ok(Box::new(port));
+    }
+
+    fn create_output_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Writer + '_>, Error> {
+        let port = self.open_output_port(name, buffer_size)?;
+        return Ok(Box::new(port));
+    }
+}
+
+/// Identical controllers share the same name: from the second one on, an index gets appended to
+/// the name (e.g. "Arturia KeyStep 37 #2"), so that each of them can be configured.
+fn get_unique_name<T>(devices: &HashMap<String, T>, name: &str) -> String {
+    if !devices.contains_key(name) {
+        return name.to_string();
+    }
+
+    let unique_name = (2..)
+        .map(|index| format!("{} #{}", name, index))
+        .find(|unique_name| !devices.contains_key(unique_name))
+        .unwrap();
+
+    eprintln!("[midi] WARNING: several devices are named {}, registering this one as {}", name, unique_name);
+    return unique_name;
+}
+
+/// Create a port, retrying the given number of times if it cannot be initialized, as the OS may
+/// briefly hold the device (e.g. right after it got plugged in). Other errors are returned right away.
+fn retry_port_initialization<P, S, F>(retries: u32, delay: Duration, mut sleep: S, mut create_port: F) -> Result<P, Error> where
+    S: FnMut(Duration),
+    F: FnMut() -> Result<P, Error>,
+{
+    let mut attempts = 0;
+    loop {
+        match create_port() {
+            Err(Error::PortInitializationError) if attempts < retries => {
+                attempts += 1;
+                sleep(delay);
+            },
+            result => return result,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{get_unique_name, retry_port_initialization, Duration, Error, HashMap};
+
+    /// Register the given device names the same way devices get registered
+    fn register(names: Vec<&str>) -> Vec<String> {
+        let mut devices = HashMap::new();
+        let mut registered_names = vec![];
+        for name in names {
+            let unique_name = get_unique_name(&devices, name);
+            devices.insert(unique_name.clone(), ());
+            registered_names.push(unique_name);
+        }
+        return registered_names;
+    }
+
+    #[test]
+    fn get_unique_name_when_names_are_distinct_then_keep_them_as_they_are() {
+        assert_eq!(register(vec!["Arturia KeyStep 37", "Launchpad Pro MIDI 2"]), vec![
+            "Arturia KeyStep 37".to_string(),
+            "Launchpad Pro MIDI 2".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn get_unique_name_when_two_devices_share_a_name_then_append_an_index_to_the_second_one() {
+        assert_eq!(register(vec!["Arturia KeyStep 37", "Launchpad Pro MIDI 2", "Arturia KeyStep 37"]), vec![
+            "Arturia KeyStep 37".to_string(),
+            "Launchpad Pro MIDI 2".to_string(),
+            "Arturia KeyStep 37 #2".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn get_unique_name_when_the_indexed_name_is_taken_then_use_the_next_index() {
+        assert_eq!(register(vec!["KeyStep #2", "KeyStep", "KeyStep", "KeyStep"]), vec![
+            "KeyStep #2".to_string(),
+            "KeyStep".to_string(),
+            "KeyStep #3".to_string(),
+            "KeyStep #4".to_string(),
+        ]);
+    }
+
+    /// Fail to initialize the port the given number of times, then succeed
+    fn get_failing_port_factory(failures: u32) -> impl FnMut() -> Result<u32, Error> {
+        let mut attempts = 0;
+        return move || {
+            attempts += 1;
+            return if attempts > failures { Ok(attempts) } else { Err(Error::PortInitializationError) };
+        };
+    }
+
+    #[test]
+    fn retry_port_initialization_when_port_fails_less_than_retries_then_succeed_eventually() {
+        let mut delays = vec![];
+        let result = retry_port_initialization(3, Duration::from_millis(100), |delay| delays.push(delay), get_failing_port_factory(2));
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(delays, vec![Duration::from_millis(100); 2]);
+    }
+
+    #[test]
+    fn retry_port_initialization_when_port_keeps_failing_then_give_up_after_the_retries() {
+        let mut delays = vec![];
+        let result = retry_port_initialization(3, Duration::from_millis(100), |delay| delays.push(delay), get_failing_port_factory(10));
+
+        assert_eq!(result, Err(Error::PortInitializationError));
+        assert_eq!(delays.len(), 3);
+    }
+
+    #[test]
+    fn retry_port_initialization_when_device_is_not_found_then_do_not_retry() {
+        let mut attempts = 0;
+        let result: Result<(), Error> = retry_port_initialization(3, Duration::from_millis(100), |_| {}, || {
+            attempts += 1;
+            return Err(Error::DeviceNotFound);
+        });
+
+        assert_eq!(result, Err(Error::DeviceNotFound));
+        assert_eq!(attempts, 1);
+    }
+
     #[test]
     #[cfg(not(feature = "launchpadpro"))]
     #[cfg(not(feature = "planckez"))]
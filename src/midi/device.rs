@@ -1,12 +1,18 @@
+use std::collections::VecDeque;
 use std::convert::From;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 extern crate portmidi;
 use portmidi::{InputPort, OutputPort, MidiEvent, MidiMessage};
+use serde::{Serialize, Deserialize};
 
 pub use crate::image::Image;
 use super::Error;
 
-#[derive(Clone, Debug, PartialEq)]
+/// Events get serialized like server commands, using the name of the variant as a key:
+/// `{"Midi":[144,36,100,0]}` or `{"SysEx":[240,0,32,41,247]}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Event {
     Midi([u8; 4]),
     SysEx(Vec<u8>),
@@ -36,6 +42,77 @@ impl<'a> Reader for (InputPort<'a>, OutputPort<'a>) {
     }
 }
 
+/// Control changes read by a `CoalescingReader`, waiting for the end of their window. It outlives
+/// the readers, which get recreated every time devices are polled, so that none of them gets lost.
+pub struct CoalescingState {
+    window: Duration,
+    /// Control changes waiting for the end of the window, in the order their controller first moved
+    pending: Vec<[u8; 4]>,
+    window_start: Option<Instant>,
+    ready: VecDeque<[u8; 4]>,
+}
+
+impl CoalescingState {
+    pub fn new(window: Duration) -> Self {
+        return CoalescingState { window, pending: vec![], window_start: None, ready: VecDeque::new() };
+    }
+
+    fn coalesce(&mut self, event: [u8; 4], now: Instant) {
+        // Other events must not overtake the control changes read before them
+        if !(176..=191).contains(&event[0]) {
+            self.flush();
+            self.ready.push_back(event);
+            return;
+        }
+
+        match self.pending.iter_mut().find(|pending| pending[0..2] == event[0..2]) {
+            Some(pending) => *pending = event,
+            None => {
+                if self.pending.is_empty() {
+                    self.window_start = Some(now);
+                }
+                self.pending.push(event);
+            },
+        }
+    }
+
+    fn flush(&mut self) {
+        self.ready.extend(self.pending.drain(..));
+        self.window_start = None;
+    }
+}
+
+/// Keep only the latest value of each controller (by status and controller number) among the
+/// control change events read within the given window, so that faders and encoders do not flood
+/// apps. Other events, notes included, are read right away.
+pub struct CoalescingReader<'a, C: FnMut() -> Instant> {
+    reader: Box<dyn Reader + 'a>,
+    state: Arc<Mutex<CoalescingState>>,
+    now: C,
+}
+
+impl<'a, C: FnMut() -> Instant> CoalescingReader<'a, C> {
+    pub fn new(reader: Box<dyn Reader + 'a>, state: Arc<Mutex<CoalescingState>>, now: C) -> Self {
+        return CoalescingReader { reader, state, now };
+    }
+}
+
+impl<C: FnMut() -> Instant> Reader for CoalescingReader<'_, C> {
+    fn read_midi(&mut self) -> Result<Option<[u8; 4]>, Error> {
+        let mut state = self.state.lock().unwrap();
+        while let Some(event) = self.reader.read_midi()? {
+            state.coalesce(event, (self.now)());
+        }
+
+        if let Some(window_start) = state.window_start {
+            if (self.now)().duration_since(window_start) >= state.window {
+                state.flush();
+            }
+        }
+        return Ok(state.ready.pop_front());
+    }
+}
+
 /// MIDI Device that is able to receive MIDI events and SysEx MIDI messages
 pub trait Writer {
     fn write_midi(&mut self, event: &[u8; 4]) -> Result<(), Error>;
@@ -47,6 +124,62 @@ pub trait Writer {
             Event::SysEx(event) => self.write_sysex(&event),
         };
     }
+
+    /// Write a batch of events in a row, stopping at the first one that cannot be written.
+    fn write_all(&mut self, events: &[Event]) -> Result<(), Error> {
+        for event in events {
+            match event {
+                Event::Midi(event) => self.write_midi(event)?,
+                Event::SysEx(event) => self.write_sysex(event)?,
+            }
+        }
+        return Ok(());
+    }
+
+    /// Write a SysEx message that does not come from a device implementation (e.g. from the
+    /// configuration file), and cannot be trusted to be properly framed.
+    fn write_sysex_macro(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        validate_sysex(bytes)?;
+        return self.write_sysex(bytes);
+    }
+}
+
+/// Check that the message starts with 240, ends with 247, and that all the bytes in between are data bytes
+pub fn validate_sysex(bytes: &[u8]) -> Result<(), Error> {
+    return match bytes {
+        [240, data @ .., 247] if data.iter().all(|byte| *byte < 128) => Ok(()),
+        _ => Err(Error::InvalidSysExError),
+    };
+}
+
+/// Wait for the given gap before writing a SysEx message right after another one, as some devices
+/// drop messages when their buffer gets overrun. Short messages are written right away.
+pub struct SysExGapWriter<'a, S: FnMut(Duration)> {
+    writer: Box<dyn Writer + 'a>,
+    gap: Duration,
+    sleep: S,
+    after_sysex: bool,
+}
+
+impl<'a, S: FnMut(Duration)> SysExGapWriter<'a, S> {
+    pub fn new(writer: Box<dyn Writer + 'a>, gap: Duration, sleep: S) -> Self {
+        return SysExGapWriter { writer, gap, sleep, after_sysex: false };
+    }
+}
+
+impl<S: FnMut(Duration)> Writer for SysExGapWriter<'_, S> {
+    fn write_midi(&mut self, event: &[u8; 4]) -> Result<(), Error> {
+        self.after_sysex = false;
+        return self.writer.write_midi(event);
+    }
+
+    fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+        if self.after_sysex {
+            (self.sleep)(self.gap);
+        }
+        self.after_sysex = true;
+        return self.writer.write_sysex(event);
+    }
 }
 
 impl Writer for OutputPort<'_> {
@@ -68,3 +201,272 @@ impl<'a> Writer for (InputPort<'a>, OutputPort<'a>) {
         return Writer::write_sysex(&mut self.1, event);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct FakeWriter {
+        sysex: Vec<Vec<u8>>,
+    }
+
+    impl Writer for FakeWriter {
+        fn write_midi(&mut self, _event: &[u8; 4]) -> Result<(), Error> {
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+            self.sysex.push(event.to_vec());
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn validate_sysex_when_properly_framed_then_succeed() {
+        assert_eq!(validate_sysex(&[240, 247]), Ok(()));
+        assert_eq!(validate_sysex(&[240, 0, 32, 41, 2, 16, 14, 1, 247]), Ok(()));
+    }
+
+    #[test]
+    fn validate_sysex_when_badly_framed_then_fail() {
+        assert_eq!(validate_sysex(&[]), Err(Error::InvalidSysExError));
+        assert_eq!(validate_sysex(&[240]), Err(Error::InvalidSysExError));
+        assert_eq!(validate_sysex(&[0, 32, 41, 247]), Err(Error::InvalidSysExError));
+        assert_eq!(validate_sysex(&[240, 0, 32, 41]), Err(Error::InvalidSysExError));
+        assert_eq!(validate_sysex(&[240, 0, 144, 41, 247]), Err(Error::InvalidSysExError));
+    }
+
+    #[test]
+    fn write_sysex_macro_when_invalid_then_write_nothing() {
+        let mut writer = FakeWriter { sysex: vec![] };
+        assert_eq!(writer.write_sysex_macro(&[240, 0, 32]), Err(Error::InvalidSysExError));
+        assert_eq!(writer.write_sysex_macro(&[240, 0, 32, 247]), Ok(()));
+        assert_eq!(writer.sysex, vec![vec![240, 0, 32, 247]]);
+    }
+
+    #[test]
+    fn write_all_when_a_write_fails_then_stop_writing() {
+        let mut writer = FakeWriter { sysex: vec![] };
+        let events = vec![
+            Event::SysEx(vec![240, 1, 247]),
+            Event::SysEx(vec![240, 2, 247]),
+        ];
+        assert_eq!(writer.write_all(&events), Ok(()));
+        assert_eq!(writer.sysex, vec![vec![240, 1, 247], vec![240, 2, 247]]);
+
+        let mut writer = FailingWriter { written: 0 };
+        let events = vec![Event::Midi([144, 36, 100, 0]); 3];
+        assert_eq!(writer.write_all(&events), Err(Error::WriteError));
+        assert_eq!(writer.written, 1);
+    }
+
+    /// Fails on the second write
+    struct FailingWriter {
+        written: usize,
+    }
+
+    impl Writer for FailingWriter {
+        fn write_midi(&mut self, _event: &[u8; 4]) -> Result<(), Error> {
+            if self.written > 0 {
+                return Err(Error::WriteError);
+            }
+            self.written += 1;
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, _event: &[u8]) -> Result<(), Error> {
+            return Err(Error::WriteError);
+        }
+    }
+
+    /// Logs the writes, so that they can be interleaved with other calls
+    struct LoggingWriter {
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Writer for LoggingWriter {
+        fn write_midi(&mut self, event: &[u8; 4]) -> Result<(), Error> {
+            self.log.borrow_mut().push(format!("midi {:?}", event));
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+            self.log.borrow_mut().push(format!("sysex {:?}", event));
+            return Ok(());
+        }
+    }
+
+    /// Events are read in order, then the reader runs dry
+    struct QueueReader {
+        events: Rc<RefCell<VecDeque<[u8; 4]>>>,
+    }
+
+    impl Reader for QueueReader {
+        fn read_midi(&mut self) -> Result<Option<[u8; 4]>, Error> {
+            return Ok(self.events.borrow_mut().pop_front());
+        }
+    }
+
+    fn read_all(reader: &mut dyn Reader) -> Vec<[u8; 4]> {
+        let mut events = vec![];
+        while let Some(event) = reader.read_midi().unwrap() {
+            events.push(event);
+        }
+        return events;
+    }
+
+    #[test]
+    fn coalescing_reader_when_a_controller_moves_within_the_window_then_only_read_its_last_value() {
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let start = Instant::now();
+        let now = Rc::new(RefCell::new(start));
+        let clock = Rc::clone(&now);
+        let mut reader = CoalescingReader::new(
+            Box::new(QueueReader { events: Rc::clone(&events) }),
+            Arc::new(Mutex::new(CoalescingState::new(Duration::from_millis(20)))),
+            move || *clock.borrow(),
+        );
+
+        events.borrow_mut().extend([[176, 1, 10, 0], [176, 1, 20, 0], [176, 2, 5, 0], [176, 1, 30, 0]]);
+        assert_eq!(read_all(&mut reader), vec![]);
+
+        *now.borrow_mut() = start + Duration::from_millis(10);
+        events.borrow_mut().extend([[176, 1, 40, 0], [177, 1, 50, 0]]);
+        assert_eq!(read_all(&mut reader), vec![]);
+
+        *now.borrow_mut() = start + Duration::from_millis(20);
+        assert_eq!(read_all(&mut reader), vec![[176, 1, 40, 0], [176, 2, 5, 0], [177, 1, 50, 0]]);
+
+        // the next burst gets its own window
+        events.borrow_mut().extend([[176, 1, 60, 0], [176, 1, 70, 0]]);
+        assert_eq!(read_all(&mut reader), vec![]);
+        *now.borrow_mut() = start + Duration::from_millis(40);
+        assert_eq!(read_all(&mut reader), vec![[176, 1, 70, 0]]);
+    }
+
+    #[test]
+    fn coalescing_reader_when_notes_are_read_then_read_each_of_them_right_away() {
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let start = Instant::now();
+        let mut reader = CoalescingReader::new(
+            Box::new(QueueReader { events: Rc::clone(&events) }),
+            Arc::new(Mutex::new(CoalescingState::new(Duration::from_millis(20)))),
+            move || start,
+        );
+
+        // the control change read before the second note gets flushed, so as not to be overtaken
+        events.borrow_mut().extend([[144, 36, 100, 0], [176, 1, 10, 0], [144, 36, 100, 0], [128, 36, 0, 0]]);
+        assert_eq!(read_all(&mut reader), vec![[144, 36, 100, 0], [176, 1, 10, 0], [144, 36, 100, 0], [128, 36, 0, 0]]);
+    }
+
+    #[test]
+    fn coalescing_reader_when_a_note_follows_control_changes_then_read_them_before_the_note() {
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let start = Instant::now();
+        let mut reader = CoalescingReader::new(
+            Box::new(QueueReader { events: Rc::clone(&events) }),
+            Arc::new(Mutex::new(CoalescingState::new(Duration::from_millis(20)))),
+            move || start,
+        );
+
+        events.borrow_mut().extend([[176, 1, 10, 0], [176, 1, 20, 0], [144, 36, 100, 0], [176, 1, 30, 0]]);
+        assert_eq!(read_all(&mut reader), vec![[176, 1, 20, 0], [144, 36, 100, 0]]);
+    }
+
+    #[test]
+    fn coalescing_reader_when_the_reader_is_recreated_then_keep_the_pending_control_changes() {
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let state = Arc::new(Mutex::new(CoalescingState::new(Duration::from_millis(20))));
+        let start = Instant::now();
+
+        events.borrow_mut().extend([[176, 1, 10, 0], [176, 1, 20, 0]]);
+        let mut reader = CoalescingReader::new(Box::new(QueueReader { events: Rc::clone(&events) }), Arc::clone(&state), move || start);
+        assert_eq!(read_all(&mut reader), vec![]);
+        drop(reader);
+
+        let later = start + Duration::from_millis(20);
+        let mut reader = CoalescingReader::new(Box::new(QueueReader { events: Rc::clone(&events) }), Arc::clone(&state), move || later);
+        assert_eq!(read_all(&mut reader), vec![[176, 1, 20, 0]]);
+    }
+
+    fn write_with_sysex_gap(events: Vec<Event>) -> Vec<String> {
+        let log = Rc::new(RefCell::new(vec![]));
+        let sleep_log = Rc::clone(&log);
+        let mut writer = SysExGapWriter::new(
+            Box::new(LoggingWriter { log: Rc::clone(&log) }),
+            Duration::from_millis(5),
+            move |gap: Duration| sleep_log.borrow_mut().push(format!("sleep {:?}", gap)),
+        );
+
+        writer.write_all(&events).unwrap();
+        return log.take();
+    }
+
+    #[test]
+    fn sysex_gap_writer_when_writing_consecutive_sysex_then_sleep_in_between() {
+        assert_eq!(write_with_sysex_gap(vec![
+            Event::SysEx(vec![240, 1, 247]),
+            Event::SysEx(vec![240, 2, 247]),
+        ]), vec![
+            "sysex [240, 1, 247]",
+            "sleep 5ms",
+            "sysex [240, 2, 247]",
+        ]);
+    }
+
+    #[test]
+    fn sysex_gap_writer_when_writing_short_messages_then_never_sleep() {
+        assert_eq!(write_with_sysex_gap(vec![
+            Event::Midi([144, 36, 100, 0]),
+            Event::Midi([144, 37, 100, 0]),
+            Event::SysEx(vec![240, 1, 247]),
+            Event::Midi([128, 36, 0, 0]),
+            Event::SysEx(vec![240, 2, 247]),
+        ]), vec![
+            "midi [144, 36, 100, 0]",
+            "midi [144, 37, 100, 0]",
+            "sysex [240, 1, 247]",
+            "midi [128, 36, 0, 0]",
+            "sysex [240, 2, 247]",
+        ]);
+    }
+
+    #[test]
+    fn serialize_when_midi_event_then_use_midi_key() {
+        let json = serde_json::to_string(&Event::Midi([144, 36, 100, 0])).unwrap();
+        assert_eq!(json, r#"{"Midi":[144,36,100,0]}"#);
+    }
+
+    #[test]
+    fn serialize_when_sysex_event_then_use_sysex_key() {
+        let json = serde_json::to_string(&Event::SysEx(vec![240, 0, 32, 41, 247])).unwrap();
+        assert_eq!(json, r#"{"SysEx":[240,0,32,41,247]}"#);
+    }
+
+    #[test]
+    fn deserialize_when_serialized_events_then_return_same_events() {
+        let events = vec![
+            Event::Midi([144, 36, 100, 0]),
+            Event::Midi([128, 36, 0, 0]),
+            Event::SysEx(vec![240, 0, 32, 41, 2, 16, 40, 11, 45, 247]),
+            Event::SysEx(vec![]),
+        ];
+
+        let json = serde_json::to_string(&events).unwrap();
+        let actual_events = serde_json::from_str::<Vec<Event>>(&json).unwrap();
+        assert_eq!(actual_events, events);
+    }
+
+    #[test]
+    fn deserialize_when_midi_event_has_wrong_length_then_fail() {
+        assert!(serde_json::from_str::<Event>(r#"{"Midi":[144,36,100]}"#).is_err());
+    }
+
+    #[test]
+    fn deserialize_when_unknown_variant_then_fail() {
+        assert!(serde_json::from_str::<Event>(r#"{"Note":[144,36,100,0]}"#).is_err());
+    }
+}
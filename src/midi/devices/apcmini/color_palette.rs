@@ -0,0 +1,84 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::features::{R, ColorPalette};
+
+use super::device::{ApcMiniFeatures, OFF};
+
+/// Velocity lighting the LED of a button below the grid, which can only be red
+const BUTTON_ON: u8 = 1;
+
+/// On the APC Mini, the color palette is the row of buttons below the grid, so that the whole
+/// grid remains available (e.g. as a canvas):
+///  ...
+/// ╔══╗╔══╗╔══╗╔══╗╔══╗╔══╗╔══╗╔══╗
+/// ║ 0║║ 1║║ 2║║ 3║║ 4║║ 5║║ 6║║ 7║
+/// ╚══╝╚══╝╚══╝╚══╝╚══╝╚══╝╚══╝╚══╝
+///  64  65  66  67  68  69  70  71
+///  ↖0  ↖1  ↖2  ↖3  ↖4  ↖5  ↖6  ↖7
+///
+/// Those buttons only have a red LED: it is lit for every color but black.
+impl ColorPalette for ApcMiniFeatures {
+    fn into_color_palette_index(&self, event: Event) -> R<Option<usize>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 && note >= 64 && note < 72 => {
+                Some(usize::from(note - 64))
+            },
+            _ => None,
+        });
+    }
+
+    fn from_color_palette_to_events(&self, colors: Vec<[u8; 3]>) -> R<Vec<Event>> {
+        if colors.len() > 8 {
+            return Err(Box::new(Error::OutOfBoundIndexError));
+        }
+
+        return Ok(colors.iter().enumerate().map(|(index, color)| {
+            let velocity = if *color == [0, 0, 0] { OFF } else { BUTTON_ON };
+            Event::Midi([144, 64 + index as u8, velocity, 0])
+        }).collect());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_color_palette_index_should_return_the_position_of_the_button_below_the_grid() {
+        let features = ApcMiniFeatures::new();
+        let actual_output = [0, 63, 64, 68, 71, 72]
+            .iter()
+            .map(|note| features
+                .into_color_palette_index(Event::Midi([144, *note, 127, 0]))
+                .expect("into_color_palette_index should not fail"))
+            .collect::<Vec<Option<usize>>>();
+
+        assert_eq!(actual_output, vec![None, None, Some(0), Some(4), Some(7), None]);
+    }
+
+    #[test]
+    fn from_color_palette_to_events_when_too_many_colors_then_return_out_of_bound_error() {
+        let features = ApcMiniFeatures::new();
+        assert!(features.from_color_palette_to_events(vec![[0, 0, 0]; 9]).is_err());
+    }
+
+    #[test]
+    fn from_color_palette_to_events_should_light_the_buttons_below_the_grid_unless_black() {
+        let features = ApcMiniFeatures::new();
+        let actual_events = features.from_color_palette_to_events(vec![
+            [0, 0, 0],
+            [0, 255, 64],
+            [255, 32, 0],
+            [255, 255, 0],
+        ]).unwrap();
+
+        assert_eq!(actual_events, vec![
+            Event::Midi([144, 64, 0, 0]),
+            Event::Midi([144, 65, 1, 0]),
+            Event::Midi([144, 66, 1, 0]),
+            Event::Midi([144, 67, 1, 0]),
+        ]);
+    }
+}
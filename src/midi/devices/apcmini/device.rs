@@ -0,0 +1,107 @@
+use std::convert::TryFrom;
+
+use crate::midi::devices::config::{DeviceConfig, GridLayout, Origin};
+use crate::midi::devices::grid_geometry::GridGeometry;
+use crate::midi::features::Features;
+
+/// Velocities lighting the pads of the grid: unlike the Launchpad Pro, the APC Mini has no RGB
+/// LEDs, but a fixed set of colors, each of them available steady or blinking.
+pub const OFF: u8 = 0;
+pub const GREEN: u8 = 1;
+pub const RED: u8 = 3;
+pub const YELLOW: u8 = 5;
+
+const LED_COLORS: [([u8; 3], u8); 4] = [
+    ([0, 0, 0], OFF),
+    ([0, 255, 0], GREEN),
+    ([255, 0, 0], RED),
+    ([255, 255, 0], YELLOW),
+];
+
+/// The APC Mini provides an 8x8 grid of pads, with a row of 8 buttons below it, a column of 8
+/// "scene" buttons on its right, and 9 faders.
+///
+/// The pads send notes from 0 (bottom-left) to 63 (top-right), row by row, and the faders send
+/// controller changes from 48 to 56 (the master fader, on the right).
+pub struct ApcMiniFeatures {
+    pub grid_layout: GridLayout,
+}
+
+/// The notes of the pads, which do not depend on the layout picked for the indices
+const PADS: GridGeometry = GridGeometry { width: 8, height: 8, origin: Origin::BottomLeft, layout: GridLayout::RowMajor };
+
+impl ApcMiniFeatures {
+    pub fn new() -> ApcMiniFeatures {
+        ApcMiniFeatures {
+            grid_layout: GridLayout::RowMajor,
+        }
+    }
+
+    /// Return the position of the pad (the x-coordinate first) if the note corresponds to a pad,
+    /// with (0, 0) being the top-left pad.
+    pub(super) fn into_pad(&self, note: u8) -> Option<(usize, usize)> {
+        return PADS.index_to_xy(note.into());
+    }
+
+    pub(super) fn from_pad(&self, x: usize, y: usize) -> Option<u8> {
+        return PADS.xy_to_index(x, y).and_then(|note| u8::try_from(note).ok());
+    }
+
+    pub(super) fn get_geometry(&self) -> GridGeometry {
+        return GridGeometry { layout: self.grid_layout, ..PADS };
+    }
+}
+
+impl From<&DeviceConfig> for ApcMiniFeatures {
+    fn from(config: &DeviceConfig) -> ApcMiniFeatures {
+        ApcMiniFeatures {
+            grid_layout: config.grid_layout,
+        }
+    }
+}
+
+/// Pick the velocity of the LED color that is the closest to the given RGB color
+pub fn get_nearest_led_color(color: [u8; 3]) -> u8 {
+    let distance = |led_color: &[u8; 3]| -> u32 {
+        led_color.iter().zip(color.iter())
+            .map(|(a, b)| (i32::from(*a) - i32::from(*b)).pow(2) as u32)
+            .sum()
+    };
+
+    return LED_COLORS.iter()
+        .min_by_key(|(led_color, _)| distance(led_color))
+        .map(|(_, velocity)| *velocity)
+        .unwrap_or(OFF);
+}
+
+/// Every color has a blinking variant, whose velocity follows the one of the steady color
+pub fn get_blinking_led_color(velocity: u8) -> u8 {
+    return if velocity == OFF { OFF } else { velocity + 1 };
+}
+
+impl Features for ApcMiniFeatures {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_nearest_led_color_should_pick_the_closest_color_of_the_device() {
+        assert_eq!(get_nearest_led_color([0, 0, 0]), OFF);
+        assert_eq!(get_nearest_led_color([20, 10, 30]), OFF);
+        assert_eq!(get_nearest_led_color([0, 255, 0]), GREEN);
+        assert_eq!(get_nearest_led_color([0, 200, 255]), GREEN);
+        assert_eq!(get_nearest_led_color([255, 0, 0]), RED);
+        assert_eq!(get_nearest_led_color([255, 0, 255]), RED);
+        assert_eq!(get_nearest_led_color([255, 200, 0]), YELLOW);
+        assert_eq!(get_nearest_led_color([255, 255, 255]), YELLOW);
+    }
+
+    #[test]
+    fn get_blinking_led_color_should_return_the_blinking_variant_of_the_color() {
+        assert_eq!(get_blinking_led_color(OFF), OFF);
+        assert_eq!(get_blinking_led_color(GREEN), 2);
+        assert_eq!(get_blinking_led_color(RED), 4);
+        assert_eq!(get_blinking_led_color(YELLOW), 6);
+    }
+}
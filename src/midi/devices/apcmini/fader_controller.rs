@@ -0,0 +1,45 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, FaderController};
+
+use super::device::ApcMiniFeatures;
+
+/// On the APC Mini, the 8 track faders send the controllers 48 to 55 from left to right,
+/// and the master fader sends the controller 56:
+///  ║  ║  ║  ║  ║  ║  ║  ║  ║
+///  ╫  ╫  ╫  ╫  ╫  ╫  ╫  ╫  ╫
+///  ║  ║  ║  ║  ║  ║  ║  ║  ║
+///  0  1  2  3  4  5  6  7  8
+impl FaderController for ApcMiniFeatures {
+    fn into_fader_value(&self, event: Event) -> R<Option<(usize, u8)>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // 176: controller change, on the first channel
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if control >= 48 && control <= 56 => {
+                Some((usize::from(control - 48), value))
+            },
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_fader_value_should_return_the_index_and_position_of_the_fader() {
+        let features = ApcMiniFeatures::new();
+        assert_eq!(features.into_fader_value(Event::Midi([176, 48, 0, 0])).unwrap(), Some((0, 0)));
+        assert_eq!(features.into_fader_value(Event::Midi([176, 52, 64, 0])).unwrap(), Some((4, 64)));
+        assert_eq!(features.into_fader_value(Event::Midi([176, 56, 127, 0])).unwrap(), Some((8, 127)));
+    }
+
+    #[test]
+    fn into_fader_value_given_other_events_should_return_none() {
+        let features = ApcMiniFeatures::new();
+        assert_eq!(features.into_fader_value(Event::Midi([176, 47, 10, 0])).unwrap(), None);
+        assert_eq!(features.into_fader_value(Event::Midi([176, 57, 10, 0])).unwrap(), None);
+        assert_eq!(features.into_fader_value(Event::Midi([144, 48, 10, 0])).unwrap(), None);
+    }
+}
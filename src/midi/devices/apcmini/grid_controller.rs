@@ -0,0 +1,54 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, GridController};
+
+use super::device::ApcMiniFeatures;
+
+impl GridController for ApcMiniFeatures {
+    fn get_grid_size(&self) -> R<(usize, usize)> {
+        return Ok((8, 8));
+    }
+
+    fn into_coordinates(&self, event: Event) -> R<Option<(usize, usize)>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" (144) with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => self.into_pad(note),
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_coordinates_given_low_velocity_should_return_none() {
+        let features = ApcMiniFeatures::new();
+        let event = Event::Midi([144, 0, 0, 0]);
+        assert_eq!(None, features.into_coordinates(event).expect("into_coordinates should not fail"));
+    }
+
+    #[test]
+    fn into_coordinates_given_out_of_grid_note_should_return_none() {
+        let features = ApcMiniFeatures::new();
+        for note in [64, 71, 82, 89, 98] {
+            let event = Event::Midi([144, note, 127, 0]);
+            assert_eq!(None, features.into_coordinates(event).expect("into_coordinates should not fail"));
+        }
+    }
+
+    #[test]
+    fn into_coordinates_should_start_from_the_top_left_pad() {
+        let features = ApcMiniFeatures::new();
+        let actual_output = [56, 63, 0, 7, 27]
+            .iter()
+            .map(|note| features
+                .into_coordinates(Event::Midi([144, *note, 127, 0]))
+                .expect("into_coordinates should not fail"))
+            .collect::<Vec<Option<(usize, usize)>>>();
+
+        assert_eq!(actual_output, vec![Some((0, 0)), Some((7, 0)), Some((0, 7)), Some((7, 7)), Some((3, 4))]);
+    }
+}
@@ -0,0 +1,132 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::features::{R, IndexSelector};
+
+use super::device::{get_blinking_led_color, get_nearest_led_color, ApcMiniFeatures, GREEN, OFF};
+
+/// The notes of the pads already start from the bottom-left one, so they are the indices of the
+/// default row-major layout:
+/// ╔══╗╔══╗╔══╗     ╔══╗
+/// ║56║║57║║58║ ... ║63║
+/// ╚══╝╚══╝╚══╝     ╚══╝
+///  ...
+/// ╔══╗╔══╗╔══╗     ╔══╗
+/// ║ 0║║ 1║║ 2║ ... ║ 7║
+/// ╚══╝╚══╝╚══╝     ╚══╝
+impl IndexSelector for ApcMiniFeatures {
+    fn into_index(&self, event: Event) -> R<Option<usize>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => {
+                self.into_pad(note).and_then(|(x, y)| self.get_geometry().xy_to_index(x, y))
+            },
+            _ => None,
+        });
+    }
+
+    fn from_index_to_highlight(&self, index: usize) -> R<Event> {
+        return self.from_index_to_highlight_with_color(index, [0, 255, 0]);
+    }
+
+    /// The velocity gives the color of the pad, the nearest one the device supports
+    fn from_index_to_highlight_with_color(&self, index: usize, color: [u8; 3]) -> R<Event> {
+        let note = match self.get_geometry().index_to_xy(index).and_then(|(x, y)| self.from_pad(x, y)) {
+            Some(note) => note,
+            None => return Err(Box::new(Error::OutOfBoundIndexError)),
+        };
+
+        let velocity = match get_nearest_led_color(color) {
+            // lighting the pad off would not highlight anything
+            OFF => GREEN,
+            velocity => velocity,
+        };
+        return Ok(Event::Midi([144, note, get_blinking_led_color(velocity), 0]));
+    }
+
+    fn from_index_to_coordinates(&self, index: usize) -> R<Option<(usize, usize)>> {
+        return Ok(self.get_geometry().index_to_xy(index));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::midi::devices::config::GridLayout;
+
+    #[test]
+    fn into_index_given_incorrect_status_should_return_none() {
+        let features = ApcMiniFeatures::new();
+        let event = Event::Midi([128, 0, 10, 0]);
+        assert_eq!(None, features.into_index(event).expect("into_index should not fail"));
+    }
+
+    #[test]
+    fn into_index_given_out_of_grid_note_should_return_none() {
+        let features = ApcMiniFeatures::new();
+        for note in [64, 71, 82, 89, 98] {
+            let event = Event::Midi([144, note, 127, 0]);
+            assert_eq!(None, features.into_index(event).expect("into_index should not fail"));
+        }
+    }
+
+    #[test]
+    fn into_index_should_return_the_note_of_the_pad() {
+        let features = ApcMiniFeatures::new();
+        let actual_output = [0, 7, 8, 56, 63]
+            .iter()
+            .map(|note| features
+                .into_index(Event::Midi([144, *note, 127, 0]))
+                .expect("into_index should not fail"))
+            .collect::<Vec<Option<usize>>>();
+
+        assert_eq!(actual_output, vec![Some(0), Some(7), Some(8), Some(56), Some(63)]);
+    }
+
+    #[test]
+    fn into_index_and_into_coordinates_should_agree_on_the_position_of_the_pads() {
+        use crate::midi::features::GridController;
+
+        let features = ApcMiniFeatures::new();
+        for note in 0..64 {
+            let event = Event::Midi([144, note, 127, 0]);
+            let index = features.into_index(event.clone()).unwrap().unwrap();
+            let (x, y) = features.into_coordinates(event).unwrap().unwrap();
+            // indices start from the bottom-left pad, coordinates from the top-left one
+            assert_eq!(index, (7 - y) * 8 + x);
+        }
+    }
+
+    #[test]
+    fn into_released_index_should_return_the_index_of_the_released_pad() {
+        let features = ApcMiniFeatures::new();
+        assert_eq!(Some(0), features.into_released_index(Event::Midi([128, 0, 127, 0])).unwrap());
+        assert_eq!(Some(63), features.into_released_index(Event::Midi([144, 63, 0, 0])).unwrap());
+    }
+
+    #[test]
+    fn from_index_to_highlight_should_make_the_pad_blink_in_green() {
+        let features = ApcMiniFeatures::new();
+        assert_eq!(features.from_index_to_highlight(0).unwrap(), Event::Midi([144, 0, 2, 0]));
+        assert_eq!(features.from_index_to_highlight(63).unwrap(), Event::Midi([144, 63, 2, 0]));
+        assert!(features.from_index_to_highlight(64).is_err());
+    }
+
+    #[test]
+    fn from_index_to_highlight_with_color_should_use_the_nearest_color_of_the_device() {
+        let features = ApcMiniFeatures::new();
+        assert_eq!(features.from_index_to_highlight_with_color(9, [255, 20, 0]).unwrap(), Event::Midi([144, 9, 4, 0]));
+        assert_eq!(features.from_index_to_highlight_with_color(9, [255, 220, 0]).unwrap(), Event::Midi([144, 9, 6, 0]));
+        assert_eq!(features.from_index_to_highlight_with_color(9, [0, 0, 0]).unwrap(), Event::Midi([144, 9, 2, 0]));
+    }
+
+    #[test]
+    fn into_index_given_column_major_layout_should_number_pads_from_bottom_to_top() {
+        let features = ApcMiniFeatures { grid_layout: GridLayout::ColumnMajor };
+        assert_eq!(features.into_index(Event::Midi([144, 0, 127, 0])).unwrap(), Some(0));
+        assert_eq!(features.into_index(Event::Midi([144, 8, 127, 0])).unwrap(), Some(1));
+        assert_eq!(features.into_index(Event::Midi([144, 1, 127, 0])).unwrap(), Some(8));
+        assert_eq!(features.from_index_to_highlight(1).unwrap(), Event::Midi([144, 8, 2, 0]));
+        assert_eq!(features.from_index_to_coordinates(1).unwrap(), Some((0, 6)));
+    }
+}
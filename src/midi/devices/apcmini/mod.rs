@@ -0,0 +1,8 @@
+mod device;
+
+mod color_palette;
+mod fader_controller;
+mod grid_controller;
+mod index_selector;
+
+pub use device::ApcMiniFeatures;
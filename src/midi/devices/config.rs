@@ -5,6 +5,7 @@ use serde::{Serialize, Deserialize};
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
 
 use crate::midi::Connections;
+use crate::midi::features::GlobalAction;
 
 pub type Config = HashMap<String, DeviceConfig>;
 
@@ -13,13 +14,165 @@ pub struct DeviceConfig {
     pub name: String,
     #[serde(rename = "type")]
     pub device_type: DeviceType,
+    /// Only used by the default device type: the row of 8 notes hosting the color palette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_palette_row: Option<u8>,
+    /// Only used by the default device type: the note played by each pad of the grid, for
+    /// controllers whose pads follow no particular numbering
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grid_pads: Vec<GridPad>,
+    /// Only used by the default device type: the endless encoder scrolling through the color palette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette_encoder: Option<RelativeEncoder>,
+    /// Only used by the Launchpad Pro device type: the palette index of the highlighted pad,
+    /// from 0 to 127 (45 by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight_color: Option<u8>,
+    /// Only used by the Launchpad Pro device type: light the app-selection and color-palette pads
+    /// with the nearest palette colors, which is more reliable over USB than RGB colors
+    #[serde(default)]
+    pub palette_lighting: bool,
+    /// Only used by the Launchpad Pro device type: stretch the colors of rendered images to the
+    /// whole range supported by the device, instead of clipping the brightest ones
+    #[serde(default)]
+    pub normalize_images: bool,
+    /// Only used by the Launchpad Pro device type: the colors rendered images get reduced to,
+    /// each pixel taking the nearest one, for a retro look (e.g. a 16-color palette)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_palette: Option<Vec<[u8; 3]>>,
+    /// Only used by the Launchpad Pro device type: the corner the pads and LEDs of the grid are
+    /// numbered from, the bottom-left one for genuine devices, but the top-left one for some clones
+    #[serde(default)]
+    pub origin: Origin,
+    /// Only used by the Launchpad Pro device type: the row or column of buttons around the grid
+    /// hosting the color palette, the bottom row by default
+    #[serde(default)]
+    pub color_palette_edge: Edge,
+    /// Only used by the Launchpad Pro and APC Mini device types: the order in which the pads of
+    /// the grid map to the indices selecting items (e.g. the tracks of a playlist), row by row by
+    /// default
+    #[serde(default)]
+    pub grid_layout: GridLayout,
+    /// Only used by the Launchpad Pro device type: the factor (between 0 and 1) applied to the
+    /// colors of the apps that are not selected, which all get full brightness if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_app_brightness: Option<f32>,
+    /// Only used by the Launchpad Pro device type: the factor (between 0 and 1) applied to every
+    /// color rendered on the grid, full brightness if unset. It can be changed from the web UI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<f32>,
+    /// Only used by the Launchpad Pro device type: the buttons around the grid triggering global actions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub global_actions: Vec<GlobalActionButton>,
+    /// Number of events the input and output ports can buffer (1024 by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<usize>,
+    /// Time to wait between two consecutive SysEx messages written to the device (none by
+    /// default), for devices dropping messages that arrive back-to-back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sysex_gap_ms: Option<u64>,
+    /// Custom SysEx messages (starting with 240 and ending with 247), identified by their name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub sysex_macros: HashMap<String, Vec<u8>>,
+    /// Names of the SysEx macros to send every time the device gets connected
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_connect: Vec<String>,
 }
 
-#[derive(Clone, Debug, Copy, Serialize, Deserialize)]
+/// (0, 0) being the top-left corner of the grid
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GridPad {
+    pub note: u8,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// An endless encoder sending the given controller number, with values relative to its last position
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RelativeEncoder {
+    pub control: u8,
+    #[serde(default)]
+    pub encoding: RelativeEncoding,
+}
+
+/// How a relative value gets encoded in the 7 bits of a controller value
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelativeEncoding {
+    /// 1 to 63 when turning clockwise, 127 (-1) down to 64 (-64) when turning counterclockwise
+    TwosComplement,
+    /// 1 to 63 when turning clockwise, 65 (-1) to 127 (-63) when turning counterclockwise
+    SignMagnitude,
+}
+
+impl Default for RelativeEncoding {
+    fn default() -> Self {
+        return RelativeEncoding::TwosComplement;
+    }
+}
+
+/// Corner of the grid holding the pad numbered 11
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    BottomLeft,
+    TopLeft,
+}
+
+impl Default for Origin {
+    fn default() -> Self {
+        return Origin::BottomLeft;
+    }
+}
+
+/// Row or column of buttons around the grid
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Default for Edge {
+    fn default() -> Self {
+        return Edge::Bottom;
+    }
+}
+
+/// Order in which the pads of the grid are numbered, starting from the bottom-left one
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridLayout {
+    /// From left to right, then from the bottom row to the top one
+    RowMajor,
+    /// From bottom to top, then from the left column to the right one
+    ColumnMajor,
+    /// Row by row from the bottom, going back and forth: left to right, then right to left, etc.
+    Snake,
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        return GridLayout::RowMajor;
+    }
+}
+
+/// A button sending the given controller number when pressed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GlobalActionButton {
+    pub control: u8,
+    pub action: GlobalAction,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     Default,
+    Launchkey,
     LaunchpadPro,
+    ApcMini,
 }
 
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
@@ -53,6 +206,23 @@ pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
         config.insert(device_id, DeviceConfig {
             name,
             device_type,
+            color_palette_row: None,
+            grid_pads: vec![],
+            palette_encoder: None,
+            highlight_color: None,
+            palette_lighting: false,
+            normalize_images: false,
+            image_palette: None,
+            origin: Origin::BottomLeft,
+            color_palette_edge: Edge::Bottom,
+            grid_layout: GridLayout::RowMajor,
+            inactive_app_brightness: None,
+            brightness: None,
+            global_actions: vec![],
+            buffer_size: None,
+            sysex_gap_ms: None,
+            sysex_macros: HashMap::new(),
+            on_connect: vec![],
         });
     }
 
@@ -60,7 +230,7 @@ pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
 }
 
 fn configure_type(name: &String) -> Result<DeviceType, Box<dyn std::error::Error>> {
-    let device_types = vec![DeviceType::Default, DeviceType::LaunchpadPro];
+    let device_types = vec![DeviceType::Default, DeviceType::Launchkey, DeviceType::LaunchpadPro, DeviceType::ApcMini];
     let serialized_device_types = device_types.as_slice().into_iter()
         .map(|t| format!("{:?}", t))
         .collect::<Vec<String>>();
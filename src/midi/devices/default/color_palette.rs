@@ -0,0 +1,205 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::devices::config::RelativeEncoding;
+use crate::midi::features::{R, ColorPalette};
+
+use super::device::DefaultFeatures;
+
+/// A generic device exposes its color palette as a row of 8 consecutive notes (or controllers):
+///
+/// ║8r║8r+1║8r+2║8r+3║8r+4║8r+5║8r+6║8r+7║
+/// ║ ↖0 ↖1   ↖2   ↖3   ↖4   ↖5   ↖6   ↖7
+///
+/// Most generic controllers light their pads when receiving a note-on event, using the velocity
+/// to pick the brightness (or the color), so we render each swatch that way.
+///
+/// Controllers with endless encoders can also scroll through the palette with one of them.
+impl ColorPalette for DefaultFeatures {
+    fn into_color_palette_index(&self, event: Event) -> R<Option<usize>> {
+        let first = self.get_color_palette_first_note();
+        return Ok(match MidiMessage::try_from(event) {
+            // note-down or controller on
+            // velocity or value: strictly positive (the key must be pressed)
+            Ok(MidiMessage::NoteOn { channel: 0, note: code, velocity: value })
+            | Ok(MidiMessage::ControlChange { channel: 0, control: code, value }) if value > 0 => {
+                let code = usize::from(code);
+                if code >= first && code < first + 8 {
+                    Some(code - first)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        });
+    }
+
+    fn into_palette_delta(&self, event: Event) -> R<Option<i8>> {
+        return Ok(match (&self.palette_encoder, MidiMessage::try_from(event)) {
+            // controller change, whose value is relative to the previous position of the encoder
+            (Some(encoder), Ok(MidiMessage::ControlChange { channel: 0, control, value })) if control == encoder.control => {
+                decode_relative_value(encoder.encoding, value)
+            },
+            _ => None,
+        });
+    }
+
+    fn from_color_palette_to_events(&self, colors: Vec<[u8; 3]>) -> R<Vec<Event>> {
+        let first = self.get_color_palette_first_note();
+        if colors.len() > 8 || first + colors.len() > 128 {
+            return Err(Box::new(Error::OutOfBoundIndexError));
+        }
+
+        return Ok(colors.iter().enumerate().map(|(index, color)| {
+            let note = (first + index) as u8;
+            // The brightest channel gives the velocity, scaled down to the [0; 127] range
+            let velocity = color.iter().max().map(|max| max / 2).unwrap_or(0);
+            Event::Midi([144, note, velocity, 0])
+        }).collect());
+    }
+}
+
+impl DefaultFeatures {
+    fn get_color_palette_first_note(&self) -> usize {
+        return usize::from(self.color_palette_row) * 8;
+    }
+}
+
+/// A value of zero means that the encoder did not move, which is not worth a delta
+fn decode_relative_value(encoding: RelativeEncoding, value: u8) -> Option<i8> {
+    let value = value & 0x7F;
+    let delta = match encoding {
+        RelativeEncoding::TwosComplement if value >= 64 => (i16::from(value) - 128) as i8,
+        RelativeEncoding::SignMagnitude if value >= 64 => -((value - 64) as i8),
+        _ => value as i8,
+    };
+    return if delta != 0 { Some(delta) } else { None };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midi::devices::config::RelativeEncoder;
+    use super::*;
+
+    fn get_features_with_encoding(encoding: RelativeEncoding) -> DefaultFeatures {
+        return DefaultFeatures {
+            palette_encoder: Some(RelativeEncoder { control: 70, encoding }),
+            ..DefaultFeatures::new()
+        };
+    }
+
+    #[test]
+    fn into_color_palette_index_given_incorrect_status_should_return_none() {
+        let features = DefaultFeatures::new();
+        let event = Event::Midi([128, 17, 10, 0]);
+        assert_eq!(None, features
+            .into_color_palette_index(event)
+            .expect("into_color_palette_index should not fail"));
+    }
+
+    #[test]
+    fn into_color_palette_index_given_low_velocity_should_return_none() {
+        let features = DefaultFeatures::new();
+        let event = Event::Midi([144, 17, 0, 0]);
+        assert_eq!(None, features
+            .into_color_palette_index(event)
+            .expect("into_color_palette_index should not fail"));
+    }
+
+    #[test]
+    fn into_color_palette_index_given_value_outside_of_the_row_should_return_none() {
+        let features = DefaultFeatures::new();
+        for data1 in [0, 8, 15, 24, 36, 127] {
+            assert_eq!(None, features
+                .into_color_palette_index(Event::Midi([144, data1, 10, 0]))
+                .expect("into_color_palette_index should not fail"));
+        }
+    }
+
+    #[test]
+    fn into_color_palette_index_given_notes_or_controllers_should_return_correct_value() {
+        let features = DefaultFeatures::new();
+        let actual_output = (16..24)
+            .flat_map(|code| vec![
+                features.into_color_palette_index(Event::Midi([144, code, 10, 0])),
+                features.into_color_palette_index(Event::Midi([176, code, 10, 0])),
+            ])
+            .map(|index| index.expect("into_color_palette_index should not fail"))
+            .collect::<Vec<Option<usize>>>();
+
+        let expected_output = (0..8)
+            .flat_map(|index| vec![Some(index), Some(index)])
+            .collect::<Vec<Option<usize>>>();
+
+        assert_eq!(expected_output, actual_output);
+    }
+
+    #[test]
+    fn into_color_palette_index_given_configured_row_should_return_correct_value() {
+        let features = DefaultFeatures { color_palette_row: 5, ..DefaultFeatures::new() };
+        assert_eq!(None, features.into_color_palette_index(Event::Midi([144, 16, 10, 0])).unwrap());
+        assert_eq!(Some(0), features.into_color_palette_index(Event::Midi([144, 40, 10, 0])).unwrap());
+        assert_eq!(Some(7), features.into_color_palette_index(Event::Midi([176, 47, 10, 0])).unwrap());
+        assert_eq!(None, features.into_color_palette_index(Event::Midi([144, 48, 10, 0])).unwrap());
+    }
+
+    #[test]
+    fn from_color_palette_to_events_when_too_many_colors_then_return_out_of_bound_error() {
+        let features = DefaultFeatures::new();
+        let actual_events = features.from_color_palette_to_events(vec![[0, 0, 0]; 9]);
+        assert!(actual_events.is_err());
+    }
+
+    #[test]
+    fn from_color_palette_to_events_when_valid_palette_then_return_one_note_on_per_swatch() {
+        let features = DefaultFeatures::new();
+        let actual_events = features.from_color_palette_to_events(vec![
+            [0, 0, 0],
+            [0, 0, 255],
+            [128, 64, 0],
+        ]).unwrap();
+
+        assert_eq!(actual_events, vec![
+            Event::Midi([144, 16, 0, 0]),
+            Event::Midi([144, 17, 127, 0]),
+            Event::Midi([144, 18, 64, 0]),
+        ]);
+    }
+
+    #[test]
+    fn into_palette_delta_when_twos_complement_then_decode_increments_and_decrements() {
+        let features = get_features_with_encoding(RelativeEncoding::TwosComplement);
+        let actual_deltas = [1, 3, 63, 127, 125, 64]
+            .iter()
+            .map(|value| features.into_palette_delta(Event::Midi([176, 70, *value, 0])).unwrap())
+            .collect::<Vec<Option<i8>>>();
+
+        assert_eq!(actual_deltas, vec![Some(1), Some(3), Some(63), Some(-1), Some(-3), Some(-64)]);
+    }
+
+    #[test]
+    fn into_palette_delta_when_sign_magnitude_then_decode_increments_and_decrements() {
+        let features = get_features_with_encoding(RelativeEncoding::SignMagnitude);
+        let actual_deltas = [1, 3, 63, 65, 67, 127]
+            .iter()
+            .map(|value| features.into_palette_delta(Event::Midi([176, 70, *value, 0])).unwrap())
+            .collect::<Vec<Option<i8>>>();
+
+        assert_eq!(actual_deltas, vec![Some(1), Some(3), Some(63), Some(-1), Some(-3), Some(-63)]);
+    }
+
+    #[test]
+    fn into_palette_delta_when_encoder_did_not_move_then_return_none() {
+        let features = get_features_with_encoding(RelativeEncoding::SignMagnitude);
+        assert_eq!(None, features.into_palette_delta(Event::Midi([176, 70, 0, 0])).unwrap());
+        assert_eq!(None, features.into_palette_delta(Event::Midi([176, 70, 64, 0])).unwrap());
+    }
+
+    #[test]
+    fn into_palette_delta_when_another_controller_or_no_encoder_then_return_none() {
+        let features = get_features_with_encoding(RelativeEncoding::TwosComplement);
+        assert_eq!(None, features.into_palette_delta(Event::Midi([176, 71, 1, 0])).unwrap());
+        assert_eq!(None, features.into_palette_delta(Event::Midi([144, 70, 1, 0])).unwrap());
+        assert_eq!(None, DefaultFeatures::new().into_palette_delta(Event::Midi([176, 70, 1, 0])).unwrap());
+    }
+}
@@ -0,0 +1,41 @@
+use std::convert::From;
+
+use crate::midi::features::Features;
+use crate::midi::devices::config::{DeviceConfig, GridPad, RelativeEncoder};
+
+/// The color palette occupies the notes from 16 to 23 by default, so that it does not overlap with
+/// the C-1/B-1 octave used to select apps, nor with the notes from C2 and upwards used to select
+/// indices.
+pub const DEFAULT_COLOR_PALETTE_ROW: u8 = 2;
+
+pub struct DefaultFeatures {
+    /// Rows are groups of 8 consecutive notes (or controllers): row 0 spans 0..8, row 1 spans 8..16…
+    pub color_palette_row: u8,
+    /// Pads of the grid, if the device is configured as a grid controller
+    pub grid_pads: Vec<GridPad>,
+    /// Endless encoder scrolling through the color palette, if any
+    pub palette_encoder: Option<RelativeEncoder>,
+}
+
+impl Features for DefaultFeatures {}
+
+impl DefaultFeatures {
+    pub fn new() -> DefaultFeatures {
+        DefaultFeatures {
+            color_palette_row: DEFAULT_COLOR_PALETTE_ROW,
+            grid_pads: vec![],
+            palette_encoder: None,
+        }
+    }
+}
+
+impl From<&DeviceConfig> for DefaultFeatures {
+    fn from(config: &DeviceConfig) -> DefaultFeatures {
+        DefaultFeatures {
+            color_palette_row: config.color_palette_row.unwrap_or(DEFAULT_COLOR_PALETTE_ROW),
+            grid_pads: config.grid_pads.clone(),
+            palette_encoder: config.palette_encoder.clone(),
+            ..DefaultFeatures::new()
+        }
+    }
+}
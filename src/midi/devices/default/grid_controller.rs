@@ -0,0 +1,92 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, GridController, UnsupportedFeatureError};
+
+use super::device::DefaultFeatures;
+
+/// A generic device only behaves as a grid controller when the note of each of its pads is
+/// configured: the size of the grid is then deduced from the pads’ coordinates.
+impl GridController for DefaultFeatures {
+    fn get_grid_size(&self) -> R<(usize, usize)> {
+        let width = self.grid_pads.iter().map(|pad| pad.x + 1).max();
+        let height = self.grid_pads.iter().map(|pad| pad.y + 1).max();
+        return match (width, height) {
+            (Some(width), Some(height)) => Ok((width, height)),
+            _ => Err(Box::new(UnsupportedFeatureError::from("grid-controller:get_grid_size"))),
+        };
+    }
+
+    fn into_coordinates(&self, event: Event) -> R<Option<(usize, usize)>> {
+        if self.grid_pads.is_empty() {
+            return Err(Box::new(UnsupportedFeatureError::from("grid-controller:into_coordinates")));
+        }
+
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" (144) with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => {
+                self.grid_pads.iter()
+                    .find(|pad| pad.note == note)
+                    .map(|pad| (pad.x, pad.y))
+            },
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midi::devices::config::GridPad;
+    use super::*;
+
+    /// A 2x2 grid whose notes go clockwise from the top-left pad
+    fn get_features() -> DefaultFeatures {
+        return DefaultFeatures {
+            grid_pads: vec![
+                GridPad { note: 60, x: 0, y: 0 },
+                GridPad { note: 62, x: 1, y: 0 },
+                GridPad { note: 64, x: 1, y: 1 },
+                GridPad { note: 67, x: 0, y: 1 },
+            ],
+            ..DefaultFeatures::new()
+        };
+    }
+
+    #[test]
+    fn get_grid_size_when_pads_are_configured_then_return_their_bounds() {
+        assert_eq!(get_features().get_grid_size().expect("get_grid_size should not fail"), (2, 2));
+    }
+
+    #[test]
+    fn get_grid_size_when_no_pads_are_configured_then_fail() {
+        let err = DefaultFeatures::new().get_grid_size().unwrap_err();
+        assert!(err.is::<UnsupportedFeatureError>());
+    }
+
+    #[test]
+    fn into_coordinates_should_return_the_configured_coordinates() {
+        let features = get_features();
+        for (note, coordinates) in [(60, (0, 0)), (62, (1, 0)), (64, (1, 1)), (67, (0, 1))] {
+            let event = Event::Midi([144, note, 100, 0]);
+            assert_eq!(Some(coordinates), features.into_coordinates(event).expect("into_coordinates should not fail"));
+        }
+    }
+
+    #[test]
+    fn into_coordinates_given_unmapped_note_should_return_none() {
+        let event = Event::Midi([144, 61, 100, 0]);
+        assert_eq!(None, get_features().into_coordinates(event).expect("into_coordinates should not fail"));
+    }
+
+    #[test]
+    fn into_coordinates_given_low_velocity_should_return_none() {
+        let event = Event::Midi([144, 60, 0, 0]);
+        assert_eq!(None, get_features().into_coordinates(event).expect("into_coordinates should not fail"));
+    }
+
+    #[test]
+    fn into_coordinates_when_no_pads_are_configured_then_fail() {
+        let err = DefaultFeatures::new().into_coordinates(Event::Midi([144, 60, 100, 0])).unwrap_err();
+        assert!(err.is::<UnsupportedFeatureError>());
+    }
+}
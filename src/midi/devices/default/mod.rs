@@ -1,9 +1,6 @@
-use crate::midi::features::Features;
+mod device;
 
-pub struct DefaultFeatures {}
-impl Features for DefaultFeatures {}
-impl DefaultFeatures {
-    pub fn new() -> DefaultFeatures {
-        DefaultFeatures {}
-    }
-}
+mod color_palette;
+mod grid_controller;
+
+pub use device::DefaultFeatures;
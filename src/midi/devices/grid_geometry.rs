@@ -0,0 +1,216 @@
+use super::config::{GridLayout, Origin};
+
+/// Conversions between the three ways of pointing at a pad of a grid, so that every feature of
+/// a device agrees on where each pad is:
+/// - coordinates (x, y) are the ones of images, (0, 0) being the top-left pad;
+/// - indices start from the bottom-left pad whatever the origin, and follow the layout;
+/// - notes are numbered like on Novation grids, with the row in the tens and the column in the
+///   units, both starting from 1 at the origin (e.g. 11 for the corner pad, 88 for the opposite one).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridGeometry {
+    pub width: usize,
+    pub height: usize,
+    pub origin: Origin,
+    pub layout: GridLayout,
+}
+
+impl GridGeometry {
+    pub fn index_to_xy(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.width * self.height {
+            return None;
+        }
+
+        // row 0 being the bottom one
+        let (x, row) = match self.layout {
+            GridLayout::RowMajor => (index % self.width, index / self.width),
+            GridLayout::ColumnMajor => (index / self.height, index % self.height),
+            GridLayout::Snake if (index / self.width) % 2 == 1 => (self.width - 1 - index % self.width, index / self.width),
+            GridLayout::Snake => (index % self.width, index / self.width),
+        };
+        return Some((x, self.height - 1 - row));
+    }
+
+    pub fn xy_to_index(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let row = self.height - 1 - y;
+        return Some(match self.layout {
+            GridLayout::RowMajor => row * self.width + x,
+            GridLayout::ColumnMajor => x * self.height + row,
+            GridLayout::Snake if row % 2 == 1 => row * self.width + (self.width - 1 - x),
+            GridLayout::Snake => row * self.width + x,
+        });
+    }
+
+    /// Notes of the buttons around the grid (e.g. 19 or 91) have no coordinates
+    pub fn note_to_xy(&self, note: u8) -> Option<(usize, usize)> {
+        let pad_row = usize::from(note / 10);
+        let pad_column = usize::from(note % 10);
+        if pad_row < 1 || pad_row > self.height || pad_column < 1 || pad_column > self.width {
+            return None;
+        }
+
+        let y = match self.origin {
+            Origin::BottomLeft => self.height - pad_row,
+            Origin::TopLeft => pad_row - 1,
+        };
+        return Some((pad_column - 1, y));
+    }
+
+    pub fn xy_to_note(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let pad_row = match self.origin {
+            Origin::BottomLeft => self.height - y,
+            Origin::TopLeft => y + 1,
+        };
+        return u8::try_from(pad_row * 10 + x + 1).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ORIGINS: [Origin; 2] = [Origin::BottomLeft, Origin::TopLeft];
+    const LAYOUTS: [GridLayout; 3] = [GridLayout::RowMajor, GridLayout::ColumnMajor, GridLayout::Snake];
+
+    fn get_geometry(origin: Origin, layout: GridLayout) -> GridGeometry {
+        return GridGeometry { width: 8, height: 8, origin, layout };
+    }
+
+    /// Indices of the pads, from the top-left one to the bottom-right one
+    fn get_indices(geometry: GridGeometry) -> Vec<usize> {
+        return (0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+            .map(|(x, y)| geometry.xy_to_index(x, y).unwrap())
+            .collect();
+    }
+
+    #[test]
+    fn index_to_xy_and_xy_to_index_should_be_inverses_whatever_the_origin_and_layout() {
+        for origin in ORIGINS {
+            for layout in LAYOUTS {
+                let geometry = get_geometry(origin, layout);
+                for index in 0..64 {
+                    let (x, y) = geometry.index_to_xy(index).unwrap();
+                    assert_eq!(geometry.xy_to_index(x, y), Some(index), "{:?} {:?} {}", origin, layout, index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn note_to_xy_and_xy_to_note_should_be_inverses_whatever_the_origin() {
+        for origin in ORIGINS {
+            let geometry = get_geometry(origin, GridLayout::RowMajor);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let note = geometry.xy_to_note(x, y).unwrap();
+                    assert_eq!(geometry.note_to_xy(note), Some((x, y)), "{:?} {} {}", origin, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn index_to_xy_and_xy_to_index_given_out_of_bound_values_should_return_none() {
+        let geometry = get_geometry(Origin::BottomLeft, GridLayout::RowMajor);
+        assert_eq!(geometry.index_to_xy(64), None);
+        assert_eq!(geometry.xy_to_index(8, 0), None);
+        assert_eq!(geometry.xy_to_index(0, 8), None);
+        assert_eq!(geometry.xy_to_note(8, 0), None);
+    }
+
+    #[test]
+    fn note_to_xy_given_the_notes_of_the_side_buttons_should_return_none() {
+        for origin in ORIGINS {
+            let geometry = get_geometry(origin, GridLayout::RowMajor);
+            for note in [0, 1, 8, 9, 10, 19, 80, 89, 90, 91, 98, 99, 127] {
+                assert_eq!(geometry.note_to_xy(note), None, "{:?} {}", origin, note);
+            }
+        }
+    }
+
+    #[test]
+    fn note_to_xy_given_bottom_left_origin_should_put_11_at_the_bottom_left_corner() {
+        let geometry = get_geometry(Origin::BottomLeft, GridLayout::RowMajor);
+        assert_eq!(geometry.note_to_xy(11), Some((0, 7)));
+        assert_eq!(geometry.note_to_xy(18), Some((7, 7)));
+        assert_eq!(geometry.note_to_xy(81), Some((0, 0)));
+        assert_eq!(geometry.note_to_xy(88), Some((7, 0)));
+    }
+
+    #[test]
+    fn note_to_xy_given_top_left_origin_should_put_11_at_the_top_left_corner() {
+        let geometry = get_geometry(Origin::TopLeft, GridLayout::RowMajor);
+        assert_eq!(geometry.note_to_xy(11), Some((0, 0)));
+        assert_eq!(geometry.note_to_xy(18), Some((7, 0)));
+        assert_eq!(geometry.note_to_xy(81), Some((0, 7)));
+        assert_eq!(geometry.note_to_xy(88), Some((7, 7)));
+    }
+
+    #[test]
+    fn xy_to_index_given_row_major_layout_should_number_pads_from_the_bottom_row() {
+        assert_eq!(get_indices(get_geometry(Origin::BottomLeft, GridLayout::RowMajor)), vec![
+            56, 57, 58, 59, 60, 61, 62, 63,
+            48, 49, 50, 51, 52, 53, 54, 55,
+            40, 41, 42, 43, 44, 45, 46, 47,
+            32, 33, 34, 35, 36, 37, 38, 39,
+            24, 25, 26, 27, 28, 29, 30, 31,
+            16, 17, 18, 19, 20, 21, 22, 23,
+            8, 9, 10, 11, 12, 13, 14, 15,
+            0, 1, 2, 3, 4, 5, 6, 7,
+        ]);
+    }
+
+    #[test]
+    fn xy_to_index_given_column_major_layout_should_number_pads_from_the_left_column() {
+        assert_eq!(get_indices(get_geometry(Origin::BottomLeft, GridLayout::ColumnMajor)), vec![
+            7, 15, 23, 31, 39, 47, 55, 63,
+            6, 14, 22, 30, 38, 46, 54, 62,
+            5, 13, 21, 29, 37, 45, 53, 61,
+            4, 12, 20, 28, 36, 44, 52, 60,
+            3, 11, 19, 27, 35, 43, 51, 59,
+            2, 10, 18, 26, 34, 42, 50, 58,
+            1, 9, 17, 25, 33, 41, 49, 57,
+            0, 8, 16, 24, 32, 40, 48, 56,
+        ]);
+    }
+
+    #[test]
+    fn xy_to_index_given_snake_layout_should_go_back_and_forth_from_the_bottom_row() {
+        assert_eq!(get_indices(get_geometry(Origin::BottomLeft, GridLayout::Snake)), vec![
+            63, 62, 61, 60, 59, 58, 57, 56,
+            48, 49, 50, 51, 52, 53, 54, 55,
+            47, 46, 45, 44, 43, 42, 41, 40,
+            32, 33, 34, 35, 36, 37, 38, 39,
+            31, 30, 29, 28, 27, 26, 25, 24,
+            16, 17, 18, 19, 20, 21, 22, 23,
+            15, 14, 13, 12, 11, 10, 9, 8,
+            0, 1, 2, 3, 4, 5, 6, 7,
+        ]);
+    }
+
+    #[test]
+    fn xy_to_index_should_not_depend_on_the_origin() {
+        for layout in LAYOUTS {
+            assert_eq!(
+                get_indices(get_geometry(Origin::TopLeft, layout)),
+                get_indices(get_geometry(Origin::BottomLeft, layout)),
+            );
+        }
+    }
+
+    #[test]
+    fn index_to_xy_given_a_grid_that_is_not_square_should_stay_within_bounds() {
+        let geometry = GridGeometry { width: 8, height: 2, origin: Origin::BottomLeft, layout: GridLayout::RowMajor };
+        assert_eq!(geometry.index_to_xy(0), Some((0, 1)));
+        assert_eq!(geometry.index_to_xy(15), Some((7, 0)));
+        assert_eq!(geometry.index_to_xy(16), None);
+        assert_eq!(geometry.xy_to_index(7, 0), Some(15));
+    }
+}
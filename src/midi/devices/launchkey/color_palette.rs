@@ -0,0 +1,102 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::features::{R, ColorPalette};
+
+use super::device::LaunchkeyFeatures;
+
+/// On the Launchkey Mini, each knob selects the color lit on the top pad right below it:
+///  ◯   ◯   ◯   ◯   ◯   ◯   ◯   ◯
+/// ↖0  ↖1  ↖2  ↖3  ↖4  ↖5  ↖6  ↖7
+/// ╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗
+/// ║0║║1║║2║║3║║4║║5║║6║║7║
+/// ╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝
+/// ╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗
+/// ║ ║║ ║║ ║║ ║║ ║║ ║║ ║║ ║
+/// ╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝
+impl ColorPalette for LaunchkeyFeatures {
+    fn into_color_palette_index(&self, event: Event) -> R<Option<usize>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // 176: controller on
+            // control: between 21 and 28
+            // value: strictly positive (the knob must be turned)
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if value > 0 && control >= 21 && control <= 28 => {
+                Some(usize::from(control - 21))
+            },
+            _ => None,
+        });
+    }
+
+    fn from_color_palette(&self, colors: Vec<[u8; 3]>) -> R<Event> {
+        if colors.len() > 8 {
+            return Err(Box::new(Error::OutOfBoundIndexError));
+        }
+
+        // Prefix for "RGB lighting" a set of pads
+        let mut bytes = vec![240, 0, 32, 41, 2, 15, 3];
+
+        for index in 0..colors.len() {
+            let led = 96 + index as u8;
+            bytes.append(&mut vec![
+                // 3: the color is given as RGB values
+                3,
+                led,
+                // The Launchkey Mini only accepts 7-bit color values
+                colors[index][0] / 2,
+                colors[index][1] / 2,
+                colors[index][2] / 2,
+            ]);
+        }
+        bytes.push(247);
+
+        return Ok(Event::SysEx(bytes));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_color_palette_index_given_incorrect_status_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        let event = Event::Midi([144, 21, 10, 0]);
+        assert_eq!(None, features.into_color_palette_index(event).expect("into_color_palette_index should not fail"));
+    }
+
+    #[test]
+    fn into_color_palette_index_should_return_correct_value() {
+        let features = LaunchkeyFeatures::new();
+        let actual_output = (20..30)
+            .map(|code| features
+                .into_color_palette_index(Event::Midi([176, code, 10, 0]))
+                .expect("into_color_palette_index should not fail"))
+            .collect::<Vec<Option<usize>>>();
+
+        assert_eq!(actual_output, vec![
+            None, Some(0), Some(1), Some(2), Some(3), Some(4), Some(5), Some(6), Some(7), None,
+        ]);
+    }
+
+    #[test]
+    fn from_color_palette_when_too_many_colors_then_return_out_of_bound_error() {
+        let features = LaunchkeyFeatures::new();
+        assert!(features.from_color_palette(vec![[0, 0, 0]; 9]).is_err());
+    }
+
+    #[test]
+    fn from_color_palette_when_valid_palette_then_light_top_pads() {
+        let features = LaunchkeyFeatures::new();
+        let actual_event = features.from_color_palette(vec![
+            [12, 24, 48],
+            [255, 0, 128],
+        ]).unwrap();
+
+        assert_eq!(actual_event, Event::SysEx(vec![
+            240, 0, 32, 41, 2, 15, 3,
+            3, 96, 6, 12, 24,
+            3, 97, 127, 0, 64,
+            247,
+        ]));
+    }
+}
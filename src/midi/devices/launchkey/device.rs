@@ -0,0 +1,33 @@
+use crate::midi::features::Features;
+
+/// The Launchkey Mini provides two rows of 8 pads, and 8 knobs above them.
+///
+/// In its "DAW" mode, the top row of pads sends notes from 96 to 103, the bottom row sends notes
+/// from 112 to 119, and the knobs send controller changes from 21 to 28.
+pub struct LaunchkeyFeatures {}
+impl LaunchkeyFeatures {
+    pub fn new() -> LaunchkeyFeatures {
+        LaunchkeyFeatures {}
+    }
+
+    /// Return the position of the pad (the x-coordinate first) if the note corresponds to a pad,
+    /// with (0, 0) being the top-left pad.
+    pub(super) fn into_pad(&self, note: u8) -> Option<(usize, usize)> {
+        return match note {
+            96..=103 => Some((usize::from(note - 96), 0)),
+            112..=119 => Some((usize::from(note - 112), 1)),
+            _ => None,
+        };
+    }
+
+    /// Return the note of the pad at the given position, with (0, 0) being the top-left pad.
+    pub(super) fn from_pad(&self, x: usize, y: usize) -> Option<u8> {
+        return match (x, y) {
+            (x, 0) if x < 8 => Some(96 + x as u8),
+            (x, 1) if x < 8 => Some(112 + x as u8),
+            _ => None,
+        };
+    }
+}
+
+impl Features for LaunchkeyFeatures {}
@@ -0,0 +1,63 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, GridController};
+
+use super::device::LaunchkeyFeatures;
+
+impl GridController for LaunchkeyFeatures {
+    fn get_grid_size(&self) -> R<(usize, usize)> {
+        return Ok((8, 2));
+    }
+
+    fn into_coordinates(&self, event: Event) -> R<Option<(usize, usize)>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" (144) with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => self.into_pad(note),
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_coordinates_given_incorrect_status_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        let event = Event::Midi([128, 96, 10, 0]);
+        assert_eq!(None, features.into_coordinates(event).expect("into_coordinates should not fail"));
+    }
+
+    #[test]
+    fn into_coordinates_given_low_velocity_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        let event = Event::Midi([144, 96, 0, 0]);
+        assert_eq!(None, features.into_coordinates(event).expect("into_coordinates should not fail"));
+    }
+
+    #[test]
+    fn into_coordinates_should_return_correct_value() {
+        let features = LaunchkeyFeatures::new();
+        let actual_output = vec![
+            096, 097, 098, 099, 100, 101, 102, 103,
+            112, 113, 114, 115, 116, 117, 118, 119,
+        ]
+            .iter()
+            .map(|code| features
+                .into_coordinates(Event::Midi([144, *code, 10, 0]))
+                .expect("into_coordinates should not fail"))
+            .collect::<Vec<Option<(usize, usize)>>>();
+
+        let expected_output = vec![
+            (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0),
+            (0, 1), (1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1),
+        ]
+            .iter()
+            .map(|coordinates| Some(*coordinates))
+            .collect::<Vec<Option<(usize, usize)>>>();
+
+        assert_eq!(expected_output, actual_output);
+    }
+}
@@ -0,0 +1,119 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::devices::config::{GridLayout, Origin};
+use crate::midi::devices::grid_geometry::GridGeometry;
+use crate::midi::features::{R, IndexSelector};
+
+use super::device::LaunchkeyFeatures;
+
+/// Only used to convert indices into coordinates: the notes of the pads are not numbered by rows of 10
+const GEOMETRY: GridGeometry = GridGeometry { width: 8, height: 2, origin: Origin::BottomLeft, layout: GridLayout::RowMajor };
+
+/// Like on the Launchpad Pro, indices start from the bottom-left pad:
+/// ╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗
+/// ║8║║9║║…║║ ║║ ║║ ║║ ║║F║
+/// ╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝
+/// ╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗╔═╗
+/// ║0║║1║║2║║…║║ ║║ ║║ ║║7║
+/// ╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝╚═╝
+impl IndexSelector for LaunchkeyFeatures {
+    fn into_index(&self, event: Event) -> R<Option<usize>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // event must be a "note down" with a strictly positive velocity
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => {
+                self.into_pad(note).and_then(|(x, y)| GEOMETRY.xy_to_index(x, y))
+            },
+            _ => None,
+        });
+    }
+
+    fn from_index_to_highlight(&self, index: usize) -> R<Event> {
+        let note = GEOMETRY.index_to_xy(index).and_then(|(x, y)| self.from_pad(x, y));
+        return match note {
+            // 145: note-down on the second channel makes the pad flash,
+            // using the velocity as an index in the device’s color palette.
+            Some(note) => Ok(Event::Midi([145, note, 45, 0])),
+            None => Err(Box::new(Error::OutOfBoundIndexError)),
+        };
+    }
+
+    fn from_index_to_coordinates(&self, index: usize) -> R<Option<(usize, usize)>> {
+        return Ok(GEOMETRY.index_to_xy(index));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_index_given_incorrect_status_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        let event = Event::Midi([128, 112, 10, 0]);
+        assert_eq!(None, features.into_index(event).expect("into_index should not fail"));
+    }
+
+    #[test]
+    fn into_index_given_low_velocity_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        let event = Event::Midi([144, 112, 0, 0]);
+        assert_eq!(None, features.into_index(event).expect("into_index should not fail"));
+    }
+
+    #[test]
+    fn into_index_given_out_of_grid_value_should_return_none() {
+        let features = LaunchkeyFeatures::new();
+        for code in [0, 36, 95, 104, 111, 120, 127] {
+            let event = Event::Midi([144, code, 10, 0]);
+            assert_eq!(None, features.into_index(event).expect("into_index should not fail"));
+        }
+    }
+
+    #[test]
+    fn into_index_should_return_correct_value() {
+        let features = LaunchkeyFeatures::new();
+        let actual_output = vec![
+            096, 097, 098, 099, 100, 101, 102, 103,
+            112, 113, 114, 115, 116, 117, 118, 119,
+        ]
+            .iter()
+            .map(|code| features
+                .into_index(Event::Midi([144, *code, 10, 0]))
+                .expect("into_index should not fail"))
+            .collect::<Vec<Option<usize>>>();
+
+        let expected_output = vec![
+            08, 09, 10, 11, 12, 13, 14, 15,
+            00, 01, 02, 03, 04, 05, 06, 07,
+        ]
+            .iter()
+            .map(|index| Some(*index))
+            .collect::<Vec<Option<usize>>>();
+
+        assert_eq!(expected_output, actual_output);
+    }
+
+    #[test]
+    fn into_released_index_should_return_the_index_of_the_released_pad() {
+        let features = LaunchkeyFeatures::new();
+        assert_eq!(Some(0), features.into_released_index(Event::Midi([128, 112, 0, 0])).unwrap());
+        assert_eq!(Some(15), features.into_released_index(Event::Midi([144, 103, 0, 0])).unwrap());
+        assert_eq!(None, features.into_released_index(Event::Midi([144, 103, 10, 0])).unwrap());
+    }
+
+    #[test]
+    fn from_index_to_highlight_should_flash_the_corresponding_pad() {
+        let features = LaunchkeyFeatures::new();
+        assert_eq!(features.from_index_to_highlight(0).unwrap(), Event::Midi([145, 112, 45, 0]));
+        assert_eq!(features.from_index_to_highlight(7).unwrap(), Event::Midi([145, 119, 45, 0]));
+        assert_eq!(features.from_index_to_highlight(8).unwrap(), Event::Midi([145, 96, 45, 0]));
+        assert_eq!(features.from_index_to_highlight(15).unwrap(), Event::Midi([145, 103, 45, 0]));
+    }
+
+    #[test]
+    fn from_index_to_highlight_given_out_of_bound_index_should_return_error() {
+        let features = LaunchkeyFeatures::new();
+        assert!(features.from_index_to_highlight(16).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+mod device;
+
+mod color_palette;
+mod grid_controller;
+mod index_selector;
+
+pub use device::LaunchkeyFeatures;
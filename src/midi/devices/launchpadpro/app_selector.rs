@@ -1,4 +1,6 @@
-use crate::midi::{Error, Event};
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
 use crate::midi::features::{R, AppSelector};
 
 use super::device::LaunchpadProFeatures;
@@ -27,15 +29,15 @@ use super::device::LaunchpadProFeatures;
 
 impl AppSelector for LaunchpadProFeatures {
     fn into_app_index(&self, event: Event) ->  R<Option<usize>> {
-        return Ok(match event {
+        return Ok(match MidiMessage::try_from(event) {
             // event must be a "note down" with a strictly positive velocity
             // 176: controller on
-            // data1: 19/29/../89
-            // data2: strictly positive (the key must be pressed)
-            Event::Midi([176, data1, data2, _]) if data2 > 0 => {
+            // control: 19/29/../89
+            // value: strictly positive (the key must be pressed)
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if value > 0 => {
                 // the device provides a 10x10 grid if you count the buttons on the sides
-                let row = data1 / 10;
-                let column  = data1 % 10;
+                let row = control / 10;
+                let column  = control % 10;
 
                 if row >= 1 && row <= 8 && column == 9 {
                     Some(8 - row).map(|index| index.into())
@@ -47,28 +49,29 @@ impl AppSelector for LaunchpadProFeatures {
         });
     }
 
-    fn from_app_colors(&self, app_colors: Vec<[u8; 3]>) -> R<Event> {
+    fn from_app_colors(&self, app_colors: Vec<[u8; 3]>, selected_app: Option<usize>) -> R<Event> {
         if app_colors.len() > 8 {
             return Err(Box::new(Error::OutOfBoundIndexError));
         }
 
-        let mut bytes = vec![240, 0, 32, 41, 2, 16, 11];
-
-        for index in 0..app_colors.len() {
-            let led = (89 - 10 * index) as u8;
-            bytes.append(&mut vec![
-                led,
-                app_colors[index][0] / 4,
-                app_colors[index][1] / 4,
-                app_colors[index][2] / 4,
-            ]);
-        }
-        bytes.push(247);
+        let leds = app_colors.into_iter().enumerate()
+            .map(|(index, color)| match (self.inactive_app_brightness, selected_app) {
+                (Some(brightness), Some(selected_app)) if index != selected_app => (index, dim(color, brightness)),
+                _ => (index, color),
+            })
+            .map(|(index, color)| ((89 - 10 * index) as u8, color))
+            .collect();
 
-        return Ok(Event::SysEx(bytes));
+        return Ok(self.light_leds(leds));
     }
 }
 
+/// Multiply each channel of the color by the given brightness, kept within [0; 1]
+pub(super) fn dim(color: [u8; 3], brightness: f32) -> [u8; 3] {
+    let brightness = brightness.max(0.0).min(1.0);
+    return color.map(|channel| (channel as f32 * brightness) as u8);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -134,7 +137,7 @@ mod test {
         let features = super::super::LaunchpadProFeatures::new();
         // the Launchpad Pro won’t support nine applications, even if they all use black!
         let app_colors = vec![[0, 0, 0]; 9];
-        let actual_event = features.from_app_colors(app_colors);
+        let actual_event = features.from_app_colors(app_colors, None);
         assert!(actual_event.is_err());
     }
 
@@ -147,7 +150,7 @@ mod test {
             [8, 192, 56],
         ];
 
-        let actual_event = features.from_app_colors(app_colors).unwrap();
+        let actual_event = features.from_app_colors(app_colors, None).unwrap();
         assert_eq!(actual_event, Event::SysEx(vec![
                 // Prefix for "bluk lighting" a set of LEDs
                 240, 0, 32, 41, 2, 16, 11,
@@ -164,4 +167,30 @@ mod test {
                 247,
         ]));
     }
+
+    #[test]
+    fn from_app_colors_when_inactive_app_brightness_then_dim_all_apps_but_the_selected_one() {
+        let features = super::super::LaunchpadProFeatures { inactive_app_brightness: Some(0.5), ..super::super::LaunchpadProFeatures::new() };
+        let app_colors = vec![[200, 100, 40], [200, 100, 40], [200, 100, 40]];
+
+        let actual_event = features.from_app_colors(app_colors, Some(1)).unwrap();
+        assert_eq!(actual_event, Event::SysEx(vec![
+            240, 0, 32, 41, 2, 16, 11,
+            // inactive apps: half of the color, divided by four
+            89, 25, 12, 5,
+            // selected app: full brightness
+            79, 50, 25, 10,
+            69, 25, 12, 5,
+            247,
+        ]));
+    }
+
+    #[test]
+    fn from_app_colors_when_no_inactive_app_brightness_then_keep_full_brightness() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let app_colors = vec![[200, 100, 40], [200, 100, 40]];
+
+        let actual_event = features.from_app_colors(app_colors, Some(1)).unwrap();
+        assert_eq!(actual_event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 89, 50, 25, 10, 79, 50, 25, 10, 247]));
+    }
 }
@@ -1,9 +1,12 @@
-use crate::midi::{Error, Event};
+use std::convert::TryFrom;
+
+use crate::midi::{Error, Event, MidiMessage};
+use crate::midi::devices::config::Edge;
 use crate::midi::features::{R, ColorPalette};
 
 use super::device::LaunchpadProFeatures;
 
-/// On the Launchpad Pro, we’ll use the bottom row to select colors:
+/// On the Launchpad Pro, we’ll use the bottom row to select colors by default:
 ///    ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮
 ///    ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯
 /// ╭╮ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╭╮
@@ -25,19 +28,19 @@ use super::device::LaunchpadProFeatures;
 ///    ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮
 ///    ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯
 ///     ↖0 ↖1 ↖2 ↖3 ↖4 ↖5 ↖6 ↖7
+/// The palette can be moved to another edge of the grid, in which case colors are laid out from
+/// left to right (top row), or from top to bottom (left and right columns). Beware that the right
+/// column also selects apps, the top row playlists, and that the bottom-left button saves tracks:
+/// the router warns about the features getting in the way of the palette when it starts.
 impl ColorPalette for LaunchpadProFeatures {
     fn into_color_palette_index(&self, event: Event) -> R<Option<usize>> {
-        return Ok(match event {
+        return Ok(match MidiMessage::try_from(event) {
             // 176: controller on
-            // data1: between 1 and 8
-            // data2: strictly positive (the key must be pressed)
-            Event::Midi([176, data1, data2, _]) if data2 > 0 => {
-                if data1 >= 1 && data1 <= 8 {
-                    Some(data1 - 1).map(|index| index.into())
-                } else {
-                    None
-                }
-            },
+            // control: one of the buttons of the palette edge
+            // value: strictly positive (the key must be pressed)
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if value > 0 => (0..8)
+                .find(|index| get_palette_button(self.color_palette_edge, *index) == control)
+                .map(|index| index.into()),
             _ => None,
         });
     }
@@ -47,23 +50,24 @@ impl ColorPalette for LaunchpadProFeatures {
             return Err(Box::new(Error::OutOfBoundIndexError));
         }
 
-        let mut bytes = vec![240, 0, 32, 41, 2, 16, 11];
-
-        for index in 0..colors.len() {
-            let led = (index + 1) as u8;
-            bytes.append(&mut vec![
-                led,
-                colors[index][0] / 4,
-                colors[index][1] / 4,
-                colors[index][2] / 4,
-            ]);
-        }
-        bytes.push(247);
+        let leds = colors.into_iter().enumerate()
+            .map(|(index, color)| (get_palette_button(self.color_palette_edge, index as u8), color))
+            .collect();
 
-        return Ok(Event::SysEx(bytes));
+        return Ok(self.light_leds(leds));
     }
 }
 
+/// Number of the button (and LED) hosting the color at the given index of the palette
+fn get_palette_button(edge: Edge, index: u8) -> u8 {
+    return match edge {
+        Edge::Bottom => index + 1,
+        Edge::Top => index + 91,
+        Edge::Left => (8 - index) * 10,
+        Edge::Right => (8 - index) * 10 + 9,
+    };
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -165,4 +169,50 @@ mod test {
                 247,
         ]));
     }
+
+    #[test]
+    fn into_color_palette_index_when_palette_is_on_another_edge_then_map_its_buttons() {
+        let features = LaunchpadProFeatures { color_palette_edge: Edge::Top, ..LaunchpadProFeatures::new() };
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 91, 10, 0])).unwrap(), Some(0));
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 98, 10, 0])).unwrap(), Some(7));
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 1, 10, 0])).unwrap(), None);
+
+        let features = LaunchpadProFeatures { color_palette_edge: Edge::Left, ..LaunchpadProFeatures::new() };
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 80, 10, 0])).unwrap(), Some(0));
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 10, 10, 0])).unwrap(), Some(7));
+        assert_eq!(features.into_color_palette_index(Event::Midi([176, 19, 10, 0])).unwrap(), None);
+    }
+
+    #[test]
+    fn from_color_palette_when_palette_is_on_another_edge_then_light_its_leds() {
+        let features = LaunchpadProFeatures { color_palette_edge: Edge::Right, ..LaunchpadProFeatures::new() };
+        let color_palette = vec![[12, 24, 48], [96, 16, 36]];
+
+        let actual_event = features.from_color_palette(color_palette).unwrap();
+        assert_eq!(actual_event, Event::SysEx(vec![
+                240, 0, 32, 41, 2, 16, 11,
+                // The right column is numbered from top to bottom
+                89, 3, 6, 12,
+                79, 24, 4, 9,
+                247,
+        ]));
+    }
+
+    #[test]
+    fn from_color_palette_when_palette_lighting_then_send_palette_indices() {
+        let features = LaunchpadProFeatures { palette_lighting: true, ..LaunchpadProFeatures::new() };
+        let color_palette = vec![[0, 0, 0], [0, 0, 255], [255, 255, 0]];
+
+        let actual_event = features.from_color_palette(color_palette).unwrap();
+        assert_eq!(actual_event, Event::SysEx(vec![
+                // Prefix for lighting a set of LEDs with palette indices
+                240, 0, 32, 41, 2, 16, 10,
+                // Identifier and palette index of each LED
+                1, 0,
+                2, 45,
+                3, 13,
+                // Suffix for LaunchpadPro SysEx commands
+                247,
+        ]));
+    }
 }
@@ -0,0 +1,50 @@
+use std::error::Error as StdError;
+
+use crate::midi::Writer;
+use crate::midi::features::{R, ConnectionHandler};
+
+use super::device::LaunchpadProFeatures;
+
+/// Mode selection SysEx (33), switching back to the "Live" mode (the Launchpad Pro’s default one)
+/// from the "Programmer" layout the hub may have put it in, so that it works standalone again.
+/// The layout selection SysEx (44) would only switch between the layouts of the current mode.
+pub const LIVE_MODE_SYSEX: [u8; 9] = [240, 0, 32, 41, 2, 16, 33, 0, 247];
+
+impl ConnectionHandler for LaunchpadProFeatures {
+    fn on_disconnect(&self, port: &mut dyn Writer) -> R<()> {
+        return port.write_sysex(&LIVE_MODE_SYSEX).map_err(|err| {
+            let err: Box<dyn StdError + Send> = Box::new(err);
+            err
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midi::Error;
+    use super::*;
+
+    struct FakeWriter {
+        sysex: Vec<Vec<u8>>,
+    }
+
+    impl Writer for FakeWriter {
+        fn write_midi(&mut self, _event: &[u8; 4]) -> Result<(), Error> {
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+            self.sysex.push(event.to_vec());
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn on_disconnect_should_switch_back_to_the_live_mode() {
+        let features = LaunchpadProFeatures::new();
+        let mut writer = FakeWriter { sysex: vec![] };
+
+        features.on_disconnect(&mut writer).expect("the exit SysEx should be written");
+        assert_eq!(writer.sysex, vec![vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x10, 0x21, 0x00, 0xF7]]);
+    }
+}
@@ -1,7 +1,13 @@
 use std::convert::From;
+use std::sync::Mutex;
 
 use crate::midi::{Reader, Writer, Error};
 use crate::midi::features::Features;
+use crate::midi::devices::config::{DeviceConfig, Edge, GlobalActionButton, GridLayout, Origin};
+use crate::midi::devices::grid_geometry::GridGeometry;
+
+/// The index of the highlight color in the Launchpad Pro’s palette
+pub const DEFAULT_HIGHLIGHT_COLOR: u8 = 45;
 
 pub struct LaunchpadPro<C> where C: Reader + Writer {
     pub connection: C,
@@ -30,11 +36,83 @@ impl<C> Writer for LaunchpadPro<C> where C: Reader + Writer {
     }
 }
 
-pub struct LaunchpadProFeatures {}
+pub struct LaunchpadProFeatures {
+    /// Index, in the Launchpad Pro’s palette, of the color used to highlight the selected pad
+    pub highlight_color: u8,
+    /// Light the app-selection and color-palette pads with palette indices instead of RGB colors
+    pub palette_lighting: bool,
+    /// Stretch each color channel of rendered images to the [0; 64[ range
+    pub normalize_images: bool,
+    /// Colors the pixels of rendered images get reduced to, if any
+    pub image_palette: Option<Vec<[u8; 3]>>,
+    /// Corner the pads and LEDs of the grid are numbered from
+    pub origin: Origin,
+    /// Buttons around the grid hosting the color palette
+    pub color_palette_edge: Edge,
+    /// Order in which the pads of the grid map to indices
+    pub grid_layout: GridLayout,
+    /// Factor applied to the colors of the apps that are not selected
+    pub inactive_app_brightness: Option<f32>,
+    /// Buttons around the grid triggering global actions
+    pub global_actions: Vec<GlobalActionButton>,
+    /// Factor applied to every color rendered on the device, which can be changed at runtime
+    pub brightness: Mutex<f32>,
+}
+
 impl LaunchpadProFeatures {
     pub fn new() -> LaunchpadProFeatures {
-        LaunchpadProFeatures {}
+        LaunchpadProFeatures {
+            highlight_color: DEFAULT_HIGHLIGHT_COLOR,
+            palette_lighting: false,
+            normalize_images: false,
+            image_palette: None,
+            origin: Origin::BottomLeft,
+            color_palette_edge: Edge::Bottom,
+            grid_layout: GridLayout::RowMajor,
+            inactive_app_brightness: None,
+            global_actions: vec![],
+            brightness: Mutex::new(1.0),
+        }
+    }
+
+    pub fn get_brightness(&self) -> f32 {
+        return *self.brightness.lock().unwrap();
+    }
+
+    /// The central 8x8 grid, leaving the buttons on the sides out
+    pub(super) fn get_geometry(&self) -> GridGeometry {
+        return GridGeometry { width: 8, height: 8, origin: self.origin, layout: self.grid_layout };
+    }
+}
+
+impl From<&DeviceConfig> for LaunchpadProFeatures {
+    fn from(config: &DeviceConfig) -> LaunchpadProFeatures {
+        LaunchpadProFeatures {
+            highlight_color: get_highlight_color(config),
+            palette_lighting: config.palette_lighting,
+            normalize_images: config.normalize_images,
+            image_palette: config.image_palette.clone(),
+            origin: config.origin,
+            color_palette_edge: config.color_palette_edge,
+            grid_layout: config.grid_layout,
+            inactive_app_brightness: config.inactive_app_brightness,
+            global_actions: config.global_actions.clone(),
+            brightness: Mutex::new(config.brightness.unwrap_or(1.0).max(0.0).min(1.0)),
+            ..LaunchpadProFeatures::new()
+        }
     }
 }
 
+/// Palette indices are sent as SysEx data bytes, which cannot exceed 127
+fn get_highlight_color(config: &DeviceConfig) -> u8 {
+    return match config.highlight_color {
+        Some(color) if color > 127 => {
+            eprintln!("[launchpadpro] WARNING: highlight color {} is not a palette index, using {} instead", color, DEFAULT_HIGHLIGHT_COLOR);
+            DEFAULT_HIGHLIGHT_COLOR
+        },
+        Some(color) => color,
+        None => DEFAULT_HIGHLIGHT_COLOR,
+    };
+}
+
 impl Features for LaunchpadProFeatures {}
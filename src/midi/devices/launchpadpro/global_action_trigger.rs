@@ -0,0 +1,61 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, GlobalAction, GlobalActionTrigger};
+
+use super::device::LaunchpadProFeatures;
+
+/// On the Launchpad Pro, the buttons around the grid send controller events when pressed:
+/// 91 to 98 for the top row, 10 to 80 for the left column, 1 to 8 for the bottom row, and
+/// 19 to 89 for the right column (which is used to select apps).
+impl GlobalActionTrigger for LaunchpadProFeatures {
+    fn into_global_action(&self, event: Event) -> R<Option<GlobalAction>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // 176: controller on
+            // value: strictly positive (the button must be pressed)
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if value > 0 => self.global_actions.iter()
+                .find(|button| button.control == control)
+                .map(|button| button.action),
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::midi::devices::config::GlobalActionButton;
+    use super::*;
+
+    fn get_features() -> LaunchpadProFeatures {
+        return LaunchpadProFeatures {
+            global_actions: vec![
+                GlobalActionButton { control: 91, action: GlobalAction::Previous },
+                GlobalActionButton { control: 92, action: GlobalAction::Next },
+                GlobalActionButton { control: 10, action: GlobalAction::Panic },
+            ],
+            ..LaunchpadProFeatures::new()
+        };
+    }
+
+    #[test]
+    fn into_global_action_when_a_mapped_top_row_button_is_pressed_then_return_its_action() {
+        let features = get_features();
+        assert_eq!(features.into_global_action(Event::Midi([176, 92, 127, 0])).unwrap(), Some(GlobalAction::Next));
+        assert_eq!(features.into_global_action(Event::Midi([176, 91, 127, 0])).unwrap(), Some(GlobalAction::Previous));
+        assert_eq!(features.into_global_action(Event::Midi([176, 10, 127, 0])).unwrap(), Some(GlobalAction::Panic));
+    }
+
+    #[test]
+    fn into_global_action_when_a_mapped_button_is_released_then_return_none() {
+        let features = get_features();
+        assert_eq!(features.into_global_action(Event::Midi([176, 92, 0, 0])).unwrap(), None);
+    }
+
+    #[test]
+    fn into_global_action_when_the_button_is_not_mapped_then_return_none() {
+        let features = get_features();
+        assert_eq!(features.into_global_action(Event::Midi([176, 93, 127, 0])).unwrap(), None);
+        assert_eq!(features.into_global_action(Event::Midi([144, 92, 127, 0])).unwrap(), None);
+        assert_eq!(LaunchpadProFeatures::new().into_global_action(Event::Midi([176, 92, 127, 0])).unwrap(), None);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::midi::Event;
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
 use crate::midi::features::{R, GridController};
 
 use super::device::LaunchpadProFeatures;
@@ -9,20 +11,10 @@ impl GridController for LaunchpadProFeatures {
     }
 
     fn into_coordinates(&self, event: Event) -> R<Option<(usize, usize)>> {
-        return Ok(match event {
+        return Ok(match MidiMessage::try_from(event) {
             // event must be a "note down" (144) with a strictly positive velocity
-            Event::Midi([144, data1, data2, _]) if data2 > 0 => {
-                // the device provides a 10x10 grid if you count the buttons on the sides
-                let row = data1 / 10;
-                let column  = data1 % 10;
-
-                // we’ll only return coordinates for the central 8x8 grid
-                if row >= 1 && row <= 8 && column >= 1 && column <= 8 {
-                    Some(((column - 1).into(), (8 - row).into()))
-                } else {
-                    None
-                }
-            },
+            // we’ll only return coordinates for the central 8x8 grid
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => self.get_geometry().note_to_xy(note),
             _ => None,
         });
     }
@@ -30,6 +22,7 @@ impl GridController for LaunchpadProFeatures {
 
 #[cfg(test)]
 mod test {
+    use crate::midi::devices::config::Origin;
     use super::*;
 
     #[test]
@@ -105,4 +98,15 @@ mod test {
 
         assert_eq!(expected_output, actual_output);
     }
+
+    #[test]
+    fn into_coordinates_given_top_left_origin_should_not_flip_rows() {
+        let features = LaunchpadProFeatures { origin: Origin::TopLeft, ..LaunchpadProFeatures::new() };
+        let coordinates = |code| features.into_coordinates(Event::Midi([144, code, 10, 0])).expect("into_coordinates should not fail");
+
+        assert_eq!(coordinates(11), Some((0, 0)));
+        assert_eq!(coordinates(18), Some((7, 0)));
+        assert_eq!(coordinates(81), Some((0, 7)));
+        assert_eq!(coordinates(88), Some((7, 7)));
+    }
 }
@@ -1,9 +1,10 @@
 use std::error::Error as StdError;
 use std::fmt::{Display, Error, Formatter};
 
-use crate::image::{Image, scale};
-use crate::midi::Event;
+use crate::image::{Image, quantize, render_number, scale};
+use crate::midi::{Error as MidiError, Event};
 use crate::midi::features::{R, GridController, ImageRenderer};
+use crate::midi::devices::config::Origin;
 
 use super::device::LaunchpadProFeatures;
 
@@ -20,6 +21,19 @@ impl Display for UnexpectedNumberOfBytes {
     }
 }
 
+#[derive(Debug)]
+struct UnexpectedNumberOfImages {
+    actual_images: usize,
+    expected_images: usize,
+}
+
+impl StdError for UnexpectedNumberOfImages {}
+impl Display for UnexpectedNumberOfImages {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "expected number of images: {}; got: {}", self.expected_images, self.actual_images)
+    }
+}
+
 impl ImageRenderer for LaunchpadProFeatures {
     fn from_image(&self, image: Image) -> R<Event> {
         let (width, height) = self.get_grid_size()?;
@@ -28,17 +42,107 @@ impl ImageRenderer for LaunchpadProFeatures {
                 let err: Box<dyn StdError + Send> = Box::new(err);
                 return err;
             })?;
-        return self.render_24bit_image_reversed(scaled_image.bytes);
+        return self.render_24bit_image_from_top(self.quantize_if_enabled(self.normalize_if_enabled(scaled_image.bytes)));
+    }
+
+    fn from_images(&self, images: Vec<Image>) -> R<Event> {
+        let (width, height) = self.get_grid_size()?;
+        let bytes = compose_mosaic(images, width, height)?;
+        return self.render_24bit_image_from_top(self.quantize_if_enabled(self.normalize_if_enabled(bytes)));
+    }
+
+    fn from_number(&self, n: u16, color: [u8; 3]) -> R<Event> {
+        let (width, height) = self.get_grid_size()?;
+        let image = render_number(n, color, width, height)
+            .map_err(|err| {
+                let err: Box<dyn StdError + Send> = Box::new(err);
+                return err;
+            })?;
+        return self.render_24bit_image_from_top(image.bytes);
+    }
+
+    fn from_pixel(&self, x: usize, y: usize, color: [u8; 3]) -> R<Event> {
+        // LEDs are numbered from the corner of the origin (11) to the opposite one (88)
+        let led = match self.get_geometry().xy_to_note(x, y) {
+            Some(led) => led,
+            None => return Err(Box::new(MidiError::OutOfBoundIndexError)),
+        };
+        return Ok(self.light_leds(vec![(led, color)]));
+    }
+
+    fn set_brightness(&self, brightness: f32) -> R<()> {
+        *self.brightness.lock().unwrap() = brightness.max(0.0).min(1.0);
+        return Ok(());
     }
 }
 
+/// Scale each image down to a single pixel, and lay them out on a grid of the given size.
+fn compose_mosaic(images: Vec<Image>, width: usize, height: usize) -> R<Vec<u8>> {
+    if images.len() != width * height {
+        return Err(Box::new(UnexpectedNumberOfImages { actual_images: images.len(), expected_images: width * height }));
+    }
+
+    let mut bytes = Vec::with_capacity(width * height * 3);
+    for image in images {
+        let mut pixel = scale(&image, 1, 1)
+            .map_err(|err| {
+                let err: Box<dyn StdError + Send> = Box::new(err);
+                return err;
+            })?;
+        bytes.append(&mut pixel.bytes);
+    }
+
+    return Ok(bytes);
+}
+
+/// Stretch each color channel so that its darkest value becomes 0 and its brightest one 255.
+/// Channels holding a single value are left untouched, so that plain images keep their color.
+fn normalize(bytes: Vec<u8>) -> Vec<u8> {
+    let mut normalized_bytes = bytes.clone();
+
+    for c in 0..3 {
+        let channel = bytes.iter().skip(c).step_by(3);
+        let min = *channel.clone().min().unwrap_or(&0) as u32;
+        let max = *channel.max().unwrap_or(&0) as u32;
+
+        if min == max {
+            continue;
+        }
+
+        for index in (c..bytes.len()).step_by(3) {
+            normalized_bytes[index] = ((bytes[index] as u32 - min) * 255 / (max - min)) as u8;
+        }
+    }
+
+    return normalized_bytes;
+}
+
 impl LaunchpadProFeatures {
+    fn normalize_if_enabled(&self, bytes: Vec<u8>) -> Vec<u8> {
+        return if self.normalize_images { normalize(bytes) } else { bytes };
+    }
+
+    fn quantize_if_enabled(&self, bytes: Vec<u8>) -> Vec<u8> {
+        return match &self.image_palette {
+            Some(palette) => quantize(bytes, palette),
+            None => bytes,
+        };
+    }
+
     fn get_size(&self) -> R<usize> {
         let (width, height) = self.get_grid_size()?;
         // one byte for each red/green/blue color
         return Ok(width * height * 3);
     }
 
+    /// Render an image with (0,0) being the top-left corner, whatever the origin of the device
+    fn render_24bit_image_from_top(&self, bytes: Vec<u8>) -> R<Event> {
+        return match self.origin {
+            Origin::BottomLeft => self.render_24bit_image_reversed(bytes),
+            Origin::TopLeft => self.render_24bit_image(bytes),
+        };
+    }
+
     /// The LaunchpadPro’s coordinate system places the origin at the bottom-left corner, so we
     /// need to give an easy option to render an image with (0,0) being the top-left corner.
     fn render_24bit_image_reversed(&self, bytes: Vec<u8>) -> R<Event> {
@@ -53,12 +157,13 @@ impl LaunchpadProFeatures {
             return Err(Box::new(UnexpectedNumberOfBytes { actual_bytes: bytes.len(), expected_bytes: size }));
         }
 
+        let brightness = self.get_brightness();
         let mut picture = Vec::with_capacity(size);
         picture.append(&mut vec![240, 0, 32, 41, 2, 16, 15, 1]);
         for byte in bytes {
             // The LaunchpadPro also only supports values from the [0; 64[ range, so we need to make sure
             // that our 24-bit-RGB-color bytes get transformed.
-            picture.push(byte / 4);
+            picture.push((byte as f32 * brightness) as u8 / 4);
         }
         picture.append(&mut vec![247]);
 
@@ -89,6 +194,7 @@ impl LaunchpadProFeatures {
 
 #[cfg(test)]
 mod tests {
+    use crate::image::TestPattern;
     use super::*;
 
     #[test]
@@ -118,6 +224,86 @@ mod tests {
         ]);
     }
 
+    fn get_plain_image(width: usize, height: usize, color: [u8; 3]) -> Image {
+        return Image { width, height, bytes: color.repeat(width * height) };
+    }
+
+    #[test]
+    fn test_compose_mosaic_given_2x2_grid_should_put_one_pixel_per_image() {
+        let images = vec![
+            get_plain_image(4, 4, [255, 0, 0]),
+            get_plain_image(2, 2, [0, 255, 0]),
+            get_plain_image(1, 1, [0, 0, 255]),
+            get_plain_image(8, 8, [255, 255, 255]),
+        ];
+
+        let bytes = compose_mosaic(images, 2, 2).expect("Test input is expected to be valid");
+        assert_eq!(bytes, vec![
+            255, 000, 000, 000, 255, 000,
+            000, 000, 255, 255, 255, 255,
+        ]);
+    }
+
+    #[test]
+    fn test_compose_mosaic_given_wrong_number_of_images_should_fail() {
+        let images = vec![get_plain_image(1, 1, [255, 0, 0]); 3];
+        assert!(compose_mosaic(images, 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_from_images_given_wrong_number_of_images_should_fail() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let images = vec![get_plain_image(1, 1, [255, 0, 0]); 4];
+        assert!(features.from_images(images).is_err());
+    }
+
+    #[test]
+    fn test_from_images_should_reverse_rows_and_divide_color_values_by_four() {
+        let features = super::super::LaunchpadProFeatures::new();
+
+        // The top row is red, and the others are green
+        let images = (0..64)
+            .map(|index| get_plain_image(2, 2, if index < 8 { [255, 0, 0] } else { [0, 255, 0] }))
+            .collect::<Vec<Image>>();
+
+        let event = features.from_images(images).unwrap();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            [0, 63, 0].repeat(7 * 8),
+            [63, 0, 0].repeat(8),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn from_test_pattern_when_color_bars_then_light_one_bar_per_column() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let event = features.from_test_pattern(TestPattern::ColorBars).expect("from_test_pattern should not fail");
+        let row = vec![
+            63, 63, 63,  63, 63, 0,  0, 63, 63,  0, 63, 0,
+            63, 0, 63,  63, 0, 0,  0, 0, 63,  0, 0, 0,
+        ];
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            row.repeat(8),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn from_test_pattern_when_grayscale_ramp_then_light_columns_from_black_to_white() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let event = features.from_test_pattern(TestPattern::GrayscaleRamp).expect("from_test_pattern should not fail");
+        let row = [0, 9, 18, 27, 36, 45, 54, 63].iter()
+            .flat_map(|gray| [*gray, *gray, *gray])
+            .collect::<Vec<u8>>();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            row.repeat(8),
+            Vec::from([247]),
+        ].concat()));
+    }
+
     #[test]
     fn test_from_image_should_reverse_rows_and_divide_color_values_by_four() {
         let features = super::super::LaunchpadProFeatures::new();
@@ -161,4 +347,176 @@ mod tests {
             Vec::from([247]),
         ].concat()));
     }
+
+    #[test]
+    fn test_from_image_given_normalize_images_should_spread_a_narrow_bright_range() {
+        let features = LaunchpadProFeatures { normalize_images: true, ..LaunchpadProFeatures::new() };
+
+        // Every value would read as 60, 61, 62 or 63 without normalization
+        let image = Image { width: 8, height: 8, bytes: vec![
+            Vec::from([240; 8 * 3]),
+            Vec::from([240; 8 * 3]),
+            Vec::from([245; 8 * 3]),
+            Vec::from([245; 8 * 3]),
+            Vec::from([250; 8 * 3]),
+            Vec::from([250; 8 * 3]),
+            Vec::from([255; 8 * 3]),
+            Vec::from([255; 8 * 3]),
+        ].concat() };
+
+        let event = features.from_image(image).unwrap();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            Vec::from([63; 8 * 3]),
+            Vec::from([63; 8 * 3]),
+            Vec::from([42; 8 * 3]),
+            Vec::from([42; 8 * 3]),
+            Vec::from([21; 8 * 3]),
+            Vec::from([21; 8 * 3]),
+            Vec::from([00; 8 * 3]),
+            Vec::from([00; 8 * 3]),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn test_from_image_given_image_palette_should_render_the_nearest_palette_colors() {
+        let features = LaunchpadProFeatures {
+            image_palette: Some(vec![[0, 0, 0], [255, 0, 0]]),
+            ..LaunchpadProFeatures::new()
+        };
+
+        let image = Image { width: 8, height: 8, bytes: vec![
+            [40, 40, 40].repeat(8 * 4),
+            [200, 20, 20].repeat(8 * 4),
+        ].concat() };
+
+        let event = features.from_image(image).unwrap();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            // The bottom rows come first
+            [63, 0, 0].repeat(8 * 4),
+            Vec::from([0; 8 * 4 * 3]),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn test_from_pixel_given_brightness_set_should_dim_the_subsequently_rendered_colors() {
+        let features = LaunchpadProFeatures::new();
+        assert_eq!(features.from_pixel(0, 7, [200, 100, 0]).unwrap(), Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 11, 50, 25, 0, 247]));
+
+        features.set_brightness(0.5).unwrap();
+        assert_eq!(features.from_pixel(0, 7, [200, 100, 0]).unwrap(), Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 11, 25, 12, 0, 247]));
+    }
+
+    #[test]
+    fn test_from_image_given_brightness_set_should_dim_the_subsequently_rendered_image() {
+        let features = LaunchpadProFeatures::new();
+        features.set_brightness(0.25).unwrap();
+
+        let image = Image { width: 8, height: 8, bytes: vec![255; 8 * 8 * 3] };
+        assert_eq!(features.from_image(image).unwrap(), Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            Vec::from([15; 8 * 8 * 3]),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn test_set_brightness_given_a_value_out_of_range_should_clamp_it() {
+        let features = LaunchpadProFeatures::new();
+        features.set_brightness(2.0).unwrap();
+        assert_eq!(features.get_brightness(), 1.0);
+
+        features.set_brightness(-1.0).unwrap();
+        assert_eq!(features.get_brightness(), 0.0);
+    }
+
+    #[test]
+    fn test_normalize_should_stretch_each_channel_independently() {
+        let bytes = vec![
+            100, 50, 7,
+            150, 60, 7,
+            200, 70, 7,
+        ];
+
+        assert_eq!(normalize(bytes), vec![
+            000, 000, 7,
+            127, 127, 7,
+            255, 255, 7,
+        ]);
+    }
+
+    #[test]
+    fn test_from_number_should_light_the_pads_of_both_digits_from_the_bottom_row() {
+        let features = super::super::LaunchpadProFeatures::new();
+
+        // "12" in white, divided by four for the Launchpad Pro
+        let k = [0, 0, 0];
+        let w = [63, 63, 63];
+
+        let event = features.from_number(12, [255, 255, 255]).unwrap();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            // rows go from the bottom to the top of the grid
+            [k, k, k, k, k, k, k, k].concat(),
+            [k, k, k, k, k, k, k, k].concat(),
+            [w, w, w, k, w, w, w, k].concat(),
+            [k, w, k, k, w, k, k, k].concat(),
+            [k, w, k, k, w, w, w, k].concat(),
+            [w, w, k, k, k, k, w, k].concat(),
+            [k, w, k, k, w, w, w, k].concat(),
+            [k, k, k, k, k, k, k, k].concat(),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn test_from_number_when_number_has_three_digits_should_return_err() {
+        let features = super::super::LaunchpadProFeatures::new();
+        assert!(features.from_number(123, [255, 255, 255]).is_err());
+    }
+
+    #[test]
+    fn from_pixel_should_light_a_single_led() {
+        let features = super::super::LaunchpadProFeatures::new();
+
+        let event = features.from_pixel(0, 0, [4, 8, 252]).expect("from_pixel should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 81, 1, 2, 63, 247]));
+
+        let event = features.from_pixel(7, 7, [4, 8, 252]).expect("from_pixel should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 18, 1, 2, 63, 247]));
+    }
+
+    #[test]
+    fn from_pixel_when_out_of_the_grid_then_fail() {
+        let features = super::super::LaunchpadProFeatures::new();
+        assert!(features.from_pixel(8, 0, [0, 0, 0]).is_err());
+        assert!(features.from_pixel(0, 8, [0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn from_image_given_top_left_origin_should_not_reverse_rows() {
+        let features = LaunchpadProFeatures { origin: Origin::TopLeft, ..LaunchpadProFeatures::new() };
+        let image = Image { width: 8, height: 8, bytes: (0..8u8).flat_map(|y| vec![y * 32; 8 * 3]).collect() };
+
+        let event = features.from_image(image).unwrap();
+        assert_eq!(event, Event::SysEx(vec![
+            Vec::from([240, 0, 32, 41, 2, 16, 15, 1]),
+            (0..8u8).flat_map(|y| vec![y * 8; 8 * 3]).collect(),
+            Vec::from([247]),
+        ].concat()));
+    }
+
+    #[test]
+    fn from_pixel_given_top_left_origin_should_light_the_led_of_the_same_row() {
+        let features = LaunchpadProFeatures { origin: Origin::TopLeft, ..LaunchpadProFeatures::new() };
+
+        let event = features.from_pixel(0, 0, [4, 8, 252]).expect("from_pixel should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 11, 1, 2, 63, 247]));
+
+        let event = features.from_pixel(7, 7, [4, 8, 252]).expect("from_pixel should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 88, 1, 2, 63, 247]));
+    }
 }
@@ -1,7 +1,8 @@
+use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
 
-use crate::midi::Event;
+use crate::midi::{Event, MidiMessage};
 use crate::midi::features::{R, IndexSelector};
 
 use super::device::LaunchpadProFeatures;
@@ -21,41 +22,49 @@ impl Display for IndexOutOfBoundError {
 
 impl IndexSelector for LaunchpadProFeatures {
     fn into_index(&self, event: Event) -> R<Option<usize>> {
-        return Ok(match event {
+        return Ok(match MidiMessage::try_from(event) {
             // event must be a "note down" with a strictly positive velocity
-            Event::Midi([144, data1, data2, _]) if data2 > 0 => {
-                // the device provides a 10x10 grid if you count the buttons on the sides
-                let row = data1 / 10;
-                let column  = data1 % 10;
-
-                // but in this implementation, we’ll only focus on the central 8x8 grid
-                if row >= 1 && row <= 8 && column >= 1 && column <= 8 {
-                    Some((row - 1) * 8 + (column - 1)).map(|index| index.into())
-                } else {
-                    None
-                }
+            // the buttons on the sides of the central 8x8 grid have no index
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 => {
+                let geometry = self.get_geometry();
+                geometry.note_to_xy(note).and_then(|(x, y)| geometry.xy_to_index(x, y))
             },
             _ => None,
         });
     }
 
     fn from_index_to_highlight(&self, index: usize) -> R<Event> {
-        if index > 63 {
-            return Err(Box::new(IndexOutOfBoundError { actual_value: index, maximum_value: 63 }));
-        }
+        let bytes = vec![240, 0, 32, 41, 2, 16, 40, self.get_led(index)?, self.highlight_color, 247];
+        return Ok(Event::SysEx(bytes));
+    }
 
-        let index = index as u8;
-        let row = index / 8 + 1;
-        let column = index % 8 + 1;
-        let led = row * 10 + column;
+    fn from_index_to_highlight_with_color(&self, index: usize, color: [u8; 3]) -> R<Event> {
+        // Unlike the default highlight, the LED doesn’t pulse: it is simply lit with the given color
+        return Ok(self.light_leds(vec![(self.get_led(index)?, color)]));
+    }
 
-        let bytes = vec![240, 0, 32, 41, 2, 16, 40, led, 45, 247];
-        return Ok(Event::SysEx(bytes));
+    fn from_index_to_coordinates(&self, index: usize) -> R<Option<(usize, usize)>> {
+        return Ok(self.get_geometry().index_to_xy(index));
+    }
+}
+
+impl LaunchpadProFeatures {
+    /// LEDs are numbered from the corner of the origin (11) to the opposite one (88),
+    /// while indices start from the bottom-left corner
+    fn get_led(&self, index: usize) -> R<u8> {
+        let geometry = self.get_geometry();
+        return geometry.index_to_xy(index)
+            .and_then(|(x, y)| geometry.xy_to_note(x, y))
+            .ok_or_else(|| {
+                let err: Box<dyn StdError + Send> = Box::new(IndexOutOfBoundError { actual_value: index, maximum_value: 63 });
+                err
+            });
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::midi::devices::config::{DeviceConfig, DeviceType, GridLayout, Origin};
     use super::*;
 
     #[test]
@@ -131,4 +140,135 @@ mod tests {
 
         assert_eq!(expected_output, actual_output);
     }
+
+    #[test]
+    fn from_index_to_highlight_should_use_the_default_highlight_color() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let event = features.from_index_to_highlight(27).expect("from_index_to_highlight should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 40, 44, 45, 247]));
+    }
+
+    fn get_config_with_highlight_color(highlight_color: Option<u8>) -> DeviceConfig {
+        return DeviceConfig {
+            name: "Launchpad Pro".to_string(),
+            device_type: DeviceType::LaunchpadPro,
+            color_palette_row: None,
+            grid_pads: vec![],
+            palette_encoder: None,
+            highlight_color,
+            palette_lighting: false,
+            normalize_images: false,
+            image_palette: None,
+            origin: Origin::BottomLeft,
+            color_palette_edge: crate::midi::devices::config::Edge::Bottom,
+            grid_layout: crate::midi::devices::config::GridLayout::RowMajor,
+            inactive_app_brightness: None,
+            brightness: None,
+            global_actions: vec![],
+            buffer_size: None,
+            sysex_gap_ms: None,
+            sysex_macros: std::collections::HashMap::new(),
+            on_connect: vec![],
+        };
+    }
+
+    #[test]
+    fn from_index_to_highlight_should_use_the_configured_highlight_color() {
+        let features = super::super::LaunchpadProFeatures::from(&get_config_with_highlight_color(Some(5)));
+        let event = features.from_index_to_highlight(27).expect("from_index_to_highlight should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 40, 44, 5, 247]));
+    }
+
+    #[test]
+    fn from_index_to_highlight_when_the_highlight_color_is_not_a_data_byte_then_use_the_default_one() {
+        let features = super::super::LaunchpadProFeatures::from(&get_config_with_highlight_color(Some(200)));
+        let event = features.from_index_to_highlight(27).expect("from_index_to_highlight should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 40, 44, 45, 247]));
+    }
+
+    #[test]
+    fn from_index_to_highlight_with_color_should_light_the_led_with_the_given_color() {
+        let features = super::super::LaunchpadProFeatures::new();
+        let event = features.from_index_to_highlight_with_color(27, [40, 40, 40]).expect("from_index_to_highlight_with_color should not fail");
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 44, 10, 10, 10, 247]));
+    }
+
+    #[test]
+    fn from_index_to_highlight_with_color_given_out_of_bound_index_should_return_error() {
+        let features = super::super::LaunchpadProFeatures::new();
+        assert!(features.from_index_to_highlight_with_color(64, [40, 40, 40]).is_err());
+    }
+
+    #[test]
+    fn into_index_given_top_left_origin_should_start_from_the_bottom_left_corner_too() {
+        let features = LaunchpadProFeatures { origin: Origin::TopLeft, ..LaunchpadProFeatures::new() };
+        let index = |code| features.into_index(Event::Midi([144, code, 10, 0])).expect("into_index should not fail");
+
+        assert_eq!(index(81), Some(0));
+        assert_eq!(index(88), Some(7));
+        assert_eq!(index(11), Some(56));
+        assert_eq!(index(18), Some(63));
+    }
+
+    #[test]
+    fn from_index_to_highlight_should_light_the_pad_of_the_index_whatever_the_origin() {
+        for origin in [Origin::BottomLeft, Origin::TopLeft] {
+            let features = LaunchpadProFeatures { origin, ..LaunchpadProFeatures::new() };
+            for index in 0..64 {
+                let led = match features.from_index_to_highlight(index).expect("from_index_to_highlight should not fail") {
+                    Event::SysEx(bytes) => bytes[7],
+                    event => panic!("unexpected event: {:?}", event),
+                };
+                assert_eq!(features.into_index(Event::Midi([144, led, 10, 0])).unwrap(), Some(index), "{:?}", origin);
+            }
+        }
+    }
+
+    #[test]
+    fn into_index_given_column_major_layout_should_number_pads_from_bottom_to_top() {
+        let features = LaunchpadProFeatures { grid_layout: GridLayout::ColumnMajor, ..LaunchpadProFeatures::new() };
+        let index = |code| features.into_index(Event::Midi([144, code, 10, 0])).expect("into_index should not fail");
+
+        assert_eq!(index(11), Some(0));
+        assert_eq!(index(21), Some(1));
+        assert_eq!(index(81), Some(7));
+        assert_eq!(index(12), Some(8));
+        assert_eq!(index(88), Some(63));
+    }
+
+    #[test]
+    fn into_index_given_snake_layout_should_go_back_and_forth_row_by_row() {
+        let features = LaunchpadProFeatures { grid_layout: GridLayout::Snake, ..LaunchpadProFeatures::new() };
+        let index = |code| features.into_index(Event::Midi([144, code, 10, 0])).expect("into_index should not fail");
+
+        assert_eq!(index(11), Some(0));
+        assert_eq!(index(18), Some(7));
+        assert_eq!(index(28), Some(8));
+        assert_eq!(index(21), Some(15));
+        assert_eq!(index(31), Some(16));
+        assert_eq!(index(81), Some(63));
+    }
+
+    #[test]
+    fn from_index_to_highlight_should_light_the_pad_of_the_index_whatever_the_layout() {
+        for grid_layout in [GridLayout::RowMajor, GridLayout::ColumnMajor, GridLayout::Snake] {
+            let features = LaunchpadProFeatures { grid_layout, origin: Origin::TopLeft, ..LaunchpadProFeatures::new() };
+            for index in 0..64 {
+                let led = match features.from_index_to_highlight(index).expect("from_index_to_highlight should not fail") {
+                    Event::SysEx(bytes) => bytes[7],
+                    event => panic!("unexpected event: {:?}", event),
+                };
+                assert_eq!(features.into_index(Event::Midi([144, led, 10, 0])).unwrap(), Some(index), "{:?}", grid_layout);
+            }
+        }
+    }
+
+    #[test]
+    fn from_index_to_coordinates_given_column_major_layout_should_go_up_the_columns() {
+        let features = LaunchpadProFeatures { grid_layout: GridLayout::ColumnMajor, ..LaunchpadProFeatures::new() };
+        assert_eq!(features.from_index_to_coordinates(0).unwrap(), Some((0, 7)));
+        assert_eq!(features.from_index_to_coordinates(1).unwrap(), Some((0, 6)));
+        assert_eq!(features.from_index_to_coordinates(8).unwrap(), Some((1, 7)));
+        assert_eq!(features.from_index_to_coordinates(64).unwrap(), None);
+    }
 }
@@ -2,9 +2,14 @@ mod device;
 
 mod app_selector;
 mod color_palette;
+mod connection_handler;
+mod global_action_trigger;
 mod grid_controller;
 mod image_renderer;
 mod index_selector;
+mod palette;
+mod playlist_selector;
+mod save_gesture_trigger;
 
 pub use device::LaunchpadPro;
 pub use device::LaunchpadProFeatures;
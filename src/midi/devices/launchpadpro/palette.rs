@@ -0,0 +1,117 @@
+use crate::midi::Event;
+
+use super::app_selector::dim;
+use super::device::LaunchpadProFeatures;
+
+/// RGB values of the 128 colors of the Launchpad Pro’s palette, indexed by their palette index
+pub const PALETTE: [[u8; 3]; 128] = [
+    // 0-7
+    [0, 0, 0], [30, 30, 30], [127, 127, 127], [255, 255, 255], [255, 76, 76], [255, 0, 0], [89, 0, 0], [25, 0, 0],
+    // 8-15
+    [255, 189, 108], [255, 84, 0], [89, 29, 0], [39, 27, 0], [255, 255, 76], [255, 255, 0], [89, 89, 0], [25, 25, 0],
+    // 16-23
+    [136, 255, 76], [84, 255, 0], [29, 89, 0], [20, 43, 0], [76, 255, 76], [0, 255, 0], [0, 89, 0], [0, 25, 0],
+    // 24-31
+    [76, 255, 94], [0, 255, 25], [0, 89, 13], [0, 25, 2], [76, 255, 136], [0, 255, 85], [0, 89, 29], [0, 31, 18],
+    // 32-39
+    [76, 255, 183], [0, 255, 153], [0, 89, 53], [0, 25, 18], [76, 195, 255], [0, 169, 255], [0, 65, 82], [0, 16, 25],
+    // 40-47
+    [76, 136, 255], [0, 85, 255], [0, 29, 89], [0, 8, 25], [76, 76, 255], [0, 0, 255], [0, 0, 89], [0, 0, 25],
+    // 48-55
+    [135, 76, 255], [84, 0, 255], [25, 0, 100], [15, 0, 48], [255, 76, 255], [255, 0, 255], [89, 0, 89], [25, 0, 25],
+    // 56-63
+    [255, 76, 135], [255, 0, 84], [89, 0, 29], [34, 0, 19], [255, 21, 0], [153, 53, 0], [121, 81, 0], [67, 100, 0],
+    // 64-71
+    [3, 57, 0], [0, 87, 53], [0, 84, 127], [0, 0, 255], [0, 69, 79], [37, 0, 204], [127, 127, 127], [32, 32, 32],
+    // 72-79
+    [255, 0, 0], [189, 255, 45], [175, 237, 6], [100, 255, 9], [16, 139, 0], [0, 255, 135], [0, 169, 255], [0, 42, 255],
+    // 80-87
+    [63, 0, 255], [122, 0, 255], [178, 26, 125], [64, 33, 0], [255, 74, 0], [136, 225, 6], [114, 255, 21], [0, 255, 0],
+    // 88-95
+    [59, 255, 38], [89, 255, 113], [56, 255, 204], [91, 138, 255], [49, 81, 198], [135, 127, 233], [211, 29, 255], [255, 0, 93],
+    // 96-103
+    [255, 127, 0], [185, 176, 0], [144, 255, 0], [131, 93, 7], [57, 43, 0], [20, 76, 16], [13, 80, 56], [21, 21, 42],
+    // 104-111
+    [22, 32, 90], [105, 60, 28], [168, 0, 10], [222, 81, 61], [216, 106, 28], [255, 225, 38], [158, 225, 47], [103, 181, 15],
+    // 112-119
+    [30, 30, 48], [220, 255, 107], [128, 255, 189], [154, 153, 255], [142, 102, 255], [64, 64, 64], [117, 117, 117], [224, 255, 255],
+    // 120-127
+    [160, 0, 0], [53, 0, 0], [26, 208, 0], [7, 66, 0], [185, 176, 0], [63, 49, 0], [179, 95, 0], [75, 21, 2],
+];
+
+/// Find the index of the palette color that is the closest to the given one.
+/// When several palette colors are equally close, the lowest index wins.
+pub fn nearest_palette_index(color: [u8; 3]) -> u8 {
+    let distance = |palette_color: &[u8; 3]| -> u32 {
+        return (0..3)
+            .map(|i| (color[i] as i32 - palette_color[i] as i32).pow(2) as u32)
+            .sum();
+    };
+
+    let mut nearest_index = 0;
+    for index in 1..PALETTE.len() {
+        if distance(&PALETTE[index]) < distance(&PALETTE[nearest_index]) {
+            nearest_index = index;
+        }
+    }
+    return nearest_index as u8;
+}
+
+impl LaunchpadProFeatures {
+    /// Light each LED with its color, either with RGB values (command 11)
+    /// or with the index of the nearest palette color (command 10), depending on the configuration.
+    pub fn light_leds(&self, leds: Vec<(u8, [u8; 3])>) -> Event {
+        let command = if self.palette_lighting { 10 } else { 11 };
+        let mut bytes = vec![240, 0, 32, 41, 2, 16, command];
+        let brightness = self.get_brightness();
+
+        for (led, color) in leds {
+            let color = dim(color, brightness);
+            bytes.push(led);
+            if self.palette_lighting {
+                bytes.push(nearest_palette_index(color));
+            } else {
+                // The Launchpad Pro only accepts values within the [0; 63] range
+                bytes.append(&mut vec![color[0] / 4, color[1] / 4, color[2] / 4]);
+            }
+        }
+        bytes.push(247);
+
+        return Event::SysEx(bytes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nearest_palette_index_when_color_is_in_the_palette_then_return_its_index() {
+        assert_eq!(nearest_palette_index([0, 0, 0]), 0);
+        assert_eq!(nearest_palette_index([255, 255, 255]), 3);
+        assert_eq!(nearest_palette_index([0, 0, 255]), 45);
+        assert_eq!(nearest_palette_index([255, 0, 255]), 53);
+    }
+
+    #[test]
+    fn nearest_palette_index_when_color_is_not_in_the_palette_then_return_the_closest_one() {
+        // closer to pure green (21) than to anything else
+        assert_eq!(nearest_palette_index([10, 240, 5]), 21);
+        // dark greys, among 1 (30, 30, 30), 71 (32, 32, 32) and 117 (64, 64, 64)
+        assert_eq!(nearest_palette_index([28, 28, 28]), 1);
+        assert_eq!(nearest_palette_index([55, 55, 55]), 117);
+    }
+
+    #[test]
+    fn nearest_palette_index_when_colors_are_equally_close_then_return_the_lowest_index() {
+        // pure red appears as both 5 and 72 in the palette
+        assert_eq!(nearest_palette_index([255, 0, 0]), 5);
+    }
+
+    #[test]
+    fn light_leds_when_palette_lighting_then_send_one_index_per_led() {
+        let features = LaunchpadProFeatures { palette_lighting: true, ..LaunchpadProFeatures::new() };
+        let event = features.light_leds(vec![(89, [0, 255, 0]), (79, [250, 250, 250])]);
+        assert_eq!(event, Event::SysEx(vec![240, 0, 32, 41, 2, 16, 10, 89, 21, 79, 3, 247]));
+    }
+}
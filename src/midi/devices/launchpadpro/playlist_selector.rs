@@ -0,0 +1,53 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, PlaylistSelector};
+
+use super::device::LaunchpadProFeatures;
+
+/// On the Launchpad Pro, we’ll use the top row to select playlists, from left to right:
+///    ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮
+///    ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯
+///     ↖  ↖  ↖  ↖  ↖  ↖  ↖  ↖
+///     0  1  2  3  4  5  6  7
+/// Buttons assigned to global actions never reach the apps, and thus cannot select a playlist.
+impl PlaylistSelector for LaunchpadProFeatures {
+    fn into_playlist_index(&self, event: Event) -> R<Option<usize>> {
+        return Ok(match MidiMessage::try_from(event) {
+            // 176: controller on
+            // control: 91/92/../98
+            // value: strictly positive (the button must be pressed)
+            Ok(MidiMessage::ControlChange { channel: 0, control, value }) if value > 0 && control >= 91 && control <= 98 => {
+                Some((control - 91).into())
+            },
+            _ => None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_playlist_index_when_a_top_row_button_is_pressed_then_return_its_position() {
+        let features = LaunchpadProFeatures::new();
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 91, 127, 0])).unwrap(), Some(0));
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 94, 127, 0])).unwrap(), Some(3));
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 98, 127, 0])).unwrap(), Some(7));
+    }
+
+    #[test]
+    fn into_playlist_index_when_a_top_row_button_is_released_then_return_none() {
+        let features = LaunchpadProFeatures::new();
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 92, 0, 0])).unwrap(), None);
+    }
+
+    #[test]
+    fn into_playlist_index_when_another_button_is_pressed_then_return_none() {
+        let features = LaunchpadProFeatures::new();
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 89, 127, 0])).unwrap(), None);
+        assert_eq!(features.into_playlist_index(Event::Midi([176, 99, 127, 0])).unwrap(), None);
+        assert_eq!(features.into_playlist_index(Event::Midi([144, 92, 127, 0])).unwrap(), None);
+    }
+}
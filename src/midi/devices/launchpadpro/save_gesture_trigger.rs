@@ -0,0 +1,44 @@
+use std::convert::TryFrom;
+
+use crate::midi::{Event, MidiMessage};
+use crate::midi::features::{R, SaveGestureTrigger};
+
+use super::device::LaunchpadProFeatures;
+
+/// On the Launchpad Pro, we’ll use the bottom button of the left column ("Record") to save:
+/// ╭╮ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╔╗ ╭╮
+/// ╰╯ ╚╝ ╚╝ ╚╝ ╚╝ ╚╝ ╚╝ ╚╝ ╚╝ ╰╯
+///  ↖ save
+///    ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮ ╭╮
+///    ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯ ╰╯
+/// Buttons assigned to global actions never reach the apps, and thus cannot save anything.
+impl SaveGestureTrigger for LaunchpadProFeatures {
+    fn into_save_gesture(&self, event: Event) -> R<bool> {
+        return Ok(match MidiMessage::try_from(event) {
+            // controller on
+            // control: 10
+            // value: strictly positive (the button must be pressed)
+            Ok(MidiMessage::ControlChange { channel: 0, control: 10, value }) => value > 0,
+            _ => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_save_gesture_when_the_record_button_is_pressed_then_return_true() {
+        let features = LaunchpadProFeatures::new();
+        assert!(features.into_save_gesture(Event::Midi([176, 10, 127, 0])).unwrap());
+    }
+
+    #[test]
+    fn into_save_gesture_when_the_record_button_is_released_or_another_one_is_pressed_then_return_false() {
+        let features = LaunchpadProFeatures::new();
+        assert!(!features.into_save_gesture(Event::Midi([176, 10, 0, 0])).unwrap());
+        assert!(!features.into_save_gesture(Event::Midi([176, 20, 127, 0])).unwrap());
+        assert!(!features.into_save_gesture(Event::Midi([144, 10, 127, 0])).unwrap());
+    }
+}
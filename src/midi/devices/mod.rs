@@ -1,13 +1,17 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::midi::{Error, Connections, InputPort, OutputPort};
+use crate::midi::{Error, PortFactory, Reader, SysExGapWriter, Writer, DEFAULT_BUFFER_SIZE};
 use crate::midi::features::Features;
 
 pub mod config;
+pub mod grid_geometry;
 
 // device types
+pub mod apcmini;
 pub mod default;
+pub mod launchkey;
 pub mod launchpadpro;
 
 pub struct Devices {
@@ -19,7 +23,7 @@ impl Devices {
         return self.devices.get(id);
     }
 
-    pub fn get_input_port<'a>(&self, id: &str, connections: &'a Connections) -> Result<DeviceWithInputPort<'a>, Error> {
+    pub fn get_input_port<'a>(&self, id: &str, connections: &'a dyn PortFactory) -> Result<DeviceWithInputPort<'a>, Error> {
         let device = self.get(id).ok_or(Error::DeviceNotFound)?;
         let port = device.get_input_port(connections)?;
         Ok(DeviceWithInputPort {
@@ -31,7 +35,7 @@ impl Devices {
         })
     }
 
-    pub fn get_output_port<'a>(&self, id: &str, connections: &'a Connections) -> Result<DeviceWithOutputPort<'a>, Error> {
+    pub fn get_output_port<'a>(&self, id: &str, connections: &'a dyn PortFactory) -> Result<DeviceWithOutputPort<'a>, Error> {
         let device = self.get(id).ok_or(Error::DeviceNotFound)?;
         let port = device.get_output_port(connections)?;
         Ok(DeviceWithOutputPort {
@@ -53,9 +57,13 @@ impl From<&config::Config> for Devices {
                 id: device_id.to_string(),
                 name: device_config.name.to_string(),
                 device_type: device_config.device_type.clone(),
+                buffer_size: device_config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE),
+                sysex_gap: device_config.sysex_gap_ms.map(Duration::from_millis),
                 features: match device_config.device_type {
-                    config::DeviceType::Default => Arc::new(default::DefaultFeatures::new()),
-                    config::DeviceType::LaunchpadPro => Arc::new(launchpadpro::LaunchpadProFeatures::new()),
+                    config::DeviceType::Default => Arc::new(default::DefaultFeatures::from(device_config)),
+                    config::DeviceType::Launchkey => Arc::new(launchkey::LaunchkeyFeatures::new()),
+                    config::DeviceType::LaunchpadPro => Arc::new(launchpadpro::LaunchpadProFeatures::from(device_config)),
+                    config::DeviceType::ApcMini => Arc::new(apcmini::ApcMiniFeatures::from(device_config)),
                 },
             });
         }
@@ -64,20 +72,37 @@ impl From<&config::Config> for Devices {
     }
 }
 
+/// Features of the given device type, with its default options
+pub fn get_default_features(device_type: config::DeviceType) -> Arc<dyn Features + Sync + Send> {
+    return match device_type {
+        config::DeviceType::Default => Arc::new(default::DefaultFeatures::new()),
+        config::DeviceType::Launchkey => Arc::new(launchkey::LaunchkeyFeatures::new()),
+        config::DeviceType::LaunchpadPro => Arc::new(launchpadpro::LaunchpadProFeatures::new()),
+        config::DeviceType::ApcMini => Arc::new(apcmini::ApcMiniFeatures::new()),
+    };
+}
+
 pub struct Device {
     pub id: String,
     pub name: String,
     pub device_type: config::DeviceType,
+    pub buffer_size: usize,
+    pub sysex_gap: Option<Duration>,
     pub features: Arc<dyn Features + Sync + Send>,
 }
 
 impl Device {
-    pub fn get_input_port<'a>(&self, connections: &'a Connections) -> Result<InputPort<'a>, Error> {
-        return connections.create_input_port(&self.name);
+    pub fn get_input_port<'a, P: PortFactory + ?Sized>(&self, ports: &'a P) -> Result<Box<dyn Reader + 'a>, Error> {
+        return ports.create_input_port(&self.name, self.buffer_size);
     }
 
-    pub fn get_output_port<'a>(&self, connections: &'a Connections) -> Result<OutputPort<'a>, Error> {
-        return connections.create_output_port(&self.name);
+    pub fn get_output_port<'a, P: PortFactory + ?Sized>(&self, ports: &'a P) -> Result<Box<dyn Writer + 'a>, Error> {
+        let port = ports.create_output_port(&self.name, self.buffer_size)?;
+        let port: Box<dyn Writer + 'a> = match self.sysex_gap {
+            Some(gap) => Box::new(SysExGapWriter::new(port, gap, std::thread::sleep)),
+            None => port,
+        };
+        return Ok(port);
     }
 }
 
@@ -86,7 +111,8 @@ pub struct DeviceWithInputPort<'a> {
     pub name: String,
     pub device_type: config::DeviceType,
     pub features: Arc<dyn Features + Sync + Send>,
-    pub port: InputPort<'a>,
+    /// A trait object, so that the router can be tested with fake ports
+    pub port: Box<dyn Reader + 'a>,
 }
 
 pub struct DeviceWithOutputPort<'a> {
@@ -94,5 +120,85 @@ pub struct DeviceWithOutputPort<'a> {
     pub name: String,
     pub device_type: config::DeviceType,
     pub features: Arc<dyn Features + Sync + Send>,
-    pub port: OutputPort<'a>,
+    /// A trait object, so that the router can be tested with fake ports
+    pub port: Box<dyn Writer + 'a>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use super::*;
+
+    /// Record the parameters given to PortMidi, then fail as no device is plugged
+    struct FakePortFactory {
+        calls: RefCell<Vec<(String, usize)>>,
+    }
+    impl PortFactory for FakePortFactory {
+        fn get_device_names(&self) -> Vec<String> {
+            return vec![];
+        }
+
+        fn create_input_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Reader + '_>, Error> {
+            self.calls.borrow_mut().push((name.clone(), buffer_size));
+            return Err(Error::DeviceNotFound);
+        }
+
+        fn create_output_port(&self, name: &String, buffer_size: usize) -> Result<Box<dyn Writer + '_>, Error> {
+            self.calls.borrow_mut().push((name.clone(), buffer_size));
+            return Err(Error::DeviceNotFound);
+        }
+    }
+
+    fn get_devices(buffer_size: Option<usize>) -> Devices {
+        let config = vec![("keyboard".to_string(), config::DeviceConfig {
+            name: "Arturia KeyStep 37".to_string(),
+            device_type: config::DeviceType::Default,
+            color_palette_row: None,
+            grid_pads: vec![],
+            palette_encoder: None,
+            highlight_color: None,
+            palette_lighting: false,
+            normalize_images: false,
+            image_palette: None,
+            origin: config::Origin::BottomLeft,
+            color_palette_edge: config::Edge::Bottom,
+            grid_layout: config::GridLayout::RowMajor,
+            inactive_app_brightness: None,
+            brightness: None,
+            global_actions: vec![],
+            buffer_size,
+            sysex_gap_ms: None,
+            sysex_macros: HashMap::new(),
+            on_connect: vec![],
+        })].into_iter().collect();
+        return Devices::from(&config);
+    }
+
+    #[test]
+    fn get_ports_when_buffer_size_configured_then_pass_it_to_port_midi() {
+        let ports = FakePortFactory { calls: RefCell::new(vec![]) };
+        let device = get_devices(Some(64)).devices.remove("keyboard").unwrap();
+
+        let _ = device.get_input_port(&ports);
+        let _ = device.get_output_port(&ports);
+
+        assert_eq!(ports.calls.into_inner(), vec![
+            ("Arturia KeyStep 37".to_string(), 64),
+            ("Arturia KeyStep 37".to_string(), 64),
+        ]);
+    }
+
+    #[test]
+    fn get_ports_when_buffer_size_not_configured_then_pass_the_default_one_to_port_midi() {
+        let ports = FakePortFactory { calls: RefCell::new(vec![]) };
+        let device = get_devices(None).devices.remove("keyboard").unwrap();
+
+        let _ = device.get_input_port(&ports);
+        let _ = device.get_output_port(&ports);
+
+        assert_eq!(ports.calls.into_inner(), vec![
+            ("Arturia KeyStep 37".to_string(), 1024),
+            ("Arturia KeyStep 37".to_string(), 1024),
+        ]);
+    }
 }
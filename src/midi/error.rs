@@ -10,6 +10,7 @@ pub enum Error {
     ReadError,
     WriteError,
     OutOfBoundIndexError,
+    InvalidSysExError,
 }
 
 impl fmt::Display for Error {
@@ -22,6 +23,7 @@ impl fmt::Display for Error {
             Error::ReadError => write!(f, "[midi] could not read an event"),
             Error::WriteError => write!(f, "[midi] could not write an event"),
             Error::OutOfBoundIndexError => write!(f, "[midi] could not handle index"),
+            Error::InvalidSysExError => write!(f, "[midi] SysEx messages must start with 240, end with 247, and only contain 7-bit data"),
         }
     }
 }
@@ -1,10 +1,14 @@
-use std::convert::From;
+use std::convert::{From, TryFrom};
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display, Error, Formatter};
 
-use crate::image::Image;
+use serde::{Serialize, Deserialize};
 
-use super::Event;
+use crate::image::{render_test_pattern, Image, TestPattern};
+
+use super::{Event, MidiMessage, Writer};
+use super::devices::config::{GridLayout, Origin};
+use super::devices::grid_geometry::GridGeometry;
 
 pub type R<A> = Result<A, Box<dyn StdError + Send>>;
 
@@ -25,7 +29,7 @@ impl From<&'static str> for UnsupportedFeatureError {
     }
 }
 
-pub trait Features: AppSelector + ColorPalette + GridController + ImageRenderer + IndexSelector {}
+pub trait Features: AppSelector + ColorPalette + ConnectionHandler + FaderController + GlobalActionTrigger + GridController + ImageRenderer + IndexSelector + PlaylistSelector + SaveGestureTrigger {}
 
 /// An app selector is a device that provides a UI to switch between different midi-hub apps.
 pub trait AppSelector {
@@ -34,24 +38,24 @@ pub trait AppSelector {
 
     /// If the device supports it, it will be passed a vector of colors,
     /// to light the "app-selection" UI elements with their corresponding color.
-    fn from_app_colors(&self, app_colors: Vec<[u8; 3]>) -> R<Event>;
+    /// The index of the selected app is given for devices to set it apart from the others.
+    fn from_app_colors(&self, app_colors: Vec<[u8; 3]>, selected_app: Option<usize>) -> R<Event>;
 }
 
 impl<T> AppSelector for T {
     /// This default implementation uses note-down events for notes from the C-1/B-1 octave.
     default fn into_app_index(&self, event: Event) -> R<Option<usize>> {
-        match event {
-            // 144: note-down
-            // data1 < 12: corresponds to the C-1/B-1 octave
-            // data2 > 0: corresponds to the velocity (the key really needs to be pressed)
-            Event::Midi([144, data1, data2, _]) if data1 < 12 && data2 > 0 => {
-                Ok(Some(data1.into()))
+        match MidiMessage::try_from(event) {
+            // 144: note-down, with a strictly positive velocity (the key really needs to be pressed)
+            // note < 12: corresponds to the C-1/B-1 octave
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 && note < 12 => {
+                Ok(Some(note.into()))
             },
             _ => Ok(None),
         }
     }
 
-    default fn from_app_colors(&self, _app_colors: Vec<[u8; 3]>) -> R<Event> {
+    default fn from_app_colors(&self, _app_colors: Vec<[u8; 3]>, _selected_app: Option<usize>) -> R<Event> {
         Err(Box::new(UnsupportedFeatureError::from("app-selector:from_app_colors")))
     }
 }
@@ -62,9 +66,17 @@ pub trait ColorPalette {
     /// triggering the selection of the corresponding color.
     fn into_color_palette_index(&self, event: Event) -> R<Option<usize>>;
 
+    /// Convert a MIDI event into a number of colors to move the selection by (e.g. when turning
+    /// an endless encoder), a negative number moving it towards the first color.
+    fn into_palette_delta(&self, event: Event) -> R<Option<i8>>;
+
     /// If the device supports it, it will be passed a vector of colors,
     /// to light the "color-palette" UI elements with their corresponding color.
     fn from_color_palette(&self, app_colors: Vec<[u8; 3]>) -> R<Event>;
+
+    /// Devices that cannot light all the "color-palette" UI elements with a single event (e.g.
+    /// those expecting one note-on per LED) can override this function instead.
+    fn from_color_palette_to_events(&self, colors: Vec<[u8; 3]>) -> R<Vec<Event>>;
 }
 
 impl<T> ColorPalette for T {
@@ -72,9 +84,70 @@ impl<T> ColorPalette for T {
         Err(Box::new(UnsupportedFeatureError::from("color-palette:into_color_index")))
     }
 
+    default fn into_palette_delta(&self, _event: Event) -> R<Option<i8>> {
+        Err(Box::new(UnsupportedFeatureError::from("color-palette:into_palette_delta")))
+    }
+
     default fn from_color_palette(&self, _colors: Vec<[u8; 3]>) -> R<Event> {
         Err(Box::new(UnsupportedFeatureError::from("color-palette:from_color_palette")))
     }
+
+    default fn from_color_palette_to_events(&self, colors: Vec<[u8; 3]>) -> R<Vec<Event>> {
+        self.from_color_palette(colors).map(|event| vec![event])
+    }
+}
+
+/// A fader controller is a device with faders (or knobs) sending absolute values.
+pub trait FaderController {
+    /// Convert a MIDI event into the index of the fader being moved, and its position
+    /// (from 0 at the bottom to 127 at the top), if any.
+    fn into_fader_value(&self, event: Event) -> R<Option<(usize, u8)>>;
+}
+
+impl<T> FaderController for T {
+    default fn into_fader_value(&self, _event: Event) -> R<Option<(usize, u8)>> {
+        Err(Box::new(UnsupportedFeatureError::from("fader-controller:into_fader_value")))
+    }
+}
+
+/// Actions handled by the router itself, whatever the app receiving the events of the device
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlobalAction {
+    /// Select the app following the selected one (in selection mode)
+    Next,
+    /// Select the app preceding the selected one (in selection mode)
+    Previous,
+    /// Toggle writing to devices
+    Freeze,
+    /// Silence every output device
+    Panic,
+}
+
+/// A connection handler is a device that needs to be put back in a given state when the hub stops
+/// using it, so that it remains usable on its own.
+pub trait ConnectionHandler {
+    /// Write whatever restores the device to its standalone state, on shutdown.
+    fn on_disconnect(&self, port: &mut dyn Writer) -> R<()>;
+}
+
+impl<T> ConnectionHandler for T {
+    /// Most devices have nothing to restore
+    default fn on_disconnect(&self, _port: &mut dyn Writer) -> R<()> {
+        Ok(())
+    }
+}
+
+/// A global action trigger is a device whose buttons can be dedicated to global actions.
+pub trait GlobalActionTrigger {
+    /// Convert a MIDI event into the global action it triggers, if any.
+    fn into_global_action(&self, event: Event) -> R<Option<GlobalAction>>;
+}
+
+impl<T> GlobalActionTrigger for T {
+    default fn into_global_action(&self, _event: Event) -> R<Option<GlobalAction>> {
+        Err(Box::new(UnsupportedFeatureError::from("global-action-trigger:into_global_action")))
+    }
 }
 
 /// A grid controller is typically a MIDI device with pads arranged on a grid layout.
@@ -103,12 +176,51 @@ impl<T> GridController for T {
 /// so that an image can be rendered (in low quality, admittedly).
 pub trait ImageRenderer: GridController {
     fn from_image(&self, image: Image) -> R<Event>;
+
+    /// Render one image per pad, starting from the top-left corner and going from left to right,
+    /// then from top to bottom. The device expects as many images as it has pads.
+    fn from_images(&self, images: Vec<Image>) -> R<Event>;
+
+    /// Render a number of one or two digits (e.g. the number of the track being played).
+    fn from_number(&self, n: u16, color: [u8; 3]) -> R<Event>;
+
+    /// Light a single pad, leaving the others untouched, (0, 0) being the top-left corner.
+    fn from_pixel(&self, x: usize, y: usize, color: [u8; 3]) -> R<Event>;
+
+    /// Scale the colors of everything rendered from now on by the given brightness (from 0 to 1).
+    fn set_brightness(&self, brightness: f32) -> R<()>;
+
+    /// Render a known pattern on the whole grid, to tune how the device renders colors.
+    fn from_test_pattern(&self, pattern: TestPattern) -> R<Event>;
 }
 
 impl<T> ImageRenderer for T {
     default fn from_image(&self, _image: Image) -> R<Event> {
         Err(Box::new(UnsupportedFeatureError::from("image-renderer:from_image")))
     }
+
+    default fn from_images(&self, _images: Vec<Image>) -> R<Event> {
+        Err(Box::new(UnsupportedFeatureError::from("image-renderer:from_images")))
+    }
+
+    default fn from_number(&self, _n: u16, _color: [u8; 3]) -> R<Event> {
+        Err(Box::new(UnsupportedFeatureError::from("image-renderer:from_number")))
+    }
+
+    default fn from_pixel(&self, _x: usize, _y: usize, _color: [u8; 3]) -> R<Event> {
+        Err(Box::new(UnsupportedFeatureError::from("image-renderer:from_pixel")))
+    }
+
+    default fn set_brightness(&self, _brightness: f32) -> R<()> {
+        Err(Box::new(UnsupportedFeatureError::from("image-renderer:set_brightness")))
+    }
+
+    /// The default implementation renders the pattern as an image of the size of the grid, so
+    /// that it goes through the same options (normalization, palette, etc.) as any other image.
+    default fn from_test_pattern(&self, pattern: TestPattern) -> R<Event> {
+        let (width, height) = self.get_grid_size()?;
+        return self.from_image(render_test_pattern(pattern, width, height));
+    }
 }
 
 /// An index selector is a device that can be used to select an item in a collection.
@@ -116,9 +228,20 @@ impl<T> ImageRenderer for T {
 pub trait IndexSelector {
     fn into_index(&self, event: Event) -> R<Option<usize>>;
 
+    /// Convert a MIDI event into the index whose UI element is being released, if any.
+    fn into_released_index(&self, event: Event) -> R<Option<usize>>;
+
     /// This function will be called to highlight the UI element of the device
     /// corresponding to the index being currently selected.
     fn from_index_to_highlight(&self, index: usize) -> R<Event>;
+
+    /// Like `from_index_to_highlight`, but lighting the UI element with the given color,
+    /// e.g. to show where playback got paused.
+    fn from_index_to_highlight_with_color(&self, index: usize, color: [u8; 3]) -> R<Event>;
+
+    /// Coordinates of the pad selecting the given index, (0, 0) being the top-left corner,
+    /// e.g. to render the indices that do something as an image.
+    fn from_index_to_coordinates(&self, index: usize) -> R<Option<(usize, usize)>>;
 }
 
 impl<T> IndexSelector for T {
@@ -129,13 +252,28 @@ impl<T> IndexSelector for T {
     /// ║C2╚╦╝D2╚╦╝E2║F2╚╦╝G2╚╦╝A2...
     /// ╚═══╩════╩═══╩═══╩════╩═══
     default fn into_index(&self, event: Event) -> R<Option<usize>> {
-         return match event {
+         return match MidiMessage::try_from(event) {
             // filter "note down" events, for notes higher than C2 (36), and with strictly positive velocity
-            // 144: note-down
-            // data1 >= 36: corresponds to C2 and upwards
-            // data2 > 0: corresponds to the velocity (the key really needs to be pressed)
-            Event::Midi([144, data1, data2, _]) if data1 >= 36 && data2 > 0 => {
-                Ok(Some((data1 - 36).into()))
+            // 144: note-down, with a strictly positive velocity (the key really needs to be pressed)
+            // note >= 36: corresponds to C2 and upwards
+            Ok(MidiMessage::NoteOn { channel: 0, note, velocity }) if velocity > 0 && note >= 36 => {
+                Ok(Some((note - 36).into()))
+            },
+            _ => Ok(None),
+        };
+    }
+
+    /// The default implementation turns "note up" events (and their "note down" or "controller"
+    /// equivalents with a null velocity) into "note down" events, and gives them to `into_index`.
+    default fn into_released_index(&self, event: Event) -> R<Option<usize>> {
+        return match MidiMessage::try_from(event) {
+            // note-up, or note-down with a null velocity
+            Ok(MidiMessage::NoteOff { channel: 0, note, .. }) | Ok(MidiMessage::NoteOn { channel: 0, note, velocity: 0 }) => {
+                self.into_index(MidiMessage::NoteOn { channel: 0, note, velocity: 127 }.into())
+            },
+            // controller off
+            Ok(MidiMessage::ControlChange { channel: 0, control, value: 0 }) => {
+                self.into_index(MidiMessage::ControlChange { channel: 0, control, value: 127 }.into())
             },
             _ => Ok(None),
         };
@@ -144,4 +282,41 @@ impl<T> IndexSelector for T {
     default fn from_index_to_highlight(&self, _index: usize) -> R<Event> {
         Err(Box::new(UnsupportedFeatureError::from("index-selector:from_index_to_highlight")))
     }
+
+    default fn from_index_to_highlight_with_color(&self, _index: usize, _color: [u8; 3]) -> R<Event> {
+        Err(Box::new(UnsupportedFeatureError::from("index-selector:from_index_to_highlight_with_color")))
+    }
+
+    /// The default implementation assumes that indices start from the bottom-left pad of the
+    /// grid, and go from left to right, then from bottom to top.
+    default fn from_index_to_coordinates(&self, index: usize) -> R<Option<(usize, usize)>> {
+        let (width, height) = self.get_grid_size()?;
+        let geometry = GridGeometry { width, height, origin: Origin::BottomLeft, layout: GridLayout::RowMajor };
+        return Ok(geometry.index_to_xy(index));
+    }
+}
+
+/// A playlist selector is a device that provides a UI to switch between several playlists.
+pub trait PlaylistSelector {
+    /// Convert a MIDI event into the index of the playlist to switch to, if any.
+    fn into_playlist_index(&self, event: Event) -> R<Option<usize>>;
+}
+
+impl<T> PlaylistSelector for T {
+    default fn into_playlist_index(&self, _event: Event) -> R<Option<usize>> {
+        Err(Box::new(UnsupportedFeatureError::from("playlist-selector:into_playlist_index")))
+    }
+}
+
+/// A save gesture trigger is a device with a button reserved to saving what is being played,
+/// e.g. adding the current track to the user’s library.
+pub trait SaveGestureTrigger {
+    /// Return whether the MIDI event is the press of the save button.
+    fn into_save_gesture(&self, event: Event) -> R<bool>;
+}
+
+impl<T> SaveGestureTrigger for T {
+    default fn into_save_gesture(&self, _event: Event) -> R<bool> {
+        Err(Box::new(UnsupportedFeatureError::from("save-gesture-trigger:into_save_gesture")))
+    }
 }
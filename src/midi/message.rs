@@ -0,0 +1,185 @@
+use std::convert::TryFrom;
+
+use super::Event;
+
+/// Channel-voice messages, decoded from the status and data bytes of MIDI events.
+/// Channels go from 0 to 15, and data values from 0 to 127.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MidiMessage {
+    /// 128-143
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    /// 144-159: a null velocity releases the note, like a note-off
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    /// 160-175: the pressure applied on a single key
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    /// 176-191
+    ControlChange { channel: u8, control: u8, value: u8 },
+    /// 192-207
+    ProgramChange { channel: u8, program: u8 },
+    /// 208-223: the pressure applied on the whole channel
+    ChannelPressure { channel: u8, pressure: u8 },
+    /// 224-239: a 14-bit value, 8192 being the center
+    PitchBend { channel: u8, value: u16 },
+}
+
+impl MidiMessage {
+    pub fn channel(&self) -> u8 {
+        return match *self {
+            MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => channel,
+        };
+    }
+
+    /// The same message, sent on another channel
+    pub fn with_channel(self, channel: u8) -> MidiMessage {
+        let channel = channel & 0x0F;
+        return match self {
+            MidiMessage::NoteOff { note, velocity, .. } => MidiMessage::NoteOff { channel, note, velocity },
+            MidiMessage::NoteOn { note, velocity, .. } => MidiMessage::NoteOn { channel, note, velocity },
+            MidiMessage::PolyPressure { note, pressure, .. } => MidiMessage::PolyPressure { channel, note, pressure },
+            MidiMessage::ControlChange { control, value, .. } => MidiMessage::ControlChange { channel, control, value },
+            MidiMessage::ProgramChange { program, .. } => MidiMessage::ProgramChange { channel, program },
+            MidiMessage::ChannelPressure { pressure, .. } => MidiMessage::ChannelPressure { channel, pressure },
+            MidiMessage::PitchBend { value, .. } => MidiMessage::PitchBend { channel, value },
+        };
+    }
+}
+
+/// SysEx messages, system messages and messages with invalid data bytes cannot be decoded:
+/// the event is given back, so that it can be passed through as it is.
+impl TryFrom<Event> for MidiMessage {
+    type Error = Event;
+
+    fn try_from(event: Event) -> Result<MidiMessage, Event> {
+        let [status, data1, data2, _] = match event {
+            Event::Midi(bytes) if bytes[1] < 128 && bytes[2] < 128 => bytes,
+            event => return Err(event),
+        };
+
+        let channel = status & 0x0F;
+        return match status & 0xF0 {
+            0x80 => Ok(MidiMessage::NoteOff { channel, note: data1, velocity: data2 }),
+            0x90 => Ok(MidiMessage::NoteOn { channel, note: data1, velocity: data2 }),
+            0xA0 => Ok(MidiMessage::PolyPressure { channel, note: data1, pressure: data2 }),
+            0xB0 => Ok(MidiMessage::ControlChange { channel, control: data1, value: data2 }),
+            0xC0 => Ok(MidiMessage::ProgramChange { channel, program: data1 }),
+            0xD0 => Ok(MidiMessage::ChannelPressure { channel, pressure: data1 }),
+            0xE0 => Ok(MidiMessage::PitchBend { channel, value: (data2 as u16) << 7 | data1 as u16 }),
+            _ => Err(event),
+        };
+    }
+}
+
+/// Unused data bytes are set to 0, and so is the 4th byte: see `into_event_like` to keep it
+impl From<MidiMessage> for Event {
+    fn from(message: MidiMessage) -> Event {
+        return message.encode(0);
+    }
+}
+
+impl MidiMessage {
+    /// Encode the message, keeping the 4th byte of the event it replaces: channel-voice messages
+    /// don’t use it, but some devices still set it, and expect to get it back.
+    pub fn into_event_like(self, original: &Event) -> Event {
+        return match original {
+            Event::Midi([_, _, _, data3]) => self.encode(*data3),
+            Event::SysEx(_) => self.encode(0),
+        };
+    }
+
+    fn encode(self, data3: u8) -> Event {
+        let [status, data1, data2] = match self {
+            MidiMessage::NoteOff { channel, note, velocity } => [0x80 | channel, note, velocity],
+            MidiMessage::NoteOn { channel, note, velocity } => [0x90 | channel, note, velocity],
+            MidiMessage::PolyPressure { channel, note, pressure } => [0xA0 | channel, note, pressure],
+            MidiMessage::ControlChange { channel, control, value } => [0xB0 | channel, control, value],
+            MidiMessage::ProgramChange { channel, program } => [0xC0 | channel, program, 0],
+            MidiMessage::ChannelPressure { channel, pressure } => [0xD0 | channel, pressure, 0],
+            MidiMessage::PitchBend { channel, value } => [0xE0 | channel, (value & 0x7F) as u8, (value >> 7 & 0x7F) as u8],
+        };
+        return Event::Midi([status, data1, data2, data3]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_from_when_channel_voice_event_then_decode_status_and_channel() {
+        assert_eq!(MidiMessage::try_from(Event::Midi([128, 60, 0, 0])), Ok(MidiMessage::NoteOff { channel: 0, note: 60, velocity: 0 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([159, 60, 100, 0])), Ok(MidiMessage::NoteOn { channel: 15, note: 60, velocity: 100 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([161, 60, 90, 0])), Ok(MidiMessage::PolyPressure { channel: 1, note: 60, pressure: 90 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([178, 64, 127, 0])), Ok(MidiMessage::ControlChange { channel: 2, control: 64, value: 127 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([195, 12, 0, 0])), Ok(MidiMessage::ProgramChange { channel: 3, program: 12 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([212, 80, 0, 0])), Ok(MidiMessage::ChannelPressure { channel: 4, pressure: 80 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([229, 0, 64, 0])), Ok(MidiMessage::PitchBend { channel: 5, value: 8192 }));
+    }
+
+    #[test]
+    fn try_from_when_pitch_bend_then_combine_the_least_and_most_significant_bits() {
+        assert_eq!(MidiMessage::try_from(Event::Midi([224, 0, 0, 0])), Ok(MidiMessage::PitchBend { channel: 0, value: 0 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([224, 1, 0, 0])), Ok(MidiMessage::PitchBend { channel: 0, value: 1 }));
+        assert_eq!(MidiMessage::try_from(Event::Midi([224, 127, 127, 0])), Ok(MidiMessage::PitchBend { channel: 0, value: 16383 }));
+    }
+
+    #[test]
+    fn try_from_when_system_or_sysex_event_then_give_it_back() {
+        assert_eq!(MidiMessage::try_from(Event::Midi([248, 0, 0, 0])), Err(Event::Midi([248, 0, 0, 0])));
+        assert_eq!(MidiMessage::try_from(Event::Midi([60, 100, 0, 0])), Err(Event::Midi([60, 100, 0, 0])));
+        assert_eq!(MidiMessage::try_from(Event::SysEx(vec![240, 1, 247])), Err(Event::SysEx(vec![240, 1, 247])));
+    }
+
+    #[test]
+    fn try_from_when_data_bytes_are_invalid_then_give_the_event_back() {
+        assert_eq!(MidiMessage::try_from(Event::Midi([144, 128, 100, 0])), Err(Event::Midi([144, 128, 100, 0])));
+        assert_eq!(MidiMessage::try_from(Event::Midi([144, 60, 200, 0])), Err(Event::Midi([144, 60, 200, 0])));
+    }
+
+    #[test]
+    fn from_when_decoding_every_status_and_channel_then_round_trip() {
+        for status in 128..240u8 {
+            // program changes and channel pressure only have one data byte
+            let event = if (192..224).contains(&status) {
+                Event::Midi([status, 42, 0, 0])
+            } else {
+                Event::Midi([status, 42, 101, 0])
+            };
+            let message = MidiMessage::try_from(event.clone()).expect("channel-voice events should be decoded");
+            assert_eq!(message.channel(), status & 0x0F);
+            assert_eq!(Event::from(message), event);
+        }
+    }
+
+    #[test]
+    fn from_when_pitch_bend_then_round_trip_every_value() {
+        for value in 0..16384u16 {
+            let message = MidiMessage::PitchBend { channel: 9, value };
+            assert_eq!(MidiMessage::try_from(Event::from(message)), Ok(message));
+        }
+    }
+
+    #[test]
+    fn into_event_like_then_keep_the_fourth_byte_of_the_original_event() {
+        let original = Event::Midi([144, 60, 100, 42]);
+        let message = MidiMessage::try_from(original.clone()).expect("note-on events should be decoded");
+        assert_eq!(message.into_event_like(&original), original);
+        assert_eq!(message.with_channel(3).into_event_like(&original), Event::Midi([147, 60, 100, 42]));
+        assert_eq!(Event::from(message), Event::Midi([144, 60, 100, 0]));
+    }
+
+    #[test]
+    fn with_channel_then_keep_the_data_of_the_message() {
+        let message = MidiMessage::NoteOn { channel: 0, note: 60, velocity: 100 }.with_channel(9);
+        assert_eq!(message, MidiMessage::NoteOn { channel: 9, note: 60, velocity: 100 });
+        assert_eq!(Event::from(message), Event::Midi([153, 60, 100, 0]));
+
+        let message = MidiMessage::PitchBend { channel: 3, value: 1000 }.with_channel(16 + 2);
+        assert_eq!(message, MidiMessage::PitchBend { channel: 2, value: 1000 });
+    }
+}
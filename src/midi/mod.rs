@@ -1,6 +1,7 @@
 mod connections;
 mod device;
 mod error;
+mod message;
 
 pub mod devices;
 pub mod features;
@@ -9,3 +10,4 @@ pub use connections::*;
 pub use device::*;
 pub use devices::Devices;
 pub use error::Error;
+pub use message::MidiMessage;
@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+/// Delays applied between two attempts to connect to the MIDI devices
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    /// Delay before the first reconnection attempt (1s by default)
+    pub initial_delay_ms: u64,
+    /// The delay stops doubling once it reaches this value (60s by default)
+    pub max_delay_ms: u64,
+    /// Number of times the creation of a port gets retried, when the OS briefly holds the device
+    /// (e.g. right after it got plugged in). 3 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_retries: Option<u32>,
+    /// Delay between two attempts to create a port (100ms by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_retry_delay_ms: Option<u64>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        return ReconnectConfig {
+            initial_delay_ms: 1_000,
+            max_delay_ms: 60_000,
+            port_retries: None,
+            port_retry_delay_ms: None,
+        };
+    }
+}
+
+/// The delay doubles after every failed attempt, until it reaches the maximum delay.
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    failed_attempts: u32,
+}
+
+impl From<&ReconnectConfig> for Backoff {
+    fn from(config: &ReconnectConfig) -> Self {
+        return Backoff {
+            initial_delay: Duration::from_millis(config.initial_delay_ms),
+            max_delay: Duration::from_millis(config.max_delay_ms),
+            failed_attempts: 0,
+        };
+    }
+}
+
+impl Backoff {
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2u32.checked_pow(self.failed_attempts).unwrap_or(u32::MAX);
+        let delay = self.initial_delay.checked_mul(factor).unwrap_or(self.max_delay);
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        return delay.min(self.max_delay);
+    }
+
+    /// Wait for the next delay, using the given sleep function
+    pub fn wait<F>(&mut self, mut sleep: F) where F: FnMut(Duration) {
+        sleep(self.next_delay());
+    }
+
+    pub fn reset(&mut self) {
+        self.failed_attempts = 0;
+    }
+}
+
+/// Keep track of missing devices, so that we only log when their availability changes
+#[derive(Default)]
+pub struct MissingDevices {
+    devices: HashSet<String>,
+}
+
+impl MissingDevices {
+    /// Return the devices that went missing, and the ones that got found, since the last update.
+    pub fn update(&mut self, missing_devices: HashSet<String>) -> (Vec<String>, Vec<String>) {
+        let mut lost = missing_devices.difference(&self.devices).map(|id| id.clone()).collect::<Vec<String>>();
+        let mut found = self.devices.difference(&missing_devices).map(|id| id.clone()).collect::<Vec<String>>();
+        lost.sort();
+        found.sort();
+
+        self.devices = missing_devices;
+        return (lost, found);
+    }
+
+    pub fn is_missing(&self, device: &str) -> bool {
+        return self.devices.contains(device);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_backoff() -> Backoff {
+        return Backoff::from(&ReconnectConfig { initial_delay_ms: 1_000, max_delay_ms: 10_000, ..ReconnectConfig::default() });
+    }
+
+    #[test]
+    fn wait_when_failing_repeatedly_then_double_the_delay_until_the_max_delay() {
+        let mut backoff = get_backoff();
+        let mut delays = vec![];
+
+        for _ in 0..6 {
+            backoff.wait(|delay| delays.push(delay));
+        }
+
+        assert_eq!(delays, vec![1_000, 2_000, 4_000, 8_000, 10_000, 10_000]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect::<Vec<Duration>>());
+    }
+
+    #[test]
+    fn wait_when_reset_then_start_from_the_initial_delay_again() {
+        let mut backoff = get_backoff();
+        let mut delays = vec![];
+
+        backoff.wait(|delay| delays.push(delay));
+        backoff.wait(|delay| delays.push(delay));
+        backoff.reset();
+        backoff.wait(|delay| delays.push(delay));
+
+        assert_eq!(delays, vec![1_000, 2_000, 1_000]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect::<Vec<Duration>>());
+    }
+
+    #[test]
+    fn next_delay_when_failing_for_a_very_long_time_then_do_not_overflow() {
+        let mut backoff = get_backoff();
+        for _ in 0..100 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), Duration::from_millis(10_000));
+    }
+
+    #[test]
+    fn update_should_only_return_changes_in_availability() {
+        let mut missing_devices = MissingDevices::default();
+        let set = |ids: Vec<&str>| ids.into_iter().map(|id| id.to_string()).collect::<HashSet<String>>();
+
+        assert_eq!(missing_devices.update(set(vec!["a", "b"])), (vec!["a".to_string(), "b".to_string()], vec![]));
+        assert_eq!(missing_devices.update(set(vec!["a", "b"])), (vec![], vec![]));
+        assert_eq!(missing_devices.update(set(vec!["b", "c"])), (vec!["c".to_string()], vec!["a".to_string()]));
+        assert_eq!(missing_devices.update(set(vec![])), (vec![], vec!["b".to_string(), "c".to_string()]));
+    }
+}
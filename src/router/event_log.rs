@@ -0,0 +1,166 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use crate::midi::Event;
+
+const DEFAULT_MAX_SIZE_BYTES: u64 = 10_000_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventLogConfig {
+    /// File the events get appended to, as newline-delimited JSON
+    pub path: PathBuf,
+    /// Once the file would grow beyond this size, it gets renamed with a `.1` suffix (replacing
+    /// the previous one), and a new file gets started (10MB by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_bytes: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Read from a device, and sent to an app
+    In,
+    /// Emitted by an app, and written to a device
+    Out,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    timestamp_ms: u128,
+    direction: Direction,
+    app: &'a str,
+    device: &'a str,
+    event: &'a Event,
+}
+
+/// Every event routed between apps and devices, for auditing purposes.
+///
+/// Failing to write an entry is logged, but never gets in the way of routing events.
+pub struct EventLog {
+    path: PathBuf,
+    max_size_bytes: u64,
+    /// Opened on the first entry, along with its current size
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl EventLog {
+    pub fn new(config: &EventLogConfig) -> Self {
+        return EventLog {
+            path: config.path.clone(),
+            max_size_bytes: config.max_size_bytes.unwrap_or(DEFAULT_MAX_SIZE_BYTES),
+            file: Mutex::new(None),
+        };
+    }
+
+    pub fn log(&self, direction: Direction, app: &str, device: &str, event: &Event) {
+        let line = format_entry(SystemTime::now(), direction, app, device, event);
+        self.write_line(&line).unwrap_or_else(|err| {
+            eprintln!("[router] could not write to the event log {}: {}", self.path.display(), err);
+        });
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        let size = match file.as_ref() {
+            Some((_, size)) => *size,
+            None => fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or(0),
+        };
+
+        if should_rotate(size, line.len() as u64, self.max_size_bytes) {
+            // The file must be closed before being renamed, on some platforms
+            *file = None;
+            fs::rename(&self.path, get_rotated_path(&self.path))?;
+        }
+
+        if file.is_none() {
+            let opened = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            let size = opened.metadata()?.len();
+            *file = Some((opened, size));
+        }
+
+        let (opened, size) = file.as_mut().expect("the event log should be open");
+        opened.write_all(line.as_bytes())?;
+        *size += line.len() as u64;
+        return Ok(());
+    }
+}
+
+/// A single JSON object per line, e.g.
+/// `{"timestamp_ms":1700000000000,"direction":"in","app":"forward","device":"keyboard","event":{"Midi":[144,36,100,0]}}`
+fn format_entry(timestamp: SystemTime, direction: Direction, app: &str, device: &str, event: &Event) -> String {
+    let timestamp_ms = timestamp.duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or(0);
+    let entry = Entry { timestamp_ms, direction, app, device, event };
+    let json = serde_json::to_string(&entry).expect("events should always be serializable");
+    return format!("{}\n", json);
+}
+
+/// A single entry bigger than the limit still gets written to an empty file
+fn should_rotate(size: u64, line_size: u64, max_size: u64) -> bool {
+    return size > 0 && size + line_size > max_size;
+}
+
+fn get_rotated_path(path: &Path) -> PathBuf {
+    let mut rotated_path = OsString::from(path.as_os_str());
+    rotated_path.push(".1");
+    return PathBuf::from(rotated_path);
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn format_entry_should_write_one_json_object_per_line() {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        assert_eq!(
+            format_entry(timestamp, Direction::In, "forward", "keyboard", &Event::Midi([144, 36, 100, 0])),
+            "{\"timestamp_ms\":1700000000123,\"direction\":\"in\",\"app\":\"forward\",\"device\":\"keyboard\",\"event\":{\"Midi\":[144,36,100,0]}}\n",
+        );
+        assert_eq!(
+            format_entry(timestamp, Direction::Out, "paint", "launchpad", &Event::SysEx(vec![240, 0, 247])),
+            "{\"timestamp_ms\":1700000000123,\"direction\":\"out\",\"app\":\"paint\",\"device\":\"launchpad\",\"event\":{\"SysEx\":[240,0,247]}}\n",
+        );
+    }
+
+    #[test]
+    fn should_rotate_when_the_entry_would_exceed_the_max_size_then_return_true() {
+        assert!(!should_rotate(0, 50, 100));
+        assert!(!should_rotate(50, 50, 100));
+        assert!(should_rotate(51, 50, 100));
+        // entries bigger than the limit do not make empty files rotate forever
+        assert!(!should_rotate(0, 150, 100));
+    }
+
+    #[test]
+    fn log_when_the_file_is_full_then_rotate_it() {
+        let path = std::env::temp_dir().join(format!("midi-hub-event-log-{}.jsonl", std::process::id()));
+        let rotated_path = get_rotated_path(&path);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&rotated_path);
+
+        let line_size = format_entry(SystemTime::now(), Direction::In, "forward", "keyboard", &Event::Midi([144, 36, 100, 0])).len() as u64;
+        let event_log = EventLog::new(&EventLogConfig { path: path.clone(), max_size_bytes: Some(line_size * 2) });
+
+        for _ in 0..3 {
+            event_log.log(Direction::In, "forward", "keyboard", &Event::Midi([144, 36, 100, 0]));
+        }
+
+        let lines = fs::read_to_string(&path).unwrap();
+        let rotated_lines = fs::read_to_string(&rotated_path).unwrap();
+        assert_eq!(lines.lines().count(), 1);
+        assert_eq!(rotated_lines.lines().count(), 2);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated_path).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::apps;
+use crate::image::Image;
+use crate::midi::{Devices, Event, MidiMessage};
+use crate::midi::features::Features;
+
+use super::{get_merged_input_names, Config, Mode};
+
+/// Configurations that are valid, but most likely not what users meant
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// Every event of the device gets sent to each of the apps
+    SharedInput { device: String, apps: Vec<String> },
+    /// The app cannot work as expected with the device
+    UnsupportedFeature { app: String, device: String, feature: Feature },
+    /// Some buttons of the color palette of the device also trigger another feature
+    PaletteConflict { device: String, feature: Feature },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Warning::SharedInput { device, apps } => {
+                write!(f, "device {} is the input of several apps ({}), which will all receive its events", device, apps.join(", "))
+            },
+            Warning::UnsupportedFeature { app, device, feature } => {
+                write!(f, "app {} needs device {} to be a {}, which it is not", app, device, feature.get_name())
+            },
+            Warning::PaletteConflict { device, feature } => {
+                write!(f, "the color palette of device {} shares buttons with its {}, which will get in the way of painting", device, feature.get_name())
+            },
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    AppSelector,
+    GlobalActionTrigger,
+    GridController,
+    ImageRenderer,
+    PlaylistSelector,
+    SaveGestureTrigger,
+}
+
+impl Feature {
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            Feature::AppSelector => "app selector",
+            Feature::GlobalActionTrigger => "global action trigger",
+            Feature::GridController => "grid controller",
+            Feature::ImageRenderer => "image renderer",
+            Feature::PlaylistSelector => "playlist selector",
+            Feature::SaveGestureTrigger => "save gesture trigger",
+        };
+    }
+
+    /// Features are implemented with specialization, so the only way to know whether a device
+    /// supports one is to call the method the app needs, and see if it returns an error.
+    /// Image renderers get probed with a blank image of the size of their grid.
+    fn is_supported_by(&self, features: &(dyn Features + Sync + Send)) -> bool {
+        return match self {
+            Feature::AppSelector => features.from_app_colors(vec![[0, 0, 0]], None).is_ok(),
+            Feature::GridController => features.get_grid_size().is_ok(),
+            Feature::ImageRenderer => features.get_grid_size()
+                .and_then(|(width, height)| features.from_image(Image { width, height, bytes: vec![0; width * height * 3] }))
+                .is_ok(),
+            _ => true,
+        };
+    }
+
+    /// Whether pressing one of the buttons of the color palette would also trigger this feature,
+    /// found by pressing every note and controller of the device.
+    fn shares_palette_buttons_of(&self, features: &(dyn Features + Sync + Send)) -> bool {
+        return (0..128u8)
+            .flat_map(|code| vec![
+                Event::from(MidiMessage::NoteOn { channel: 0, note: code, velocity: 127 }),
+                Event::from(MidiMessage::ControlChange { channel: 0, control: code, value: 127 }),
+            ])
+            .filter(|event| matches!(features.into_color_palette_index(event.clone()), Ok(Some(_))))
+            .any(|event| match self {
+                Feature::AppSelector => matches!(features.into_app_index(event), Ok(Some(_))),
+                Feature::GlobalActionTrigger => matches!(features.into_global_action(event), Ok(Some(_))),
+                Feature::PlaylistSelector => matches!(features.into_playlist_index(event), Ok(Some(_))),
+                Feature::SaveGestureTrigger => matches!(features.into_save_gesture(event), Ok(true)),
+                _ => false,
+            });
+    }
+}
+
+/// Features required from the input and output devices of the given app
+fn get_required_features(app_name: &str) -> (Vec<Feature>, Vec<Feature>) {
+    return match app_name {
+        apps::paint::app::NAME => (vec![Feature::GridController], vec![Feature::ImageRenderer]),
+        apps::spotify::app::NAME | apps::youtube::app::NAME => (vec![], vec![Feature::ImageRenderer]),
+        apps::selection::app::NAME => (vec![], vec![Feature::AppSelector]),
+        _ => (vec![], vec![]),
+    };
+}
+
+/// Features that would catch some of the events meant for the color palette of the paint app,
+/// given the other apps reading from its input device
+fn get_palette_conflicts(mode: &Mode, apps_on_input: &[&str]) -> Vec<Feature> {
+    let mut features = vec![Feature::GlobalActionTrigger];
+    // The selection app catches app indices before forwarding events to the selected app
+    if apps_on_input.contains(&apps::selection::app::NAME) {
+        features.push(Feature::AppSelector);
+    }
+    // In selection mode, the spotify app does not receive events while painting
+    if *mode == Mode::Direct && apps_on_input.contains(&apps::spotify::app::NAME) {
+        features.push(Feature::PlaylistSelector);
+        features.push(Feature::SaveGestureTrigger);
+    }
+    return features;
+}
+
+/// Look for misconfigurations, sorted so that they get reported in the same order every time.
+pub fn lint(config: &Config) -> Vec<Warning> {
+    let devices = Devices::from(&config.devices);
+    let mut warnings = vec![];
+
+    // In selection mode, every app uses the devices of the selection app, one at a time
+    let links: Vec<(String, String, String, Vec<String>)> = match &config.mode {
+        Mode::Direct => config.links.iter()
+            .map(|(app_name, (input, output))| (
+                app_name.clone(),
+                input.clone(),
+                output.clone(),
+                get_merged_input_names(&config.apps, app_name),
+            ))
+            .collect(),
+        Mode::Selection { input, output } => config.apps.get_configured_app_names().into_iter()
+            .filter(|app_name| app_name != apps::selection::app::NAME)
+            .chain(std::iter::once(apps::selection::app::NAME.to_string()))
+            .map(|app_name| (app_name, input.clone(), output.clone(), vec![]))
+            .collect(),
+    };
+
+    if config.mode == Mode::Direct {
+        let mut apps_by_input: HashMap<&str, Vec<String>> = HashMap::new();
+        for (app_name, input, _, merged_inputs) in &links {
+            for input in std::iter::once(input).chain(merged_inputs.iter()) {
+                apps_by_input.entry(input.as_str()).or_default().push(app_name.clone());
+            }
+        }
+
+        for (device, mut apps) in apps_by_input {
+            apps.sort();
+            apps.dedup();
+            if apps.len() > 1 {
+                warnings.push(Warning::SharedInput { device: device.to_string(), apps });
+            }
+        }
+    }
+
+    for (app_name, input, output, _) in &links {
+        let (input_features, output_features) = get_required_features(app_name);
+        let required = input_features.into_iter().map(|feature| (input, feature))
+            .chain(output_features.into_iter().map(|feature| (output, feature)));
+
+        for (device_id, feature) in required {
+            // Devices that are not configured get reported when starting the router
+            if let Some(device) = devices.get(device_id.as_str()) {
+                if !feature.is_supported_by(device.features.as_ref()) {
+                    warnings.push(Warning::UnsupportedFeature { app: app_name.clone(), device: device_id.clone(), feature });
+                }
+            }
+        }
+    }
+
+    for (_, input, _, _) in links.iter().filter(|(app_name, _, _, _)| app_name == apps::paint::app::NAME) {
+        if let Some(device) = devices.get(input.as_str()) {
+            let apps_on_input: Vec<&str> = links.iter()
+                .filter(|(_, other_input, _, merged_inputs)| other_input == input || merged_inputs.contains(input))
+                .map(|(app_name, _, _, _)| app_name.as_str())
+                .collect();
+
+            for feature in get_palette_conflicts(&config.mode, &apps_on_input) {
+                if feature.shares_palette_buttons_of(device.features.as_ref()) {
+                    warnings.push(Warning::PaletteConflict { device: input.clone(), feature });
+                }
+            }
+        }
+    }
+
+    warnings.sort_by_key(|warning| warning.to_string());
+    return warnings;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_config(apps_and_links: &str) -> Config {
+        return toml::from_str(format!(r#"
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            {}
+        "#, apps_and_links).as_str()).expect("the config should be valid");
+    }
+
+    #[test]
+    fn lint_when_each_app_has_its_own_capable_devices_then_return_no_warnings() {
+        let config = get_config(r#"
+            [apps.forward]
+            [apps.paint]
+
+            [links]
+            forward = ["keyboard", "keyboard"]
+            paint = ["launchpad", "launchpad"]
+        "#);
+        assert_eq!(lint(&config), vec![]);
+    }
+
+    #[test]
+    fn lint_when_a_device_is_the_input_of_several_apps_then_warn_about_it() {
+        let config = get_config(r#"
+            [apps.forward]
+            [apps.paint]
+
+            [links]
+            forward = ["launchpad", "keyboard"]
+            paint = ["launchpad", "launchpad"]
+        "#);
+        assert_eq!(lint(&config), vec![
+            Warning::SharedInput { device: "launchpad".to_string(), apps: vec!["forward".to_string(), "paint".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn lint_when_a_merged_input_is_the_input_of_another_app_then_warn_about_it() {
+        let config = get_config(r#"
+            [apps.forward]
+            merge_inputs = ["launchpad"]
+            [apps.paint]
+
+            [links]
+            forward = ["keyboard", "keyboard"]
+            paint = ["launchpad", "launchpad"]
+        "#);
+        assert_eq!(lint(&config), vec![
+            Warning::SharedInput { device: "launchpad".to_string(), apps: vec!["forward".to_string(), "paint".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn lint_when_a_device_lacks_the_features_of_its_app_then_warn_about_each_of_them() {
+        let config = get_config(r#"
+            [apps.paint]
+
+            [links]
+            paint = ["launchpad", "keyboard"]
+        "#);
+        assert_eq!(lint(&config), vec![
+            Warning::UnsupportedFeature { app: "paint".to_string(), device: "keyboard".to_string(), feature: Feature::ImageRenderer },
+        ]);
+        assert_eq!(lint(&config)[0].to_string(), "app paint needs device keyboard to be a image renderer, which it is not");
+    }
+
+    #[test]
+    fn lint_when_selection_mode_then_check_every_app_against_the_selection_devices() {
+        let config = get_config(r#"
+            mode = { type = "selection", input = "launchpad", output = "keyboard" }
+
+            [apps.forward]
+            [apps.paint]
+        "#);
+        assert_eq!(lint(&config), vec![
+            Warning::UnsupportedFeature { app: "paint".to_string(), device: "keyboard".to_string(), feature: Feature::ImageRenderer },
+            Warning::UnsupportedFeature { app: "selection".to_string(), device: "keyboard".to_string(), feature: Feature::AppSelector },
+        ]);
+    }
+
+    #[test]
+    fn lint_when_the_color_palette_shares_buttons_with_the_app_selector_then_warn_about_it() {
+        let config: Config = toml::from_str(r#"
+            mode = { type = "selection", input = "launchpad", output = "launchpad" }
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+            color_palette_edge = "right"
+
+            [apps.paint]
+        "#).expect("the config should be valid");
+        assert_eq!(lint(&config), vec![
+            Warning::PaletteConflict { device: "launchpad".to_string(), feature: Feature::AppSelector },
+        ]);
+    }
+
+    #[test]
+    fn lint_when_the_color_palette_is_on_its_default_edge_then_return_no_warnings() {
+        let config: Config = toml::from_str(r#"
+            mode = { type = "selection", input = "launchpad", output = "launchpad" }
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [apps.paint]
+        "#).expect("the config should be valid");
+        assert_eq!(lint(&config), vec![]);
+    }
+}
@@ -1,9 +1,9 @@
 extern crate signal_hook as sh;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -13,56 +13,179 @@ use tokio::sync::mpsc::error::TryRecvError;
 
 use crate::apps;
 use crate::apps::{App, Out};
+use crate::image::{Image, TestPattern};
 use crate::midi;
-use midi::{Connections, Error, Reader, Writer, Devices};
-use crate::server::HttpServer;
+use midi::{CoalescingReader, CoalescingState, Connections, Error, PortFactory, Reader, Writer, Devices};
+use midi::devices::{DeviceWithInputPort, DeviceWithOutputPort};
+use midi::features::{ConnectionHandler, Features, GlobalAction, GridController, ImageRenderer, UnsupportedFeatureError, R};
+use crate::server;
+use crate::server::{Command, HttpServer};
+
+mod backoff;
+use backoff::{Backoff, MissingDevices};
+pub use backoff::ReconnectConfig;
+
+mod event_log;
+use event_log::{Direction, EventLog};
+pub use event_log::EventLogConfig;
+
+mod lint;
+
+mod stats;
+pub use stats::Stats;
 
 const MIDI_DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(10_000);
 const MIDI_EVENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Color rendered on every output device during the self-test, and for how long
+const SELF_TEST_COLOR: [u8; 3] = [255, 255, 255];
+const SELF_TEST_DURATION: Duration = Duration::from_millis(500);
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub devices: midi::devices::config::Config,
     pub apps: apps::Config,
+    /// Ignored in selection mode
+    #[serde(default)]
     pub links: Links,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+    #[serde(default)]
+    pub server: server::config::Config,
+    /// Measure the time between reading an event and writing the output of its app
+    #[serde(default)]
+    pub measure_latency: bool,
+    /// Append every event read from or written to devices to a file, for auditing purposes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_log: Option<EventLogConfig>,
+    /// On startup, briefly light the whole grid of every output device able to render images,
+    /// so that devices refusing writes get reported before they are needed
+    #[serde(default)]
+    pub self_test: bool,
+    /// Window (in milliseconds) within which the control changes read for the given apps (by
+    /// link name) get coalesced, only keeping the latest value of each controller
+    #[serde(default)]
+    pub coalesce_cc_ms: HashMap<String, u64>,
 }
 
 pub type Links = HashMap<String, (String, String)>;
 
+/// How apps get linked to devices
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Mode {
+    /// Each app is linked to the devices given in the links
+    Direct,
+    /// A single selection app is linked to the given devices, and lets users switch between all
+    /// the configured apps
+    Selection { input: String, output: String },
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        return Mode::Direct;
+    }
+}
+
 pub struct Router {
     term: Arc<AtomicBool>,
     server: HttpServer,
     devices: Devices,
-    links: Vec<(Box<dyn App>, String, String)>,
+    /// Apps, with the ids of their input device, output device, and extra input devices
+    links: Vec<(Box<dyn App>, String, String, Vec<String>)>,
+    stats: Arc<Stats>,
+    backoff: Backoff,
+    port_retries: u32,
+    port_retry_delay: Duration,
+    missing_devices: MissingDevices,
+    configured_device_names: Vec<String>,
+    device_names_checked: bool,
+    connect_macros: HashMap<String, Vec<Vec<u8>>>,
+    connected_outputs: HashSet<String>,
+    frozen: bool,
+    /// Name of the only app whose MIDI events get written to devices, for debugging purposes
+    solo: Option<String>,
+    measure_latency: bool,
+    event_log: Option<EventLog>,
+    self_test: bool,
+    /// Control changes being coalesced for the links' input devices, by app name, kept across
+    /// cycles so that the ones pending when devices get polled again are not lost
+    coalescing_states: HashMap<String, Arc<Mutex<CoalescingState>>>,
 }
 
 impl Router {
-    pub fn new(config: Config) -> Self {
-        let term = Arc::new(AtomicBool::new(false));
+    pub fn new(config: Config) -> Result<Self, server::Error> {
+        for warning in lint::lint(&config) {
+            eprintln!("[router] WARNING: {}", warning);
+        }
 
-        let server = HttpServer::start();
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let server = HttpServer::start(Arc::clone(&stats), &config.server)?;
 
         let devices = Devices::from(&config.devices);
         let mut links = vec![];
 
-        for (app_name, (input_name, output_name)) in &config.links {
+        let (apps_config, links_config) = get_apps_and_links(&config);
+        for (app_name, (input_name, output_name)) in &links_config {
             let input = devices.get(input_name.as_str())
                 .expect(format!("{} is set as an input device for {}, but needs to be configured", input_name, app_name).as_str());
 
             let output = devices.get(output_name.as_str())
                 .expect(format!("{} is set as an output device for {}, but needs to be configured", output_name, app_name).as_str());
 
-            let app = config.apps.start(app_name, Arc::clone(&input.features), Arc::clone(&output.features))
+            let merged_input_names = get_merged_input_names(&apps_config, app_name);
+            for merged_input_name in &merged_input_names {
+                devices.get(merged_input_name.as_str())
+                    .expect(format!("{} is set as an input device to merge for {}, but needs to be configured", merged_input_name, app_name).as_str());
+            }
+
+            let app = apps_config.start(app_name, Arc::clone(&input.features), Arc::clone(&output.features))
                 .expect(format!("The {} application needs to be configured", app_name).as_str());
 
-            links.push((app, input_name.clone(), output_name.clone()));
+            links.push((app, input_name.clone(), output_name.clone(), merged_input_names));
         }
 
+        return Ok(Router::from_parts(&config, server, stats, devices, links));
+    }
+
+    /// Apps are linked to their input and output devices by the ids of the devices
+    fn from_parts(
+        config: &Config,
+        server: HttpServer,
+        stats: Arc<Stats>,
+        devices: Devices,
+        links: Vec<(Box<dyn App>, String, String, Vec<String>)>,
+    ) -> Self {
         return Router {
-            term,
+            term: Arc::new(AtomicBool::new(false)),
             server,
             devices,
             links,
+            stats,
+            backoff: Backoff::from(&config.reconnect),
+            port_retries: config.reconnect.port_retries.unwrap_or(midi::DEFAULT_PORT_RETRIES),
+            port_retry_delay: config.reconnect.port_retry_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(midi::DEFAULT_PORT_RETRY_DELAY),
+            missing_devices: MissingDevices::default(),
+            configured_device_names: config.devices.values().map(|device| device.name.clone()).collect(),
+            device_names_checked: false,
+            connect_macros: config.devices.iter()
+                .map(|(id, device)| (id.clone(), get_connect_macros(id, device)))
+                .collect(),
+            connected_outputs: HashSet::new(),
+            frozen: false,
+            solo: None,
+            measure_latency: config.measure_latency,
+            event_log: config.event_log.as_ref().map(EventLog::new),
+            self_test: config.self_test,
+            coalescing_states: config.coalesce_cc_ms.iter()
+                .map(|(app_name, window)| {
+                    let state = CoalescingState::new(Duration::from_millis(*window));
+                    (app_name.clone(), Arc::new(Mutex::new(state)))
+                })
+                .collect(),
         };
     }
 
@@ -70,125 +193,2089 @@ impl Router {
         println!("Press ^C or send SIGINT to terminate the program");
         let _sigint = sh::flag::register(sh::consts::signal::SIGINT, Arc::clone(&self.term));
 
+        if self.self_test {
+            match Connections::with_port_retries(self.port_retries, self.port_retry_delay) {
+                Ok(connections) => {
+                    let failures = self.self_test_outputs(&connections, SELF_TEST_DURATION);
+                    if failures.is_empty() {
+                        println!("[router] self-test: every output device accepted writes");
+                    }
+                },
+                Err(err) => eprintln!("[router] could not run the self-test: {}", err),
+            }
+        }
+
         let mut inner_result = Ok(());
         while !self.term.load(Ordering::Relaxed) && inner_result.is_ok() {
-            inner_result = self.run_one_cycle(Instant::now());
+            let cycle = Connections::with_port_retries(self.port_retries, self.port_retry_delay)
+                .and_then(|connections| self.run_one_cycle(&connections, Instant::now()));
+
+            inner_result = match cycle {
+                Ok(()) => {
+                    self.backoff.reset();
+                    Ok(())
+                },
+                // Devices may be plugged (or become available) later on, so we keep trying
+                Err(Error::ConnectionInitializationError)
+                | Err(Error::DeviceLoadingError)
+                | Err(Error::DeviceNotFound)
+                | Err(Error::PortInitializationError) => {
+                    let term = Arc::clone(&self.term);
+                    self.backoff.wait(|delay| sleep_unless_terminated(delay, &term));
+                    Ok(())
+                },
+                Err(err) => Err(err),
+            };
+        }
+
+        if self.term.load(Ordering::Relaxed) {
+            match Connections::with_port_retries(self.port_retries, self.port_retry_delay) {
+                Ok(connections) => self.disconnect_outputs(&connections),
+                Err(err) => eprintln!("[router] could not restore the output devices: {}", err),
+            }
         }
         return inner_result;
     }
 
-    fn run_one_cycle(&mut self, start: Instant) -> Result<(), Error> {
-        return Connections::new().and_then(|connections| {
-            let mut resolved_links = vec![];
+    /// Render a solid color on every output device able to render images, then clear it.
+    ///
+    /// Return the ids of the devices that could not be written to, which get logged as well.
+    fn self_test_outputs(&self, connections: &dyn PortFactory, duration: Duration) -> Vec<String> {
+        let mut output_names = self.links.iter()
+            .map(|(_, _, output_name, _)| output_name.clone())
+            .collect::<Vec<String>>();
+        output_names.sort();
+        output_names.dedup();
+
+        let mut failures = vec![];
+        for output_name in output_names {
+            let result = self.devices.get_output_port(output_name.as_str(), connections)
+                .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send>)
+                .and_then(|mut output| self_test_output(&mut output, duration));
+
+            if let Err(err) = result {
+                self.stats.track_write_error(output_name.as_str());
+                eprintln!("[router] self-test: device {} failed: {}", output_name, err);
+                failures.push(output_name);
+            }
+        }
+        return failures;
+    }
+
+    /// Let every output device restore its standalone state (e.g. its default layout) on shutdown
+    fn disconnect_outputs(&self, connections: &dyn PortFactory) {
+        let mut output_names = self.links.iter()
+            .map(|(_, _, output_name, _)| output_name.clone())
+            .collect::<Vec<String>>();
+        output_names.sort();
+        output_names.dedup();
 
-            for (app, input_name, output_name) in &mut self.links {
-                let input = self.devices.get_input_port(input_name.as_str(), &connections);
-                let output = self.devices.get_output_port(output_name.as_str(), &connections);
-                resolved_links.push((app, input, output));
+        for output_name in output_names {
+            match self.devices.get_output_port(output_name.as_str(), connections) {
+                Ok(mut output) => output.features.on_disconnect(output.port.as_mut()).unwrap_or_else(|err| {
+                    self.stats.track_write_error(output_name.as_str());
+                    eprintln!("[router] could not restore device {}: {}", output_name, err);
+                }),
+                Err(err) => eprintln!("[router] could not restore device {}: {}", output_name, err),
             }
+        }
+    }
+
+    /// Open the ports of every link through the given connections, then route events until the
+    /// devices need to be polled again, or until one of them fails.
+    fn run_one_cycle(&mut self, connections: &dyn PortFactory, start: Instant) -> Result<(), Error> {
+        if !self.device_names_checked {
+            self.device_names_checked = true;
+            let report = DeviceNamesReport::new(&self.configured_device_names, &connections.get_device_names());
+            report.log();
+        }
+
+        let mut resolved_links = vec![];
 
-            let mut execution = Ok(());
+        let mut missing_devices = HashSet::new();
 
-            while !self.term.load(Ordering::Relaxed) && execution.is_ok() && start.elapsed() < MIDI_DEVICE_POLL_INTERVAL {
-                // If no application could read from/write to any devices, we’ll fail the execution
-                // so that devices get pulled again.
-                execution = Err(Error::DeviceNotFound);
+        for (app, input_name, output_name, merged_input_names) in &mut self.links {
+            let input = self.devices.get_input_port(input_name.as_str(), connections)
+                .map(|mut input| {
+                    if let Some(state) = self.coalescing_states.get(app.get_name()) {
+                        input.port = Box::new(CoalescingReader::new(input.port, Arc::clone(state), Instant::now));
+                    }
+                    input
+                });
+            let mut output = self.devices.get_output_port(output_name.as_str(), connections);
 
-                let server_command = match self.server.receive() {
-                    Ok(command) => Some(command),
-                    Err(TryRecvError::Disconnected) => {
-                        eprintln!("[router] server has disconnected");
-                        None
+            // Missing extra inputs do not tear the link down: the other inputs keep being routed.
+            // They are not read at all without the main input though, as the link is torn down.
+            let mut merged_inputs = vec![];
+            for merged_input_name in merged_input_names.iter().filter(|_| input.is_ok()) {
+                match self.devices.get_input_port(merged_input_name.as_str(), connections) {
+                    Ok(merged_input) => merged_inputs.push(merged_input),
+                    Err(_) => {
+                        missing_devices.insert(merged_input_name.clone());
                     },
-                    _ => None,
-                };
-
-                for (app, input, output) in &mut resolved_links {
-                    let input_execution = match input.as_mut() {
-                        Ok(input) => {
-                            if let Some(command) = server_command.clone() {
-                                app.send(command.into()).unwrap_or_else(|err| {
-                                    eprintln!("[router] could not send event to app {}: {}", app.get_name(), err);
-                                });
-                            }
+                }
+            }
 
-                            match Reader::read(&mut input.port) {
-                                Ok(Some(event)) => app.send(event.into()).unwrap_or_else(|err| {
-                                    eprintln!("[router] could not send event to app {}: {}", app.get_name(), err);
-                                }),
-                                Err(err) => eprintln!("[router] error when reading event from device {}: {}", input.id, err),
-                                _ => {},
-                            }
-                            Ok(())
-                        },
-                        Err(err) => Err(*err),
-                    };
-
-                    let output_execution = match output.as_mut() {
-                        Ok(output) => {
-                            match app.receive() {
-                                Ok(Out::Server(command)) => {
-                                    self.server.send(command);
-                                },
-                                Ok(Out::Midi(event)) => output.port.write(event).unwrap_or_else(|err| {
-                                    eprintln!("[router] error when writing event to device {}: {}", output.id, err);
-                                }),
-                                Err(TryRecvError::Disconnected) => {
-                                    eprintln!("[router] app has disconnected: {}", app.get_name());
-                                },
-                                _ => {},
+            if input.is_err() {
+                // The link is torn down: let the app release what it was holding
+                if !self.missing_devices.is_missing(input_name) {
+                    app.on_deselect();
+                }
+                missing_devices.insert(input_name.clone());
+            }
+            match output.as_mut() {
+                // While frozen, the macros get sent once writing to devices is resumed
+                Ok(output) => if !self.frozen && self.connected_outputs.insert(output_name.clone()) {
+                    let macros = self.connect_macros.get(output_name).map(|macros| macros.as_slice()).unwrap_or_default();
+                    send_connect_macros(output_name.as_str(), output.port.as_mut(), macros, &self.stats);
+                },
+                Err(_) => {
+                    self.connected_outputs.remove(output_name);
+                    missing_devices.insert(output_name.clone());
+                },
+            }
+
+            resolved_links.push((app, input, output, merged_inputs));
+        }
+
+        let (lost_devices, found_devices) = self.missing_devices.update(missing_devices);
+        for device in lost_devices {
+            eprintln!("[router] waiting for device {}", device);
+        }
+        for device in &found_devices {
+            println!("[router] device {} is available", device);
+        }
+
+        // Reconnected devices have lost what was displayed on them
+        for (app, _, output, _) in &mut resolved_links {
+            if let Ok(output) = output {
+                if found_devices.contains(&output.id) {
+                    app.render();
+                }
+            }
+        }
+
+        let mut execution = Ok(());
+        let mut rescan_requested = false;
+        // Global actions triggered by input devices get handled at the beginning of the next cycle
+        let mut global_actions = vec![];
+
+        while !self.term.load(Ordering::Relaxed) && execution.is_ok() && !is_device_poll_due(start, rescan_requested) {
+            // If no application could read from/write to any devices, we’ll fail the execution
+            // so that devices get pulled again.
+            execution = Err(Error::DeviceNotFound);
+            let cycle_start = Instant::now();
+
+            let server_command = match self.server.receive() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Disconnected) => {
+                    eprintln!("[router] server has disconnected");
+                    None
+                },
+                _ => None,
+            };
+
+            let server_command = match server_command {
+                Some(Command::SetBrightness { device, brightness }) => {
+                    if set_brightness(&self.devices, device.as_str(), brightness) {
+                        // What the device displays only changes once it gets rendered again
+                        for (app, _, output, _) in &mut resolved_links {
+                            if matches!(output, Ok(output) if output.id == device) {
+                                app.render();
                             }
-                            Ok(())
-                        },
-                        Err(err) => Err(*err),
-                    };
+                        }
+                    }
+                    None
+                },
+                Some(Command::RenderImage { device, image }) => {
+                    let output = resolved_links.iter_mut()
+                        .find_map(|(_, _, output, _)| output.as_mut().ok().filter(|output| output.id == device));
+                    render_image(output, device.as_str(), image, self.frozen);
+                    None
+                },
+                command => command,
+            };
+
+            let was_frozen = self.frozen;
+            let mut commands = handle_router_command(server_command, &mut self.frozen, &mut self.solo, &mut rescan_requested)
+                .into_iter()
+                .collect::<Vec<Command>>();
+
+            let mut panic = false;
+            for action in global_actions.drain(..) {
+                commands.extend(handle_global_action(action, &mut self.frozen, &mut panic));
+            }
+            let just_frozen = self.frozen && !was_frozen;
+
+            let cycle = Cycle {
+                commands: &commands,
+                frozen: self.frozen,
+                solo: &self.solo,
+                silence: just_frozen || panic,
+                measure_latency: self.measure_latency,
+                event_log: self.event_log.as_ref(),
+            };
 
-                    execution = execution.or(input_execution.and(output_execution));
+            for (app, input, output, merged_inputs) in &mut resolved_links {
+                let (link_execution, command) = route_link(app, input.as_mut(), output.as_mut(), &cycle, &self.stats, &mut global_actions);
+                if let Some(command) = command {
+                    self.server.try_send(command);
                 }
+                execution = execution.or(link_execution);
 
-                match execution {
-                    Ok(_) => thread::sleep(MIDI_EVENT_POLL_INTERVAL),
-                    _ => thread::sleep(MIDI_DEVICE_POLL_INTERVAL),
+                for merged_input in merged_inputs.iter_mut() {
+                    let routing = route_input_event(app, merged_input.id.as_str(), merged_input.port.as_mut(), merged_input.features.as_ref(), &self.stats, self.event_log.as_ref());
+                    if let InputRouting::GlobalAction(action) = routing {
+                        global_actions.push(action);
+                    }
                 }
             }
 
-            return execution;
+            self.stats.track_cycle(cycle_start.elapsed());
+
+            // On failure, the caller decides how long to wait before trying again
+            if execution.is_ok() {
+                thread::sleep(MIDI_EVENT_POLL_INTERVAL);
+            }
+        }
+
+        return execution;
+    }
+}
+
+/// What the router decided at the beginning of a cycle, applying to every link
+struct Cycle<'a> {
+    /// Commands to send to every app
+    commands: &'a [Command],
+    frozen: bool,
+    solo: &'a Option<String>,
+    /// Whether every output device should be sent all-notes-off
+    silence: bool,
+    measure_latency: bool,
+    event_log: Option<&'a EventLog>,
+}
+
+/// Route events between an app and its devices, for one cycle: the commands of the cycle and one
+/// event read from the input device get sent to the app, then one event emitted by the app gets
+/// written to the output device. Global actions triggered by the input device are collected.
+///
+/// Return whether both devices are available, and the command emitted by the app for the server, if any.
+fn route_link(
+    app: &mut Box<dyn App>,
+    input: Result<&mut DeviceWithInputPort, &mut Error>,
+    output: Result<&mut DeviceWithOutputPort, &mut Error>,
+    cycle: &Cycle,
+    stats: &Stats,
+    global_actions: &mut Vec<GlobalAction>,
+) -> (Result<(), Error>, Option<Command>) {
+    let mut read_at = None;
+    let input_execution = match input {
+        Ok(input) => {
+            for command in cycle.commands {
+                app.send(command.clone().into()).unwrap_or_else(|err| {
+                    eprintln!("[router] could not send event to app {}: {}", app.get_name(), err);
+                });
+            }
+
+            match route_input_event(app, input.id.as_str(), input.port.as_mut(), input.features.as_ref(), stats, cycle.event_log) {
+                InputRouting::Routed if cycle.measure_latency => read_at = Some(Instant::now()),
+                InputRouting::GlobalAction(action) => global_actions.push(action),
+                _ => {},
+            }
+            Ok(())
+        },
+        Err(err) => Err(*err),
+    };
+
+    let mut command = None;
+    let output_execution = match output {
+        Ok(output) => {
+            if cycle.silence {
+                send_all_notes_off(output.id.as_str(), output.port.as_mut(), stats);
+            }
+
+            let muted = is_muted(app.get_name(), cycle.frozen, cycle.solo);
+            command = route_output_event(app, output.id.as_str(), output.port.as_mut(), stats, muted, read_at, cycle.event_log);
+            Ok(())
+        },
+        Err(err) => Err(*err),
+    };
+
+    return (input_execution.and(output_execution), command);
+}
+
+/// Differences between the device names found in the configuration and the connected devices
+#[derive(Debug, PartialEq)]
+struct DeviceNamesReport {
+    absent: Vec<String>,
+    unconfigured: Vec<String>,
+}
+
+impl DeviceNamesReport {
+    fn new(configured_names: &Vec<String>, present_names: &Vec<String>) -> Self {
+        let mut absent = configured_names.iter()
+            .filter(|name| !present_names.contains(name))
+            .map(|name| name.clone())
+            .collect::<Vec<String>>();
+        absent.sort();
+        absent.dedup();
+
+        let mut unconfigured = present_names.iter()
+            .filter(|name| !configured_names.contains(name))
+            .map(|name| name.clone())
+            .collect::<Vec<String>>();
+        unconfigured.sort();
+        unconfigured.dedup();
+
+        return DeviceNamesReport { absent, unconfigured };
+    }
+
+    /// A typo in a device name would otherwise only show up as a device that never becomes available
+    fn log(&self) {
+        if !self.absent.is_empty() {
+            eprintln!("[router] WARNING: the following configured devices are not connected: {}", self.absent.join(", "));
+        }
+        if !self.unconfigured.is_empty() {
+            eprintln!("[router] WARNING: the following devices are connected but not configured: {}", self.unconfigured.join(", "));
+        }
+    }
+}
+
+/// Silence the device on every channel, with the "all notes off" controller (123)
+fn send_all_notes_off(output_id: &str, output: &mut dyn Writer, stats: &Stats) {
+    for channel in 0..16 {
+        output.write(midi::Event::Midi([176 + channel, 123, 0, 0])).unwrap_or_else(|err| {
+            stats.track_write_error(output_id);
+            eprintln!("[router] could not send all-notes-off to device {}: {}", output_id, err);
+        });
+    }
+}
+
+/// Devices that cannot render images are skipped
+fn self_test_output(output: &mut DeviceWithOutputPort, duration: Duration) -> R<()> {
+    let (width, height) = match output.features.get_grid_size() {
+        Ok(size) => size,
+        Err(err) if err.is::<UnsupportedFeatureError>() => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    for (color, delay) in [(SELF_TEST_COLOR, duration), ([0, 0, 0], Duration::ZERO)] {
+        let image = Image { width, height, bytes: color.repeat(width * height) };
+        let event = match output.features.from_image(image) {
+            Ok(event) => event,
+            Err(err) if err.is::<UnsupportedFeatureError>() => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        output.port.write(event).map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send>)?;
+        thread::sleep(delay);
+    }
+    return Ok(());
+}
+
+/// Ids of the input devices whose events get merged into the ones of the main input of the app
+fn get_merged_input_names(apps_config: &apps::Config, app_name: &str) -> Vec<String> {
+    if app_name != apps::forward::app::NAME {
+        return vec![];
+    }
+    return apps_config.forward.as_ref()
+        .map(|config| config.merge_inputs.clone())
+        .unwrap_or_default();
+}
+
+/// In selection mode, all the configured apps get wrapped into a selection app,
+/// which becomes the only app to be linked to devices.
+fn get_apps_and_links(config: &Config) -> (apps::Config, Links) {
+    return match &config.mode {
+        Mode::Direct => (config.apps.clone(), config.links.clone()),
+        Mode::Selection { input, output } => {
+            if !config.links.is_empty() {
+                eprintln!("[router] WARNING: links are ignored in selection mode");
+            }
+
+            let mut selectable_apps = config.apps.clone();
+            let (attract_mode, app_order) = selectable_apps.selection.take()
+                .map(|selection| (selection.attract_mode, selection.app_order))
+                .unwrap_or_default();
+
+            let apps = apps::Config {
+                forward: None,
+                macropad: None,
+                paint: None,
+                spotify: None,
+                youtube: None,
+                webhook: None,
+                selection: Some(apps::selection::config::Config {
+                    apps: Box::new(selectable_apps),
+                    attract_mode,
+                    app_order,
+                }),
+                overrides: HashMap::new(),
+                send_policy: config.apps.send_policy,
+            };
+
+            let links = vec![(apps::selection::app::NAME.to_string(), (input.clone(), output.clone()))].into_iter().collect();
+            (apps, links)
+        },
+    };
+}
+
+/// Resolve the names of the SysEx macros to send when the device gets connected
+fn get_connect_macros(id: &str, device: &midi::devices::config::DeviceConfig) -> Vec<Vec<u8>> {
+    return device.on_connect.iter().filter_map(|name| {
+        let sysex = device.sysex_macros.get(name).map(|sysex| sysex.clone());
+        if sysex.is_none() {
+            eprintln!("[router] device {} should send the {} SysEx macro on connect, but it is not defined", id, name);
+        }
+        return sysex;
+    }).collect();
+}
+
+fn send_connect_macros(output_id: &str, output: &mut dyn Writer, macros: &[Vec<u8>], stats: &Stats) {
+    for sysex in macros {
+        output.write_sysex_macro(sysex).unwrap_or_else(|err| {
+            stats.track_write_error(output_id);
+            eprintln!("[router] could not send SysEx macro {:?} to device {}: {}", sysex, output_id, err);
         });
     }
 }
 
+/// Sleep for the given delay, waking up regularly to stop early if the program gets terminated
+fn sleep_unless_terminated(delay: Duration, term: &AtomicBool) {
+    let start = Instant::now();
+    while !term.load(Ordering::Relaxed) && start.elapsed() < delay {
+        thread::sleep(MIDI_EVENT_POLL_INTERVAL.min(delay.saturating_sub(start.elapsed())));
+    }
+}
+
+/// What became of the event read from an input device
+#[derive(Debug, PartialEq)]
+enum InputRouting {
+    /// No event could be read, or the app could not receive it
+    Dropped,
+    /// The event got sent to the app
+    Routed,
+    /// The event triggered a global action, for the router to handle
+    GlobalAction(GlobalAction),
+}
+
+/// Read one event from the input device, and send it to the app,
+/// unless the device maps it to a global action.
+fn route_input_event(
+    app: &mut Box<dyn App>,
+    input_id: &str,
+    input: &mut dyn Reader,
+    features: &(dyn Features + Sync + Send),
+    stats: &Stats,
+    event_log: Option<&EventLog>,
+) -> InputRouting {
+    match Reader::read(input) {
+        Ok(Some(event)) => {
+            if let Ok(Some(action)) = features.into_global_action(event.clone()) {
+                return InputRouting::GlobalAction(action);
+            }
+
+            if let Some(event_log) = event_log {
+                event_log.log(Direction::In, app.get_name(), input_id, &event);
+            }
+
+            match app.send(event.into()) {
+                Ok(_) => {
+                    stats.track_routed_event();
+                    return InputRouting::Routed;
+                },
+                Err(err) => eprintln!("[router] could not send event to app {}: {}", app.get_name(), err),
+            }
+        },
+        Err(err) => {
+            stats.track_read_error(input_id);
+            eprintln!("[router] error when reading event from device {}: {}", input_id, err);
+        },
+        _ => {},
+    }
+    return InputRouting::Dropped;
+}
+
+fn write_events(
+    app_name: &str,
+    output_id: &str,
+    output: &mut dyn Writer,
+    events: &[midi::Event],
+    stats: &Stats,
+    read_at: Option<Instant>,
+    event_log: Option<&EventLog>,
+) {
+    match output.write_all(events) {
+        Ok(_) => {
+            for event in events {
+                stats.track_written_event();
+                if let Some(event_log) = event_log {
+                    event_log.log(Direction::Out, app_name, output_id, event);
+                }
+            }
+            if let Some(read_at) = read_at {
+                stats.track_latency(read_at.elapsed());
+            }
+        },
+        Err(err) => {
+            stats.track_write_error(output_id);
+            eprintln!("[router] error when writing event to device {}: {}", output_id, err);
+        },
+    }
+}
+
+/// Freezing, soloing and rescanning devices are handled by the router itself, and do not concern apps:
+/// such commands get applied, while the other ones are given back to be sent to apps.
+fn handle_router_command(
+    command: Option<Command>,
+    frozen: &mut bool,
+    solo: &mut Option<String>,
+    rescan_requested: &mut bool,
+) -> Option<Command> {
+    return match command {
+        Some(Command::Freeze { enabled }) => {
+            *frozen = enabled;
+            println!("[router] writing to devices is {}", if enabled { "frozen" } else { "resumed" });
+            None
+        },
+        Some(Command::SoloApp { name }) => {
+            match &name {
+                Some(name) => println!("[router] only writing the output of {} to devices", name),
+                None => println!("[router] writing the output of every app to devices"),
+            }
+            *solo = name;
+            None
+        },
+        Some(Command::RescanDevices) => {
+            println!("[router] rescanning devices");
+            *rescan_requested = true;
+            None
+        },
+        command => command,
+    };
+}
+
+/// Update the brightness of the given device, returning whether it did change
+fn set_brightness(devices: &Devices, device_id: &str, brightness: f32) -> bool {
+    let device = match devices.get(device_id) {
+        Some(device) => device,
+        None => {
+            eprintln!("[router] cannot set the brightness of unknown device {}", device_id);
+            return false;
+        },
+    };
+
+    return match device.features.set_brightness(brightness) {
+        Ok(()) => {
+            println!("[router] setting the brightness of device {} to {}", device_id, brightness);
+            true
+        },
+        Err(err) => {
+            eprintln!("[router] cannot set the brightness of device {}: {}", device_id, err);
+            false
+        },
+    };
+}
+
+/// Render the image on the given output device right away, without going through its app,
+/// which overwrites it the next time it renders.
+fn render_image(output: Option<&mut DeviceWithOutputPort>, device_id: &str, image: Image, frozen: bool) {
+    let output = match output {
+        Some(output) => output,
+        None => {
+            eprintln!("[router] cannot render an image on device {}, which is not a connected output", device_id);
+            return;
+        },
+    };
+
+    if frozen {
+        println!("[router] not rendering the image on device {}, as writing to devices is frozen", device_id);
+        return;
+    }
+
+    let result = output.features.from_image(image)
+        .and_then(|event| output.port.write(event)
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send>));
+
+    match result {
+        Ok(()) => println!("[router] rendered an image on device {}", device_id),
+        Err(err) => eprintln!("[router] cannot render an image on device {}: {}", device_id, err),
+    }
+}
+
+/// Freezing and silencing devices are handled by the router itself, while selecting another app
+/// is turned into a command for the apps.
+fn handle_global_action(action: GlobalAction, frozen: &mut bool, panic: &mut bool) -> Option<Command> {
+    println!("[router] triggering global action: {:?}", action);
+    return match action {
+        GlobalAction::Next => Some(Command::SelectNextApp),
+        GlobalAction::Previous => Some(Command::SelectPreviousApp),
+        GlobalAction::Freeze => {
+            *frozen = !*frozen;
+            println!("[router] writing to devices is {}", if *frozen { "frozen" } else { "resumed" });
+            None
+        },
+        GlobalAction::Panic => {
+            *panic = true;
+            None
+        },
+    };
+}
+
+/// Connections get recreated (picking up newly plugged devices) once the poll interval has elapsed,
+/// or as soon as a rescan has been requested.
+fn is_device_poll_due(start: Instant, rescan_requested: bool) -> bool {
+    return rescan_requested || start.elapsed() >= MIDI_DEVICE_POLL_INTERVAL;
+}
+
+/// The output of an app is muted while the router is frozen, or while another app is soloed.
+fn is_muted(app_name: &str, frozen: bool, solo: &Option<String>) -> bool {
+    return frozen || solo.as_ref().map(|solo| solo != app_name).unwrap_or(false);
+}
+
+/// Receive one event from the app, and write it to the output device.
+/// Commands meant for the server are returned instead, for the router to forward them.
+/// Telemetry events get logged and counted, even when the app is muted.
+///
+/// When muted, MIDI events are still received (so that apps don’t get stuck) but get dropped.
+///
+/// When given the instant an input event was read at, within the same cycle and for the same link,
+/// the time elapsed until the output gets written is tracked as the latency of the link.
+fn route_output_event(
+    app: &mut Box<dyn App>,
+    output_id: &str,
+    output: &mut dyn Writer,
+    stats: &Stats,
+    muted: bool,
+    read_at: Option<Instant>,
+    event_log: Option<&EventLog>,
+) -> Option<Command> {
+    match app.receive() {
+        Ok(Out::Server(command)) => return Some(command),
+        Ok(Out::Telemetry(event)) => {
+            println!("[router][{}] {:?}", app.get_name(), event);
+            stats.track_telemetry(&event);
+        },
+        Ok(Out::Midi(_)) | Ok(Out::MidiBatch(_)) if muted => {},
+        Ok(Out::Midi(event)) => write_events(app.get_name(), output_id, output, &[event], stats, read_at, event_log),
+        // A batch is written at once, so that events from other apps cannot interleave with it
+        Ok(Out::MidiBatch(events)) => write_events(app.get_name(), output_id, output, &events, stats, read_at, event_log),
+        Err(TryRecvError::Disconnected) => {
+            eprintln!("[router] app has disconnected: {}", app.get_name());
+        },
+        _ => {},
+    }
+    return None;
+}
+
 pub fn configure() -> Result<Config, Box<dyn std::error::Error>> {
     let devices = midi::devices::config::configure()?;
     let apps = apps::configure()?;
 
     let app_names = apps.get_configured_app_names();
-    let links = configure_links(app_names, devices.keys().collect())?;
+    let links = configure_links(app_names, &devices)?;
 
     return Ok(Config {
         devices,
         apps,
         links,
+        mode: Mode::Direct,
+        reconnect: ReconnectConfig::default(),
+        server: server::config::Config::default(),
+        measure_latency: false,
+        event_log: None,
+        self_test: false,
+        coalesce_cc_ms: HashMap::new(),
     });
 }
 
-fn configure_links(app_names: Vec<String>, devices: Vec<&String>) -> Result<HashMap<String, (String, String)>, Box<dyn std::error::Error>> {
+/// What the user picked when being asked which device to link to an app
+#[derive(Clone, Debug, PartialEq)]
+enum LinkSelection {
+    Device(String),
+    Identify,
+}
+
+const IDENTIFY_ITEM: &'static str = "[identify a device: flash its grid or play a note]";
+
+/// Device ids are sorted, so that the prompt remains the same across calls
+fn get_device_ids(devices: &midi::devices::config::Config) -> Vec<String> {
+    let mut device_ids = devices.keys().map(|id| id.clone()).collect::<Vec<String>>();
+    device_ids.sort();
+    return device_ids;
+}
+
+/// Label each device with its name, and whether it is currently detected
+fn get_link_items(
+    device_ids: &Vec<String>,
+    devices: &midi::devices::config::Config,
+    detected_names: &Vec<String>,
+) -> Vec<String> {
+    let mut items = device_ids.iter().map(|id| {
+        let name = devices.get(id).map(|device| device.name.clone()).unwrap_or_default();
+        let status = if detected_names.contains(&name) { "" } else { ", not detected" };
+        return format!("{} ({}{})", id, name, status);
+    }).collect::<Vec<String>>();
+    items.push(IDENTIFY_ITEM.to_string());
+    return items;
+}
+
+fn into_link_selection(device_ids: &Vec<String>, selection: usize) -> Option<LinkSelection> {
+    return match device_ids.get(selection) {
+        Some(id) => Some(LinkSelection::Device(id.clone())),
+        None if selection == device_ids.len() => Some(LinkSelection::Identify),
+        None => None,
+    };
+}
+
+fn configure_links(
+    app_names: Vec<String>,
+    devices: &midi::devices::config::Config,
+) -> Result<HashMap<String, (String, String)>, Box<dyn std::error::Error>> {
+    let connections = Connections::new()?;
+    let device_ids = get_device_ids(devices);
+    let items = get_link_items(&device_ids, devices, &connections.get_device_names());
+
     let mut links = HashMap::new();
 
     for app_name in app_names {
-        let input_selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!("[router] what device do you want to use as an input for this app: {}?", app_name))
-            .items(devices.as_slice())
+        let input_prompt = format!("[router] what device do you want to use as an input for this app: {}?", app_name);
+        let input_name = select_link_device(&input_prompt, &device_ids, &items, devices, &connections)?;
+
+        let output_prompt = format!("[router] what device do you want to use as an output for this app: {}?", app_name);
+        let output_name = select_link_device(&output_prompt, &device_ids, &items, devices, &connections)?;
+
+        links.insert(app_name, (input_name, output_name));
+    }
+
+    return Ok(links);
+}
+
+fn select_link_device(
+    prompt: &String,
+    device_ids: &Vec<String>,
+    items: &Vec<String>,
+    devices: &midi::devices::config::Config,
+    connections: &Connections,
+) -> Result<String, Box<dyn std::error::Error>> {
+    loop {
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items.as_slice())
             .interact()?;
-        let input_name = devices[input_selection];
 
-        let output_selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!("[router] what device do you want to use as an output for this app: {}?", app_name))
-            .items(devices.as_slice())
+        match into_link_selection(device_ids, selection) {
+            Some(LinkSelection::Device(id)) => return Ok(id),
+            Some(LinkSelection::Identify) => {
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("[router] what device do you want to identify?")
+                    .items(&items[0..device_ids.len()])
+                    .interact()?;
+
+                identify_device(&device_ids[selection], devices, connections);
+            },
+            None => eprintln!("[router] unexpected selection: {}", selection),
+        }
+    }
+}
+
+/// Make the device noticeable: light its whole grid if it can render images,
+/// or play a note otherwise.
+fn identify_device(id: &String, devices: &midi::devices::config::Config, connections: &Connections) {
+    let devices = Devices::from(devices);
+    let device = match devices.get(id.as_str()) {
+        Some(device) => device,
+        None => return,
+    };
+
+    let mut port = match device.get_output_port(connections) {
+        Ok(port) => port,
+        Err(err) => {
+            eprintln!("[router] cannot identify {}, as it cannot be written to: {}", id, err);
+            return;
+        },
+    };
+
+    let (on, off) = get_identify_events(device.features.as_ref());
+    for (events, delay) in vec![(on, Duration::from_millis(1_000)), (off, Duration::from_millis(0))] {
+        for event in events {
+            port.write(event).unwrap_or_else(|err| {
+                eprintln!("[router] error when writing event to device {}: {}", id, err);
+            });
+        }
+        thread::sleep(delay);
+    }
+}
+
+fn get_identify_events(features: &(dyn midi::features::Features + Sync + Send)) -> (Vec<midi::Event>, Vec<midi::Event>) {
+    let grid = features.get_grid_size().ok().and_then(|(width, height)| {
+        let on = features.from_image(Image { width, height, bytes: vec![255; width * height * 3] }).ok()?;
+        let off = features.from_image(Image { width, height, bytes: vec![0; width * height * 3] }).ok()?;
+        return Some((vec![on], vec![off]));
+    });
+
+    // 144: note-down, 128: note-up, 60: middle C
+    return grid.unwrap_or((
+        vec![midi::Event::Midi([144, 60, 127, 0])],
+        vec![midi::Event::Midi([128, 60, 0, 0])],
+    ));
+}
+
+const CALIBRATION_DONE_ITEM: &'static str = "[done]";
+
+/// Report the misconfigurations found by the lint pass, without starting anything
+pub fn check(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let warnings = lint::lint(config);
+    if warnings.is_empty() {
+        println!("[router] no issues found");
+    }
+    for warning in &warnings {
+        println!("[router] WARNING: {}", warning);
+    }
+    return Ok(());
+}
+
+/// Render the test patterns picked by the user on the given device, one after the other,
+/// so that its brightness, normalize_images or image_palette options can be tuned.
+pub fn calibrate(config: &Config, device_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let devices = Devices::from(&config.devices);
+    let device = devices.get(device_id)
+        .ok_or_else(|| format!("{} needs to be configured to be calibrated", device_id))?;
+
+    let connections = Connections::new()?;
+    let mut port = device.get_output_port(&connections)?;
+
+    let mut items = TestPattern::ALL.iter().map(|pattern| pattern.get_name()).collect::<Vec<&str>>();
+    items.push(CALIBRATION_DONE_ITEM);
+
+    loop {
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("[router] what pattern do you want to render on {}?", device_id))
+            .default(0)
+            .items(items.as_slice())
             .interact()?;
-        let output_name = devices[output_selection];
 
-        links.insert(app_name, (input_name.clone(), output_name.clone()));
+        let pattern = match TestPattern::ALL.get(selection) {
+            Some(pattern) => *pattern,
+            None => return Ok(()),
+        };
+
+        match device.features.from_test_pattern(pattern) {
+            Ok(event) => port.write(event)?,
+            Err(err) => eprintln!("[router] cannot render the {} pattern on {}: {}", pattern.get_name(), device_id, err),
+        }
     }
+}
 
-    return Ok(links);
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use tokio::sync::mpsc::error::SendError;
+
+    use crate::apps::{In, TelemetryEvent};
+    use crate::midi::Event;
+    use crate::midi::features::ImageRenderer;
+    use super::*;
+
+    struct FakeReader {
+        events: Vec<[u8; 4]>,
+    }
+    impl Reader for FakeReader {
+        fn read_midi(&mut self) -> Result<Option<[u8; 4]>, Error> {
+            return Ok(self.events.pop());
+        }
+    }
+
+    struct FailingReader {}
+    impl Reader for FailingReader {
+        fn read_midi(&mut self) -> Result<Option<[u8; 4]>, Error> {
+            return Err(Error::ReadError);
+        }
+    }
+
+    struct FakeWriter {
+        events: Vec<Event>,
+    }
+    impl Writer for FakeWriter {
+        fn write_midi(&mut self, event: &[u8; 4]) -> Result<(), Error> {
+            self.events.push(Event::Midi(*event));
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+            self.events.push(Event::SysEx(event.to_vec()));
+            return Ok(());
+        }
+    }
+
+    struct FakeApp {
+        received: Vec<In>,
+        to_send: Vec<Out>,
+    }
+    impl App for FakeApp {
+        fn get_name(&self) -> &'static str {
+            return "fake";
+        }
+
+        fn get_color(&self) -> [u8; 3] {
+            return [0, 0, 0];
+        }
+
+        fn get_logo(&self) -> Image {
+            return Image { width: 0, height: 0, bytes: vec![] };
+        }
+
+        fn send(&mut self, event: In) -> Result<(), SendError<In>> {
+            self.received.push(event);
+            return Ok(());
+        }
+
+        fn receive(&mut self) -> Result<Out, TryRecvError> {
+            return self.to_send.pop().ok_or(TryRecvError::Empty);
+        }
+
+        fn on_select(&mut self) {}
+    }
+
+    #[test]
+    fn route_input_event_when_routing_n_events_then_increment_routed_events() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp { received: vec![], to_send: vec![] });
+        let mut input = FakeReader { events: vec![[144, 36, 100, 0]; 5] };
+
+        for _ in 0..8 {
+            route_input_event(&mut app, "input", &mut input, &midi::devices::default::DefaultFeatures::new(), &stats, None);
+        }
+
+        assert_eq!(stats.snapshot().routed_events, 5);
+    }
+
+    #[test]
+    fn route_input_event_when_read_fails_then_increment_read_errors() {
+        let device_ids = vec!["input".to_string()];
+        let stats = Stats::new(&device_ids);
+        let mut app: Box<dyn App> = Box::new(FakeApp { received: vec![], to_send: vec![] });
+
+        route_input_event(&mut app, "input", &mut FailingReader {}, &midi::devices::default::DefaultFeatures::new(), &stats, None);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.routed_events, 0);
+        assert_eq!(snapshot.devices.get("input").map(|device| device.read_errors), Some(1));
+    }
+
+    #[test]
+    fn route_input_event_when_event_triggers_a_global_action_then_do_not_send_it_to_the_app() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp { received: vec![], to_send: vec![] });
+        let features = midi::devices::launchpadpro::LaunchpadProFeatures {
+            global_actions: vec![midi::devices::config::GlobalActionButton { control: 91, action: GlobalAction::Next }],
+            ..midi::devices::launchpadpro::LaunchpadProFeatures::new()
+        };
+        // events are popped from the end
+        let mut input = FakeReader { events: vec![[176, 92, 127, 0], [176, 91, 127, 0]] };
+
+        assert_eq!(route_input_event(&mut app, "input", &mut input, &features, &stats, None), InputRouting::GlobalAction(GlobalAction::Next));
+        assert_eq!(route_input_event(&mut app, "input", &mut input, &features, &stats, None), InputRouting::Routed);
+        assert_eq!(route_input_event(&mut app, "input", &mut input, &features, &stats, None), InputRouting::Dropped);
+
+        // only the unmapped event got routed to the app
+        assert_eq!(stats.snapshot().routed_events, 1);
+    }
+
+    #[test]
+    fn handle_global_action_when_next_or_previous_then_give_a_command_for_the_apps() {
+        let mut frozen = false;
+        let mut panic = false;
+
+        assert_eq!(handle_global_action(GlobalAction::Next, &mut frozen, &mut panic), Some(Command::SelectNextApp));
+        assert_eq!(handle_global_action(GlobalAction::Previous, &mut frozen, &mut panic), Some(Command::SelectPreviousApp));
+        assert!(!frozen);
+        assert!(!panic);
+    }
+
+    #[test]
+    fn handle_global_action_when_freeze_or_panic_then_apply_them() {
+        let mut frozen = false;
+        let mut panic = false;
+
+        assert_eq!(handle_global_action(GlobalAction::Freeze, &mut frozen, &mut panic), None);
+        assert!(frozen);
+        assert_eq!(handle_global_action(GlobalAction::Freeze, &mut frozen, &mut panic), None);
+        assert!(!frozen);
+
+        assert_eq!(handle_global_action(GlobalAction::Panic, &mut frozen, &mut panic), None);
+        assert!(panic);
+    }
+
+    #[test]
+    fn route_output_event_when_routing_n_events_then_increment_written_events() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![
+                Out::Midi(Event::Midi([144, 36, 100, 0])),
+                Out::Server(Command::SpotifyPause),
+                Out::Midi(Event::SysEx(vec![240, 247])),
+            ],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        let commands = (0..4)
+            .filter_map(|_| route_output_event(&mut app, "output", &mut output, &stats, false, None, None))
+            .collect::<Vec<Command>>();
+
+        assert_eq!(commands, vec![Command::SpotifyPause]);
+        assert_eq!(output.events, vec![Event::SysEx(vec![240, 247]), Event::Midi([144, 36, 100, 0])]);
+        assert_eq!(stats.snapshot().written_events, 2);
+    }
+
+    #[test]
+    fn route_output_event_when_telemetry_then_count_it_without_writing_anything() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![
+                Out::Telemetry(TelemetryEvent::TrackChanged { index: 1, name: "Conscious Club".to_string() }),
+            ],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        let command = route_output_event(&mut app, "output", &mut output, &stats, true, None, None);
+
+        assert_eq!(command, None);
+        assert_eq!(output.events, vec![]);
+        assert_eq!(stats.snapshot().track_changes, 1);
+    }
+
+    #[test]
+    fn route_output_event_when_frozen_then_write_nothing_but_still_forward_commands() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![
+                Out::Midi(Event::Midi([144, 36, 100, 0])),
+                Out::Server(Command::SpotifyPause),
+                Out::Midi(Event::SysEx(vec![240, 247])),
+            ],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        let commands = (0..4)
+            .filter_map(|_| route_output_event(&mut app, "output", &mut output, &stats, true, None, None))
+            .collect::<Vec<Command>>();
+
+        assert_eq!(commands, vec![Command::SpotifyPause]);
+        assert_eq!(output.events, vec![]);
+        assert_eq!(stats.snapshot().written_events, 0);
+    }
+
+    #[test]
+    fn route_output_event_when_another_app_is_soloed_then_suppress_its_writes() {
+        let stats = Stats::new(vec![]);
+        let solo = Some("forward".to_string());
+        let mut forward: Box<dyn App> = Box::new(crate::apps::forward::app::Forward::new(
+            crate::apps::forward::config::Config { transpose: 0, boundary: crate::apps::forward::config::Boundary::Drop, force_channel: None, merge_inputs: vec![] },
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            apps::send_policy::SendPolicy::default(),
+        ));
+        let mut fake: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![Out::Midi(Event::Midi([144, 40, 100, 0]))],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        forward.send(In::Midi(Event::Midi([144, 36, 100, 0]))).unwrap();
+        for app in vec![&mut forward, &mut fake] {
+            let muted = is_muted(app.get_name(), false, &solo);
+            route_output_event(app, "output", &mut output, &stats, muted, None, None);
+        }
+
+        assert_eq!(output.events, vec![Event::Midi([144, 36, 100, 0])]);
+        assert_eq!(fake.receive(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn handle_router_command_when_rescan_devices_then_request_a_rescan_and_consume_the_command() {
+        let (mut frozen, mut solo, mut rescan_requested) = (false, None, false);
+        let command = handle_router_command(Some(Command::RescanDevices), &mut frozen, &mut solo, &mut rescan_requested);
+        assert_eq!(command, None);
+        assert_eq!(rescan_requested, true);
+        assert_eq!(is_device_poll_due(Instant::now(), rescan_requested), true);
+    }
+
+    #[test]
+    fn handle_router_command_when_freeze_or_solo_then_apply_them_and_consume_the_command() {
+        let (mut frozen, mut solo, mut rescan_requested) = (false, None, false);
+
+        let command = handle_router_command(Some(Command::Freeze { enabled: true }), &mut frozen, &mut solo, &mut rescan_requested);
+        assert_eq!(command, None);
+        assert_eq!(frozen, true);
+
+        let command = handle_router_command(Some(Command::SoloApp { name: Some("fake".to_string()) }), &mut frozen, &mut solo, &mut rescan_requested);
+        assert_eq!(command, None);
+        assert_eq!(solo, Some("fake".to_string()));
+        assert_eq!(rescan_requested, false);
+    }
+
+    #[test]
+    fn set_brightness_when_device_supports_it_then_dim_the_subsequently_rendered_colors() {
+        let devices = Devices::from(&get_devices_config());
+        let features = &devices.get("launchpad").unwrap().features;
+        assert_eq!(features.from_pixel(0, 7, [200, 100, 0]).unwrap(), Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 11, 50, 25, 0, 247]));
+
+        assert!(set_brightness(&devices, "launchpad", 0.5));
+        assert_eq!(features.from_pixel(0, 7, [200, 100, 0]).unwrap(), Event::SysEx(vec![240, 0, 32, 41, 2, 16, 11, 11, 25, 12, 0, 247]));
+    }
+
+    #[test]
+    fn set_brightness_when_device_is_unknown_or_does_not_support_it_then_do_nothing() {
+        let devices = Devices::from(&get_devices_config());
+        assert!(!set_brightness(&devices, "synth", 0.5));
+        assert!(!set_brightness(&devices, "keyboard", 0.5));
+    }
+
+    #[test]
+    fn handle_router_command_when_app_command_then_give_it_back() {
+        let (mut frozen, mut solo, mut rescan_requested) = (false, None, false);
+        let command = handle_router_command(Some(Command::SpotifyPause), &mut frozen, &mut solo, &mut rescan_requested);
+        assert_eq!(command, Some(Command::SpotifyPause));
+        assert_eq!((frozen, solo, rescan_requested), (false, None, false));
+    }
+
+    #[test]
+    fn is_device_poll_due_when_no_rescan_then_wait_for_the_poll_interval() {
+        assert_eq!(is_device_poll_due(Instant::now(), false), false);
+        assert_eq!(is_device_poll_due(Instant::now() - MIDI_DEVICE_POLL_INTERVAL, false), true);
+    }
+
+    #[test]
+    fn is_muted_when_no_app_is_soloed_then_only_mute_when_frozen() {
+        assert_eq!(is_muted("fake", false, &None), false);
+        assert_eq!(is_muted("fake", true, &None), true);
+        assert_eq!(is_muted("fake", true, &Some("fake".to_string())), true);
+    }
+
+    #[test]
+    fn route_output_event_when_apps_emit_a_batch_then_write_it_contiguously() {
+        let stats = Stats::new(vec![]);
+        let mut batching_app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![
+                Out::Midi(Event::SysEx(vec![240, 4, 247])),
+                Out::MidiBatch(vec![
+                    Event::SysEx(vec![240, 1, 247]),
+                    Event::SysEx(vec![240, 2, 247]),
+                    Event::SysEx(vec![240, 3, 247]),
+                ]),
+            ],
+        });
+        let mut other_app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![Out::Midi(Event::Midi([144, 36, 100, 0])); 2],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        for _ in 0..2 {
+            route_output_event(&mut batching_app, "output", &mut output, &stats, false, None, None);
+            route_output_event(&mut other_app, "output", &mut output, &stats, false, None, None);
+        }
+
+        assert_eq!(output.events, vec![
+            Event::SysEx(vec![240, 1, 247]),
+            Event::SysEx(vec![240, 2, 247]),
+            Event::SysEx(vec![240, 3, 247]),
+            Event::Midi([144, 36, 100, 0]),
+            Event::SysEx(vec![240, 4, 247]),
+            Event::Midi([144, 36, 100, 0]),
+        ]);
+        assert_eq!(stats.snapshot().written_events, 6);
+    }
+
+    #[test]
+    fn route_output_event_when_read_at_is_given_then_track_the_latency_of_written_events() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![Out::Midi(Event::Midi([144, 36, 100, 0]))],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        route_output_event(&mut app, "output", &mut output, &stats, false, Some(Instant::now()), None);
+        // nothing left to write: no latency to track
+        route_output_event(&mut app, "output", &mut output, &stats, false, Some(Instant::now()), None);
+
+        let latencies = stats.snapshot().latency_histogram.iter().map(|bucket| bucket.count).sum::<u64>();
+        assert_eq!(latencies, 1);
+    }
+
+    #[test]
+    fn route_output_event_when_muted_then_do_not_track_latency() {
+        let stats = Stats::new(vec![]);
+        let mut app: Box<dyn App> = Box::new(FakeApp {
+            received: vec![],
+            to_send: vec![Out::Midi(Event::Midi([144, 36, 100, 0]))],
+        });
+        let mut output = FakeWriter { events: vec![] };
+
+        route_output_event(&mut app, "output", &mut output, &stats, true, Some(Instant::now()), None);
+
+        let latencies = stats.snapshot().latency_histogram.iter().map(|bucket| bucket.count).sum::<u64>();
+        assert_eq!(latencies, 0);
+    }
+
+    #[test]
+    fn send_all_notes_off_should_silence_every_channel() {
+        let stats = Stats::new(vec![]);
+        let mut output = FakeWriter { events: vec![] };
+
+        send_all_notes_off("output", &mut output, &stats);
+
+        assert_eq!(output.events, (176..192).map(|status| Event::Midi([status, 123, 0, 0])).collect::<Vec<Event>>());
+    }
+
+    /// Keep the written events reachable once the writer is boxed into a device
+    struct SharedWriter {
+        events: Rc<RefCell<Vec<Event>>>,
+    }
+    impl Writer for SharedWriter {
+        fn write_midi(&mut self, event: &[u8; 4]) -> Result<(), Error> {
+            self.events.borrow_mut().push(Event::Midi(*event));
+            return Ok(());
+        }
+
+        fn write_sysex(&mut self, event: &[u8]) -> Result<(), Error> {
+            self.events.borrow_mut().push(Event::SysEx(event.to_vec()));
+            return Ok(());
+        }
+    }
+
+    fn get_input_device<'a>(reader: impl Reader + 'a, features: Arc<dyn Features + Sync + Send>) -> DeviceWithInputPort<'a> {
+        return DeviceWithInputPort {
+            id: "input".to_string(),
+            name: "Input".to_string(),
+            device_type: midi::devices::config::DeviceType::Default,
+            features,
+            port: Box::new(reader),
+        };
+    }
+
+    fn get_output_device<'a>(writer: impl Writer + 'a) -> DeviceWithOutputPort<'a> {
+        return DeviceWithOutputPort {
+            id: "output".to_string(),
+            name: "Output".to_string(),
+            device_type: midi::devices::config::DeviceType::Default,
+            features: Arc::new(midi::devices::default::DefaultFeatures::new()),
+            port: Box::new(writer),
+        };
+    }
+
+    fn get_forward_app() -> Box<dyn App> {
+        return Box::new(crate::apps::forward::app::Forward::new(
+            crate::apps::forward::config::Config { transpose: 0, boundary: crate::apps::forward::config::Boundary::Drop, force_channel: None, merge_inputs: vec![] },
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            Arc::new(crate::midi::devices::default::DefaultFeatures::new()),
+            apps::send_policy::SendPolicy::default(),
+        ));
+    }
+
+    fn get_cycle(solo: &Option<String>) -> Cycle {
+        return Cycle { commands: &[], frozen: false, solo, silence: false, measure_latency: false, event_log: None };
+    }
+
+    #[test]
+    fn route_link_when_both_devices_are_available_then_write_the_app_output_to_the_output_device() {
+        let stats = Stats::new(vec![]);
+        let mut app = get_forward_app();
+        let mut input = get_input_device(FakeReader { events: vec![[144, 36, 100, 0]] }, Arc::new(midi::devices::default::DefaultFeatures::new()));
+        let written = Rc::new(RefCell::new(vec![]));
+        let mut output = get_output_device(SharedWriter { events: Rc::clone(&written) });
+        let mut global_actions = vec![];
+
+        let (execution, command) = route_link(&mut app, Ok(&mut input), Ok(&mut output), &get_cycle(&None), &stats, &mut global_actions);
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(command, None);
+        assert_eq!(*written.borrow(), vec![Event::Midi([144, 36, 100, 0])]);
+        assert_eq!(global_actions, vec![]);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.routed_events, 1);
+        assert_eq!(snapshot.written_events, 1);
+    }
+
+    #[test]
+    fn route_link_when_output_device_is_missing_then_still_collect_global_actions_and_report_the_error() {
+        let stats = Stats::new(vec![]);
+        let mut app = get_forward_app();
+        let features = midi::devices::launchpadpro::LaunchpadProFeatures {
+            global_actions: vec![midi::devices::config::GlobalActionButton { control: 91, action: GlobalAction::Freeze }],
+            ..midi::devices::launchpadpro::LaunchpadProFeatures::new()
+        };
+        let mut input = get_input_device(FakeReader { events: vec![[176, 91, 127, 0]] }, Arc::new(features));
+        let mut global_actions = vec![];
+
+        let (execution, command) = route_link(&mut app, Ok(&mut input), Err(&mut Error::DeviceNotFound), &get_cycle(&None), &stats, &mut global_actions);
+
+        assert_eq!(execution, Err(Error::DeviceNotFound));
+        assert_eq!(command, None);
+        assert_eq!(global_actions, vec![GlobalAction::Freeze]);
+    }
+
+    #[test]
+    fn route_link_when_silencing_while_frozen_then_only_write_all_notes_off() {
+        let stats = Stats::new(vec![]);
+        let mut app = get_forward_app();
+        let mut input = get_input_device(FakeReader { events: vec![[144, 36, 100, 0]] }, Arc::new(midi::devices::default::DefaultFeatures::new()));
+        let written = Rc::new(RefCell::new(vec![]));
+        let mut output = get_output_device(SharedWriter { events: Rc::clone(&written) });
+        let solo = None;
+        let cycle = Cycle { frozen: true, silence: true, ..get_cycle(&solo) };
+
+        let (execution, _) = route_link(&mut app, Ok(&mut input), Ok(&mut output), &cycle, &stats, &mut vec![]);
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(*written.borrow(), (176..192).map(|status| Event::Midi([status, 123, 0, 0])).collect::<Vec<Event>>());
+    }
+
+    /// Reads the given events, then terminates the router once they have all been read
+    struct TerminatingReader {
+        events: Vec<[u8; 4]>,
+        term: Arc<AtomicBool>,
+    }
+    impl Reader for TerminatingReader {
+        fn read_midi(&mut self) -> Result<Option<[u8; 4]>, Error> {
+            let event = self.events.pop();
+            if event.is_none() {
+                self.term.store(true, Ordering::Relaxed);
+            }
+            return Ok(event);
+        }
+    }
+
+    /// Connections to fake devices, identified by their names
+    struct FakeConnections {
+        inputs: RefCell<HashMap<String, Vec<[u8; 4]>>>,
+        outputs: HashMap<String, Rc<RefCell<Vec<Event>>>>,
+        term: Arc<AtomicBool>,
+    }
+    impl PortFactory for FakeConnections {
+        fn get_device_names(&self) -> Vec<String> {
+            let mut device_names = self.inputs.borrow().keys().chain(self.outputs.keys()).cloned().collect::<Vec<String>>();
+            device_names.sort();
+            device_names.dedup();
+            return device_names;
+        }
+
+        fn create_input_port(&self, name: &String, _buffer_size: usize) -> Result<Box<dyn Reader + '_>, Error> {
+            let events = self.inputs.borrow_mut().remove(name).ok_or(Error::DeviceNotFound)?;
+            return Ok(Box::new(TerminatingReader { events, term: Arc::clone(&self.term) }));
+        }
+
+        fn create_output_port(&self, name: &String, _buffer_size: usize) -> Result<Box<dyn Writer + '_>, Error> {
+            let events = self.outputs.get(name).ok_or(Error::DeviceNotFound)?;
+            return Ok(Box::new(SharedWriter { events: Rc::clone(events) }));
+        }
+    }
+
+    /// Records the events it receives, and sends them back as they are
+    struct EchoApp {
+        received: Rc<RefCell<Vec<In>>>,
+        pending: Vec<Out>,
+    }
+    impl App for EchoApp {
+        fn get_name(&self) -> &'static str {
+            return "echo";
+        }
+
+        fn get_color(&self) -> [u8; 3] {
+            return [0, 0, 0];
+        }
+
+        fn get_logo(&self) -> Image {
+            return Image { width: 0, height: 0, bytes: vec![] };
+        }
+
+        fn send(&mut self, event: In) -> Result<(), SendError<In>> {
+            self.received.borrow_mut().push(event.clone());
+            if let In::Midi(event) = event {
+                self.pending.insert(0, Out::Midi(event));
+            }
+            return Ok(());
+        }
+
+        fn receive(&mut self) -> Result<Out, TryRecvError> {
+            return self.pending.pop().ok_or(TryRecvError::Empty);
+        }
+
+        fn on_select(&mut self) {}
+    }
+
+    /// A router linking an echo app to the keyboard (as input) and the synth (as output),
+    /// merging the events of the given extra inputs
+    fn get_echo_router(received: &Rc<RefCell<Vec<In>>>, merged_input_names: Vec<String>) -> Router {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.pedalboard]
+            name = "Pedalboard"
+            type = "default"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let app: Box<dyn App> = Box::new(EchoApp { received: Rc::clone(received), pending: vec![] });
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        return Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (app, "keyboard".to_string(), "synth".to_string(), merged_input_names),
+        ]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_an_input_event_is_read_then_send_it_to_the_app_and_write_its_output() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec![]);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(*received.borrow(), vec![In::Midi(Event::Midi([144, 36, 100, 0]))]);
+        assert_eq!(*written.borrow(), vec![Event::Midi([144, 36, 100, 0])]);
+        let snapshot = router.stats.snapshot();
+        assert_eq!(snapshot.routed_events, 1);
+        assert_eq!(snapshot.written_events, 1);
+    }
+
+    #[test]
+    fn run_one_cycle_when_event_log_configured_then_log_the_events_read_and_written() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec![]);
+
+        let path = std::env::temp_dir().join(format!("midi-hub-router-event-log-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        router.event_log = Some(EventLog::new(&EventLogConfig { path: path.clone(), max_size_bytes: None }));
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+        assert_eq!(execution, Ok(()));
+
+        let entries = std::fs::read_to_string(&path).unwrap().lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|entry| (entry["direction"].clone(), entry["app"].clone(), entry["device"].clone(), entry["event"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![
+            (serde_json::json!("in"), serde_json::json!("echo"), serde_json::json!("keyboard"), serde_json::json!({ "Midi": [144, 36, 100, 0] })),
+            (serde_json::json!("out"), serde_json::json!("echo"), serde_json::json!("synth"), serde_json::json!({ "Midi": [144, 36, 100, 0] })),
+        ]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn disconnect_outputs_should_restore_each_output_device_once() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let received = Rc::new(RefCell::new(vec![]));
+        let get_app = || -> Box<dyn App> { Box::new(EchoApp { received: Rc::clone(&received), pending: vec![] }) };
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (get_app(), "keyboard".to_string(), "launchpad".to_string(), vec![]),
+            (get_app(), "launchpad".to_string(), "launchpad".to_string(), vec![]),
+            (get_app(), "keyboard".to_string(), "synth".to_string(), vec![]),
+        ]);
+
+        let launchpad_written = Rc::new(RefCell::new(vec![]));
+        let synth_written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(HashMap::new()),
+            outputs: vec![
+                ("Launchpad Pro MIDI 2".to_string(), Rc::clone(&launchpad_written)),
+                ("Synth".to_string(), Rc::clone(&synth_written)),
+            ].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        router.disconnect_outputs(&connections);
+
+        assert_eq!(*launchpad_written.borrow(), vec![Event::SysEx(vec![240, 0, 32, 41, 2, 16, 33, 0, 247])]);
+        assert_eq!(*synth_written.borrow(), vec![]);
+    }
+
+    #[test]
+    fn self_test_outputs_should_light_and_clear_image_renderers_and_report_failing_devices() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [devices.unplugged]
+            name = "Launchpad Pro MIDI 3"
+            type = "launchpadpro"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let received = Rc::new(RefCell::new(vec![]));
+        let get_app = || -> Box<dyn App> { Box::new(EchoApp { received: Rc::clone(&received), pending: vec![] }) };
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (get_app(), "keyboard".to_string(), "launchpad".to_string(), vec![]),
+            (get_app(), "keyboard".to_string(), "synth".to_string(), vec![]),
+            (get_app(), "keyboard".to_string(), "unplugged".to_string(), vec![]),
+        ]);
+
+        let launchpad_written = Rc::new(RefCell::new(vec![]));
+        let synth_written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(HashMap::new()),
+            outputs: vec![
+                ("Launchpad Pro MIDI 2".to_string(), Rc::clone(&launchpad_written)),
+                ("Synth".to_string(), Rc::clone(&synth_written)),
+            ].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let failures = router.self_test_outputs(&connections, Duration::ZERO);
+
+        let features = midi::devices::launchpadpro::LaunchpadProFeatures::new();
+        let lit = features.from_image(Image { width: 8, height: 8, bytes: SELF_TEST_COLOR.repeat(64) }).unwrap();
+        let cleared = features.from_image(Image { width: 8, height: 8, bytes: vec![0; 8 * 8 * 3] }).unwrap();
+        assert_eq!(*launchpad_written.borrow(), vec![lit, cleared]);
+        // devices without a grid cannot render images, and are not tested
+        assert_eq!(*synth_written.borrow(), vec![]);
+        assert_eq!(failures, vec!["unplugged".to_string()]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_an_image_is_sent_to_render_then_write_it_to_the_target_device() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let received = Rc::new(RefCell::new(vec![]));
+        let get_app = || -> Box<dyn App> { Box::new(EchoApp { received: Rc::clone(&received), pending: vec![] }) };
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let mut router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (get_app(), "keyboard".to_string(), "launchpad".to_string(), vec![]),
+            (get_app(), "keyboard".to_string(), "synth".to_string(), vec![]),
+        ]);
+
+        let image = Image { width: 8, height: 8, bytes: [0, 0, 255].repeat(64) };
+        router.server.try_send(Command::RenderImage { device: "launchpad".to_string(), image: image.clone() });
+
+        let launchpad_written = Rc::new(RefCell::new(vec![]));
+        let synth_written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![])].into_iter().collect()),
+            outputs: vec![
+                ("Launchpad Pro MIDI 2".to_string(), Rc::clone(&launchpad_written)),
+                ("Synth".to_string(), Rc::clone(&synth_written)),
+            ].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        let features = midi::devices::launchpadpro::LaunchpadProFeatures::new();
+        assert_eq!(execution, Ok(()));
+        assert_eq!(*launchpad_written.borrow(), vec![features.from_image(image).unwrap()]);
+        assert_eq!(*synth_written.borrow(), vec![]);
+        // the apps are bypassed
+        assert_eq!(*received.borrow(), vec![]);
+    }
+
+    #[test]
+    fn render_image_when_device_is_not_a_connected_output_or_writing_is_frozen_then_write_nothing() {
+        let image = Image { width: 8, height: 8, bytes: [0, 0, 255].repeat(64) };
+        render_image(None, "unplugged", image.clone(), false);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let mut output = get_output_device(SharedWriter { events: Rc::clone(&written) });
+        output.features = Arc::new(midi::devices::launchpadpro::LaunchpadProFeatures::new());
+        render_image(Some(&mut output), "output", image, true);
+        assert_eq!(*written.borrow(), vec![]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_control_changes_are_pending_at_the_end_of_a_cycle_then_route_them_in_the_next_one() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec![]);
+        let state = CoalescingState::new(Duration::from_millis(20));
+        router.coalescing_states.insert("echo".to_string(), Arc::new(Mutex::new(state)));
+
+        // events are read from the end
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[176, 1, 20, 0], [176, 1, 10, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::new(RefCell::new(vec![])))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+        assert_eq!(router.run_one_cycle(&connections, Instant::now()), Ok(()));
+        assert_eq!(*received.borrow(), vec![]);
+
+        // the devices get polled again once the window is over
+        thread::sleep(Duration::from_millis(20));
+        router.term.store(false, Ordering::Relaxed);
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::new(RefCell::new(vec![])))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+        assert_eq!(router.run_one_cycle(&connections, Instant::now()), Ok(()));
+        assert_eq!(*received.borrow(), vec![In::Midi(Event::Midi([176, 1, 20, 0]))]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_the_output_device_is_missing_then_fail_with_device_not_found() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec![]);
+
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]])].into_iter().collect()),
+            outputs: HashMap::new(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Err(Error::DeviceNotFound));
+        // the input device is still read, so that the app does not miss any event
+        assert_eq!(*received.borrow(), vec![In::Midi(Event::Midi([144, 36, 100, 0]))]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_inputs_are_merged_then_send_the_events_of_both_to_the_app() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec!["pedalboard".to_string()]);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![
+                ("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]]),
+                ("Pedalboard".to_string(), vec![[176, 64, 127, 0]]),
+            ].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(*received.borrow(), vec![
+            In::Midi(Event::Midi([144, 36, 100, 0])),
+            In::Midi(Event::Midi([176, 64, 127, 0])),
+        ]);
+        assert_eq!(*written.borrow(), vec![
+            Event::Midi([144, 36, 100, 0]),
+            Event::Midi([176, 64, 127, 0]),
+        ]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_a_merged_input_is_missing_then_keep_routing_the_main_input() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec!["pedalboard".to_string()]);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(*written.borrow(), vec![Event::Midi([144, 36, 100, 0])]);
+        assert!(router.missing_devices.is_missing("pedalboard"));
+    }
+
+    #[test]
+    fn run_one_cycle_when_the_input_device_is_unplugged_with_sustain_held_then_release_it() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let app: Box<dyn App> = Box::new(crate::apps::forward::app::Forward::new(
+            crate::apps::forward::config::Config { transpose: 0, boundary: crate::apps::forward::config::Boundary::Drop, force_channel: None, merge_inputs: vec![] },
+            Arc::new(midi::devices::default::DefaultFeatures::new()),
+            Arc::new(midi::devices::default::DefaultFeatures::new()),
+            apps::send_policy::SendPolicy::default(),
+        ));
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let mut router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (app, "keyboard".to_string(), "synth".to_string(), vec![]),
+        ]);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Arturia KeyStep 37".to_string(), vec![[176, 64, 127, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+        assert_eq!(router.run_one_cycle(&connections, Instant::now()), Ok(()));
+        assert_eq!(*written.borrow(), vec![Event::Midi([176, 64, 127, 0])]);
+
+        // the keyboard is not connected anymore
+        router.term.store(false, Ordering::Relaxed);
+        assert_eq!(router.run_one_cycle(&connections, Instant::now()), Err(Error::DeviceNotFound));
+        assert_eq!(*written.borrow(), vec![Event::Midi([176, 64, 127, 0]), Event::Midi([176, 64, 0, 0])]);
+    }
+
+    #[test]
+    fn run_one_cycle_when_the_channel_of_an_app_is_full_then_keep_routing_events() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.pedalboard]
+            name = "Pedalboard"
+            type = "default"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let features = Arc::new(midi::devices::launchpadpro::LaunchpadProFeatures::new());
+        let app: Box<dyn App> = Box::new(crate::apps::paint::app::Paint::new(
+            crate::apps::paint::config::Config { min_velocity: 0, pressure_brightness: false, default_grid_size: None },
+            Arc::clone(&features) as Arc<dyn Features + Sync + Send>,
+            features,
+            apps::send_policy::SendPolicy::Drop,
+        ));
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let mut router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (app, "keyboard".to_string(), "synth".to_string(), vec!["pedalboard".to_string()]),
+        ]);
+
+        // Both inputs light a pad at every cycle, while only one event gets written per cycle:
+        // the channel of the app fills up after 32 cycles.
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![
+                ("Arturia KeyStep 37".to_string(), vec![[144, 11, 100, 0]; 40]),
+                ("Pedalboard".to_string(), vec![[144, 11, 100, 0]; 40]),
+            ].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Ok(()));
+        // one event per cycle, the last cycle being the one that finds the keyboard exhausted
+        assert_eq!(written.borrow().len(), 41);
+    }
+
+    #[test]
+    fn run_one_cycle_when_the_server_channel_is_full_then_drop_the_commands_of_the_apps() {
+        let config: Config = toml::from_str(r#"
+            [devices.keyboard]
+            name = "Arturia KeyStep 37"
+            type = "default"
+
+            [devices.pedalboard]
+            name = "Pedalboard"
+            type = "default"
+
+            [devices.synth]
+            name = "Synth"
+            type = "default"
+
+            [apps]
+        "#).expect("the config should be valid");
+
+        let get_app = || -> Box<dyn App> { Box::new(FakeApp { received: vec![], to_send: vec![Out::Server(Command::SpotifyPause); 2] }) };
+        let stats = Arc::new(Stats::new(config.devices.keys()));
+        let mut router = Router::from_parts(&config, HttpServer::detached(), stats, Devices::from(&config.devices), vec![
+            (get_app(), "keyboard".to_string(), "synth".to_string(), vec![]),
+            (get_app(), "pedalboard".to_string(), "synth".to_string(), vec![]),
+        ]);
+
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![
+                ("Arturia KeyStep 37".to_string(), vec![[144, 36, 100, 0]]),
+                ("Pedalboard".to_string(), vec![[176, 64, 127, 0]]),
+            ].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::new(RefCell::new(vec![])))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        // The channel of the server only holds one command: the one of the second app gets
+        // dropped at every cycle, instead of waiting for a web UI that will never read it.
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Ok(()));
+        assert_eq!(router.server.receive(), Ok(Command::SpotifyPause));
+        assert_eq!(router.server.receive(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn run_one_cycle_when_the_main_input_is_missing_then_do_not_read_the_merged_inputs() {
+        let received = Rc::new(RefCell::new(vec![]));
+        let mut router = get_echo_router(&received, vec!["pedalboard".to_string()]);
+
+        let written = Rc::new(RefCell::new(vec![]));
+        let connections = FakeConnections {
+            inputs: RefCell::new(vec![("Pedalboard".to_string(), vec![[176, 64, 127, 0]])].into_iter().collect()),
+            outputs: vec![("Synth".to_string(), Rc::clone(&written))].into_iter().collect(),
+            term: Arc::clone(&router.term),
+        };
+
+        let execution = router.run_one_cycle(&connections, Instant::now());
+
+        assert_eq!(execution, Err(Error::DeviceNotFound));
+        assert_eq!(*received.borrow(), vec![]);
+        assert_eq!(*written.borrow(), vec![]);
+        // the pedalboard did not even get opened
+        assert!(connections.inputs.borrow().contains_key("Pedalboard"));
+    }
+
+    fn get_devices_config() -> midi::devices::config::Config {
+        return vec![
+            ("launchpad", "Launchpad Pro MIDI 2", midi::devices::config::DeviceType::LaunchpadPro),
+            ("keyboard", "Arturia KeyStep 37", midi::devices::config::DeviceType::Default),
+        ].into_iter().map(|(id, name, device_type)| (id.to_string(), midi::devices::config::DeviceConfig {
+            name: name.to_string(),
+            device_type,
+            color_palette_row: None,
+            grid_pads: vec![],
+            palette_encoder: None,
+            highlight_color: None,
+            palette_lighting: false,
+            normalize_images: false,
+            image_palette: None,
+            origin: midi::devices::config::Origin::BottomLeft,
+            color_palette_edge: midi::devices::config::Edge::Bottom,
+            grid_layout: midi::devices::config::GridLayout::RowMajor,
+            inactive_app_brightness: None,
+            brightness: None,
+            global_actions: vec![],
+            buffer_size: None,
+            sysex_gap_ms: None,
+            sysex_macros: HashMap::new(),
+            on_connect: vec![],
+        })).collect();
+    }
+
+    fn get_router_config(mode: &str) -> Config {
+        return toml::from_str(format!(r#"
+            {}
+
+            [devices.launchpad]
+            name = "Launchpad Pro MIDI 2"
+            type = "launchpadpro"
+
+            [apps.forward]
+            [apps.paint]
+
+            [links]
+            forward = ["launchpad", "launchpad"]
+        "#, mode).as_str()).expect("the config should be valid");
+    }
+
+    #[test]
+    fn config_when_the_reconnect_table_is_partial_then_default_the_missing_fields() {
+        let config = get_router_config("[reconnect]\nport_retries = 5");
+        assert_eq!(config.reconnect, ReconnectConfig { port_retries: Some(5), ..ReconnectConfig::default() });
+    }
+
+    #[test]
+    fn get_apps_and_links_when_direct_mode_then_use_the_configured_links() {
+        let config = get_router_config("");
+        assert_eq!(config.mode, Mode::Direct);
+
+        let (apps, links) = get_apps_and_links(&config);
+        assert_eq!(apps.get_configured_app_names(), vec!["forward", "paint"]);
+        assert_eq!(links, config.links);
+    }
+
+    #[test]
+    fn get_apps_and_links_when_selection_mode_then_link_a_selection_of_all_apps() {
+        let config = get_router_config(r#"mode = { type = "selection", input = "launchpad", output = "launchpad" }"#);
+        assert_eq!(config.mode, Mode::Selection { input: "launchpad".to_string(), output: "launchpad".to_string() });
+
+        let (apps, links) = get_apps_and_links(&config);
+        assert_eq!(apps.get_configured_app_names(), vec!["selection"]);
+        assert_eq!(links, vec![
+            ("selection".to_string(), ("launchpad".to_string(), "launchpad".to_string())),
+        ].into_iter().collect());
+
+        let features = midi::devices::get_default_features(midi::devices::config::DeviceType::Default);
+        let app = apps.start("selection", Arc::clone(&features), features).expect("the selection app should start");
+        assert_eq!(app.get_name(), "selection");
+        assert_eq!(apps.selection.unwrap().apps.get_configured_app_names(), vec!["forward", "paint"]);
+    }
+
+    #[test]
+    fn get_connect_macros_should_resolve_names_in_order_and_skip_undefined_ones() {
+        let mut device = get_devices_config().remove("launchpad").unwrap();
+        device.sysex_macros = vec![
+            ("programmer_mode".to_string(), vec![240, 0, 32, 41, 2, 16, 44, 3, 247]),
+            ("clear".to_string(), vec![240, 0, 32, 41, 2, 16, 14, 0, 247]),
+        ].into_iter().collect();
+        device.on_connect = vec!["clear".to_string(), "undefined".to_string(), "programmer_mode".to_string()];
+
+        assert_eq!(get_connect_macros("launchpad", &device), vec![
+            vec![240, 0, 32, 41, 2, 16, 14, 0, 247],
+            vec![240, 0, 32, 41, 2, 16, 44, 3, 247],
+        ]);
+    }
+
+    #[test]
+    fn send_connect_macros_should_only_write_valid_sysex_and_count_invalid_ones_as_errors() {
+        let device_ids = vec!["output".to_string()];
+        let stats = Stats::new(&device_ids);
+        let mut output = FakeWriter { events: vec![] };
+
+        send_connect_macros("output", &mut output, &[
+            vec![240, 0, 32, 41, 2, 16, 14, 0, 247],
+            vec![0, 32, 41, 247],
+            vec![240, 1, 247],
+        ], &stats);
+
+        assert_eq!(output.events, vec![
+            Event::SysEx(vec![240, 0, 32, 41, 2, 16, 14, 0, 247]),
+            Event::SysEx(vec![240, 1, 247]),
+        ]);
+        assert_eq!(stats.snapshot().devices.get("output").unwrap().write_errors, 1);
+    }
+
+    #[test]
+    fn device_names_report_when_all_names_match_then_report_nothing() {
+        let names = vec!["Launchpad Pro MIDI 2".to_string(), "Arturia KeyStep 37".to_string()];
+        assert_eq!(DeviceNamesReport::new(&names, &names), DeviceNamesReport { absent: vec![], unconfigured: vec![] });
+    }
+
+    #[test]
+    fn device_names_report_when_names_differ_then_report_absent_and_unconfigured_devices() {
+        let configured_names = get_devices_config().values().map(|device| device.name.clone()).collect::<Vec<String>>();
+        let present_names = vec![
+            "Arturia KeyStep 37".to_string(),
+            "Launchpad Pro MIDI 1".to_string(),
+            "Launchpad Pro MIDI 3".to_string(),
+        ];
+
+        assert_eq!(DeviceNamesReport::new(&configured_names, &present_names), DeviceNamesReport {
+            absent: vec!["Launchpad Pro MIDI 2".to_string()],
+            unconfigured: vec!["Launchpad Pro MIDI 1".to_string(), "Launchpad Pro MIDI 3".to_string()],
+        });
+    }
+
+    #[test]
+    fn device_names_report_when_a_name_is_configured_twice_then_report_it_once() {
+        let configured_names = vec!["Arturia KeyStep 37".to_string(), "Arturia KeyStep 37".to_string()];
+        assert_eq!(DeviceNamesReport::new(&configured_names, &vec![]), DeviceNamesReport {
+            absent: vec!["Arturia KeyStep 37".to_string()],
+            unconfigured: vec![],
+        });
+    }
+
+    #[test]
+    fn get_link_items_should_list_sorted_devices_then_the_identify_action() {
+        let devices = get_devices_config();
+        let device_ids = get_device_ids(&devices);
+        let items = get_link_items(&device_ids, &devices, &vec!["Launchpad Pro MIDI 2".to_string()]);
+
+        assert_eq!(device_ids, vec!["keyboard".to_string(), "launchpad".to_string()]);
+        assert_eq!(items, vec![
+            "keyboard (Arturia KeyStep 37, not detected)".to_string(),
+            "launchpad (Launchpad Pro MIDI 2)".to_string(),
+            IDENTIFY_ITEM.to_string(),
+        ]);
+    }
+
+    #[test]
+    fn into_link_selection_should_map_selection_to_device_or_identify_action() {
+        let device_ids = get_device_ids(&get_devices_config());
+
+        assert_eq!(into_link_selection(&device_ids, 0), Some(LinkSelection::Device("keyboard".to_string())));
+        assert_eq!(into_link_selection(&device_ids, 1), Some(LinkSelection::Device("launchpad".to_string())));
+        assert_eq!(into_link_selection(&device_ids, 2), Some(LinkSelection::Identify));
+        assert_eq!(into_link_selection(&device_ids, 3), None);
+    }
+
+    #[test]
+    fn get_identify_events_when_device_renders_images_then_light_the_whole_grid() {
+        let features = midi::devices::launchpadpro::LaunchpadProFeatures::new();
+        let (on, off) = get_identify_events(&features);
+
+        let white = features.from_image(Image { width: 8, height: 8, bytes: vec![255; 192] }).unwrap();
+        let black = features.from_image(Image { width: 8, height: 8, bytes: vec![0; 192] }).unwrap();
+        assert_eq!(on, vec![white]);
+        assert_eq!(off, vec![black]);
+    }
+
+    #[test]
+    fn get_identify_events_when_device_cannot_render_images_then_play_a_note() {
+        let features = midi::devices::default::DefaultFeatures::new();
+        let (on, off) = get_identify_events(&features);
+
+        assert_eq!(on, vec![Event::Midi([144, 60, 127, 0])]);
+        assert_eq!(off, vec![Event::Midi([128, 60, 0, 0])]);
+    }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::apps::TelemetryEvent;
+
+/// Counters updated by the router while it routes events, and exposed by the HTTP server.
+///
+/// They only rely on atomics, so that tracking an event does not slow the router down.
+pub struct Stats {
+    started_at: Instant,
+    routed_events: AtomicU64,
+    written_events: AtomicU64,
+    cycles: AtomicU64,
+    cycles_duration_us: AtomicU64,
+    latency_buckets: Vec<AtomicU64>,
+    track_changes: AtomicU64,
+    app_errors: AtomicU64,
+    devices: HashMap<String, DeviceStats>,
+}
+
+/// Upper bounds (inclusive) of the latency histogram buckets, the last bucket being unbounded
+pub const LATENCY_BUCKETS_US: [u64; 8] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000];
+
+#[derive(Default)]
+struct DeviceStats {
+    read_errors: AtomicU64,
+    write_errors: AtomicU64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatsSnapshot {
+    pub uptime_ms: u64,
+    /// Events read from input devices and sent to apps
+    pub routed_events: u64,
+    pub events_per_second: f64,
+    /// Events written to output devices, whether apps emitted them in response to an input event or not
+    pub written_events: u64,
+    pub cycles: u64,
+    pub average_cycle_time_us: u64,
+    pub latency_histogram: Vec<LatencyBucketSnapshot>,
+    pub track_changes: u64,
+    pub app_errors: u64,
+    pub devices: HashMap<String, DeviceStatsSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LatencyBucketSnapshot {
+    /// `None` for the last bucket, counting latencies above all the bounds
+    pub upper_bound_us: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeviceStatsSnapshot {
+    pub read_errors: u64,
+    pub write_errors: u64,
+}
+
+impl Stats {
+    pub fn new<'a, I>(device_ids: I) -> Self where I: IntoIterator<Item = &'a String> {
+        return Stats {
+            started_at: Instant::now(),
+            routed_events: AtomicU64::new(0),
+            written_events: AtomicU64::new(0),
+            cycles: AtomicU64::new(0),
+            cycles_duration_us: AtomicU64::new(0),
+            latency_buckets: (0..=LATENCY_BUCKETS_US.len()).map(|_| AtomicU64::new(0)).collect(),
+            track_changes: AtomicU64::new(0),
+            app_errors: AtomicU64::new(0),
+            devices: device_ids.into_iter()
+                .map(|device_id| (device_id.clone(), DeviceStats::default()))
+                .collect(),
+        };
+    }
+
+    pub fn track_routed_event(&self) {
+        self.routed_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn track_written_event(&self) {
+        self.written_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn track_cycle(&self, duration: Duration) {
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+        self.cycles_duration_us.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn track_latency(&self, latency: Duration) {
+        let latency_us = latency.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_US.iter()
+            .position(|upper_bound_us| latency_us <= *upper_bound_us)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn track_telemetry(&self, event: &TelemetryEvent) {
+        match event {
+            TelemetryEvent::TrackChanged { .. } => self.track_changes.fetch_add(1, Ordering::Relaxed),
+            TelemetryEvent::Error { .. } => self.app_errors.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn track_read_error(&self, device_id: &str) {
+        if let Some(device) = self.devices.get(device_id) {
+            device.read_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn track_write_error(&self, device_id: &str) {
+        if let Some(device) = self.devices.get(device_id) {
+            device.write_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let uptime = self.started_at.elapsed();
+        let routed_events = self.routed_events.load(Ordering::Relaxed);
+        let cycles = self.cycles.load(Ordering::Relaxed);
+        let cycles_duration_us = self.cycles_duration_us.load(Ordering::Relaxed);
+
+        return StatsSnapshot {
+            uptime_ms: uptime.as_millis() as u64,
+            routed_events,
+            events_per_second: if uptime.as_secs_f64() > 0.0 { routed_events as f64 / uptime.as_secs_f64() } else { 0.0 },
+            written_events: self.written_events.load(Ordering::Relaxed),
+            cycles,
+            average_cycle_time_us: if cycles > 0 { cycles_duration_us / cycles } else { 0 },
+            latency_histogram: self.latency_buckets.iter().enumerate()
+                .map(|(index, count)| LatencyBucketSnapshot {
+                    upper_bound_us: LATENCY_BUCKETS_US.get(index).copied(),
+                    count: count.load(Ordering::Relaxed),
+                })
+                .collect(),
+            track_changes: self.track_changes.load(Ordering::Relaxed),
+            app_errors: self.app_errors.load(Ordering::Relaxed),
+            devices: self.devices.iter()
+                .map(|(device_id, device)| (device_id.clone(), DeviceStatsSnapshot {
+                    read_errors: device.read_errors.load(Ordering::Relaxed),
+                    write_errors: device.write_errors.load(Ordering::Relaxed),
+                }))
+                .collect(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn snapshot_when_cycles_tracked_then_return_average_cycle_time() {
+        let stats = Stats::new(vec![]);
+        stats.track_cycle(Duration::from_micros(100));
+        stats.track_cycle(Duration::from_micros(300));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.cycles, 2);
+        assert_eq!(snapshot.average_cycle_time_us, 200);
+    }
+
+    #[test]
+    fn snapshot_when_latencies_tracked_then_count_them_per_bucket() {
+        let stats = Stats::new(vec![]);
+        stats.track_latency(Duration::from_micros(80));
+        stats.track_latency(Duration::from_micros(100));
+        stats.track_latency(Duration::from_micros(700));
+        stats.track_latency(Duration::from_millis(30));
+
+        let counts = stats.snapshot().latency_histogram.iter()
+            .map(|bucket| (bucket.upper_bound_us, bucket.count))
+            .collect::<Vec<(Option<u64>, u64)>>();
+
+        assert_eq!(counts, vec![
+            (Some(100), 2),
+            (Some(250), 0),
+            (Some(500), 0),
+            (Some(1_000), 1),
+            (Some(2_500), 0),
+            (Some(5_000), 0),
+            (Some(10_000), 0),
+            (Some(25_000), 0),
+            (None, 1),
+        ]);
+    }
+
+    #[test]
+    fn snapshot_when_telemetry_tracked_then_count_it_per_kind() {
+        let stats = Stats::new(vec![]);
+        stats.track_telemetry(&TelemetryEvent::TrackChanged { index: 0, name: "Lingus".to_string() });
+        stats.track_telemetry(&TelemetryEvent::TrackChanged { index: 1, name: "Conscious Club".to_string() });
+        stats.track_telemetry(&TelemetryEvent::Error { message: "oops".to_string() });
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.track_changes, 2);
+        assert_eq!(snapshot.app_errors, 1);
+    }
+
+    #[test]
+    fn snapshot_when_errors_tracked_then_count_them_per_device() {
+        let device_ids = vec!["a".to_string(), "b".to_string()];
+        let stats = Stats::new(&device_ids);
+        stats.track_read_error("a");
+        stats.track_write_error("a");
+        stats.track_write_error("b");
+        stats.track_write_error("b");
+        // unknown devices are ignored
+        stats.track_read_error("c");
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.devices, vec![
+            ("a".to_string(), DeviceStatsSnapshot { read_errors: 1, write_errors: 1 }),
+            ("b".to_string(), DeviceStatsSnapshot { read_errors: 0, write_errors: 2 }),
+        ].into_iter().collect());
+    }
+}
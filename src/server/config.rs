@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+pub const DEFAULT_PORT: u16 = 54321;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Directory the web UI gets served from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webroot: Option<PathBuf>,
+    /// Port the web UI gets served on (54321 by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Token that requests to `/ws` and `/api/*` must carry, either in an `Authorization: Bearer
+    /// <token>` header, or in a `token` query parameter (e.g. `http://localhost:54321/?token=<token>`
+    /// for the web UI to pass it on), or none to leave them open (the default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+}
+
+impl Config {
+    /// Use the configured webroot if any, or the first existing directory among:
+    /// - `public`, next to the executable;
+    /// - `$XDG_DATA_HOME/midi-hub/public` (`~/.local/share/midi-hub/public` by default);
+    /// - `public`, in the current working directory.
+    pub fn resolve_webroot(&self) -> PathBuf {
+        let webroot = self.webroot.clone().unwrap_or_else(|| {
+            let candidates = vec![
+                std::env::current_exe().ok()
+                    .and_then(|exe| exe.parent().map(|dir| dir.join("public"))),
+                std::env::var("XDG_DATA_HOME").map(PathBuf::from)
+                    .or_else(|_| std::env::var("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+                    .ok()
+                    .map(|data_home| data_home.join("midi-hub").join("public")),
+            ];
+
+            return candidates.into_iter()
+                .flatten()
+                .find(|candidate| candidate.is_dir())
+                .unwrap_or_else(|| PathBuf::from("public"));
+        });
+
+        return std::fs::canonicalize(&webroot)
+            .or_else(|_| std::env::current_dir().map(|dir| dir.join(&webroot)))
+            .unwrap_or(webroot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_webroot_when_configured_then_return_its_absolute_path() {
+        let webroot = std::env::temp_dir();
+        let config = Config { webroot: Some(webroot.clone()), ..Config::default() };
+        assert_eq!(config.resolve_webroot(), std::fs::canonicalize(webroot).unwrap());
+    }
+
+    #[test]
+    fn resolve_webroot_when_configured_with_a_relative_path_then_make_it_absolute() {
+        let config = Config { webroot: Some(PathBuf::from("does-not-exist")), ..Config::default() };
+        let webroot = config.resolve_webroot();
+        assert!(webroot.is_absolute());
+        assert!(webroot.ends_with("does-not-exist"));
+    }
+}
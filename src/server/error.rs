@@ -0,0 +1,23 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    RuntimeInitializationError,
+    /// The port is most likely used by another process
+    BindError(u16, String),
+    /// The server thread stopped before telling whether it is listening
+    ThreadError,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Error::RuntimeInitializationError => write!(f, "[server] error when initializing the runtime"),
+            Error::BindError(port, err) => write!(f, "[server] could not listen on port {}: {}", port, err),
+            Error::ThreadError => write!(f, "[server] the server thread stopped unexpectedly"),
+        }
+    }
+}
+
+impl StdError for Error {}
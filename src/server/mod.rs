@@ -1,24 +1,78 @@
 extern crate futures_util;
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use futures_util::{SinkExt, StreamExt};
 use serde::{Serialize, Deserialize};
 use tokio::sync::RwLock;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::sync::mpsc::{Sender, Receiver};
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::runtime::Builder;
-use warp::Filter;
+use warp::{Filter, Rejection, Reply};
+use warp::http::StatusCode;
 use warp::ws::{Message, WebSocket, Ws};
 
+use crate::apps::send_policy::{send_with_policy, SendPolicy};
+use crate::image::{self, Image};
+use crate::router::Stats;
+
+pub mod config;
+
+mod error;
+pub use error::Error;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Command {
     SpotifyPlay { track_id: String, access_token: String },
     SpotifyPause,
     SpotifyToken { access_token: String },
+    SpotifySelectPlaylist { playlist_id: String },
+    SpotifyListPlaylists,
+    SpotifyPlaylists { playlists: Vec<SpotifyPlaylistSummary> },
+    SpotifyListDevices,
+    SpotifyDevicesList { devices: Vec<SpotifyDeviceSummary> },
+    /// Play tracks on the given Spotify Connect device, or on the active one if `None`
+    SpotifySelectDevice { device_id: Option<String> },
     YoutubePlay { video_id: String },
     YoutubePause,
+    /// Pull the items of the YouTube playlist right away
+    YoutubeRefresh,
+    /// Stop writing to devices while enabled, without stopping the apps
+    Freeze { enabled: bool },
+    /// Only write the output of the given app to devices, or the output of every app if `None`
+    SoloApp { name: Option<String> },
+    /// Recreate the connections right away, so that newly plugged devices get picked up
+    RescanDevices,
+    /// Select the app following the selected one, in selection mode
+    SelectNextApp,
+    /// Select the app preceding the selected one, in selection mode
+    SelectPreviousApp,
+    /// Scale the colors rendered on the given device (by id) by the given brightness, from 0 to 1
+    SetBrightness { device: String, brightness: f32 },
+    /// Render the given image on the given device (by id), bypassing its app. The web UI sends
+    /// it as a JPEG encoded as base64, which gets decoded as soon as the command is received.
+    RenderImage {
+        device: String,
+        #[serde(rename = "image_base64", with = "jpeg_base64")]
+        image: Image,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpotifyPlaylistSummary {
+    pub id: String,
+    pub name: String,
+    pub tracks: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpotifyDeviceSummary {
+    pub id: String,
+    pub name: String,
+    pub is_active: bool,
 }
 
 pub struct HttpServer {
@@ -27,50 +81,77 @@ pub struct HttpServer {
 }
 
 impl HttpServer {
-    pub fn start() -> Self {
+    /// Wait for the server to listen on the configured port before returning, so that a port
+    /// already in use gets reported to the caller instead of being swallowed by the server thread.
+    pub fn start(stats: Arc<Stats>, config: &config::Config) -> Result<Self, Error> {
+        let webroot = config.resolve_webroot();
+        println!("[server] serving the web UI from {}", webroot.display());
+
         let (tx, rx) = mpsc::channel::<Command>(1usize);
         let sender = Arc::new(RwLock::new(tx));
         let receiver = Arc::new(Mutex::new(rx));
 
+        let port = config.port.unwrap_or(config::DEFAULT_PORT);
+        let auth_token = config.auth_token.clone();
+        let (ready_tx, ready_rx) = oneshot::channel::<Result<(), Error>>();
+
         let thread_sender = Arc::clone(&sender);
         let thread_receiver = Arc::clone(&receiver);
         std::thread::spawn(move || {
-            Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async move {
-                    let public = warp::any()
-                        .and(warp::fs::dir("public"));
-
-                    let websocket_sender = Arc::clone(&thread_sender);
-                    let websocket_receiver = Arc::clone(&thread_receiver);
-                    let websocket = warp::path("ws")
-                        .and(warp::ws())
-                        .map(move |ws: Ws| {
-                            let websocket_sender = Arc::clone(&websocket_sender);
-                            let websocket_receiver = Arc::clone(&websocket_receiver);
-                            ws.on_upgrade(move |ws| handle_connection(ws, Arc::clone(&websocket_sender), Arc::clone(&websocket_receiver)))
-                        });
-
-                    let routes = public
-                        .or(websocket);
-
-                    println!("HTTP server listening on http://localhost:54321/");
-                    warp::serve(routes)
-                        .run(([0, 0, 0, 0], 54321))
-                        .await;
-                });
+            let runtime = match Builder::new_multi_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(_) => {
+                    let _ = ready_tx.send(Err(Error::RuntimeInitializationError));
+                    return;
+                },
+            };
+
+            runtime.block_on(async move {
+                let routes = get_routes(webroot, stats, auth_token, thread_sender, thread_receiver);
+
+                match warp::serve(routes).try_bind_ephemeral(([0, 0, 0, 0], port)) {
+                    Ok((_, server)) => {
+                        let _ = ready_tx.send(Ok(()));
+                        server.await;
+                    },
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(Error::BindError(port, err.to_string())));
+                    },
+                }
+            });
         });
 
-        HttpServer {
+        ready_rx.blocking_recv().map_err(|_| Error::ThreadError)??;
+        println!("HTTP server listening on http://localhost:{}/", port);
+
+        return Ok(HttpServer {
             sender,
             receiver,
+        });
+    }
+
+    /// A server that does not listen on any port, so that the router can be tested offline
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        let (tx, rx) = mpsc::channel::<Command>(1usize);
+        HttpServer {
+            sender: Arc::new(RwLock::new(tx)),
+            receiver: Arc::new(Mutex::new(rx)),
         }
     }
 
-    pub fn send(&self, command: Command) {
-        self.sender.try_read().expect("sender should be readable").blocking_send(command)
+    /// Drop the command (with a warning) if the web UI is not keeping up, or is reconnecting,
+    /// so that a slow browser can never stall the routing of MIDI events.
+    pub fn try_send(&self, command: Command) {
+        let sender = match self.sender.try_read() {
+            Ok(sender) => sender,
+            Err(_) => {
+                eprintln!("[server] dropping a command, as the web UI is reconnecting");
+                return;
+            },
+        };
+
+        send_with_policy("server", &*sender, command, SendPolicy::Drop)
             .unwrap_or_else(|err| eprintln!("Error: {:?}", err));
     }
 
@@ -80,6 +161,112 @@ impl HttpServer {
     }
 }
 
+/// Decode the image sent along with `Command::RenderImage`
+fn decode_image(image_base64: &str) -> Result<Image, image::Error> {
+    let bytes = base64::decode(image_base64).map_err(|_| image::Error::Base64DecodingError)?;
+    return Image::from_decoder(&mut jpeg_decoder::Decoder::new(bytes.as_slice()));
+}
+
+/// Images go through the websocket as JPEGs encoded as base64
+mod jpeg_base64 {
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+    use crate::image::Image;
+
+    pub fn serialize<S: Serializer>(image: &Image, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = image.encode_jpeg(100)
+            .map_err(|err| <S::Error as ser::Error>::custom(format!("cannot encode the image: {:?}", err)))?;
+        return serializer.serialize_str(base64::encode(bytes).as_str());
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Image, D::Error> {
+        let image_base64 = String::deserialize(deserializer)?;
+        return super::decode_image(image_base64.as_str())
+            .map_err(|err| <D::Error as de::Error>::custom(format!("cannot decode the image: {:?}", err)));
+    }
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Reject requests that do not carry the given token, if any, either as a bearer token or as a
+/// `token` query parameter, as browsers cannot set headers on websockets.
+fn authorized(auth_token: Option<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let auth_token = Arc::new(auth_token);
+    return warp::header::optional::<String>("authorization")
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |header: Option<String>, query: HashMap<String, String>| {
+            let is_authorized = match auth_token.as_deref() {
+                Some(auth_token) => {
+                    let bearer_token = header.as_ref().and_then(|header| header.strip_prefix("Bearer "));
+                    let query_token = query.get("token").map(|token| token.as_str());
+                    bearer_token.or(query_token)
+                        .map(|token| constant_time_eq(token.as_bytes(), auth_token.as_bytes()))
+                        .unwrap_or(false)
+                },
+                None => true,
+            };
+            async move {
+                return if is_authorized { Ok(()) } else { Err(warp::reject::custom(Unauthorized)) };
+            }
+        })
+        .untuple_one();
+}
+
+/// Compare every byte whatever the first difference, so that response times do not tell how much
+/// of a guessed token is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    return a.iter().zip(b.iter()).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0;
+}
+
+/// Answer with a 401 instead of the 500 that warp replies with for custom rejections
+async fn reply_unauthorized(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        return Ok(warp::reply::with_status("Unauthorized", StatusCode::UNAUTHORIZED));
+    }
+    return Err(rejection);
+}
+
+/// The stats API, the websocket the web UI sends and receives commands through, and the files
+/// of the web UI
+fn get_routes(
+    webroot: PathBuf,
+    stats: Arc<Stats>,
+    auth_token: Option<String>,
+    sender: Arc<RwLock<Sender<Command>>>,
+    receiver: Arc<Mutex<Receiver<Command>>>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let auth = authorized(auth_token);
+
+    let websocket = warp::path("ws")
+        .and(auth.clone())
+        .and(warp::ws())
+        .map(move |ws: Ws| {
+            let sender = Arc::clone(&sender);
+            let receiver = Arc::clone(&receiver);
+            ws.on_upgrade(move |ws| handle_connection(ws, sender, receiver))
+        });
+
+    let stats = warp::path!("api" / "stats")
+        .and(auth)
+        .and(warp::get())
+        .map(move || warp::reply::json(&stats.snapshot()));
+
+    return stats
+        .or(public_files(webroot))
+        .or(websocket)
+        .recover(reply_unauthorized);
+}
+
+fn public_files(webroot: PathBuf) -> impl Filter<Extract = (warp::fs::File,), Error = warp::Rejection> + Clone {
+    return warp::any().and(warp::fs::dir(webroot));
+}
+
 async fn handle_connection(ws: WebSocket, sender: Arc<RwLock<Sender<Command>>>, receiver: Arc<Mutex<Receiver<Command>>>) {
     let (sender_tx, mut sender_rx) = mpsc::channel::<Command>(1usize);
     let (receiver_tx, receiver_rx) = mpsc::channel::<Command>(1usize);
@@ -117,3 +304,161 @@ async fn handle_connection(ws: WebSocket, sender: Arc<RwLock<Sender<Command>>>,
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn public_files_should_serve_files_from_the_webroot() {
+        let webroot = std::env::temp_dir().join(format!("midi-hub-webroot-{}", std::process::id()));
+        std::fs::create_dir_all(&webroot).unwrap();
+        std::fs::write(webroot.join("index.html"), "<h1>midi-hub</h1>").unwrap();
+
+        let response = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(warp::test::request().path("/index.html").reply(&public_files(webroot.clone())));
+
+        std::fs::remove_dir_all(&webroot).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "<h1>midi-hub</h1>");
+    }
+
+    fn get_test_routes(auth_token: Option<&str>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+        let (tx, rx) = mpsc::channel::<Command>(1usize);
+        return get_routes(
+            PathBuf::from("does-not-exist"),
+            Arc::new(Stats::new(vec![])),
+            auth_token.map(String::from),
+            Arc::new(RwLock::new(tx)),
+            Arc::new(Mutex::new(rx)),
+        );
+    }
+
+    fn get_stats_status(auth_token: Option<&str>, path: &str, header: Option<&str>) -> StatusCode {
+        let request = warp::test::request().path(path);
+        let request = match header {
+            Some(header) => request.header("authorization", header),
+            None => request,
+        };
+
+        return Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(request.reply(&get_test_routes(auth_token)))
+            .status();
+    }
+
+    fn can_open_websocket(auth_token: Option<&str>, path: &str, header: Option<&str>) -> bool {
+        let request = warp::test::ws().path(path);
+        let request = match header {
+            Some(header) => request.header("authorization", header),
+            None => request,
+        };
+
+        return Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(request.handshake(get_test_routes(auth_token)))
+            .is_ok();
+    }
+
+    #[test]
+    fn get_routes_when_the_token_matches_then_accept_the_request() {
+        assert_eq!(get_stats_status(Some("secret"), "/api/stats", Some("Bearer secret")), StatusCode::OK);
+        assert_eq!(get_stats_status(Some("secret"), "/api/stats?token=secret", None), StatusCode::OK);
+        assert!(can_open_websocket(Some("secret"), "/ws", Some("Bearer secret")));
+        assert!(can_open_websocket(Some("secret"), "/ws?token=secret", None));
+    }
+
+    #[test]
+    fn get_routes_when_the_token_is_missing_or_wrong_then_reject_the_request() {
+        for header in [None, Some("Bearer wrong"), Some("secret"), Some("Basic secret")] {
+            assert_eq!(get_stats_status(Some("secret"), "/api/stats", header), StatusCode::UNAUTHORIZED, "{:?}", header);
+            assert!(!can_open_websocket(Some("secret"), "/ws", header), "{:?}", header);
+        }
+        assert_eq!(get_stats_status(Some("secret"), "/api/stats?token=wrong", None), StatusCode::UNAUTHORIZED);
+        assert!(!can_open_websocket(Some("secret"), "/ws?token=secre", None));
+    }
+
+    #[test]
+    fn get_routes_when_no_token_is_configured_then_accept_any_request() {
+        for header in [None, Some("Bearer whatever")] {
+            assert_eq!(get_stats_status(None, "/api/stats", header), StatusCode::OK, "{:?}", header);
+            assert!(can_open_websocket(None, "/ws", header), "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn constant_time_eq_should_only_accept_identical_bytes() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret!"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    #[test]
+    fn decode_image_when_payload_is_a_base64_jpeg_then_return_the_image() {
+        let image = Image { width: 8, height: 8, bytes: [255, 0, 0].repeat(64) };
+        let image_base64 = base64::encode(image.encode_jpeg(100).unwrap());
+
+        let decoded_image = decode_image(image_base64.as_str()).expect("Expected the image to be decodable");
+        assert_eq!((decoded_image.width, decoded_image.height), (8, 8));
+        assert_eq!(decoded_image.bytes.len(), 8 * 8 * 3);
+    }
+
+    #[test]
+    fn decode_image_when_payload_is_not_a_base64_jpeg_then_return_err() {
+        assert_eq!(decode_image("not base64!"), Err(image::Error::Base64DecodingError));
+        assert_eq!(decode_image(base64::encode("not a jpeg").as_str()), Err(image::Error::JpegDecodingError));
+    }
+
+    #[test]
+    fn command_when_render_image_is_received_then_decode_the_image_right_away() {
+        let image = Image { width: 8, height: 8, bytes: [255, 0, 0].repeat(64) };
+        let image_base64 = base64::encode(image.encode_jpeg(100).unwrap());
+        let json = format!(r#"{{"RenderImage":{{"device":"launchpad","image_base64":"{}"}}}}"#, image_base64);
+
+        match serde_json::from_str::<Command>(json.as_str()) {
+            Ok(Command::RenderImage { device, image }) => {
+                assert_eq!(device, "launchpad");
+                assert_eq!((image.width, image.height), (8, 8));
+            },
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        let json = r#"{"RenderImage":{"device":"launchpad","image_base64":"not base64!"}}"#;
+        assert!(serde_json::from_str::<Command>(json).is_err());
+    }
+
+    #[test]
+    fn start_when_the_port_is_already_in_use_then_fail() {
+        // let the system pick a port that is free, for the first server to take
+        let port = std::net::TcpListener::bind(("0.0.0.0", 0)).unwrap().local_addr().unwrap().port();
+        let config = config::Config { port: Some(port), ..config::Config::default() };
+
+        let first_server = HttpServer::start(Arc::new(Stats::new(vec![])), &config);
+        assert!(first_server.is_ok());
+
+        let second_server = HttpServer::start(Arc::new(Stats::new(vec![])), &config);
+        assert!(matches!(second_server, Err(Error::BindError(second_port, _)) if second_port == port));
+    }
+
+    #[test]
+    fn try_send_when_channel_is_full_then_drop_the_command_right_away() {
+        let server = HttpServer::detached();
+        server.try_send(Command::SpotifyPause);
+
+        // nothing reads the commands sent by a detached server, whose channel holds a single one
+        let start = std::time::Instant::now();
+        server.try_send(Command::YoutubePause);
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+
+        assert_eq!(server.receive(), Ok(Command::SpotifyPause));
+        assert_eq!(server.receive(), Err(TryRecvError::Empty));
+    }
+}